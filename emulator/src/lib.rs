@@ -21,6 +21,12 @@ pub struct Emulator {
     deck: Rc<RefCell<ControlDeck>>,
 }
 
+impl Default for Emulator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 #[wasm_bindgen]
 impl Emulator {
     #[wasm_bindgen(constructor)]
@@ -126,4 +132,27 @@ impl Emulator {
         let deck = self.deck.borrow();
         deck.wram().len()
     }
+
+    pub fn has_battery(&self) -> bool {
+        let deck = self.deck.borrow();
+        deck.cart_battery_backed().unwrap_or(false)
+    }
+
+    pub fn get_sram(&self) -> *const u8 {
+        let deck = self.deck.borrow();
+        deck.sram().as_ptr()
+    }
+
+    pub fn get_sram_len(&self) -> usize {
+        let deck = self.deck.borrow();
+        deck.sram().len()
+    }
+
+    /// Restores battery RAM saved from a previous session. Call after `load_rom`.
+    pub fn set_sram(&mut self, data: &[u8]) {
+        let mut deck = self.deck.borrow_mut();
+        let sram = &mut deck.cpu_mut().bus.prg_ram;
+        let len = data.len().min(sram.len());
+        sram[..len].copy_from_slice(&data[..len]);
+    }
 }
@@ -0,0 +1,160 @@
+//! Actors: `ACTOR` types sharing one table of slots.
+//!
+//! Every slot starts with the header below, seen through `Actors(slot)`,
+//! followed by the fields of the ACTOR type occupying it, seen through that
+//! type's own view (`Goomba(slot)`). All views have the same stride: the
+//! header plus the largest ACTOR's fields. `active` is the first byte, so the
+//! table is also a pool for `Runtime_Pool_Spawn`.
+
+use crate::compiler::ast::{ActorDecl, DataType, PlacedObject, Program, TopLevel};
+use crate::compiler::symbol_table::SymbolTable;
+
+/// Slots in the table when there is no `ACTORS n`.
+pub const DEFAULT_SLOTS: u8 = 8;
+/// Slot indices fit a byte with $FF left over for "none".
+pub const MAX_SLOTS: u8 = 64;
+/// Each class is a bit of the slot's `class` byte.
+pub const MAX_CLASSES: usize = 8;
+/// Kinds index the handler tables as `kind * 2`, which has to fit X.
+pub const MAX_KINDS: usize = 128;
+/// Hitbox width and height of an ACTOR without SIZE.
+pub const DEFAULT_SIZE: u8 = 8;
+
+pub const HEADER_SIZE: u16 = 9;
+
+/// Members of the `Actor` header type: Name, Type, Offset.
+pub fn header() -> Vec<(String, DataType, u16)> {
+    vec![
+        ("active".to_string(), DataType::Byte, 0),
+        ("kind".to_string(), DataType::Byte, 1),
+        ("class".to_string(), DataType::Byte, 2),
+        ("x".to_string(), DataType::Word, 3),
+        ("y".to_string(), DataType::Word, 5),
+        ("width".to_string(), DataType::Byte, 7),
+        ("height".to_string(), DataType::Byte, 8),
+    ]
+}
+
+/// The struct type behind an ACTOR's view, named so it cannot clash with a
+/// TYPE.
+pub fn struct_name(actor: &str) -> String {
+    format!("Actor.{}", actor)
+}
+
+/// ACTOR declarations in source order; the index is the actor's `kind`.
+pub fn declarations(program: &Program) -> Vec<&ActorDecl> {
+    program
+        .declarations
+        .iter()
+        .filter_map(|d| match d {
+            TopLevel::Actor(actor) => Some(actor.as_ref()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Size of the `actor_handlers` section: an UPDATE table then a DRAW
+/// table, each holding one handler address per kind.
+pub fn handler_table_size(program: &Program) -> usize {
+    declarations(program).len() * 4
+}
+
+pub fn slots(program: &Program) -> u8 {
+    program
+        .declarations
+        .iter()
+        .rev()
+        .find_map(|d| match d {
+            TopLevel::Actors(n) => Some(*n),
+            _ => None,
+        })
+        .unwrap_or(DEFAULT_SLOTS)
+}
+
+/// Class names in order of first appearance; class `i` is bit `i`.
+pub fn classes(program: &Program) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    for actor in declarations(program) {
+        for class in &actor.classes {
+            if !names.contains(class) {
+                names.push(class.clone());
+            }
+        }
+    }
+    names
+}
+
+pub fn class_mask(actor: &ActorDecl, classes: &[String]) -> u8 {
+    actor
+        .classes
+        .iter()
+        .filter_map(|c| classes.iter().position(|n| n == c))
+        .filter(|&bit| bit < MAX_CLASSES)
+        .fold(0, |mask, bit| mask | (1 << bit))
+}
+
+/// Objects placed in WORLD rooms, in source order.
+pub fn placed_objects(program: &Program) -> Vec<&PlacedObject> {
+    program
+        .declarations
+        .iter()
+        .filter_map(|d| match d {
+            TopLevel::WorldObject(object) => Some(object.as_ref()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// The `world_objects` section read by `World.SpawnRoomObjects`: world width
+/// and height, a little-endian offset (from the section start) per room,
+/// then each room's objects as kind, x, y, field byte count and (offset,
+/// byte) pairs, ended by $FF. Objects are assumed to have been checked by the
+/// analyzer; ones it would reject are left out.
+pub fn room_spawn_table(program: &Program, symbols: &SymbolTable) -> Vec<u8> {
+    let Some((width, height)) = program.declarations.iter().find_map(|d| match d {
+        TopLevel::World(width, height, _) => Some((*width as usize, *height as usize)),
+        _ => None,
+    }) else {
+        return Vec::new();
+    };
+    let kinds: Vec<&str> = declarations(program)
+        .iter()
+        .map(|a| a.name.as_str())
+        .collect();
+    let objects = placed_objects(program);
+
+    let mut table = vec![width as u8, height as u8];
+    table.resize(2 + width * height * 2, 0);
+    for room in 0..width * height {
+        let offset = table.len();
+        table[2 + room * 2] = offset as u8;
+        table[3 + room * 2] = (offset >> 8) as u8;
+        for object in &objects {
+            let (x, y) = (object.room.0 as usize, object.room.1 as usize);
+            if x >= width || y * width + x != room {
+                continue;
+            }
+            let Some(kind) = kinds.iter().position(|k| *k == object.actor) else {
+                continue;
+            };
+            let members = symbols
+                .resolve(&struct_name(&object.actor))
+                .and_then(|sym| sym.members.clone())
+                .unwrap_or_default();
+            let mut fields = Vec::new();
+            for (name, value) in &object.params {
+                let Some((_, dtype, offset)) = members.iter().find(|(n, _, _)| n == name) else {
+                    continue;
+                };
+                fields.extend([*offset as u8, *value as u8]);
+                if *dtype == DataType::Word {
+                    fields.extend([*offset as u8 + 1, (*value >> 8) as u8]);
+                }
+            }
+            table.extend([kind as u8, object.x, object.y, (fields.len() / 2) as u8]);
+            table.extend(fields);
+        }
+        table.push(0xFF);
+    }
+    table
+}
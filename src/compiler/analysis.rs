@@ -1,10 +1,34 @@
+use crate::compiler::actors;
 use crate::compiler::ast::{DataType, Expression, Program, Statement, TopLevel};
+use crate::compiler::collision;
+use crate::compiler::mapper::{self, Mapper};
+use crate::compiler::password::PasswordFormat;
 use crate::compiler::symbol_table::{SymbolKind, SymbolTable};
+use std::collections::BTreeSet;
+
+const DIRECTIONS: [&str; 4] = ["Up", "Down", "Left", "Right"];
 
 pub struct SemanticAnalyzer {
     pub symbol_table: SymbolTable,
     errors: Vec<String>,
     unsafe_return_depth: usize,
+    mapper: Mapper,
+    has_save: bool,
+    has_password: bool,
+    reads_nametable: bool,
+    streams_world: bool,
+    reads_world: bool,
+    reads_collision: bool,
+    splits_hud: bool,
+    spawns_room_objects: bool,
+    // ACTOR type names, in kind order
+    actor_types: Vec<String>,
+    // WORLD map (width, height, rooms) and how the program moves through it:
+    // literal World.Enter rooms (None once one is computed) and
+    // World.Transition directions (all four once one is computed)
+    world: Option<(u32, u32, Vec<i32>)>,
+    world_entries: Option<Vec<(u32, u32)>>,
+    world_directions: BTreeSet<usize>,
 }
 
 impl Default for SemanticAnalyzer {
@@ -19,6 +43,19 @@ impl SemanticAnalyzer {
             symbol_table: SymbolTable::new(),
             errors: Vec::new(),
             unsafe_return_depth: 0,
+            mapper: Mapper::Nrom,
+            has_save: false,
+            has_password: false,
+            reads_nametable: false,
+            streams_world: false,
+            reads_world: false,
+            reads_collision: false,
+            splits_hud: false,
+            spawns_room_objects: false,
+            actor_types: Vec::new(),
+            world: None,
+            world_entries: Some(Vec::new()),
+            world_directions: BTreeSet::new(),
         };
         analyzer.register_stdlib();
         analyzer
@@ -38,6 +75,48 @@ impl SemanticAnalyzer {
         ];
         let _ = self.symbol_table.define_enum("Button".to_string(), buttons);
 
+        // Flip Enum (Sprite.Draw / Animation.Draw): OAM attribute flip bits
+        let flips = vec![("H".to_string(), 0x40), ("V".to_string(), 0x80)];
+        let _ = self.symbol_table.define_enum("Flip".to_string(), flips);
+
+        // SpriteLayer Enum (Sprite.Layer): Fixed draws use the reserved OAM slots
+        let layers = vec![("Normal".to_string(), 0), ("Fixed".to_string(), 1)];
+        let _ = self
+            .symbol_table
+            .define_enum("SpriteLayer".to_string(), layers);
+
+        // HudPosition Enum (HUD.Split): where the fixed status bar is
+        let positions = vec![("Top".to_string(), 0), ("Bottom".to_string(), 1)];
+        let _ = self
+            .symbol_table
+            .define_enum("HudPosition".to_string(), positions);
+
+        // Direction Enum (World.Transition)
+        let directions = DIRECTIONS
+            .iter()
+            .enumerate()
+            .map(|(i, name)| (name.to_string(), i as i32))
+            .collect();
+        let _ = self
+            .symbol_table
+            .define_enum("Direction".to_string(), directions);
+
+        // TileFlag Enum (Collision.Flags)
+        let flags = collision::FLAGS
+            .iter()
+            .map(|(name, bit)| (name.to_string(), *bit as i32))
+            .collect();
+        let _ = self.symbol_table.define_enum("TileFlag".to_string(), flags);
+
+        // Contact Enum (Physics.MoveAndCollide)
+        let contacts = collision::CONTACTS
+            .iter()
+            .map(|(name, bit)| (name.to_string(), *bit as i32))
+            .collect();
+        let _ = self
+            .symbol_table
+            .define_enum("Contact".to_string(), contacts);
+
         // AnimState Struct
         let anim_state_members = vec![
             ("ptr".to_string(), DataType::Word, 0),
@@ -48,6 +127,58 @@ impl SemanticAnalyzer {
         let _ = self
             .symbol_table
             .define_struct("AnimState".to_string(), anim_state_members, 5);
+
+        // Body Struct (Physics.MoveAndCollide): world position in pixels and
+        // 1/16 pixel, hitbox size
+        let body_members = vec![
+            ("x".to_string(), DataType::Word, 0),
+            ("y".to_string(), DataType::Word, 2),
+            ("sub_x".to_string(), DataType::Byte, 4),
+            ("sub_y".to_string(), DataType::Byte, 5),
+            ("width".to_string(), DataType::Byte, 6),
+            ("height".to_string(), DataType::Byte, 7),
+        ];
+        let _ = self
+            .symbol_table
+            .define_struct("Body".to_string(), body_members, 8);
+    }
+
+    /// Whether the program reads the ROM nametable directly (Collision.Tile),
+    /// which needs it stored uncompressed.
+    pub fn reads_nametable(&self) -> bool {
+        self.reads_nametable
+    }
+
+    /// Whether Camera.Update or a scrolling World.Transition streams world
+    /// screens from ROM, which needs every nametable and the room map stored
+    /// uncompressed.
+    pub fn streams_world(&self) -> bool {
+        self.streams_world
+    }
+
+    /// Whether HUD.Split is used, which needs an opaque sprite-0 tile in the
+    /// CHR bank.
+    pub fn splits_hud(&self) -> bool {
+        self.splits_hud
+    }
+
+    /// Whether World.SpawnRoomObjects is used, which needs the world's
+    /// per-room spawn lists in ROM.
+    pub fn spawns_room_objects(&self) -> bool {
+        self.spawns_room_objects
+    }
+
+    /// Whether World.Enter / World.Transition look rooms up in the ROM room
+    /// map, which needs it stored uncompressed.
+    pub fn reads_world(&self) -> bool {
+        self.reads_world
+    }
+
+    /// Whether Collision.Flags or Physics.MoveAndCollide looks tiles up in
+    /// the ROM screens, which needs the collision table and every screen and
+    /// the room map stored uncompressed.
+    pub fn reads_collision(&self) -> bool {
+        self.reads_collision
     }
 
     pub fn analyze(&mut self, program: &Program) -> Result<(), Vec<String>> {
@@ -92,6 +223,20 @@ impl SemanticAnalyzer {
                         }
                     }
                 }
+                TopLevel::SaveDim(name, dtype) => {
+                    self.has_save = true;
+                    if let Err(e) =
+                        self.symbol_table
+                            .define(name.clone(), dtype.clone(), SymbolKind::Variable)
+                    {
+                        self.errors.push(e);
+                    }
+                    // Strings live in the RAM heap, which is not saved
+                    if *dtype == DataType::String {
+                        self.errors
+                            .push(format!("SAVE variable '{}' cannot be a STRING", name));
+                    }
+                }
                 TopLevel::Sub(name, params, _body) => {
                     let param_types = params.iter().map(|(_, t)| t.clone()).collect();
                     if let Err(e) = self.symbol_table.define_with_params(
@@ -161,15 +306,24 @@ impl SemanticAnalyzer {
                         self.errors.push(e);
                     }
                 }
-                TopLevel::Metasprite(name, _) => {
+                TopLevel::Metasprite(name, _, _, _) => {
                     if let Err(e) = self.symbol_table.define_metasprite(name.clone()) {
                         self.errors.push(e);
                     }
                 }
+                TopLevel::World(width, height, data) => {
+                    self.world = Some((*width, *height, data.clone()));
+                }
                 _ => {}
             }
         }
 
+        self.define_actors(program);
+        self.check_world_objects(program);
+        self.check_banks(program);
+        self.check_password(program);
+        self.check_sprite_size(program);
+
         // Second pass: analyze bodies
         for decl in &program.declarations {
             match decl {
@@ -212,6 +366,7 @@ impl SemanticAnalyzer {
                 _ => {}
             }
         }
+        self.check_world_rooms();
 
         if self.errors.is_empty() {
             Ok(())
@@ -220,6 +375,820 @@ impl SemanticAnalyzer {
         }
     }
 
+    // BANK directives must fit the selected mapper; the runtime calls Main and
+    // the interrupt handlers directly, so those stay in the fixed bank.
+    fn check_banks(&mut self, program: &Program) {
+        let target = match mapper::target(program) {
+            Ok(m) => m,
+            Err(e) => {
+                self.errors.push(e);
+                return;
+            }
+        };
+        self.mapper = target;
+        let mut bank: Option<u8> = None;
+        for decl in &program.declarations {
+            match decl {
+                TopLevel::Bank(Some(n)) => {
+                    let available = target.switchable_banks();
+                    if available == 0 {
+                        self.errors.push(format!(
+                            "BANK {} requires a bank-switching mapper (e.g. MAPPER MMC1)",
+                            n
+                        ));
+                    } else if *n as usize >= available {
+                        self.errors.push(format!(
+                            "BANK {} is out of range for {} (banks 0-{})",
+                            n,
+                            target.name(),
+                            available - 1
+                        ));
+                    }
+                    bank = Some(*n);
+                }
+                TopLevel::Bank(None) => bank = None,
+                TopLevel::Sub(name, _, _) if bank.is_some() && name == "Main" => {
+                    self.errors
+                        .push("SUB Main must be in the fixed bank".to_string());
+                }
+                TopLevel::Interrupt(name, _) if bank.is_some() => {
+                    self.errors
+                        .push(format!("INTERRUPT {} must be in the fixed bank", name));
+                }
+                TopLevel::SaveDim(name, _) if !target.has_prg_ram() => {
+                    self.errors.push(format!(
+                        "SAVE variable '{}' requires a mapper with PRG-RAM (target is {})",
+                        name,
+                        target.name()
+                    ));
+                }
+                _ => {}
+            }
+        }
+    }
+
+    // ACTOR types share one table: the `Actor` header type seen as Actors(),
+    // one view per ACTOR over the same slots padded to the largest, and the
+    // ActorType / ActorClass enums. Handlers get the slot as their argument.
+    fn define_actors(&mut self, program: &Program) {
+        let decls = actors::declarations(program);
+        if decls.is_empty() {
+            return;
+        }
+        if decls.len() > actors::MAX_KINDS {
+            self.errors.push(format!(
+                "{} ACTOR types declared (at most {})",
+                decls.len(),
+                actors::MAX_KINDS
+            ));
+        }
+        let slots = actors::slots(program);
+        if slots > actors::MAX_SLOTS {
+            self.errors.push(format!(
+                "ACTORS {} is out of range (1-{})",
+                slots,
+                actors::MAX_SLOTS
+            ));
+        }
+
+        let mut views = Vec::new();
+        let mut stride = actors::HEADER_SIZE;
+        for actor in &decls {
+            let mut members = actors::header();
+            let mut offset = actors::HEADER_SIZE;
+            for (name, dtype, value) in &actor.fields {
+                if members.iter().any(|(n, _, _)| n == name) {
+                    self.errors.push(format!(
+                        "ACTOR '{}' field '{}' is already defined",
+                        actor.name, name
+                    ));
+                }
+                let size = self.get_type_size(dtype);
+                if size == 0 && matches!(dtype, DataType::Struct(_)) {
+                    self.errors.push(format!(
+                        "Undefined or invalid type for field '{}' in ACTOR '{}'",
+                        name, actor.name
+                    ));
+                }
+                if let Some(value) = value {
+                    if !matches!(
+                        dtype,
+                        DataType::Byte
+                            | DataType::Word
+                            | DataType::Int
+                            | DataType::Bool
+                            | DataType::Enum(_)
+                    ) {
+                        self.errors.push(format!(
+                            "ACTOR '{}' field '{}' cannot have a spawn value (BYTE, WORD, INT, BOOL or enum only)",
+                            actor.name, name
+                        ));
+                    }
+                    self.analyze_expression(value);
+                }
+                members.push((name.clone(), dtype.clone(), offset));
+                offset += size;
+            }
+            if let Some((w, h)) = &actor.size {
+                self.analyze_expression(w);
+                self.analyze_expression(h);
+            }
+            stride = stride.max(offset);
+            views.push((actor.name.clone(), members));
+        }
+        if stride > 255 {
+            self.errors.push(format!(
+                "ACTOR fields take {} bytes per slot (at most 255)",
+                stride
+            ));
+        }
+
+        let table = |name: &str| {
+            DataType::Array(Box::new(DataType::Struct(name.to_string())), slots as usize)
+        };
+        let mut results = vec![
+            self.symbol_table
+                .define_struct("Actor".to_string(), actors::header(), stride),
+            self.symbol_table
+                .define("Actors".to_string(), table("Actor"), SymbolKind::Variable),
+        ];
+        for (name, members) in views {
+            let struct_name = actors::struct_name(&name);
+            results.push(
+                self.symbol_table
+                    .define_struct(struct_name.clone(), members, stride),
+            );
+            results.push(
+                self.symbol_table
+                    .define(name, table(&struct_name), SymbolKind::Variable),
+            );
+        }
+        self.actor_types = decls.iter().map(|a| a.name.clone()).collect();
+        let kinds = self
+            .actor_types
+            .iter()
+            .enumerate()
+            .map(|(i, name)| (name.clone(), i as i32))
+            .collect();
+        results.push(
+            self.symbol_table
+                .define_enum("ActorType".to_string(), kinds),
+        );
+
+        let classes = actors::classes(program);
+        if classes.len() > actors::MAX_CLASSES {
+            self.errors.push(format!(
+                "At most {} actor classes are allowed (found {})",
+                actors::MAX_CLASSES,
+                classes.len()
+            ));
+        }
+        let bits = classes
+            .into_iter()
+            .take(actors::MAX_CLASSES)
+            .enumerate()
+            .map(|(i, name)| (name, 1 << i))
+            .collect();
+        results.push(
+            self.symbol_table
+                .define_enum("ActorClass".to_string(), bits),
+        );
+        self.errors
+            .extend(results.into_iter().filter_map(Result::err));
+
+        for actor in &decls {
+            for (clause, handler) in [("UPDATE", &actor.update), ("DRAW", &actor.draw)] {
+                let Some(handler) = handler else {
+                    continue;
+                };
+                match self.symbol_table.resolve(handler) {
+                    Some(sym) if sym.kind == SymbolKind::Sub => {
+                        let params = sym.params.clone().unwrap_or_default();
+                        if params.len() > 1 || params.first().is_some_and(|p| *p != DataType::Byte)
+                        {
+                            self.errors.push(format!(
+                                "ACTOR '{}' {} handler '{}' must take no arguments or one BYTE (the slot)",
+                                actor.name, clause, handler
+                            ));
+                        }
+                    }
+                    _ => self.errors.push(format!(
+                        "ACTOR '{}' {} handler '{}' is not a SUB",
+                        actor.name, clause, handler
+                    )),
+                }
+            }
+        }
+    }
+
+    // Objects placed in WORLD rooms: each must be an ACTOR in source, in a
+    // room of the world, and set only fields the ACTOR could give a spawn
+    // value, with values that fit them.
+    fn check_world_objects(&mut self, program: &Program) {
+        for object in actors::placed_objects(program) {
+            let (x, y) = object.room;
+            let name = &object.actor;
+            if !self.actor_types.contains(name) {
+                self.errors.push(format!(
+                    "World object '{}' in room ({}, {}) is not an ACTOR in source",
+                    name, x, y
+                ));
+                continue;
+            }
+            match &self.world {
+                Some((width, height, _)) if x >= *width || y >= *height => {
+                    self.errors.push(format!(
+                        "World object '{}' in room ({}, {}) is outside the {}x{} world",
+                        name, x, y, width, height
+                    ));
+                }
+                Some((width, _, data)) if data.get((y * width + x) as usize) == Some(&-1) => {
+                    self.errors.push(format!(
+                        "World object '{}' is in room ({}, {}), which is empty (-1)",
+                        name, x, y
+                    ));
+                }
+                _ => {}
+            }
+            if object.y >= 240 {
+                self.errors.push(format!(
+                    "World object '{}' in room ({}, {}) is below the room (y {}, 0-239)",
+                    name, x, y, object.y
+                ));
+            }
+            let members = self
+                .symbol_table
+                .resolve(&actors::struct_name(name))
+                .and_then(|sym| sym.members.clone())
+                .unwrap_or_default();
+            for (field, value) in &object.params {
+                let dtype = members
+                    .iter()
+                    .skip(actors::header().len())
+                    .find(|(n, _, _)| n == field)
+                    .map(|(_, dtype, _)| dtype);
+                let range = match dtype {
+                    Some(DataType::Word) => -32768..=65535,
+                    Some(DataType::Byte | DataType::Int | DataType::Bool | DataType::Enum(_)) => {
+                        -128..=255
+                    }
+                    Some(_) => {
+                        self.errors.push(format!(
+                            "World object '{}' cannot set field '{}' (BYTE, WORD, INT, BOOL or enum only)",
+                            name, field
+                        ));
+                        continue;
+                    }
+                    None => {
+                        self.errors.push(format!(
+                            "World object '{}' sets unknown field '{}'",
+                            name, field
+                        ));
+                        continue;
+                    }
+                };
+                if !range.contains(value) {
+                    self.errors.push(format!(
+                        "World object '{}' field '{}' value {} is out of range",
+                        name, field, value
+                    ));
+                }
+            }
+        }
+    }
+
+    // Actors.UpdateAll() / Actors.DrawAll() / Actors.Clear() /
+    // Actors.Spawn(type, x, y) / Actors.Despawn(slot) /
+    // Actors.Collide(slot, classes) / Actors.Overlap(slotA, slotB)
+    fn check_actors_call(&mut self, member: &str, args: &[Expression]) {
+        let (expected, usage) = if member.eq_ignore_ascii_case("UpdateAll")
+            || member.eq_ignore_ascii_case("DrawAll")
+            || member.eq_ignore_ascii_case("Clear")
+        {
+            (0, "no arguments")
+        } else if member.eq_ignore_ascii_case("Spawn") {
+            (3, "3 arguments (type, x, y)")
+        } else if member.eq_ignore_ascii_case("Despawn") {
+            (1, "1 argument (slot)")
+        } else if member.eq_ignore_ascii_case("Collide") {
+            (2, "2 arguments (slot, classes)")
+        } else if member.eq_ignore_ascii_case("Overlap") {
+            (2, "2 arguments (slotA, slotB)")
+        } else {
+            self.errors.push(format!(
+                "Unknown Actors command '{}' (UpdateAll, DrawAll, Spawn, Despawn, Clear, Collide, Overlap)",
+                member
+            ));
+            return;
+        };
+        if self.actor_types.is_empty() {
+            self.errors
+                .push(format!("Actors.{} requires an ACTOR declaration", member));
+            return;
+        }
+        if args.len() != expected {
+            self.errors
+                .push(format!("Actors.{} expects {}", member, usage));
+            return;
+        }
+        for arg in args {
+            self.analyze_expression(arg);
+        }
+        if member.eq_ignore_ascii_case("Spawn") {
+            let is_actor =
+                matches!(&args[0], Expression::Identifier(name) if self.actor_types.contains(name));
+            if !is_actor {
+                self.errors
+                    .push("Actors.Spawn first argument must be an ACTOR type".to_string());
+            }
+        }
+    }
+
+    // PASSWORD fields must be global scalars that fit their bit width.
+    fn check_password(&mut self, program: &Program) {
+        for decl in &program.declarations {
+            let TopLevel::Password(fields, alphabet, key) = decl else {
+                continue;
+            };
+            if self.has_password {
+                self.errors
+                    .push("Only one PASSWORD declaration is allowed".to_string());
+                return;
+            }
+            self.has_password = true;
+            if let Err(e) = PasswordFormat::new(fields.clone(), alphabet.as_deref(), *key) {
+                self.errors.push(e);
+            }
+            for (name, width) in fields {
+                let max = match self.symbol_table.resolve(name) {
+                    Some(sym) if sym.kind == SymbolKind::Variable => match sym.data_type {
+                        DataType::Byte | DataType::Bool => 8,
+                        DataType::Word | DataType::Int => 16,
+                        _ => 0,
+                    },
+                    _ => {
+                        self.errors.push(format!(
+                            "PASSWORD field '{}' is not a global variable",
+                            name
+                        ));
+                        continue;
+                    }
+                };
+                if max == 0 {
+                    self.errors.push(format!(
+                        "PASSWORD field '{}' must be a BYTE, BOOL, WORD or INT",
+                        name
+                    ));
+                } else if *width > max {
+                    self.errors.push(format!(
+                        "PASSWORD field '{}' holds {} bits but uses {}",
+                        name, max, width
+                    ));
+                }
+            }
+        }
+    }
+
+    // 8x16 sprites draw an even tile over the next one; tiles 256-510 come
+    // from the $1000 pattern table.
+    fn check_sprite_size(&mut self, program: &Program) {
+        let mut height = None;
+        for decl in &program.declarations {
+            if let TopLevel::Sprites(h) = decl {
+                if height.is_some_and(|prev| prev != *h) {
+                    self.errors
+                        .push("Conflicting SPRITES directives (8X8 and 8X16)".to_string());
+                    return;
+                }
+                height = Some(*h);
+            }
+        }
+        if height != Some(16) {
+            return;
+        }
+        for decl in &program.declarations {
+            let TopLevel::Metasprite(name, tiles, _, _) = decl else {
+                continue;
+            };
+            for tile in tiles {
+                let value = match &tile.tile {
+                    Expression::Integer(v) => Some(*v),
+                    Expression::Identifier(c) => self.symbol_table.resolve(c).and_then(|s| s.value),
+                    _ => None,
+                };
+                if let Some(v) = value.filter(|v| v % 2 != 0 || !(0..=510).contains(v)) {
+                    self.errors.push(format!(
+                        "Metasprite '{}' uses tile {}; 8X16 sprites need an even tile (0-510)",
+                        name, v
+                    ));
+                }
+            }
+        }
+    }
+
+    // Save.Commit / Save.Verify checksum the DIM SAVE region, so there has to
+    // be one.
+    fn check_save_call(&mut self, member: &str, args: &[Expression]) {
+        if !args.is_empty() {
+            self.errors
+                .push(format!("Save.{} expects no arguments", member));
+        } else if !self.has_save {
+            self.errors.push(format!(
+                "Save.{} requires at least one DIM SAVE variable",
+                member
+            ));
+        }
+    }
+
+    // Screen.Load(id) / Screen.LoadTo(id, nametable); the PPU has four
+    // nametable slots ($2000, $2400, $2800, $2C00).
+    fn check_screen_call(&mut self, member: &str, args: &[Expression]) {
+        let expected = if member.eq_ignore_ascii_case("Load") {
+            1
+        } else if member.eq_ignore_ascii_case("LoadTo") {
+            2
+        } else {
+            self.errors.push(format!(
+                "Unknown Screen command '{}' (Load, LoadTo)",
+                member
+            ));
+            return;
+        };
+        if args.len() != expected {
+            let usage = if expected == 1 {
+                "1 argument (id)"
+            } else {
+                "2 arguments (id, nametable)"
+            };
+            self.errors
+                .push(format!("Screen.{} expects {}", member, usage));
+            return;
+        }
+        for arg in args {
+            self.analyze_expression(arg);
+        }
+        if let Some(Expression::Integer(n)) = args.get(1) {
+            if !(0..=3).contains(n) {
+                self.errors.push(format!(
+                    "Screen.LoadTo nametable {} is out of range (0-3)",
+                    n
+                ));
+            }
+        }
+    }
+
+    // Palette.Set(entry, color) / Palette.Load(set) / Palette.FadeIn(frames)
+    // / Palette.FadeOut(frames) / Palette.Cycle(palette, start, length, speed);
+    // a cycle stays inside one of the eight 4-color palettes.
+    fn check_palette_call(&mut self, member: &str, args: &[Expression]) {
+        let (expected, usage) = if member.eq_ignore_ascii_case("Set") {
+            (2, "2 arguments (entry, color)")
+        } else if member.eq_ignore_ascii_case("Load") {
+            (1, "1 argument (set)")
+        } else if member.eq_ignore_ascii_case("FadeIn") || member.eq_ignore_ascii_case("FadeOut") {
+            (1, "1 argument (frames)")
+        } else if member.eq_ignore_ascii_case("Cycle") {
+            (4, "4 arguments (palette, start, length, speed)")
+        } else {
+            self.errors.push(format!(
+                "Unknown Palette command '{}' (Set, Load, FadeIn, FadeOut, Cycle)",
+                member
+            ));
+            return;
+        };
+        if args.len() != expected {
+            self.errors
+                .push(format!("Palette.{} expects {}", member, usage));
+            return;
+        }
+        for arg in args {
+            self.analyze_expression(arg);
+        }
+        let literal = |i: usize| match args[i] {
+            Expression::Integer(v) => Some(v),
+            _ => None,
+        };
+        let ranges: &[(&str, i32)] = if member.eq_ignore_ascii_case("Set") {
+            &[("entry", 31), ("color", 63)]
+        } else if member.eq_ignore_ascii_case("Cycle") {
+            &[("palette", 7), ("start", 3), ("length", 4)]
+        } else {
+            &[]
+        };
+        for (i, (name, max)) in ranges.iter().enumerate() {
+            if let Some(v) = literal(i).filter(|v| !(0..=*max).contains(v)) {
+                self.errors.push(format!(
+                    "Palette.{} {} {} is out of range (0-{})",
+                    member, name, v, max
+                ));
+                return;
+            }
+        }
+        if !member.eq_ignore_ascii_case("Cycle") {
+            return;
+        }
+        if let (Some(start), Some(length)) = (literal(1), literal(2)) {
+            if start + length > 4 {
+                self.errors.push(format!(
+                    "Palette.Cycle range {}..{} runs past the palette's 4 colors",
+                    start,
+                    start + length - 1
+                ));
+            }
+        }
+    }
+
+    // Scroll.LoadColumn/LoadRow read from a whole array or from an element
+    // of one, such as `map(i * 30)`
+    fn is_array_or_element(&self, expr: &Expression) -> bool {
+        let array = match expr {
+            Expression::Call(callee, args) if args.len() == 1 => callee,
+            _ => expr,
+        };
+        matches!(self.resolve_type(array), Some(DataType::Array(_, _)))
+    }
+
+    // HUD.Split(scanline [, position]); 0 turns the split off. The NMI waits
+    // for sprite 0 with a timeout that has to end before the next vblank.
+    fn check_hud_call(&mut self, member: &str, args: &[Expression]) {
+        if !member.eq_ignore_ascii_case("Split") {
+            self.errors
+                .push(format!("Unknown HUD command '{}' (Split)", member));
+            return;
+        }
+        if !(1..=2).contains(&args.len()) {
+            self.errors
+                .push("HUD.Split expects 1 or 2 arguments (scanline, position)".to_string());
+            return;
+        }
+        for arg in args {
+            self.analyze_expression(arg);
+        }
+        self.splits_hud = true;
+        if let Expression::Integer(line) = args[0] {
+            if line != 0 && !(2..=200).contains(&line) {
+                self.errors.push(format!(
+                    "HUD.Split scanline {} is out of range (2-200, or 0 for none)",
+                    line
+                ));
+            }
+        }
+    }
+
+    // Camera.Follow(x, y) / Camera.Update() / Camera.Mode(4 | 8)
+    fn check_camera_call(&mut self, member: &str, args: &[Expression]) {
+        let expected = if member.eq_ignore_ascii_case("Follow") {
+            2
+        } else if member.eq_ignore_ascii_case("Update") {
+            self.streams_world = true;
+            0
+        } else if member.eq_ignore_ascii_case("Mode") {
+            1
+        } else {
+            self.errors.push(format!(
+                "Unknown Camera command '{}' (Follow, Update, Mode)",
+                member
+            ));
+            return;
+        };
+        if args.len() != expected {
+            let usage = match expected {
+                0 => "no arguments",
+                1 => "1 argument (4 or 8)",
+                _ => "2 arguments (x, y)",
+            };
+            self.errors
+                .push(format!("Camera.{} expects {}", member, usage));
+            return;
+        }
+        if expected == 1 && !matches!(args[0], Expression::Integer(4) | Expression::Integer(8)) {
+            self.errors
+                .push("Camera.Mode expects a literal 4 or 8".to_string());
+            return;
+        }
+        for arg in args {
+            self.analyze_expression(arg);
+        }
+    }
+
+    // World.Enter(roomX, roomY) / World.Transition(direction [, speed]) /
+    // World.SpawnRoomObjects(roomX, roomY)
+    fn check_world_call(&mut self, member: &str, args: &[Expression]) {
+        let expected = if member.eq_ignore_ascii_case("Enter") {
+            2..=2
+        } else if member.eq_ignore_ascii_case("Transition") {
+            1..=2
+        } else if member.eq_ignore_ascii_case("SpawnRoomObjects") {
+            if args.len() != 2 {
+                self.errors
+                    .push("World.SpawnRoomObjects expects 2 arguments (roomX, roomY)".to_string());
+                return;
+            }
+            if self.actor_types.is_empty() {
+                self.errors
+                    .push("World.SpawnRoomObjects requires an ACTOR declaration".to_string());
+            }
+            for arg in args {
+                self.analyze_expression(arg);
+            }
+            self.spawns_room_objects = true;
+            return;
+        } else {
+            self.errors.push(format!(
+                "Unknown World command '{}' (Enter, Transition, SpawnRoomObjects)",
+                member
+            ));
+            return;
+        };
+        if !expected.contains(&args.len()) {
+            let usage = if *expected.start() == 2 {
+                "2 arguments (roomX, roomY)"
+            } else {
+                "1 or 2 arguments (direction, speed)"
+            };
+            self.errors
+                .push(format!("World.{} expects {}", member, usage));
+            return;
+        }
+        for arg in args {
+            self.analyze_expression(arg);
+        }
+        self.reads_world = true;
+
+        if *expected.start() == 2 {
+            let room = match (&args[0], &args[1]) {
+                (Expression::Integer(x), Expression::Integer(y)) if *x >= 0 && *y >= 0 => {
+                    Some((*x as u32, *y as u32))
+                }
+                _ => None,
+            };
+            match (room, &self.world) {
+                (Some((x, y)), Some((width, height, data))) => {
+                    if x >= *width || y >= *height {
+                        self.errors.push(format!(
+                            "World.Enter room ({}, {}) is outside the {}x{} world",
+                            x, y, width, height
+                        ));
+                    } else if data.get((y * width + x) as usize) == Some(&-1) {
+                        self.errors
+                            .push(format!("World.Enter room ({}, {}) is empty (-1)", x, y));
+                    } else if let Some(entries) = &mut self.world_entries {
+                        entries.push((x, y));
+                    }
+                }
+                (None, _) => self.world_entries = None,
+                _ => {}
+            }
+            return;
+        }
+
+        let direction = match &args[0] {
+            Expression::Integer(n) => Some(*n as usize),
+            Expression::MemberAccess(base, variant) => match &**base {
+                Expression::Identifier(name) if name == "Direction" => {
+                    DIRECTIONS.iter().position(|d| d == variant)
+                }
+                _ => None,
+            },
+            _ => None,
+        };
+        match direction {
+            Some(d) if d < DIRECTIONS.len() => {
+                self.world_directions.insert(d);
+            }
+            Some(d) => self.errors.push(format!(
+                "World.Transition direction {} is out of range (0-3)",
+                d
+            )),
+            None => self.world_directions.extend(0..DIRECTIONS.len()),
+        }
+        if let Some(speed) = args.get(1) {
+            if !matches!(speed, Expression::Integer(1 | 2 | 4 | 8)) {
+                self.errors
+                    .push("World.Transition speed must be a literal 1, 2, 4 or 8".to_string());
+            }
+            self.streams_world = true;
+        }
+    }
+
+    // Physics.MoveAndCollide(body, dx, dy)
+    fn check_physics_call(&mut self, member: &str, args: &[Expression]) {
+        if !member.eq_ignore_ascii_case("MoveAndCollide") {
+            self.errors.push(format!(
+                "Unknown Physics command '{}' (MoveAndCollide)",
+                member
+            ));
+            return;
+        }
+        if args.len() != 3 {
+            self.errors
+                .push("Physics.MoveAndCollide expects 3 arguments (body, dx, dy)".to_string());
+            return;
+        }
+        for arg in args {
+            self.analyze_expression(arg);
+        }
+        if !matches!(self.resolve_type(&args[0]), Some(DataType::Struct(name)) if name == "Body") {
+            self.errors
+                .push("Physics.MoveAndCollide first argument must be Body".to_string());
+        }
+        self.reads_collision = true;
+    }
+
+    // Flood-fills the rooms the program can reach: from the World.Enter
+    // rooms (every room once one is computed, room (0, 0) when there are
+    // none) through the World.Transition directions it uses. Reaching an
+    // empty (-1) room is an error.
+    fn check_world_rooms(&mut self) {
+        let Some((width, height, data)) = &self.world else {
+            return;
+        };
+        let (width, height) = (*width as i64, *height as i64);
+        let room = |x: i64, y: i64| -> Option<i32> {
+            if x < 0 || y < 0 || x >= width || y >= height {
+                return None;
+            }
+            data.get((y * width + x) as usize).copied()
+        };
+        let mut pending: Vec<(i64, i64)> = match &self.world_entries {
+            Some(entries) if entries.is_empty() => vec![(0, 0)],
+            Some(entries) => entries
+                .iter()
+                .map(|(x, y)| (*x as i64, *y as i64))
+                .collect(),
+            None => (0..height)
+                .flat_map(|y| (0..width).map(move |x| (x, y)))
+                .collect(),
+        };
+        pending.retain(|(x, y)| room(*x, *y).is_some_and(|r| r != -1));
+        let mut seen: BTreeSet<(i64, i64)> = pending.iter().copied().collect();
+        let mut reported = BTreeSet::new();
+        let mut errors = Vec::new();
+        while let Some((x, y)) = pending.pop() {
+            for &d in &self.world_directions {
+                let (nx, ny) = match d {
+                    0 => (x, y - 1),
+                    1 => (x, y + 1),
+                    2 => (x - 1, y),
+                    _ => (x + 1, y),
+                };
+                match room(nx, ny) {
+                    Some(-1) if reported.insert((nx, ny)) => errors.push(format!(
+                        "World room ({}, {}) is empty (-1) but \
+                         World.Transition(Direction.{}) reaches it from room ({}, {})",
+                        nx, ny, DIRECTIONS[d], x, y
+                    )),
+                    Some(-1) => {}
+                    Some(_) if seen.insert((nx, ny)) => pending.push((nx, ny)),
+                    _ => {}
+                }
+            }
+        }
+        self.errors.extend(errors);
+    }
+
+    // PRG.Bank(n) / CHR.Bank(n): only on mappers that can switch that memory,
+    // and literal banks must exist on the selected mapper.
+    fn check_bank_call(&mut self, base: &str, member: &str, args: &[Expression]) {
+        if !member.eq_ignore_ascii_case("Bank") {
+            self.errors.push(format!(
+                "Unknown {} command '{}' (did you mean Bank?)",
+                base, member
+            ));
+            return;
+        }
+        if args.len() != 1 {
+            self.errors
+                .push(format!("{}.Bank expects 1 argument (bank)", base));
+            return;
+        }
+        self.analyze_expression(&args[0]);
+        // A single CHR bank means there is nothing to switch to
+        let (available, minimum, kind) = if base == "PRG" {
+            (self.mapper.switchable_banks(), 1, "switchable PRG banks")
+        } else {
+            (self.mapper.chr_banks(), 2, "CHR-ROM banking")
+        };
+        if available < minimum {
+            self.errors.push(format!(
+                "{}.Bank requires a mapper with {} ({} has none)",
+                base,
+                kind,
+                self.mapper.name()
+            ));
+        } else if let Expression::Integer(n) = &args[0] {
+            if *n < 0 || *n as usize >= available {
+                self.errors.push(format!(
+                    "{} bank {} is out of range for {} (banks 0-{})",
+                    base,
+                    n,
+                    self.mapper.name(),
+                    available - 1
+                ));
+            }
+        }
+    }
+
     fn analyze_block(&mut self, statements: &[Statement]) {
         for stmt in statements {
             self.analyze_statement(stmt);
@@ -351,14 +1320,17 @@ impl SemanticAnalyzer {
                             }
                         } else if base_name.eq_ignore_ascii_case("Sprite") {
                             if member.eq_ignore_ascii_case("Draw") {
-                                if args.len() != 3 {
+                                if args.len() != 3 && args.len() != 4 {
                                     self.errors.push(
-                                        "Sprite.Draw expects 3 arguments (x, y, metasprite)"
+                                        "Sprite.Draw expects 3 or 4 arguments (x, y, metasprite[, flags])"
                                             .to_string(),
                                     );
                                 } else {
                                     self.analyze_expression(&args[0]);
                                     self.analyze_expression(&args[1]);
+                                    if let Some(flags) = args.get(3) {
+                                        self.analyze_expression(flags);
+                                    }
                                     // 3rd arg is metasprite name, but we can't easily validate type here
                                     // if it's passed as a variable (pointer).
                                     // If it's an identifier, we can check if it's a metasprite.
@@ -395,9 +1367,26 @@ impl SemanticAnalyzer {
                                     self.analyze_expression(&args[0]);
                                 }
                                 return;
+                            } else if member.eq_ignore_ascii_case("Reserve")
+                                || member.eq_ignore_ascii_case("Layer")
+                            {
+                                if args.len() != 1 {
+                                    let arg = if member.eq_ignore_ascii_case("Reserve") {
+                                        "slots"
+                                    } else {
+                                        "layer"
+                                    };
+                                    self.errors.push(format!(
+                                        "Sprite.{} expects 1 argument ({})",
+                                        member, arg
+                                    ));
+                                } else {
+                                    self.analyze_expression(&args[0]);
+                                }
+                                return;
                             } else {
                                 self.errors.push(format!(
-                                    "Unknown Sprite command '{}' (Draw, Clear, SetFlicker)",
+                                    "Unknown Sprite command '{}' (Draw, Clear, SetFlicker, Reserve, Layer)",
                                     member
                                 ));
                                 return;
@@ -466,15 +1455,15 @@ impl SemanticAnalyzer {
                                 }
                                 return;
                             } else if member.eq_ignore_ascii_case("Draw") {
-                                if args.len() != 3 {
+                                if args.len() != 3 && args.len() != 4 {
                                     self.errors.push(
-                                        "Animation.Draw expects 3 arguments (x, y, state)"
+                                        "Animation.Draw expects 3 or 4 arguments (x, y, state[, flags])"
                                             .to_string(),
                                     );
                                 } else {
-                                    self.analyze_expression(&args[0]);
-                                    self.analyze_expression(&args[1]);
-                                    self.analyze_expression(&args[2]);
+                                    for arg in args {
+                                        self.analyze_expression(arg);
+                                    }
                                     if let Some(DataType::Struct(name)) =
                                         self.resolve_type(&args[2])
                                     {
@@ -553,10 +1542,7 @@ impl SemanticAnalyzer {
                                 } else {
                                     self.analyze_expression(&args[0]);
                                     self.analyze_expression(&args[1]);
-                                    if let Some(DataType::Array(_, _)) = self.resolve_type(&args[1])
-                                    {
-                                        // OK
-                                    } else {
+                                    if !self.is_array_or_element(&args[1]) {
                                         self.errors.push(
                                             "Scroll.LoadColumn expects an array as 2nd argument"
                                                 .to_string(),
@@ -572,10 +1558,7 @@ impl SemanticAnalyzer {
                                 } else {
                                     self.analyze_expression(&args[0]);
                                     self.analyze_expression(&args[1]);
-                                    if let Some(DataType::Array(_, _)) = self.resolve_type(&args[1])
-                                    {
-                                        // OK
-                                    } else {
+                                    if !self.is_array_or_element(&args[1]) {
                                         self.errors.push(
                                             "Scroll.LoadRow expects an array as 2nd argument"
                                                 .to_string(),
@@ -590,6 +1573,54 @@ impl SemanticAnalyzer {
                                 ));
                                 return;
                             }
+                        } else if base_name.eq_ignore_ascii_case("Save") {
+                            if member.eq_ignore_ascii_case("Commit") {
+                                self.check_save_call(member, args);
+                            } else {
+                                self.errors
+                                    .push(format!("Unknown Save command '{}'", member));
+                            }
+                            return;
+                        } else if base_name.eq_ignore_ascii_case("CHR")
+                            && member.eq_ignore_ascii_case("Load")
+                        {
+                            if args.len() != 3 {
+                                self.errors.push(
+                                    "CHR.Load expects 3 arguments (bank, tileStart, count)"
+                                        .to_string(),
+                                );
+                            } else {
+                                for arg in args {
+                                    self.analyze_expression(arg);
+                                }
+                            }
+                            return;
+                        } else if base_name.eq_ignore_ascii_case("Screen") {
+                            self.check_screen_call(member, args);
+                            return;
+                        } else if base_name.eq_ignore_ascii_case("Palette") {
+                            self.check_palette_call(member, args);
+                            return;
+                        } else if base_name.eq_ignore_ascii_case("HUD") {
+                            self.check_hud_call(member, args);
+                            return;
+                        } else if base_name.eq_ignore_ascii_case("Actors") {
+                            self.check_actors_call(member, args);
+                            return;
+                        } else if base_name.eq_ignore_ascii_case("Camera") {
+                            self.check_camera_call(member, args);
+                            return;
+                        } else if base_name.eq_ignore_ascii_case("World") {
+                            self.check_world_call(member, args);
+                            return;
+                        } else if base_name.eq_ignore_ascii_case("Physics") {
+                            self.check_physics_call(member, args);
+                            return;
+                        } else if base_name.eq_ignore_ascii_case("PRG")
+                            || base_name.eq_ignore_ascii_case("CHR")
+                        {
+                            self.check_bank_call(&base_name.to_uppercase(), member, args);
+                            return;
                         } else if base_name.eq_ignore_ascii_case("PPU") {
                             if member.eq_ignore_ascii_case("Ctrl")
                                 || member.eq_ignore_ascii_case("Mask")
@@ -601,6 +1632,18 @@ impl SemanticAnalyzer {
                                     self.analyze_expression(&args[0]);
                                 }
                                 return;
+                            } else if member.eq_ignore_ascii_case("Fill") {
+                                if args.len() != 3 {
+                                    self.errors.push(
+                                        "PPU.Fill expects 3 arguments (address, value, count)"
+                                            .to_string(),
+                                    );
+                                } else {
+                                    for arg in args {
+                                        self.analyze_expression(arg);
+                                    }
+                                }
+                                return;
                             } else {
                                 self.errors
                                     .push(format!("Unknown PPU command '{}'", member));
@@ -685,11 +1728,9 @@ impl SemanticAnalyzer {
                 }
                 self.analyze_expression(expr);
             }
-            Statement::Return(None) => {
-                if self.unsafe_return_depth > 0 {
-                    self.errors
-                        .push("Cannot RETURN from inside a loop or SELECT CASE block".to_string());
-                }
+            Statement::Return(None) if self.unsafe_return_depth > 0 => {
+                self.errors
+                    .push("Cannot RETURN from inside a loop or SELECT CASE block".to_string());
             }
             Statement::Poke(addr, val) => {
                 self.analyze_expression(addr);
@@ -727,6 +1768,26 @@ impl SemanticAnalyzer {
                 self.unsafe_return_depth -= 1;
             }
             Statement::WaitVBlank => {}
+            Statement::OnScanline(line, routine) => {
+                if self.mapper != Mapper::Mmc3 {
+                    self.errors.push(format!(
+                        "ON SCANLINE requires MAPPER MMC3 (target is {})",
+                        self.mapper.name()
+                    ));
+                }
+                self.analyze_expression(line);
+                match self.symbol_table.resolve(routine) {
+                    Some(sym) if sym.kind == SymbolKind::Sub => {
+                        if sym.params.as_ref().is_some_and(|p| !p.is_empty()) {
+                            self.errors.push(format!(
+                                "Scanline handler '{}' must not take arguments",
+                                routine
+                            ));
+                        }
+                    }
+                    _ => self.errors.push(format!("Undefined sub '{}'", routine)),
+                }
+            }
             Statement::Randomize(expr) => {
                 self.analyze_expression(expr);
             }
@@ -736,10 +1797,8 @@ impl SemanticAnalyzer {
 
     fn analyze_expression(&mut self, expr: &Expression) {
         match expr {
-            Expression::Identifier(name) => {
-                if self.symbol_table.resolve(name).is_none() {
-                    self.errors.push(format!("Undefined variable '{}'", name));
-                }
+            Expression::Identifier(name) if self.symbol_table.resolve(name).is_none() => {
+                self.errors.push(format!("Undefined variable '{}'", name));
             }
             Expression::MemberAccess(base, member) => {
                 // Check for Controller or Text or Sprite
@@ -768,6 +1827,12 @@ impl SemanticAnalyzer {
                     if base_name.eq_ignore_ascii_case("PPU") {
                         return;
                     }
+                    if base_name.eq_ignore_ascii_case("Save") {
+                        return;
+                    }
+                    if base_name.eq_ignore_ascii_case("Password") {
+                        return;
+                    }
                 }
 
                 self.analyze_expression(base);
@@ -985,6 +2050,7 @@ impl SemanticAnalyzer {
                                 }
                                 return;
                             } else if member.eq_ignore_ascii_case("Tile") {
+                                self.reads_nametable = true;
                                 if args.len() != 2 {
                                     self.errors.push(
                                         "Collision.Tile expects 2 arguments (x, y)".to_string(),
@@ -995,11 +2061,119 @@ impl SemanticAnalyzer {
                                     }
                                 }
                                 return;
+                            } else if member.eq_ignore_ascii_case("Flags") {
+                                self.reads_collision = true;
+                                if args.len() != 2 {
+                                    self.errors.push(
+                                        "Collision.Flags expects 2 arguments (worldX, worldY)"
+                                            .to_string(),
+                                    );
+                                } else {
+                                    for arg in args {
+                                        self.analyze_expression(arg);
+                                    }
+                                }
+                                return;
+                            } else if member.eq_ignore_ascii_case("Sprites") {
+                                if args.len() != 6 {
+                                    self.errors.push(
+                                        "Collision.Sprites expects 6 arguments (animA, xA, yA, animB, xB, yB)"
+                                            .to_string(),
+                                    );
+                                } else {
+                                    for arg in args {
+                                        self.analyze_expression(arg);
+                                    }
+                                    for idx in [0, 3] {
+                                        match self.resolve_type(&args[idx]) {
+                                            Some(DataType::Struct(name)) if name == "AnimState" => {}
+                                            _ => self.errors.push(
+                                                "Collision.Sprites animA and animB must be AnimState"
+                                                    .to_string(),
+                                            ),
+                                        }
+                                    }
+                                }
+                                return;
                             } else {
                                 self.errors
                                     .push(format!("Unknown Collision command '{}'", member));
                                 return;
                             }
+                        } else if base_name.eq_ignore_ascii_case("Save") {
+                            if member.eq_ignore_ascii_case("Verify") {
+                                self.check_save_call(member, args);
+                            } else {
+                                self.errors
+                                    .push(format!("Unknown Save function '{}'", member));
+                            }
+                            return;
+                        } else if base_name.eq_ignore_ascii_case("Physics") {
+                            self.check_physics_call(member, args);
+                            return;
+                        } else if base_name.eq_ignore_ascii_case("Actors") {
+                            self.check_actors_call(member, args);
+                            return;
+                        } else if base_name.eq_ignore_ascii_case("World") {
+                            if !member.eq_ignore_ascii_case("RoomX")
+                                && !member.eq_ignore_ascii_case("RoomY")
+                            {
+                                self.errors
+                                    .push(format!("Unknown World function '{}'", member));
+                            } else if !args.is_empty() {
+                                self.errors
+                                    .push(format!("World.{} expects no arguments", member));
+                            }
+                            return;
+                        } else if base_name.eq_ignore_ascii_case("Camera") {
+                            if !member.eq_ignore_ascii_case("X")
+                                && !member.eq_ignore_ascii_case("Y")
+                            {
+                                self.errors
+                                    .push(format!("Unknown Camera function '{}'", member));
+                            } else if !args.is_empty() {
+                                self.errors
+                                    .push(format!("Camera.{} expects no arguments", member));
+                            }
+                            return;
+                        } else if base_name.eq_ignore_ascii_case("PPU") {
+                            if !member.eq_ignore_ascii_case("Overflow") {
+                                self.errors
+                                    .push(format!("Unknown PPU function '{}'", member));
+                            } else if !args.is_empty() {
+                                self.errors
+                                    .push("PPU.Overflow expects no arguments".to_string());
+                            }
+                            return;
+                        } else if base_name.eq_ignore_ascii_case("Password") {
+                            let expected = if member.eq_ignore_ascii_case("Encode") {
+                                0
+                            } else if member.eq_ignore_ascii_case("Decode") {
+                                1
+                            } else {
+                                self.errors
+                                    .push(format!("Unknown Password function '{}'", member));
+                                return;
+                            };
+                            if !self.has_password {
+                                self.errors.push(format!(
+                                    "Password.{} requires a PASSWORD declaration",
+                                    member
+                                ));
+                            } else if args.len() != expected {
+                                self.errors.push(if expected == 0 {
+                                    "Password.Encode expects no arguments".to_string()
+                                } else {
+                                    "Password.Decode expects 1 argument (password)".to_string()
+                                });
+                            } else if expected == 1 {
+                                self.analyze_expression(&args[0]);
+                                if self.resolve_type(&args[0]) != Some(DataType::String) {
+                                    self.errors
+                                        .push("Password.Decode expects a STRING".to_string());
+                                }
+                            }
+                            return;
                         }
                     }
                 }
@@ -1115,13 +2289,45 @@ impl SemanticAnalyzer {
                         if base_name.eq_ignore_ascii_case("Collision") {
                             if member.eq_ignore_ascii_case("Rect")
                                 || member.eq_ignore_ascii_case("Point")
+                                || member.eq_ignore_ascii_case("Sprites")
                             {
                                 return Some(DataType::Bool);
                             }
-                            if member.eq_ignore_ascii_case("Tile") {
+                            if member.eq_ignore_ascii_case("Tile")
+                                || member.eq_ignore_ascii_case("Flags")
+                            {
                                 return Some(DataType::Byte);
                             }
                         }
+                        if (base_name.eq_ignore_ascii_case("Save")
+                            && member.eq_ignore_ascii_case("Verify"))
+                            || (base_name.eq_ignore_ascii_case("PPU")
+                                && member.eq_ignore_ascii_case("Overflow"))
+                        {
+                            return Some(DataType::Bool);
+                        }
+                        if base_name.eq_ignore_ascii_case("Camera") {
+                            return Some(DataType::Word);
+                        }
+                        if base_name.eq_ignore_ascii_case("World")
+                            || base_name.eq_ignore_ascii_case("Physics")
+                        {
+                            return Some(DataType::Byte);
+                        }
+                        if base_name.eq_ignore_ascii_case("Actors") {
+                            if member.eq_ignore_ascii_case("Overlap") {
+                                return Some(DataType::Bool);
+                            }
+                            return Some(DataType::Byte);
+                        }
+                        if base_name.eq_ignore_ascii_case("Password") {
+                            if member.eq_ignore_ascii_case("Encode") {
+                                return Some(DataType::String);
+                            }
+                            if member.eq_ignore_ascii_case("Decode") {
+                                return Some(DataType::Bool);
+                            }
+                        }
                     }
                 }
 
@@ -1,10 +1,28 @@
+use crate::compiler::cartridge::CartridgeConfig;
+use crate::compiler::linker::VECTORS_ADDR;
+use crate::compiler::mapper::{Mapper, CHR_BANK_SIZE, RESET_STUB_ADDR, SWITCH_WINDOW};
 use rs6502::Assembler as Rs6502Assembler;
+use std::collections::HashMap;
 
-pub struct Assembler;
+pub struct Assembler {
+    mapper: Mapper,
+    cartridge: CartridgeConfig,
+}
 
 impl Assembler {
     pub fn new() -> Self {
-        Self
+        Self::with_mapper(Mapper::Nrom)
+    }
+
+    /// An assembler producing an image for the given mapper.
+    pub fn with_mapper(mapper: Mapper) -> Self {
+        Self::with_cartridge(mapper, CartridgeConfig::default())
+    }
+
+    /// An assembler whose header and ROM sizes follow a project's cartridge
+    /// config. The config is expected to be validated for `mapper` already.
+    pub fn with_cartridge(mapper: Mapper, cartridge: CartridgeConfig) -> Self {
+        Self { mapper, cartridge }
     }
 
     /// Assembles a string of 6502 assembly code into a binary ROM with iNES header.
@@ -12,7 +30,7 @@ impl Assembler {
     ///
     /// # Arguments
     /// * `source` - The assembly source code
-    /// * `chr_data` - Optional 8KB CHR-ROM data
+    /// * `chr_data` - Optional CHR-ROM data (8KB on NROM, up to 256KB on MMC3)
     /// * `injections` - A list of (Address, Data) tuples to inject into PRG-ROM
     pub fn assemble(
         &self,
@@ -20,19 +38,63 @@ impl Assembler {
         chr_data: Option<&[u8]>,
         injections: Vec<(u16, Vec<u8>)>,
     ) -> Result<Vec<u8>, String> {
-        let mut assembler = Rs6502Assembler::new();
-        // 0 as offset means no global offset override, respect .ORG in source
-        let segments = assembler
-            .assemble_string(source, 0)
-            .map_err(|e| format!("Assembler error: {:?}", e))?;
+        let sections = injections
+            .into_iter()
+            .map(|(addr, data)| (format!("injection at ${:04X}", addr), None, addr, data))
+            .collect();
+        self.assemble_sections(source, chr_data, sections)
+    }
+
+    /// Like `assemble`, but each blob carries the name of the linker section it
+    /// belongs to, so overflows are reported against that section, and the
+    /// switchable bank it is placed in (`None` for the fixed bank).
+    pub fn assemble_sections(
+        &self,
+        source: &str,
+        chr_data: Option<&[u8]>,
+        sections: Vec<(String, Option<u8>, u16, Vec<u8>)>,
+    ) -> Result<Vec<u8>, String> {
+        let (fixed_source, banks) = split_banks(source)?;
+        if !banks.is_empty() && self.mapper.switchable_banks() == 0 {
+            return Err(format!(
+                "{} has no switchable PRG banks (found .BANK {})",
+                self.mapper.name(),
+                banks[0].0
+            ));
+        }
+        // rs6502 does not assemble WORD, so the interrupt vectors are
+        // resolved by label and written like any other fixed section
+        let mut sections = sections;
+        let vectors = vector_labels(&fixed_source);
+        if !vectors.is_empty() {
+            let data = self
+                .label_addresses(source, &vectors)?
+                .iter()
+                .flat_map(|addr| addr.to_le_bytes())
+                .collect();
+            sections.push(("vectors".to_string(), None, VECTORS_ADDR, data));
+        }
+        let (banked, sections): (Vec<_>, Vec<_>) = sections
+            .into_iter()
+            .partition(|(_, bank, _, _)| bank.is_some());
+        if let Some((name, Some(bank), _, _)) = banked.first() {
+            if self.mapper.switchable_banks() == 0 {
+                return Err(format!(
+                    "{} has no switchable PRG banks (section '{}' is in bank {})",
+                    self.mapper.name(),
+                    name,
+                    bank
+                ));
+            }
+        }
 
-        // Initialize NROM-256 (32KB PRG) buffer.
-        // NROM-256 is mapped at $8000-$FFFF.
-        let mut prg_rom = vec![0u8; 32768];
-        let mut usage_map = vec![false; 32768];
+        // The fixed bank: $8000-$FFFF on flat targets, $C000-$FFFF when banked
+        let base = self.mapper.fixed_bank_start();
+        let mut prg_rom = vec![0u8; 0x10000 - base as usize];
+        let mut usage_map = vec![false; prg_rom.len()];
 
         // Process Assembler Output Segments
-        for segment in segments {
+        for segment in assemble_segments(&fixed_source)? {
             let start = segment.address;
             let code = segment.code;
 
@@ -40,15 +102,15 @@ impl Assembler {
                 continue;
             }
 
-            // Check bounds to ensure code resides within PRG-ROM ($8000-$FFFF)
-            if start < 0x8000 {
+            // Check bounds to ensure code resides within PRG-ROM
+            if start < base {
                 return Err(format!(
-                    "Code segment starts at ${:04X}, which is outside PRG-ROM space ($8000+). Code: {:?}",
-                    start, code
+                    "Code segment starts at ${:04X}, which is outside PRG-ROM space (${:04X}+). Code: {:?}",
+                    start, base, code
                 ));
             }
 
-            let offset = (start - 0x8000) as usize;
+            let offset = (start - base) as usize;
             if offset + code.len() > prg_rom.len() {
                 return Err(format!(
                     "Code segment at ${:04X} exceeds PRG-ROM size",
@@ -63,41 +125,58 @@ impl Assembler {
                     return Err(format!(
                         "Code segment at ${:04X} overlaps with existing data at ${:04X}",
                         start,
-                        0x8000 + addr as u16
+                        base as usize + addr
                     ));
                 }
                 prg_rom[addr] = *byte;
                 usage_map[addr] = true;
             }
         }
+        let code_map = usage_map.clone();
 
         // Apply Binary Injections
         // This is used for data tables, assets (Nametables, Music), and any other fixed-address blobs
         // that are better handled as binary than as huge ASM source files.
-        for (addr, data) in injections {
-            if addr < 0x8000 {
+        for (name, _, addr, data) in sections {
+            if addr < base {
                 return Err(format!(
-                    "Injection address ${:04X} is outside PRG-ROM space ($8000+)",
-                    addr
+                    "Section '{}' address ${:04X} is outside PRG-ROM space (${:04X}+)",
+                    name, addr, base
                 ));
             }
-            let offset = (addr - 0x8000) as usize;
+            let offset = (addr - base) as usize;
 
             if offset + data.len() > prg_rom.len() {
                 return Err(format!(
-                    "Injection at ${:04X} with length {} exceeds PRG-ROM size",
+                    "Section '{}' at ${:04X} with length {} exceeds PRG-ROM size",
+                    name,
                     addr,
                     data.len()
                 ));
             }
 
+            let code_bytes = code_map[offset..offset + data.len()]
+                .iter()
+                .filter(|used| **used)
+                .count();
+            if code_bytes > 0 {
+                return Err(format!(
+                    "ROM overflow: {} bytes of code overlap section '{}' (${:04X}-${:04X})",
+                    code_bytes,
+                    name,
+                    addr,
+                    addr as usize + data.len() - 1
+                ));
+            }
+
             for (i, byte) in data.iter().enumerate() {
                 let current_addr = offset + i;
                 if usage_map[current_addr] {
                     return Err(format!(
-                        "Injection at ${:04X} overlaps with existing data at ${:04X}",
+                        "Section '{}' at ${:04X} overlaps with existing data at ${:04X}",
+                        name,
                         addr,
-                        0x8000 + current_addr as u16
+                        base as usize + current_addr
                     ));
                 }
                 prg_rom[current_addr] = *byte;
@@ -105,33 +184,284 @@ impl Assembler {
             }
         }
 
-        // Construct iNES Header (16 bytes)
-        // Format Spec: https://www.nesdev.org/wiki/INES
-        // Mapper 0 (NROM), 32KB PRG, 8KB CHR
-        let header = vec![
-            0x4E, 0x45, 0x53, 0x1A, // 'N', 'E', 'S', EOF
-            0x02, // PRG-ROM size: 2 x 16KB = 32KB
-            0x01, // CHR-ROM size: 1 x 8KB = 8KB
-            0x01, // Flags 6: Vertical Mirroring, No Battery, Mapper Lower 0
-            0x00, // Flags 7: Mapper Upper 0
-            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // Padding
-        ];
+        let prg_rom = if self.mapper.switchable_banks() > 0 {
+            self.build_banked_prg(&fixed_source, &banks, banked, prg_rom)?
+        } else {
+            prg_rom
+        };
+        if let Some(kb) = self.cartridge.prg_size_kb {
+            if prg_rom.len() > kb * 1024 {
+                return Err(format!(
+                    "Program needs {} KB of PRG-ROM but the cartridge is configured for {} KB",
+                    prg_rom.len() / 1024,
+                    kb
+                ));
+            }
+        }
+        let chr_rom = self.build_chr(chr_data)?;
 
+        let header = self
+            .cartridge
+            .header(self.mapper, prg_rom.len(), chr_rom.len());
         let mut final_rom = header;
         final_rom.extend_from_slice(&prg_rom);
+        final_rom.extend(chr_rom);
+
+        Ok(final_rom)
+    }
+
+    /// Addresses of fixed-bank `labels` in `source`, for sections that hold
+    /// code addresses. Each label is assembled as a `JMP` operand at $0000.
+    pub fn label_addresses(&self, source: &str, labels: &[String]) -> Result<Vec<u16>, String> {
+        let (fixed_source, _) = split_banks(source)?;
+        let mut probe = format!("{}\n.ORG $0000\n", fixed_source);
+        for label in labels {
+            probe.push_str(&format!("  JMP {}\n", label));
+        }
+        let segments = assemble_segments(&probe)?;
+        let code = segments
+            .iter()
+            .find(|s| s.address == 0)
+            .map(|s| s.code.as_slice())
+            .unwrap_or_default();
+        if code.len() != labels.len() * 3 {
+            return Err("Could not resolve label addresses".to_string());
+        }
+        Ok(code
+            .chunks(3)
+            .map(|jmp| u16::from_le_bytes([jmp[1], jmp[2]]))
+            .collect())
+    }
 
-        // Append 8KB CHR-ROM
-        // If user provided CHR data, use it (padded to 8KB).
+    // Switchable banks are assembled together with the fixed bank so they can
+    // call into the runtime by label; only their switchable-window output is
+    // kept. Banked sections then go in above the bank's code.
+    fn build_banked_prg(
+        &self,
+        fixed_source: &str,
+        banks: &[(u8, String)],
+        sections: Vec<(String, Option<u8>, u16, Vec<u8>)>,
+        fixed: Vec<u8>,
+    ) -> Result<Vec<u8>, String> {
+        let highest = banks
+            .iter()
+            .map(|(n, _)| *n)
+            .chain(sections.iter().filter_map(|(_, bank, _, _)| *bank))
+            .max();
+        let bank_size = self.mapper.prg_bank_size();
+        let configured = self.cartridge.prg_size_kb.unwrap_or(0) * 1024 / bank_size;
+        let count = self.mapper.prg_banks(highest).max(configured);
+        let mut prg = vec![0u8; count * bank_size];
+
+        if self.mapper == Mapper::Mmc1 {
+            for bank in 0..count - 1 {
+                let offset = bank * bank_size;
+                let stub = (RESET_STUB_ADDR - SWITCH_WINDOW) as usize;
+                prg[offset + stub..offset + stub + RESET_STUB.len()].copy_from_slice(&RESET_STUB);
+                // NMI, Reset and IRQ all point at the stub as seen from $C000
+                for v in 0..3 {
+                    prg[offset + 0x3FFA + v * 2] = 0xF0;
+                    prg[offset + 0x3FFB + v * 2] = 0xFF;
+                }
+            }
+        }
+
+        let mut code_maps: HashMap<u8, Vec<bool>> = HashMap::new();
+        for (bank, source) in banks {
+            let combined = format!("{}\n{}", fixed_source, source);
+            let offset = *bank as usize * bank_size;
+            let used = code_maps
+                .entry(*bank)
+                .or_insert_with(|| vec![false; bank_size]);
+            for segment in assemble_segments(&combined)? {
+                if segment.code.is_empty() || segment.address >= self.mapper.fixed_bank_start() {
+                    continue;
+                }
+                if segment.address < SWITCH_WINDOW {
+                    return Err(format!(
+                        "Bank {} code segment at ${:04X} is outside the bank window",
+                        bank, segment.address
+                    ));
+                }
+                let start = (segment.address - SWITCH_WINDOW) as usize;
+                let end = start + segment.code.len();
+                let limit = self.mapper.bank_limit();
+                if end > (limit - SWITCH_WINDOW) as usize {
+                    return Err(format!(
+                        "Bank {} overflow: code reaches ${:04X} (limit ${:04X})",
+                        bank,
+                        SWITCH_WINDOW as usize + end - 1,
+                        limit - 1
+                    ));
+                }
+                for (i, byte) in segment.code.iter().enumerate() {
+                    if used[start + i] {
+                        return Err(format!(
+                            "Bank {} code segment at ${:04X} overlaps with existing data",
+                            bank, segment.address
+                        ));
+                    }
+                    used[start + i] = true;
+                    prg[offset + start + i] = *byte;
+                }
+            }
+        }
+
+        let limit = self.mapper.bank_limit();
+        for (name, bank, addr, data) in sections {
+            let bank = bank.unwrap_or_default();
+            let end = addr as usize + data.len();
+            if addr < SWITCH_WINDOW || end > limit as usize {
+                return Err(format!(
+                    "Section '{}' at ${:04X} with length {} is outside the bank {} window (${:04X}-${:04X})",
+                    name,
+                    addr,
+                    data.len(),
+                    bank,
+                    SWITCH_WINDOW,
+                    limit - 1
+                ));
+            }
+            let start = (addr - SWITCH_WINDOW) as usize;
+            let used = code_maps
+                .entry(bank)
+                .or_insert_with(|| vec![false; bank_size]);
+            let code_bytes = used[start..start + data.len()]
+                .iter()
+                .filter(|used| **used)
+                .count();
+            if code_bytes > 0 {
+                return Err(format!(
+                    "Bank {} overflow: {} bytes of code overlap section '{}' (${:04X}-${:04X})",
+                    bank,
+                    code_bytes,
+                    name,
+                    addr,
+                    end - 1
+                ));
+            }
+            let offset = bank as usize * bank_size + start;
+            prg[offset..offset + data.len()].copy_from_slice(&data);
+        }
+
+        // The fixed region is the last 16 KB, or the top of every AxROM bank
+        if self.mapper.mirrors_fixed_region() {
+            for bank in 0..count {
+                let top = (bank + 1) * bank_size;
+                prg[top - fixed.len()..top].copy_from_slice(&fixed);
+            }
+        } else {
+            let last = prg.len() - fixed.len();
+            prg[last..].copy_from_slice(&fixed);
+        }
+        Ok(prg)
+    }
+
+    fn build_chr(&self, chr_data: Option<&[u8]>) -> Result<Vec<u8>, String> {
+        // CHR-RAM boards carry no CHR-ROM at all
+        if self.cartridge.chr_ram {
+            return Ok(Vec::new());
+        }
+        // If user provided CHR data, use it (padded to a whole number of 8KB banks).
         // Otherwise, use 8KB of zeros.
-        let mut final_chr = vec![0u8; 8192];
-        if let Some(data) = chr_data {
-            let len = data.len().min(8192);
+        let data = chr_data.unwrap_or(&[]);
+        let max = self.mapper.max_chr_size();
+        if self.mapper == Mapper::Nrom {
+            let mut final_chr = vec![0u8; CHR_BANK_SIZE];
+            let len = data.len().min(CHR_BANK_SIZE);
             final_chr[..len].copy_from_slice(&data[..len]);
+            return Ok(final_chr);
+        }
+        if data.len() > max {
+            return Err(format!(
+                "CHR data is {} bytes, {} supports at most {} KB",
+                data.len(),
+                self.mapper.name(),
+                max / 1024
+            ));
         }
-        final_rom.extend(final_chr);
+        let banks = match self.cartridge.chr_size_kb {
+            Some(kb) if data.len() > kb * 1024 => {
+                return Err(format!(
+                    "CHR data is {} bytes but the cartridge is configured for {} KB",
+                    data.len(),
+                    kb
+                ));
+            }
+            Some(kb) => kb * 1024 / CHR_BANK_SIZE,
+            None => data
+                .len()
+                .div_ceil(CHR_BANK_SIZE)
+                .max(1)
+                .next_power_of_two(),
+        };
+        let mut final_chr = vec![0u8; banks * CHR_BANK_SIZE];
+        final_chr[..data.len()].copy_from_slice(data);
+        Ok(final_chr)
+    }
+}
 
-        Ok(final_rom)
+// SEI; LDX #$FF; TXS; STX $8000 (reset MMC1, fixing the last bank at
+// $C000); JMP ($FFFC)
+const RESET_STUB: [u8; 10] = [0x78, 0xA2, 0xFF, 0x9A, 0x8E, 0x00, 0x80, 0x6C, 0xFC, 0xFF];
+
+fn assemble_segments(source: &str) -> Result<Vec<rs6502::CodeSegment>, String> {
+    let mut assembler = Rs6502Assembler::new();
+    // 0 as offset means no global offset override, respect .ORG in source
+    assembler
+        .assemble_string(source, 0)
+        .map_err(|e| format!("Assembler error: {:?}", e))
+}
+
+/// The `WORD` targets listed under `.ORG $FFFA`: NMI, reset and IRQ.
+fn vector_labels(source: &str) -> Vec<String> {
+    let mut labels = Vec::new();
+    let mut in_vectors = false;
+    for line in source.lines() {
+        let trimmed = line.trim();
+        if trimmed.len() > 4 && trimmed[..4].eq_ignore_ascii_case(".ORG") {
+            let addr = trimmed[4..].trim().trim_start_matches('$');
+            in_vectors = u16::from_str_radix(addr, 16) == Ok(VECTORS_ADDR);
+            continue;
+        }
+        if !in_vectors {
+            continue;
+        }
+        let mut tokens = trimmed.split_whitespace();
+        if tokens.any(|t| t.eq_ignore_ascii_case("WORD")) {
+            if let Some(label) = tokens.next() {
+                labels.push(label.to_string());
+            }
+        }
+    }
+    labels
+}
+
+/// Splits generated source into the fixed-bank part and `.BANK n` blocks.
+fn split_banks(source: &str) -> Result<(String, Vec<(u8, String)>), String> {
+    let mut fixed = String::new();
+    let mut banks: Vec<(u8, String)> = Vec::new();
+    for line in source.lines() {
+        let trimmed = line.trim();
+        if trimmed.len() > 5 && trimmed[..5].eq_ignore_ascii_case(".BANK") {
+            let n: u8 = trimmed[5..]
+                .trim()
+                .parse()
+                .map_err(|_| format!("Invalid bank directive '{}'", trimmed))?;
+            if banks.iter().any(|(b, _)| *b == n) {
+                return Err(format!("Bank {} defined twice", n));
+            }
+            banks.push((n, String::new()));
+            continue;
+        }
+        let target = match banks.last_mut() {
+            Some((_, text)) => text,
+            None => &mut fixed,
+        };
+        target.push_str(line);
+        target.push('\n');
     }
+    Ok((fixed, banks))
 }
 
 impl Default for Assembler {
@@ -56,9 +56,10 @@ pub enum Statement {
     Print(Vec<Expression>),
     Asm(Vec<String>), // Raw assembly lines
     Comment(String),
-    On(String, String),      // ON NMI DO RoutineName
-    Read(Vec<String>),       // READ var1, var2
-    Restore(Option<String>), // RESTORE [Label]
+    On(String, String),             // ON NMI DO RoutineName
+    OnScanline(Expression, String), // ON SCANLINE n DO RoutineName
+    Read(Vec<String>),              // READ var1, var2
+    Restore(Option<String>),        // RESTORE [Label]
     Select(
         Expression,
         Vec<(Expression, Vec<Statement>)>,
@@ -76,6 +77,37 @@ pub struct MetaspriteTile {
     pub attr: Expression,
 }
 
+/// Collision rectangle relative to a metasprite's origin (HITBOX / HURTBOX).
+#[derive(Debug, PartialEq, Clone)]
+pub struct SpriteBox {
+    pub x: Expression,
+    pub y: Expression,
+    pub w: Expression,
+    pub h: Expression,
+}
+
+/// ACTOR Name ... END ACTOR: a TYPE whose instances live in the shared actor
+/// table, with the SUBs `Actors.UpdateAll` / `Actors.DrawAll` call for it.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ActorDecl {
+    pub name: String,
+    pub fields: Vec<(String, DataType, Option<Expression>)>, // Name, Type, Spawn value
+    pub update: Option<String>,
+    pub draw: Option<String>,
+    pub classes: Vec<String>,
+    pub size: Option<(Expression, Expression)>, // Hitbox width, height
+}
+
+/// An ACTOR placed in a WORLD room (from the project's world map).
+#[derive(Debug, PartialEq, Clone)]
+pub struct PlacedObject {
+    pub room: (u32, u32),
+    pub actor: String,
+    pub x: u8,
+    pub y: u8,
+    pub params: Vec<(String, i32)>, // Field, Value
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub struct AnimationFrame {
     pub metasprite: String,
@@ -89,15 +121,28 @@ pub enum TopLevel {
     Interrupt(String, Vec<Statement>),                    // Interrupt Name (NMI/IRQ), Body
     Const(String, Expression),                            // Global Const
     Dim(String, DataType, Option<Expression>),            // Global Dim with optional initialization
+    SaveDim(String, DataType),                            // DIM SAVE: battery-backed, $6000-$7FFF
     Asm(Vec<String>),                                     // Top-level ASM block
     Data(Option<String>, Vec<Expression>),                // [Label:] DATA 1, 2, 3
     Include(String),                                      // INCLUDE "filename"
     Enum(String, Vec<(String, Option<i32>)>), // ENUM Name, Members(Name, Optional Value)
     Macro(String, Vec<String>, Vec<Statement>), // MACRO Name, Params, Body
-    Metasprite(String, Vec<MetaspriteTile>),  // METASPRITE Name, Tiles
+    Metasprite(
+        String,
+        Vec<MetaspriteTile>,
+        Option<Box<SpriteBox>>,
+        Option<Box<SpriteBox>>,
+    ), // METASPRITE Name, Tiles, Hitbox, Hurtbox
     Animation(String, Vec<AnimationFrame>, bool), // ANIMATION Name, Frames, Loops
     Metatile(String, [u8; 4], u8),            // METATILE Name, Tiles[4], Attr
     World(u32, u32, Vec<i32>),                // WORLD Width, Height, Data (Nametable Indices)
+    Mapper(String),                           // MAPPER MMC1
+    Sprites(u8),                              // SPRITES 8X8 / 8X16 (sprite height)
+    Bank(Option<u8>),                         // BANK n / BANK FIXED (applies to what follows)
+    Password(Vec<(String, u8)>, Option<String>, Option<u8>), // PASSWORD Fields, Alphabet, Key
+    Actor(Box<ActorDecl>),                    // ACTOR Name ... END ACTOR
+    Actors(u8),                               // ACTORS n (actor table slots)
+    WorldObject(Box<PlacedObject>),           // Object placed in a WORLD room
 }
 
 #[derive(Debug, PartialEq, Clone)]
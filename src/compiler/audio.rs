@@ -55,10 +55,27 @@ use std::iter;
 /// 1. `samples_blob`: The raw sample data, padded and aligned for DMC.
 /// 2. `table_blob`: A lookup table (Address Byte, Length Byte) for each sample.
 pub fn compile_samples(assets: &Option<ProjectAssets>) -> Result<(Vec<u8>, Vec<u8>), String> {
+    let (samples_blob, table_blob) = compile_samples_at(assets, SAMPLE_DATA_ADDR)?;
+    if samples_blob.len() > SAMPLE_DATA_SIZE {
+        return Err(format!(
+            "DPCM Samples exceed limit of {} bytes (got {})",
+            SAMPLE_DATA_SIZE,
+            samples_blob.len()
+        ));
+    }
+    Ok((samples_blob, table_blob))
+}
+
+/// Same as `compile_samples`, but for sample data placed at `base`
+/// (which must be 64-byte aligned and at or above $C000).
+pub fn compile_samples_at(
+    assets: &Option<ProjectAssets>,
+    base: u16,
+) -> Result<(Vec<u8>, Vec<u8>), String> {
     let mut samples_blob = Vec::new();
     let mut table_blob = Vec::new();
 
-    let start_addr = SAMPLE_DATA_ADDR as usize;
+    let start_addr = base as usize;
     let mut current_addr = start_addr;
 
     if let Some(assets) = assets {
@@ -126,18 +143,24 @@ pub fn compile_samples(assets: &Option<ProjectAssets>) -> Result<(Vec<u8>, Vec<u
             table_blob.len()
         ));
     }
-    if samples_blob.len() > SAMPLE_DATA_SIZE {
-        return Err(format!(
-            "DPCM Samples exceed limit of {} bytes (got {})",
-            SAMPLE_DATA_SIZE,
-            samples_blob.len()
-        ));
-    }
 
     Ok((samples_blob, table_blob))
 }
 
 pub fn compile_envelopes(assets: &Option<ProjectAssets>) -> Result<Vec<u8>, String> {
+    let blob = compile_envelopes_at(assets, ENVELOPE_TABLE_ADDR)?;
+    if blob.len() > ENVELOPE_TABLE_SIZE {
+        return Err(format!(
+            "Envelope Data exceeds limit of {} bytes (got {})",
+            ENVELOPE_TABLE_SIZE,
+            blob.len()
+        ));
+    }
+    Ok(blob)
+}
+
+/// Same as `compile_envelopes`, with pointers relative to `base`.
+pub fn compile_envelopes_at(assets: &Option<ProjectAssets>, base: u16) -> Result<Vec<u8>, String> {
     let mut blob = Vec::new();
     if let Some(assets) = assets {
         let user_env_count = assets.envelopes.len();
@@ -150,7 +173,7 @@ pub fn compile_envelopes(assets: &Option<ProjectAssets>) -> Result<Vec<u8>, Stri
         let pointer_table_size = total_count * 2;
         blob.extend(iter::repeat_n(0, pointer_table_size));
 
-        let start_addr = ENVELOPE_TABLE_ADDR as usize;
+        let start_addr = base as usize;
         let mut current_offset = 1 + pointer_table_size;
 
         let mut env_idx = 0;
@@ -255,17 +278,23 @@ pub fn compile_envelopes(assets: &Option<ProjectAssets>) -> Result<Vec<u8>, Stri
     if blob.is_empty() {
         blob.push(0);
     }
-    if blob.len() > ENVELOPE_TABLE_SIZE {
+    Ok(blob)
+}
+
+pub fn compile_sfx_data(assets: &Option<ProjectAssets>) -> Result<Vec<u8>, String> {
+    let blob = compile_sfx_data_at(assets, SFX_TABLE_ADDR)?;
+    if blob.len() > SFX_TABLE_SIZE {
         return Err(format!(
-            "Envelope Data exceeds limit of {} bytes (got {})",
-            ENVELOPE_TABLE_SIZE,
+            "SFX Table exceeds limit of {} bytes (got {})",
+            SFX_TABLE_SIZE,
             blob.len()
         ));
     }
     Ok(blob)
 }
 
-pub fn compile_sfx_data(assets: &Option<ProjectAssets>) -> Result<Vec<u8>, String> {
+/// Same as `compile_sfx_data`, with pointers relative to `base`.
+pub fn compile_sfx_data_at(assets: &Option<ProjectAssets>, base: u16) -> Result<Vec<u8>, String> {
     let mut blob = Vec::new();
     if let Some(assets) = assets {
         let count = assets.sound_effects.len();
@@ -281,7 +310,7 @@ pub fn compile_sfx_data(assets: &Option<ProjectAssets>) -> Result<Vec<u8>, Strin
         let user_env_count = assets.envelopes.len();
 
         for (i, sfx) in assets.sound_effects.iter().enumerate() {
-            let abs_addr = SFX_TABLE_ADDR as usize + current_offset;
+            let abs_addr = base as usize + current_offset;
             let ptr_idx = 1 + (i * 2);
             blob[ptr_idx] = (abs_addr & 0xFF) as u8;
             blob[ptr_idx + 1] = ((abs_addr >> 8) & 0xFF) as u8;
@@ -310,13 +339,6 @@ pub fn compile_sfx_data(assets: &Option<ProjectAssets>) -> Result<Vec<u8>, Strin
     if blob.is_empty() {
         blob.push(0);
     }
-    if blob.len() > SFX_TABLE_SIZE {
-        return Err(format!(
-            "SFX Table exceeds limit of {} bytes (got {})",
-            SFX_TABLE_SIZE,
-            blob.len()
-        ));
-    }
     Ok(blob)
 }
 
@@ -348,6 +370,20 @@ pub fn compile_sfx_data(assets: &Option<ProjectAssets>) -> Result<Vec<u8>, Strin
 ///   - **Pitch** (1 byte): Period Table Index. For DMC: Sample Index.
 ///     - Index `$FF` (255) represents **Silence**.
 pub fn compile_audio_data(assets: &Option<ProjectAssets>) -> Result<Vec<u8>, String> {
+    let blob = compile_audio_data_at(assets, MUSIC_DATA_ADDR)?;
+    if blob.len() > MUSIC_DATA_SIZE {
+        return Err(format!(
+            "Music Data exceeds limit of {} bytes (got {})",
+            MUSIC_DATA_SIZE,
+            blob.len()
+        ));
+    }
+    Ok(blob)
+}
+
+/// Same as `compile_audio_data`, with track pointers relative to `base`.
+/// The size is only bounded by the space the linker can find.
+pub fn compile_audio_data_at(assets: &Option<ProjectAssets>, base: u16) -> Result<Vec<u8>, String> {
     let mut blob = Vec::new();
 
     if let Some(assets) = assets {
@@ -366,7 +402,7 @@ pub fn compile_audio_data(assets: &Option<ProjectAssets>) -> Result<Vec<u8>, Str
         for (i, track) in assets.audio_tracks.iter().enumerate() {
             // Calculate absolute address of this track
             // Base Address + current_offset
-            let abs_addr = MUSIC_DATA_ADDR as usize + current_offset;
+            let abs_addr = base as usize + current_offset;
 
             // Update pointer in table
             let ptr_idx = 1 + (i * 2);
@@ -448,13 +484,5 @@ pub fn compile_audio_data(assets: &Option<ProjectAssets>) -> Result<Vec<u8>, Str
         blob.push(0);
     }
 
-    if blob.len() > MUSIC_DATA_SIZE {
-        return Err(format!(
-            "Music Data exceeds limit of {} bytes (got {})",
-            MUSIC_DATA_SIZE,
-            blob.len()
-        ));
-    }
-
     Ok(blob)
 }
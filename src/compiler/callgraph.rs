@@ -0,0 +1,293 @@
+//! Call graph over SUBs and interrupt handlers.
+//!
+//! Main, NMI and IRQ are separate roots: an interrupt can fire while any
+//! routine reachable from Main is active. Two SUBs can share (overlay) their
+//! storage only when they are reached from exactly the same single root and
+//! neither can be on the call stack while the other runs.
+
+use crate::compiler::actors;
+use crate::compiler::ast::{Expression, Program, Statement, TopLevel};
+use std::collections::{HashMap, HashSet};
+
+#[derive(Debug, Default)]
+pub struct CallGraph {
+    edges: HashMap<String, HashSet<String>>,
+    roots: Vec<String>,
+    // Root names reaching each routine
+    reached_by: HashMap<String, HashSet<String>>,
+    arg_calls: Vec<(String, String)>,
+}
+
+impl CallGraph {
+    pub fn build(program: &Program) -> Self {
+        let subs: HashSet<String> = program
+            .declarations
+            .iter()
+            .filter_map(|d| match d {
+                TopLevel::Sub(name, _, _) => Some(name.clone()),
+                _ => None,
+            })
+            .collect();
+
+        // Actors.UpdateAll / Actors.DrawAll call every ACTOR's handler
+        let mut actor_handlers: HashMap<&str, HashSet<String>> = HashMap::new();
+        for actor in actors::declarations(program) {
+            for (member, handler) in [("updateall", &actor.update), ("drawall", &actor.draw)] {
+                if let Some(handler) = handler.as_ref().filter(|h| subs.contains(*h)) {
+                    actor_handlers
+                        .entry(member)
+                        .or_default()
+                        .insert(handler.clone());
+                }
+            }
+        }
+
+        let mut graph = CallGraph::default();
+        if subs.contains("Main") {
+            graph.roots.push("Main".to_string());
+        }
+
+        for decl in &program.declarations {
+            match decl {
+                TopLevel::Sub(name, _, body) | TopLevel::Interrupt(name, body) => {
+                    let mut c = Collector {
+                        subs: &subs,
+                        actor_handlers: &actor_handlers,
+                        callees: HashSet::new(),
+                        handlers: Vec::new(),
+                        arg_calls: Vec::new(),
+                    };
+                    c.block(body);
+                    graph.edges.insert(name.clone(), c.callees);
+                    graph.arg_calls.extend(c.arg_calls);
+                    if matches!(decl, TopLevel::Interrupt(..)) {
+                        graph.roots.push(name.clone());
+                    }
+                    // ON NMI/IRQ DO Routine installs a handler that can run at any time
+                    for h in c.handlers {
+                        if !graph.roots.contains(&h) {
+                            graph.roots.push(h);
+                        }
+                    }
+                }
+                TopLevel::Asm(lines) => {
+                    // Routines reached from top-level assembly are treated as roots
+                    for callee in asm_references(lines, &subs) {
+                        if !graph.roots.contains(&callee) {
+                            graph.roots.push(callee);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        for root in graph.roots.clone() {
+            let mut stack = vec![root.clone()];
+            let mut seen = HashSet::new();
+            while let Some(node) = stack.pop() {
+                if !seen.insert(node.clone()) {
+                    continue;
+                }
+                graph
+                    .reached_by
+                    .entry(node.clone())
+                    .or_default()
+                    .insert(root.clone());
+                if let Some(callees) = graph.edges.get(&node) {
+                    stack.extend(callees.iter().cloned());
+                }
+            }
+        }
+        graph
+    }
+
+    pub fn roots(&self) -> &[String] {
+        &self.roots
+    }
+
+    pub fn callees(&self, name: &str) -> Vec<String> {
+        let mut v: Vec<String> = self
+            .edges
+            .get(name)
+            .map(|s| s.iter().cloned().collect())
+            .unwrap_or_default();
+        v.sort();
+        v
+    }
+
+    /// True if `to` can be called (directly or indirectly) from `from`.
+    pub fn reaches(&self, from: &str, to: &str) -> bool {
+        let mut stack = vec![from.to_string()];
+        let mut seen = HashSet::new();
+        while let Some(node) = stack.pop() {
+            if !seen.insert(node.clone()) {
+                continue;
+            }
+            if let Some(callees) = self.edges.get(&node) {
+                if callees.contains(to) {
+                    return true;
+                }
+                stack.extend(callees.iter().cloned());
+            }
+        }
+        false
+    }
+
+    /// True if both routines may be active at the same time.
+    pub fn may_overlap(&self, a: &str, b: &str) -> bool {
+        if a == b {
+            return true;
+        }
+        let (ra, rb) = match (self.reached_by.get(a), self.reached_by.get(b)) {
+            (Some(ra), Some(rb)) => (ra, rb),
+            // Unreachable routines may still be entered through inline assembly or pointers
+            _ => return true,
+        };
+        if ra.len() != 1 || ra != rb {
+            return true;
+        }
+        if self.reaches(a, b) || self.reaches(b, a) {
+            return true;
+        }
+        self.arg_calls.iter().any(|(callee, inner)| {
+            (callee == a && (inner == b || self.reaches(inner, b)))
+                || (callee == b && (inner == a || self.reaches(inner, a)))
+        })
+    }
+}
+
+struct Collector<'a> {
+    subs: &'a HashSet<String>,
+    actor_handlers: &'a HashMap<&'a str, HashSet<String>>,
+    callees: HashSet<String>,
+    handlers: Vec<String>,
+    // (callee, routine called while evaluating its arguments)
+    arg_calls: Vec<(String, String)>,
+}
+
+impl Collector<'_> {
+    fn block(&mut self, stmts: &[Statement]) {
+        for stmt in stmts {
+            self.stmt(stmt);
+        }
+    }
+
+    fn stmt(&mut self, stmt: &Statement) {
+        match stmt {
+            Statement::Let(target, value) | Statement::Poke(target, value) => {
+                self.expr(target);
+                self.expr(value);
+            }
+            Statement::If(cond, then_b, else_b) => {
+                self.expr(cond);
+                self.block(then_b);
+                if let Some(e) = else_b {
+                    self.block(e);
+                }
+            }
+            Statement::While(cond, body) | Statement::DoWhile(body, cond) => {
+                self.expr(cond);
+                self.block(body);
+            }
+            Statement::For(_, start, end, step, body) => {
+                self.expr(start);
+                self.expr(end);
+                if let Some(s) = step {
+                    self.expr(s);
+                }
+                self.block(body);
+            }
+            Statement::Return(Some(e)) | Statement::PlaySfx(e) | Statement::Randomize(e) => {
+                self.expr(e)
+            }
+            Statement::Call(target, args) => self.call(target, args),
+            Statement::Print(args) => {
+                for a in args {
+                    self.expr(a);
+                }
+            }
+            Statement::Select(expr, cases, else_b) => {
+                self.expr(expr);
+                for (val, body) in cases {
+                    self.expr(val);
+                    self.block(body);
+                }
+                if let Some(e) = else_b {
+                    self.block(e);
+                }
+            }
+            Statement::Asm(lines) => self.callees.extend(asm_references(lines, self.subs)),
+            Statement::On(_, routine) if self.subs.contains(routine) => {
+                self.handlers.push(routine.clone());
+            }
+            Statement::OnScanline(line, routine) => {
+                self.expr(line);
+                if self.subs.contains(routine) {
+                    self.handlers.push(routine.clone());
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn call(&mut self, callee: &Expression, args: &[Expression]) {
+        let target = match callee {
+            Expression::Identifier(name) if self.subs.contains(name) => Some(name.clone()),
+            _ => {
+                if let Expression::MemberAccess(base, member) = callee {
+                    if matches!(base.as_ref(), Expression::Identifier(n) if n.eq_ignore_ascii_case("Actors"))
+                    {
+                        let member = member.to_lowercase();
+                        if let Some(handlers) = self.actor_handlers.get(member.as_str()) {
+                            self.callees.extend(handlers.iter().cloned());
+                        }
+                    }
+                }
+                self.expr(callee);
+                None
+            }
+        };
+        // Arguments are stored into the callee's frame one by one, so any
+        // routine called while evaluating them runs with that frame live.
+        let before = std::mem::take(&mut self.callees);
+        for a in args {
+            self.expr(a);
+        }
+        let inner = std::mem::replace(&mut self.callees, before);
+        if let Some(name) = target {
+            for c in &inner {
+                self.arg_calls.push((name.clone(), c.clone()));
+            }
+            self.callees.insert(name);
+        }
+        self.callees.extend(inner);
+    }
+
+    fn expr(&mut self, expr: &Expression) {
+        match expr {
+            Expression::Call(callee, args) => self.call(callee, args),
+            Expression::BinaryOp(l, _, r) => {
+                self.expr(l);
+                self.expr(r);
+            }
+            Expression::UnaryOp(_, e) | Expression::Peek(e) | Expression::MemberAccess(e, _) => {
+                self.expr(e)
+            }
+            _ => {}
+        }
+    }
+}
+
+fn asm_references(lines: &[String], subs: &HashSet<String>) -> Vec<String> {
+    let mut found = Vec::new();
+    for line in lines {
+        let code = line.split(';').next().unwrap_or("");
+        for word in code.split(|c: char| !(c.is_alphanumeric() || c == '_')) {
+            if subs.contains(word) && !found.iter().any(|f| f == word) {
+                found.push(word.to_string());
+            }
+        }
+    }
+    found
+}
@@ -0,0 +1,201 @@
+//! Per-project cartridge configuration and the NES 2.0 header built from it.
+//!
+//! The config lives in `project.json`. Every field has a default, so older
+//! projects (and plain source compiles) get a vertical-mirrored, NTSC, CHR-ROM
+//! board of whatever size the program needs.
+
+use crate::compiler::compress::Compression;
+use crate::compiler::mapper::{Mapper, CHR_BANK_SIZE, PRG_BANK_SIZE};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Mirroring {
+    Horizontal,
+    #[default]
+    Vertical,
+    FourScreen,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum TvSystem {
+    #[default]
+    Ntsc,
+    Pal,
+    Dual,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ConsoleType {
+    #[default]
+    Nes,
+    VsSystem,
+    Playchoice10,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(default)]
+pub struct CartridgeConfig {
+    /// Selects the mapper for programs without a `MAPPER` directive; a
+    /// program that declares a different one is rejected.
+    pub mapper: Option<String>,
+    pub submapper: u8,
+    pub mirroring: Mirroring,
+    pub battery: bool,
+    /// PRG-ROM size in KB, a power of two from 32 KB; sized to fit the
+    /// program when unset.
+    pub prg_size_kb: Option<usize>,
+    /// CHR-ROM size in KB (or CHR-RAM size with `chr_ram`); sized to fit the
+    /// CHR data when unset.
+    pub chr_size_kb: Option<usize>,
+    pub chr_ram: bool,
+    /// How the startup CHR image is stored in PRG on CHR-RAM boards.
+    pub chr_compression: Compression,
+    /// Switchable PRG bank holding the CHR-RAM tiles; the fixed bank when
+    /// unset.
+    pub chr_prg_bank: Option<u8>,
+    pub tv_system: TvSystem,
+    pub console_type: ConsoleType,
+}
+
+impl CartridgeConfig {
+    /// Checks the config against what `mapper` boards can actually provide.
+    pub fn validate(&self, mapper: Mapper) -> Result<(), String> {
+        let name = mapper.name();
+        if self.submapper > 15 {
+            return Err(format!(
+                "Submapper {} is out of range (0-15)",
+                self.submapper
+            ));
+        }
+        if self.mirroring == Mirroring::FourScreen && matches!(mapper, Mapper::Mmc1 | Mapper::Axrom)
+        {
+            return Err(format!("{} does not support four-screen mirroring", name));
+        }
+        if self.battery && !mapper.has_prg_ram() {
+            return Err(format!("{} boards have no battery-backed PRG-RAM", name));
+        }
+        if let Some(kb) = self.prg_size_kb {
+            let max = mapper.max_prg_size() / 1024;
+            // Every image is at least 32 KB: NROM-128 mirroring is not emitted
+            if kb < 32 || !kb.is_power_of_two() || kb > max {
+                return Err(format!(
+                    "PRG size {} KB is invalid for {} (a power of two from 32 KB up to {} KB)",
+                    kb, name, max
+                ));
+            }
+        }
+        if self.chr_compression == Compression::Metatile {
+            return Err("Only screens can be stored as a metatile map, not CHR".to_string());
+        }
+        if self.chr_ram {
+            if mapper == Mapper::Cnrom {
+                return Err("CNROM switches CHR-ROM banks and cannot use CHR-RAM".to_string());
+            }
+            if let Some(kb) = self.chr_size_kb {
+                if !matches!(kb, 8 | 16 | 32) {
+                    return Err(format!(
+                        "CHR-RAM size {} KB is invalid (8, 16 or 32 KB)",
+                        kb
+                    ));
+                }
+            }
+        } else if let Some(kb) = self.chr_size_kb {
+            let max = mapper.max_chr_size() / 1024;
+            if kb == 0 || kb % 8 != 0 || kb > max {
+                return Err(format!(
+                    "CHR size {} KB is invalid for {} (multiples of 8 KB, up to {} KB)",
+                    kb, name, max
+                ));
+            }
+        }
+        if let Some(bank) = self.chr_prg_bank {
+            let available = mapper.switchable_banks();
+            if !self.chr_ram {
+                return Err("chr_prg_bank needs chr_ram (CHR-ROM tiles are not in PRG)".to_string());
+            }
+            if available == 0 {
+                return Err(format!(
+                    "chr_prg_bank {} requires a bank-switching mapper ({} has none)",
+                    bank, name
+                ));
+            }
+            if bank as usize >= available {
+                return Err(format!(
+                    "chr_prg_bank {} is out of range for {} (banks 0-{})",
+                    bank,
+                    name,
+                    available - 1
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// The 16-byte NES 2.0 header for a `prg_len`-byte PRG-ROM and
+    /// `chr_len`-byte CHR-ROM (0 with CHR-RAM).
+    /// Format Spec: https://www.nesdev.org/wiki/NES_2.0
+    pub fn header(&self, mapper: Mapper, prg_len: usize, chr_len: usize) -> Vec<u8> {
+        let number = mapper.number();
+        let prg_units = prg_len / PRG_BANK_SIZE;
+        let chr_units = chr_len / CHR_BANK_SIZE;
+
+        let mut flags6 = (number & 0x0F) << 4;
+        match self.mirroring {
+            Mirroring::Vertical => flags6 |= 0x01,
+            Mirroring::Horizontal => {}
+            Mirroring::FourScreen => flags6 |= 0x08,
+        }
+        if self.battery {
+            flags6 |= 0x02;
+        }
+        let console = match self.console_type {
+            ConsoleType::Nes => 0,
+            ConsoleType::VsSystem => 1,
+            ConsoleType::Playchoice10 => 2,
+        };
+
+        // RAM sizes are stored as shift counts: 64 << n bytes. The IDE
+        // emulator reads all of byte 10 as the PRG-RAM shift and rejects a
+        // PRG-NVRAM nibble, so battery-backed RAM is declared as PRG-RAM and
+        // the battery bit in flags 6 marks it as saved.
+        let prg_ram = if mapper.has_prg_ram() {
+            ram_shift(0x2000)
+        } else {
+            0
+        };
+        let chr_ram = if self.chr_ram {
+            ram_shift(self.chr_size_kb.unwrap_or(8) * 1024)
+        } else {
+            0
+        };
+        let timing = match self.tv_system {
+            TvSystem::Ntsc => 0,
+            TvSystem::Pal => 1,
+            TvSystem::Dual => 2,
+        };
+
+        let mut header = b"NES\x1A".to_vec();
+        header.extend_from_slice(&[
+            (prg_units & 0xFF) as u8,
+            (chr_units & 0xFF) as u8,
+            flags6,
+            (number & 0xF0) | 0x08 | console, // 0x08: NES 2.0 identifier
+            self.submapper << 4,
+            (((chr_units >> 8) as u8) << 4) | (prg_units >> 8) as u8,
+            prg_ram,
+            chr_ram,
+            timing,
+            0x00, // Vs. System type
+            0x00, // Miscellaneous ROMs
+            0x00, // Default expansion device: unspecified
+        ]);
+        header
+    }
+}
+
+fn ram_shift(bytes: usize) -> u8 {
+    (bytes / 64).trailing_zeros() as u8
+}
@@ -1,16 +1,70 @@
+use crate::compiler::actors;
 use crate::compiler::ast::{
-    BinaryOperator, DataType, Expression, Program, Statement, TopLevel, UnaryOperator,
+    ActorDecl, BinaryOperator, DataType, Expression, Program, Statement, TopLevel, UnaryOperator,
 };
+use crate::compiler::callgraph::CallGraph;
+use crate::compiler::cartridge::Mirroring;
+use crate::compiler::collision;
+use crate::compiler::compress::Compression;
+use crate::compiler::hud;
+use crate::compiler::ir;
+use crate::compiler::linker::{
+    Layout, POINTER_TABLE_ADDR, SECTION_ACTOR_HANDLERS, SECTION_BANK_TABLE, SECTION_CHR,
+    SECTION_CHR_TILES, SECTION_COLLISION, SECTION_ENVELOPES, SECTION_METATILES, SECTION_MUSIC,
+    SECTION_NAMETABLE, SECTION_PALETTE, SECTION_PASSWORD_ALPHABET, SECTION_PERIOD_TABLE,
+    SECTION_SAMPLE_TABLE, SECTION_SCREEN_BANKS, SECTION_SCREEN_TABLE, SECTION_SFX, SECTION_SPRITES,
+    SECTION_WORLD, SECTION_WORLD_OBJECTS, VECTORS_ADDR,
+};
+use crate::compiler::mapper::{self, Mapper, SWITCH_WINDOW};
+use crate::compiler::password::{PasswordFormat, CHECKSUM_SEED};
+use crate::compiler::sprites;
 use crate::compiler::symbol_table::{SymbolKind, SymbolTable};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
-pub const NAMETABLE_ADDR: u16 = 0xD500;
-pub const SFX_TABLE_ADDR: u16 = 0xD900;
-pub const ENVELOPE_TABLE_ADDR: u16 = 0xDA00;
 const SOUND_RAM_START: u16 = 0x0300;
-const VBLANK_BUFFER_START: u16 = 0x0380;
+// PPU update queue: a ring of [flags | length, addr hi, addr lo, data...]
+// entries in the top 128 bytes of RAM, drained by the NMI. Only programs
+// that queue updates give that RAM up. Its control block (head, tail,
+// overflow, NMI depth, scratch) follows the sound RAM.
+const PPU_QUEUE_START: u16 = 0x0780;
+const PPU_QUEUE_SIZE: u8 = 0x80;
+const PPU_QUEUE_HEAD: u16 = 0x0380;
+const PPU_QUEUE_TAIL: u16 = 0x0381;
+const PPU_QUEUE_OVERFLOW: u16 = 0x0382;
+const PPU_QUEUE_IN_NMI: u16 = 0x0383;
+const PPU_QUEUE_SCRATCH: u16 = 0x0384;
+// Data bytes written to $2007 per vblank; must cover the largest entry (63)
+const PPU_QUEUE_BUDGET: u8 = 80;
+// Camera.Follow / Camera.Update state: target x/y, camera x/y (words), room
+// row, pixel row inside it, mode, placed flag, per-frame step x/y, counter
+const CAMERA_STATE: u16 = 0x0390;
+// World.Enter / World.Transition state: current room x, room y
+const WORLD_STATE: u16 = 0x03A0;
+// Physics.MoveAndCollide working copy of the Body (x, y, sub x/y, width,
+// height), then dx, dy, contacts, scan flags, step, slope flag, old bottom
+const PHYSICS_STATE: u16 = 0x03B0;
 const STRING_HEAP_START: u16 = 0x03C0;
 const VAR_START_RAM: u16 = 0x05C0;
+const VAR_END_RAM: u16 = 0x0800;
+// Sprite layers (zero page): reserved slot count, layer, next fixed slot,
+// next stable slot, crowded block low/up slots, frame parity, crowded count
+// this and last frame; then a counter per 8-line band (bit 7 = crowded)
+const SPRITE_STATE: u8 = 0x20;
+const SPRITE_BANDS: u8 = 0x30;
+// Palette buffers (zero page): the colors shown, uploaded by the NMI while
+// dirty, and the base colors they are shaded from; then the dirty flag, bytes
+// uploaded this vblank, fade level (0-4), fade step, frames per step and
+// counter, cycle first/last entry, frames per step and counter
+const PALETTE_SHOWN: u8 = 0x50;
+const PALETTE_BASE: u8 = 0x70;
+const PALETTE_STATE: u8 = 0x90;
+// Vblank cost of the 32-byte palette upload in queue bytes (half the cycles)
+const PALETTE_UPLOAD_COST: u8 = 16;
+// HUD.Split (zero page): split line (0 = none), HudPosition
+const HUD_STATE: u8 = 0xA0;
+// Battery-backed PRG-RAM; the last two bytes hold the Save.Commit checksum
+const SAVE_RAM_START: u16 = 0x6000;
+const SAVE_CHECKSUM_ADDR: u16 = 0x7FFE;
 
 pub struct CodeGenerator {
     symbol_table: SymbolTable,
@@ -18,9 +72,60 @@ pub struct CodeGenerator {
     ram_pointer: u16,
     label_counter: usize,
     data_table_offsets: HashMap<String, u16>,
+    // Metasprite / animation name -> address in the sprites section
+    sprite_addrs: HashMap<String, u16>,
+    // Labels for the actor_handlers section: UPDATE entries by kind, then DRAW
+    actor_handlers: Vec<String>,
     sub_signatures: HashMap<String, Vec<(u16, DataType)>>,
     string_literals: HashMap<String, String>,
     select_stack_depth: usize,
+    use_ir: bool,
+    layout: Layout,
+    mapper: Mapper,
+    current_bank: Option<u8>,
+    // SUB name -> switchable bank it lives in
+    sub_banks: HashMap<String, u8>,
+    // Per-bank SUB order; entry i is reached through $8000 + 3 * i
+    bank_entries: BTreeMap<u8, Vec<String>>,
+    bank_output: BTreeMap<u8, Vec<String>>,
+    // ON SCANLINE handlers; handler i is selected by index i + 1
+    scanline_handlers: Vec<String>,
+    mirroring: Mirroring,
+    // CHR-RAM boards: how the startup CHR image is stored (None on CHR-ROM)
+    chr_ram: Option<Compression>,
+    // How the startup nametable section is stored
+    nametable_compression: Compression,
+    // One listing comment per packed asset
+    compression_report: Vec<String>,
+    // Storage format of each screen in the `screen_table` section
+    screens: Vec<Compression>,
+    uses_screen_load: bool,
+    uses_camera: bool,
+    uses_world: bool,
+    uses_world_scroll: bool,
+    uses_room_spawns: bool,
+    uses_collision_flags: bool,
+    uses_collision_sprites: bool,
+    uses_physics: bool,
+    // Text.Print, Scroll.LoadColumn/LoadRow, CHR.Load or PPU.Fill is called
+    uses_ppu_queue: bool,
+    // SPRITES 8X16: PPUCTRL bit 5 and 16-line OAM entries
+    tall_sprites: bool,
+    // HUD.Split: the opaque tile sprite 0 is drawn with (None without a split)
+    hud_tile: Option<u8>,
+    // ACTOR types in kind order, their classes, and the actor table: slots,
+    // bytes per slot, address (the loop state follows it)
+    actors: Vec<ActorDecl>,
+    actor_classes: Vec<String>,
+    actor_slots: u8,
+    actor_stride: u16,
+    actor_ram: u16,
+    // End of the DIM SAVE variables (SAVE_RAM_START when there are none)
+    save_pointer: u16,
+    // PASSWORD format and its RAM: byte buffer (payload, checksum, spill)
+    // followed by the zero-terminated character string
+    password: Option<PasswordFormat>,
+    password_ram: u16,
 }
 
 impl CodeGenerator {
@@ -31,12 +136,98 @@ impl CodeGenerator {
             ram_pointer: 0x0000,
             label_counter: 0,
             data_table_offsets: HashMap::new(),
+            sprite_addrs: HashMap::new(),
+            actor_handlers: Vec::new(),
             sub_signatures: HashMap::new(),
             string_literals: HashMap::new(),
             select_stack_depth: 0,
+            use_ir: false,
+            layout: Layout::legacy(),
+            mapper: Mapper::Nrom,
+            current_bank: None,
+            sub_banks: HashMap::new(),
+            bank_entries: BTreeMap::new(),
+            bank_output: BTreeMap::new(),
+            scanline_handlers: Vec::new(),
+            mirroring: Mirroring::Vertical,
+            chr_ram: None,
+            nametable_compression: Compression::None,
+            compression_report: Vec::new(),
+            screens: Vec::new(),
+            uses_screen_load: false,
+            uses_camera: false,
+            uses_world: false,
+            uses_room_spawns: false,
+            uses_world_scroll: false,
+            uses_collision_flags: false,
+            uses_collision_sprites: false,
+            uses_physics: false,
+            uses_ppu_queue: false,
+            tall_sprites: false,
+            hud_tile: None,
+            actors: Vec::new(),
+            actor_classes: Vec::new(),
+            actor_slots: 0,
+            actor_stride: 0,
+            actor_ram: 0,
+            save_pointer: SAVE_RAM_START,
+            password: None,
+            password_ram: 0,
         }
     }
 
+    /// Enables lowering SUB and interrupt bodies through the mid-level IR.
+    pub fn set_use_ir(&mut self, enabled: bool) {
+        self.use_ir = enabled;
+    }
+
+    /// Mirroring that mapper init selects on boards where it is software
+    /// controlled (MMC1, MMC3).
+    pub fn set_mirroring(&mut self, mirroring: Mirroring) {
+        self.mirroring = mirroring;
+    }
+
+    /// Targets a CHR-RAM board: startup uploads the `chr` section, stored
+    /// with the given compression, and `CHR.Load` becomes available.
+    pub fn set_chr_ram(&mut self, compression: Option<Compression>) {
+        self.chr_ram = compression;
+    }
+
+    /// Format of the `nametable` section, as chosen by `compress::pack`.
+    /// Anything but `None` is decompressed to the PPU at startup.
+    pub fn set_nametable_compression(&mut self, compression: Compression) {
+        self.nametable_compression = compression;
+    }
+
+    /// Asset compression lines (`compress::Packed::report`) listed after the
+    /// layout map.
+    pub fn set_compression_report(&mut self, lines: Vec<String>) {
+        self.compression_report = lines;
+    }
+
+    /// Screens reachable through `Screen.Load`, by id; screen 0 is the
+    /// startup `nametable` section.
+    pub fn set_screens(&mut self, formats: Vec<Compression>) {
+        self.screens = formats;
+    }
+
+    /// Enables `HUD.Split`, drawing sprite 0 with `tile`
+    /// (`hud::sprite_zero_tile`); the NMI then waits for its hit.
+    pub fn set_hud_tile(&mut self, tile: u8) {
+        self.hud_tile = Some(tile);
+    }
+
+    /// Labels whose addresses fill the `actor_handlers` section, known once
+    /// `generate` has run: the UPDATE entry of each kind, then the DRAW one.
+    pub fn actor_handlers(&self) -> &[String] {
+        &self.actor_handlers
+    }
+
+    /// Points generated code at the sections placed by the linker.
+    pub fn set_layout(&mut self, layout: Layout) {
+        self.layout = layout;
+    }
+
     fn new_label(&mut self) -> String {
         self.label_counter += 1;
         format!("GEN_L{}", self.label_counter)
@@ -44,30 +235,92 @@ impl CodeGenerator {
 
     pub fn generate(&mut self, program: &Program) -> Result<Vec<String>, String> {
         self.output.clear();
-        self.output.push(".ORG $8000".to_string());
+        self.actor_handlers.clear();
+        self.mapper = mapper::target(program)?;
+        self.collect_banks(program);
+        self.tall_sprites = sprites::tall(program);
+        self.actors = actors::declarations(program).into_iter().cloned().collect();
+        self.actor_classes = actors::classes(program);
+        self.actor_slots = actors::slots(program);
+        self.output
+            .push(format!(".ORG ${:04X}", self.mapper.fixed_bank_start()));
         self.output.push("; Generated by SwissArmyNES".to_string());
+        self.output.extend(self.layout.report());
+        self.output
+            .extend(self.compression_report.iter().map(|l| format!("; {}", l)));
+        self.output.extend(self.layout.symbols());
 
         self.allocate_memory(program)?;
         self.generate_startup_routine(program)?;
 
         for decl in &program.declarations {
-            self.generate_top_level(decl)?;
+            if let TopLevel::Bank(bank) = decl {
+                self.current_bank = *bank;
+                continue;
+            }
+            self.generate_banked(decl)?;
+        }
+        self.current_bank = None;
+        if (self.uses_ppu_queue || self.uses_camera) && self.ram_pointer > PPU_QUEUE_START {
+            return Err(format!(
+                "RAM overflow: variables end at ${:04X}, but the PPU queue needs ${:04X}-$07FF",
+                self.ram_pointer - 1,
+                PPU_QUEUE_START
+            ));
         }
 
+        let start = self.output.len();
         self.generate_sound_engine();
+        if let Some(bank) = self.layout.bank(SECTION_MUSIC) {
+            for name in ["Sound_Play", "SFX_Play", "Sound_Update"] {
+                self.generate_bank_wrapper(start, name, Some(bank));
+            }
+        }
         self.generate_math_helpers();
         self.generate_string_helpers();
         self.generate_string_data();
         self.generate_controller_helpers();
+        self.generate_ppu_queue_helpers();
         self.generate_text_helpers();
         self.generate_sprite_helpers();
+        self.generate_palette_helpers();
+        self.generate_hud_helpers();
         self.generate_animation_helpers();
         self.generate_pool_helpers();
+        self.generate_actor_helpers()?;
+        let start = self.output.len();
         self.generate_collision_helpers();
         self.generate_scroll_helpers();
+        self.generate_chr_helpers();
+        let chr_bank = self
+            .layout
+            .bank(SECTION_CHR)
+            .or(self.layout.bank(SECTION_CHR_TILES));
+        if let Some(bank) = chr_bank {
+            self.generate_bank_wrapper(start, "Runtime_CHR_Load", Some(bank));
+        }
+        self.generate_screen_helpers();
+        self.generate_camera_helpers();
+        self.generate_world_helpers();
+        if self.layout.get(SECTION_SCREEN_BANKS).is_some() {
+            for name in [
+                "Runtime_Collision_Flags",
+                "Runtime_Screen_Load",
+                "Runtime_Camera_Update",
+                "Runtime_World_Scroll",
+            ] {
+                self.generate_bank_wrapper(start, name, None);
+            }
+            self.generate_screen_bank_helper();
+        }
+        self.generate_decompress_helpers();
+        self.generate_save_helpers();
+        self.generate_password_helpers();
+        self.generate_bank_helpers();
         self.generate_user_data(program)?;
         self.generate_data_tables(program)?;
         self.generate_vectors(program)?;
+        self.generate_switchable_banks();
 
         Ok(self.output.clone())
     }
@@ -100,6 +353,7 @@ impl CodeGenerator {
         self.output.push("  STX $2000".to_string());
         self.output.push("  STX $2001".to_string());
         self.output.push("  STX $4010".to_string());
+        self.generate_mapper_init();
 
         self.output.push("vblankwait1:".to_string());
         self.output.push("  BIT $2002".to_string());
@@ -122,11 +376,20 @@ impl CodeGenerator {
 
         self.output.push("  LDA #0".to_string());
         self.output.push("  STA $18".to_string()); // Init Text Offset
+        self.output.push("  JSR Runtime_SpriteClear".to_string()); // Init Sprite Layers
+        if self.tall_sprites {
+            // 8x16 sprites: PPUCTRL bit 5, kept in the shadow with NMI still off
+            self.output.push("  LDA #$20".to_string());
+            self.output.push("  STA $F8".to_string());
+            self.output.push("  STA $2000".to_string());
+        }
 
         self.output.push("vblankwait2:".to_string());
         self.output.push("  BIT $2002".to_string());
         self.output.push("  BPL vblankwait2".to_string());
 
+        self.generate_chr_upload();
+
         self.output.push("  LDA #$3F".to_string());
         self.output.push("  STA $2006".to_string());
         self.output.push("  LDA #$00".to_string());
@@ -134,48 +397,74 @@ impl CodeGenerator {
 
         self.output.push("  LDX #$00".to_string());
         self.output.push("LoadPalLoop:".to_string());
-        self.output.push("  LDA $E000, X".to_string());
+        self.output.push(format!(
+            "  LDA ${:04X}, X",
+            self.layout.addr(SECTION_PALETTE)
+        ));
         self.output.push("  STA $2007".to_string());
+        self.output.push(format!("  STA ${:02X}, X", PALETTE_BASE));
+        self.output.push(format!("  STA ${:02X}, X", PALETTE_SHOWN));
         self.output.push("  INX".to_string());
         self.output.push("  CPX #32".to_string());
         self.output.push("  BNE LoadPalLoop".to_string());
+        self.output.push("  LDA #4".to_string()); // Palette fully faded in
+        self.output
+            .push(format!("  STA ${:02X}", PALETTE_STATE + 2));
 
         self.output.push("  LDA #$20".to_string());
         self.output.push("  STA $2006".to_string());
         self.output.push("  LDA #$00".to_string());
         self.output.push("  STA $2006".to_string());
 
-        self.output.push("  LDX #$00".to_string());
-        self.output.push("LoadNT1:".to_string());
-        self.output
-            .push(format!("  LDA ${:04X}, X", NAMETABLE_ADDR));
-        self.output.push("  STA $2007".to_string());
-        self.output.push("  INX".to_string());
-        self.output.push("  BNE LoadNT1".to_string());
-
-        self.output.push("  LDX #$00".to_string());
-        self.output.push("LoadNT2:".to_string());
-        self.output
-            .push(format!("  LDA ${:04X}, X", NAMETABLE_ADDR + 256));
-        self.output.push("  STA $2007".to_string());
-        self.output.push("  INX".to_string());
-        self.output.push("  BNE LoadNT2".to_string());
-
-        self.output.push("  LDX #$00".to_string());
-        self.output.push("LoadNT3:".to_string());
-        self.output
-            .push(format!("  LDA ${:04X}, X", NAMETABLE_ADDR + 512));
-        self.output.push("  STA $2007".to_string());
-        self.output.push("  INX".to_string());
-        self.output.push("  BNE LoadNT3".to_string());
-
-        self.output.push("  LDX #$00".to_string());
-        self.output.push("LoadNT4:".to_string());
-        self.output
-            .push(format!("  LDA ${:04X}, X", NAMETABLE_ADDR + 768));
-        self.output.push("  STA $2007".to_string());
-        self.output.push("  INX".to_string());
-        self.output.push("  BNE LoadNT4".to_string());
+        if self.nametable_compression != Compression::None {
+            let addr = self.layout.addr(SECTION_NAMETABLE);
+            self.output.push(format!("  LDA #${:02X}", addr & 0xFF));
+            self.output.push("  STA $02".to_string());
+            self.output.push(format!("  LDA #${:02X}", addr >> 8));
+            self.output.push("  STA $03".to_string());
+            let routine = Self::ppu_decompressor(self.nametable_compression);
+            self.output.push(format!("  JSR {}", routine));
+        } else {
+            self.output.push("  LDX #$00".to_string());
+            self.output.push("LoadNT1:".to_string());
+            self.output.push(format!(
+                "  LDA ${:04X}, X",
+                self.layout.addr(SECTION_NAMETABLE)
+            ));
+            self.output.push("  STA $2007".to_string());
+            self.output.push("  INX".to_string());
+            self.output.push("  BNE LoadNT1".to_string());
+
+            self.output.push("  LDX #$00".to_string());
+            self.output.push("LoadNT2:".to_string());
+            self.output.push(format!(
+                "  LDA ${:04X}, X",
+                self.layout.addr(SECTION_NAMETABLE) + 256
+            ));
+            self.output.push("  STA $2007".to_string());
+            self.output.push("  INX".to_string());
+            self.output.push("  BNE LoadNT2".to_string());
+
+            self.output.push("  LDX #$00".to_string());
+            self.output.push("LoadNT3:".to_string());
+            self.output.push(format!(
+                "  LDA ${:04X}, X",
+                self.layout.addr(SECTION_NAMETABLE) + 512
+            ));
+            self.output.push("  STA $2007".to_string());
+            self.output.push("  INX".to_string());
+            self.output.push("  BNE LoadNT3".to_string());
+
+            self.output.push("  LDX #$00".to_string());
+            self.output.push("LoadNT4:".to_string());
+            self.output.push(format!(
+                "  LDA ${:04X}, X",
+                self.layout.addr(SECTION_NAMETABLE) + 768
+            ));
+            self.output.push("  STA $2007".to_string());
+            self.output.push("  INX".to_string());
+            self.output.push("  BNE LoadNT4".to_string());
+        }
 
         self.output.push("  JSR Sound_Init".to_string());
 
@@ -286,6 +575,10 @@ impl CodeGenerator {
             }
         }
 
+        if self.mapper == Mapper::Mmc3 {
+            // Scanline IRQs stay masked by the mapper until a split is armed
+            self.output.push("  CLI".to_string());
+        }
         self.output.push("  JSR Main".to_string());
 
         self.output.push("forever:".to_string());
@@ -304,6 +597,7 @@ impl CodeGenerator {
             self.output.push(format!("  LDA ${:02X}", i));
             self.output.push("  PHA".to_string());
         }
+        self.generate_bank_select_save();
 
         // OAM DMA
         self.output.push("  LDA #$00".to_string());
@@ -311,63 +605,32 @@ impl CodeGenerator {
         self.output.push("  LDA #$02".to_string());
         self.output.push("  STA $4014".to_string());
 
-        // VBlank Buffer Processing
-        self.output
-            .push(format!("  LDA ${:04X}", VBLANK_BUFFER_START));
-        self.output.push("  BEQ SkipVBlankBuffer".to_string());
-        self.output.push("  LDA $2002".to_string());
-        self.output
-            .push(format!("  LDA ${:04X}", VBLANK_BUFFER_START + 2));
-        self.output.push("  STA $2006".to_string());
-        self.output
-            .push(format!("  LDA ${:04X}", VBLANK_BUFFER_START + 3));
-        self.output.push("  STA $2006".to_string());
-        // Check Type ($0441)
-        self.output
-            .push(format!("  LDA ${:04X}", VBLANK_BUFFER_START + 1));
-        self.output.push("  BNE VBlankBufferRow".to_string());
-
-        // Type 0: Column (Inc 32, Len 30)
-        self.output.push("  LDA $F8".to_string());
-        self.output.push("  ORA #$04".to_string()); // Inc 32
-        self.output.push("  STA $2000".to_string());
-        self.output.push("  LDX #0".to_string());
-        self.output.push("VBlankBufferColLoop:".to_string());
-        self.output
-            .push(format!("  LDA ${:04X}, X", VBLANK_BUFFER_START + 4));
-        self.output.push("  STA $2007".to_string());
-        self.output.push("  INX".to_string());
-        self.output.push("  CPX #30".to_string());
-        self.output.push("  BNE VBlankBufferColLoop".to_string());
-        self.output.push("  JMP VBlankBufferDone".to_string());
-
-        self.output.push("VBlankBufferRow:".to_string());
-        // Type 1: Row (Inc 1, Len 32)
-        self.output.push("  LDA $F8".to_string());
-        self.output.push("  AND #$FB".to_string()); // Inc 1 (Clear Bit 2)
-        self.output.push("  STA $2000".to_string());
-        self.output.push("  LDX #0".to_string());
-        self.output.push("VBlankBufferRowLoop:".to_string());
-        self.output
-            .push(format!("  LDA ${:04X}, X", VBLANK_BUFFER_START + 4));
-        self.output.push("  STA $2007".to_string());
-        self.output.push("  INX".to_string());
-        self.output.push("  CPX #32".to_string());
-        self.output.push("  BNE VBlankBufferRowLoop".to_string());
-
-        self.output.push("VBlankBufferDone:".to_string());
-        self.output.push("  LDA $F8".to_string());
-        self.output.push("  STA $2000".to_string());
-        self.output.push("  LDA #0".to_string());
-        self.output
-            .push(format!("  STA ${:04X}", VBLANK_BUFFER_START));
-        self.output.push("SkipVBlankBuffer:".to_string());
+        // Upload the palette buffer, then drain queued PPU updates within
+        // what is left of the frame's byte budget
+        self.output.push("  JSR Palette_Flush".to_string());
+        self.output.push("  JSR PpuQueue_Flush".to_string());
+
+        if self.mapper == Mapper::Mmc3 {
+            // Restart the frame's scanline split: $1F = line, $E4 = handler
+            self.output.push("  STA $E000".to_string());
+            self.output.push("  LDA $1F".to_string());
+            self.output.push("  BEQ SkipScanlineArm".to_string());
+            self.output.push("  STA $C000".to_string());
+            self.output.push("  STA $C001".to_string());
+            self.output.push("  STA $E001".to_string());
+            self.output.push("  LDA $E4".to_string());
+            self.output.push("  STA $E5".to_string());
+            self.output.push("SkipScanlineArm:".to_string());
+        }
 
+        self.output.push("  JSR Palette_Update".to_string());
         self.output.push("  JSR Sound_Update".to_string());
         self.output.push("  LDA $03FA".to_string());
         self.output.push("  ORA $03FB".to_string());
         self.output.push("  BEQ SkipNMI".to_string());
+        self.output.push(format!("  INC ${:04X}", PPU_QUEUE_IN_NMI));
         self.output.push("  JSR CallUserNMI".to_string());
+        self.output.push(format!("  DEC ${:04X}", PPU_QUEUE_IN_NMI));
         self.output.push("SkipNMI:".to_string());
 
         self.output.push("  LDA $2002".to_string());
@@ -375,7 +638,11 @@ impl CodeGenerator {
         self.output.push("  STA $2005".to_string());
         self.output.push("  LDA $E1".to_string());
         self.output.push("  STA $2005".to_string());
+        if self.hud_tile.is_some() {
+            self.output.push("  JSR Hud_Split".to_string());
+        }
 
+        self.generate_bank_select_restore();
         for i in (0..16).rev() {
             self.output.push("  PLA".to_string());
             self.output.push(format!("  STA ${:02X}", i));
@@ -400,6 +667,19 @@ impl CodeGenerator {
             self.output.push(format!("  LDA ${:02X}", i));
             self.output.push("  PHA".to_string());
         }
+        self.generate_bank_select_save();
+
+        if self.mapper == Mapper::Mmc3 {
+            // Acknowledge the scanline IRQ; $E6 tells ON SCANLINE it is
+            // chaining from inside a handler
+            self.output.push("  STA $E000".to_string());
+            self.output.push("  LDA $E5".to_string());
+            self.output.push("  BEQ SkipScanline".to_string());
+            self.output.push("  INC $E6".to_string());
+            self.output.push("  JSR Scanline_Dispatch".to_string());
+            self.output.push("  DEC $E6".to_string());
+            self.output.push("SkipScanline:".to_string());
+        }
 
         self.output.push("  LDA $03FC".to_string());
         self.output.push("  ORA $03FD".to_string());
@@ -407,6 +687,7 @@ impl CodeGenerator {
         self.output.push("  JSR CallUserIRQ".to_string());
         self.output.push("SkipIRQ:".to_string());
 
+        self.generate_bank_select_restore();
         for i in (0..16).rev() {
             self.output.push("  PLA".to_string());
             self.output.push(format!("  STA ${:02X}", i));
@@ -444,7 +725,8 @@ impl CodeGenerator {
 
         self.output.push("Sound_Play:".to_string());
         self.output.push("  PHA".to_string());
-        self.output.push("  LDA $D100".to_string());
+        self.output
+            .push(format!("  LDA ${:04X}", self.layout.addr(SECTION_MUSIC)));
         self.output.push("  STA $F0".to_string());
         self.output.push("  PLA".to_string());
         self.output.push("  CMP $F0".to_string());
@@ -453,9 +735,15 @@ impl CodeGenerator {
         self.output.push("SndPlay_Check1:".to_string());
         self.output.push("  ASL".to_string());
         self.output.push("  TAX".to_string());
-        self.output.push("  LDA $D101, X".to_string());
+        self.output.push(format!(
+            "  LDA ${:04X}, X",
+            self.layout.addr(SECTION_MUSIC) + 1
+        ));
         self.output.push("  STA $F0".to_string());
-        self.output.push("  LDA $D102, X".to_string());
+        self.output.push(format!(
+            "  LDA ${:04X}, X",
+            self.layout.addr(SECTION_MUSIC) + 2
+        ));
         self.output.push("  STA $F1".to_string());
 
         self.output.push("  LDY #$00".to_string());
@@ -551,7 +839,8 @@ impl CodeGenerator {
 
         self.output.push("SFX_Play:".to_string());
         self.output.push("  PHA".to_string());
-        self.output.push(format!("  LDA ${:04X}", SFX_TABLE_ADDR));
+        self.output
+            .push(format!("  LDA ${:04X}", self.layout.addr(SECTION_SFX)));
         self.output.push("  STA $F0".to_string());
         self.output.push("  PLA".to_string());
         self.output.push("  CMP $F0".to_string());
@@ -560,11 +849,15 @@ impl CodeGenerator {
         self.output.push("SFXPlay_Check1:".to_string());
         self.output.push("  ASL".to_string());
         self.output.push("  TAX".to_string());
-        self.output
-            .push(format!("  LDA ${:04X}, X", SFX_TABLE_ADDR + 1));
+        self.output.push(format!(
+            "  LDA ${:04X}, X",
+            self.layout.addr(SECTION_SFX) + 1
+        ));
         self.output.push("  STA $F0".to_string());
-        self.output
-            .push(format!("  LDA ${:04X}, X", SFX_TABLE_ADDR + 2));
+        self.output.push(format!(
+            "  LDA ${:04X}, X",
+            self.layout.addr(SECTION_SFX) + 2
+        ));
         self.output.push("  STA $F1".to_string());
 
         self.output.push("  LDY #0".to_string());
@@ -773,9 +1066,15 @@ impl CodeGenerator {
 
         self.output.push("  ASL".to_string());
         self.output.push("  TAY".to_string());
-        self.output.push("  LDA $D000, Y".to_string());
+        self.output.push(format!(
+            "  LDA ${:04X}, Y",
+            self.layout.addr(SECTION_PERIOD_TABLE)
+        ));
         self.output.push("  STA $F6".to_string());
-        self.output.push("  LDA $D001, Y".to_string());
+        self.output.push(format!(
+            "  LDA ${:04X}, Y",
+            self.layout.addr(SECTION_PERIOD_TABLE) + 1
+        ));
         self.output.push("  STA $F7".to_string());
 
         self.output.push("  LDA $F6".to_string());
@@ -875,10 +1174,16 @@ impl CodeGenerator {
         self.output.push("  LDA $F6".to_string());
         self.output.push("  ASL".to_string());
         self.output.push("  TAY".to_string());
-        self.output.push("  LDA $D480, Y".to_string());
+        self.output.push(format!(
+            "  LDA ${:04X}, Y",
+            self.layout.addr(SECTION_SAMPLE_TABLE)
+        ));
         self.output.push("  STA $4012".to_string());
         self.output.push("  INY".to_string());
-        self.output.push("  LDA $D480, Y".to_string());
+        self.output.push(format!(
+            "  LDA ${:04X}, Y",
+            self.layout.addr(SECTION_SAMPLE_TABLE)
+        ));
         self.output.push("  STA $4013".to_string());
         self.output.push("  LDA $F7".to_string());
         self.output.push("  AND #$0F".to_string());
@@ -919,11 +1224,15 @@ impl CodeGenerator {
             .push(format!("  LDA ${:04X}, X", SOUND_RAM_START + 0x06));
         self.output.push("  ASL".to_string());
         self.output.push("  TAY".to_string());
-        self.output
-            .push(format!("  LDA ${:04X}, Y", ENVELOPE_TABLE_ADDR + 1));
+        self.output.push(format!(
+            "  LDA ${:04X}, Y",
+            self.layout.addr(SECTION_ENVELOPES) + 1
+        ));
         self.output.push("  STA $F0".to_string());
-        self.output
-            .push(format!("  LDA ${:04X}, Y", ENVELOPE_TABLE_ADDR + 2));
+        self.output.push(format!(
+            "  LDA ${:04X}, Y",
+            self.layout.addr(SECTION_ENVELOPES) + 2
+        ));
         self.output.push("  STA $F1".to_string());
 
         self.output
@@ -961,11 +1270,15 @@ impl CodeGenerator {
             .push(format!("  LDA ${:04X}, X", SOUND_RAM_START + 0x06));
         self.output.push("  ASL".to_string());
         self.output.push("  TAY".to_string());
-        self.output
-            .push(format!("  LDA ${:04X}, Y", ENVELOPE_TABLE_ADDR + 1));
+        self.output.push(format!(
+            "  LDA ${:04X}, Y",
+            self.layout.addr(SECTION_ENVELOPES) + 1
+        ));
         self.output.push("  STA $F0".to_string());
-        self.output
-            .push(format!("  LDA ${:04X}, Y", ENVELOPE_TABLE_ADDR + 2));
+        self.output.push(format!(
+            "  LDA ${:04X}, Y",
+            self.layout.addr(SECTION_ENVELOPES) + 2
+        ));
         self.output.push("  STA $F1".to_string());
         self.output.push("  LDY #0".to_string());
         self.output.push("  LDA ($F0), Y".to_string());
@@ -1044,11 +1357,15 @@ impl CodeGenerator {
             .push(format!("  LDA ${:04X}, X", SOUND_RAM_START + 0x09));
         self.output.push("  ASL".to_string());
         self.output.push("  TAY".to_string());
-        self.output
-            .push(format!("  LDA ${:04X}, Y", ENVELOPE_TABLE_ADDR + 1));
+        self.output.push(format!(
+            "  LDA ${:04X}, Y",
+            self.layout.addr(SECTION_ENVELOPES) + 1
+        ));
         self.output.push("  STA $F0".to_string());
-        self.output
-            .push(format!("  LDA ${:04X}, Y", ENVELOPE_TABLE_ADDR + 2));
+        self.output.push(format!(
+            "  LDA ${:04X}, Y",
+            self.layout.addr(SECTION_ENVELOPES) + 2
+        ));
         self.output.push("  STA $F1".to_string());
         self.output
             .push(format!("  LDA ${:04X}, X", SOUND_RAM_START + 0x0A));
@@ -1081,11 +1398,15 @@ impl CodeGenerator {
             .push(format!("  LDA ${:04X}, X", SOUND_RAM_START + 0x09));
         self.output.push("  ASL".to_string());
         self.output.push("  TAY".to_string());
-        self.output
-            .push(format!("  LDA ${:04X}, Y", ENVELOPE_TABLE_ADDR + 1));
+        self.output.push(format!(
+            "  LDA ${:04X}, Y",
+            self.layout.addr(SECTION_ENVELOPES) + 1
+        ));
         self.output.push("  STA $F0".to_string());
-        self.output
-            .push(format!("  LDA ${:04X}, Y", ENVELOPE_TABLE_ADDR + 2));
+        self.output.push(format!(
+            "  LDA ${:04X}, Y",
+            self.layout.addr(SECTION_ENVELOPES) + 2
+        ));
         self.output.push("  STA $F1".to_string());
         self.output.push("  LDY #0".to_string());
         self.output.push("  LDA ($F0), Y".to_string());
@@ -1128,11 +1449,15 @@ impl CodeGenerator {
             .push(format!("  LDA ${:04X}, X", SOUND_RAM_START + 0x13));
         self.output.push("  ASL".to_string());
         self.output.push("  TAY".to_string());
-        self.output
-            .push(format!("  LDA ${:04X}, Y", ENVELOPE_TABLE_ADDR + 1));
+        self.output.push(format!(
+            "  LDA ${:04X}, Y",
+            self.layout.addr(SECTION_ENVELOPES) + 1
+        ));
         self.output.push("  STA $F0".to_string());
-        self.output
-            .push(format!("  LDA ${:04X}, Y", ENVELOPE_TABLE_ADDR + 2));
+        self.output.push(format!(
+            "  LDA ${:04X}, Y",
+            self.layout.addr(SECTION_ENVELOPES) + 2
+        ));
         self.output.push("  STA $F1".to_string());
         self.output
             .push(format!("  LDA ${:04X}, X", SOUND_RAM_START + 0x14));
@@ -1164,11 +1489,15 @@ impl CodeGenerator {
             .push(format!("  LDA ${:04X}, X", SOUND_RAM_START + 0x13));
         self.output.push("  ASL".to_string());
         self.output.push("  TAY".to_string());
-        self.output
-            .push(format!("  LDA ${:04X}, Y", ENVELOPE_TABLE_ADDR + 1));
+        self.output.push(format!(
+            "  LDA ${:04X}, Y",
+            self.layout.addr(SECTION_ENVELOPES) + 1
+        ));
         self.output.push("  STA $F0".to_string());
-        self.output
-            .push(format!("  LDA ${:04X}, Y", ENVELOPE_TABLE_ADDR + 2));
+        self.output.push(format!(
+            "  LDA ${:04X}, Y",
+            self.layout.addr(SECTION_ENVELOPES) + 2
+        ));
         self.output.push("  STA $F1".to_string());
         self.output.push("  LDY #0".to_string());
         self.output.push("  LDA ($F0), Y".to_string());
@@ -1220,11 +1549,15 @@ impl CodeGenerator {
             .push(format!("  LDA ${:04X}, X", SOUND_RAM_START + 0x0D));
         self.output.push("  ASL".to_string());
         self.output.push("  TAY".to_string());
-        self.output
-            .push(format!("  LDA ${:04X}, Y", ENVELOPE_TABLE_ADDR + 1));
+        self.output.push(format!(
+            "  LDA ${:04X}, Y",
+            self.layout.addr(SECTION_ENVELOPES) + 1
+        ));
         self.output.push("  STA $F0".to_string());
-        self.output
-            .push(format!("  LDA ${:04X}, Y", ENVELOPE_TABLE_ADDR + 2));
+        self.output.push(format!(
+            "  LDA ${:04X}, Y",
+            self.layout.addr(SECTION_ENVELOPES) + 2
+        ));
         self.output.push("  STA $F1".to_string());
         self.output
             .push(format!("  LDA ${:04X}, X", SOUND_RAM_START + 0x0E));
@@ -1256,11 +1589,15 @@ impl CodeGenerator {
             .push(format!("  LDA ${:04X}, X", SOUND_RAM_START + 0x0D));
         self.output.push("  ASL".to_string());
         self.output.push("  TAY".to_string());
-        self.output
-            .push(format!("  LDA ${:04X}, Y", ENVELOPE_TABLE_ADDR + 1));
+        self.output.push(format!(
+            "  LDA ${:04X}, Y",
+            self.layout.addr(SECTION_ENVELOPES) + 1
+        ));
         self.output.push("  STA $F0".to_string());
-        self.output
-            .push(format!("  LDA ${:04X}, Y", ENVELOPE_TABLE_ADDR + 2));
+        self.output.push(format!(
+            "  LDA ${:04X}, Y",
+            self.layout.addr(SECTION_ENVELOPES) + 2
+        ));
         self.output.push("  STA $F1".to_string());
         self.output.push("  LDY #0".to_string());
         self.output.push("  LDA ($F0), Y".to_string());
@@ -1304,9 +1641,15 @@ impl CodeGenerator {
 
         self.output.push("  ASL".to_string());
         self.output.push("  TAY".to_string());
-        self.output.push("  LDA $D000, Y".to_string());
+        self.output.push(format!(
+            "  LDA ${:04X}, Y",
+            self.layout.addr(SECTION_PERIOD_TABLE)
+        ));
         self.output.push("  STA $F6".to_string());
-        self.output.push("  LDA $D001, Y".to_string());
+        self.output.push(format!(
+            "  LDA ${:04X}, Y",
+            self.layout.addr(SECTION_PERIOD_TABLE) + 1
+        ));
         self.output.push("  STA $F7".to_string());
 
         self.output.push("  LDA $F6".to_string());
@@ -1751,6 +2094,10 @@ impl CodeGenerator {
         // Runtime_Anim_Draw
         // Input: $14 (X), $15 (Y), $16/$17 (State Ptr)
         self.output.push("Runtime_Anim_Draw:".to_string());
+        self.output.push("  LDA #0".to_string());
+        self.output.push("  STA $07".to_string());
+        // Flip flags in $07
+        self.output.push("Runtime_Anim_DrawFlags:".to_string());
 
         // Copy State Ptr to $02/$03
         self.output.push("  LDA $16".to_string());
@@ -1785,8 +2132,9 @@ impl CodeGenerator {
         self.output.push("  LDA ($04), Y".to_string());
         self.output.push("  STA $17".to_string()); // Store in Argument 2 High
 
-        // Call Sprite Draw (X/Y already in $14/$15)
-        self.output.push("  JSR Runtime_SpriteDraw".to_string());
+        // Call Sprite Draw (X/Y already in $14/$15, flags in $07)
+        self.output
+            .push("  JSR Runtime_SpriteDrawFlags".to_string());
         self.output.push("  RTS".to_string());
 
         self.output.push("".to_string());
@@ -1954,14 +2302,18 @@ impl CodeGenerator {
         self.output.push("  ADC #0".to_string());
         self.output.push("  STA $03".to_string());
 
-        // Add Base Addr $D500
+        // Add nametable base address
         self.output.push("  LDA $02".to_string());
-        self.output
-            .push(format!("  ADC #${:02X}", NAMETABLE_ADDR & 0xFF));
+        self.output.push(format!(
+            "  ADC #${:02X}",
+            self.layout.addr(SECTION_NAMETABLE) & 0xFF
+        ));
         self.output.push("  STA $02".to_string());
         self.output.push("  LDA $03".to_string());
-        self.output
-            .push(format!("  ADC #${:02X}", (NAMETABLE_ADDR >> 8) & 0xFF));
+        self.output.push(format!(
+            "  ADC #${:02X}",
+            (self.layout.addr(SECTION_NAMETABLE) >> 8) & 0xFF
+        ));
         self.output.push("  STA $03".to_string());
 
         // Read
@@ -1974,93 +2326,815 @@ impl CodeGenerator {
         self.output.push("  LDA #0".to_string());
         self.output.push("  LDX #0".to_string());
         self.output.push("  RTS".to_string());
+        if self.uses_collision_flags {
+            self.generate_collision_flags_helper();
+        }
+        if self.uses_collision_sprites {
+            self.generate_collision_sprites_helper();
+        }
+        if self.uses_physics {
+            self.generate_physics_helpers();
+        }
         self.output.push("".to_string());
     }
 
-    fn generate_scroll_helpers(&mut self) {
-        self.output.push("; --- Scroll Helpers ---".to_string());
-
-        self.output.push("Runtime_Scroll_LoadColumn:".to_string());
-
-        // Check Buffer
-        self.output
-            .push(format!("  LDA ${:04X}", VBLANK_BUFFER_START));
-        self.output.push("  BNE Scroll_LoadColumn_Done".to_string());
-
-        // Save Data Ptr (A/X) -> $00/$01
-        self.output.push("  STA $00".to_string());
-        self.output.push("  STX $01".to_string());
-
-        // Pop Virtual X -> $02/$03
+    // Collision.Flags(worldX, worldY): $00/$01 = x, $02/$03 = y
+    fn generate_collision_flags(&mut self, args: &[Expression]) -> Result<DataType, String> {
+        if self.layout.get(SECTION_COLLISION).is_none() {
+            return Err(
+                "Collision.Flags requires the collision table (compile with project assets)"
+                    .to_string(),
+            );
+        }
+        self.uses_collision_flags = true;
+        if self.generate_expression(&args[0])? == DataType::Byte {
+            self.output.push("  LDX #0".to_string());
+        }
+        self.output.push("  PHA".to_string());
+        self.output.push("  TXA".to_string());
+        self.output.push("  PHA".to_string());
+        if self.generate_expression(&args[1])? == DataType::Byte {
+            self.output.push("  LDX #0".to_string());
+        }
+        self.output.push("  STA $02".to_string());
+        self.output.push("  STX $03".to_string());
         self.output.push("  PLA".to_string());
-        self.output.push("  STA $03".to_string()); // High
+        self.output.push("  STA $01".to_string());
         self.output.push("  PLA".to_string());
-        self.output.push("  STA $02".to_string()); // Low
-
-        // Calculate Target High ($05)
-        // Base $20 or $24 based on X High Bit 0
-        self.output.push("  LDA $03".to_string());
-        self.output.push("  AND #1".to_string());
-        self.output.push("  BEQ Scroll_Base2000".to_string());
-        self.output.push("  LDA #$24".to_string());
-        self.output.push("  JMP Scroll_BaseStore".to_string());
-        self.output.push("Scroll_Base2000:".to_string());
-        self.output.push("  LDA #$20".to_string());
-        self.output.push("Scroll_BaseStore:".to_string());
-        self.output.push("  STA $05".to_string());
-
-        // Calculate Target Low ($04) = (X Low / 8)
-        self.output.push("  LDA $02".to_string());
-        self.output.push("  LSR".to_string());
-        self.output.push("  LSR".to_string());
-        self.output.push("  LSR".to_string());
-        self.output.push("  STA $04".to_string());
-
-        // Write Header
-        self.output.push("  LDA #0".to_string());
-        self.output
-            .push(format!("  STA ${:04X}", VBLANK_BUFFER_START + 1)); // Type
-        self.output.push("  LDA $05".to_string());
+        self.output.push("  STA $00".to_string());
         self.output
-            .push(format!("  STA ${:04X}", VBLANK_BUFFER_START + 2)); // High
-        self.output.push("  LDA $04".to_string());
+            .push("  JSR Runtime_Collision_Flags".to_string());
+        Ok(DataType::Byte)
+    }
+
+    // Collision.Sprites(animA, xA, yA, animB, xB, yB): the four positions go
+    // on the stack as words, the AnimState addresses in $14/$15 and $16/$17
+    fn generate_collision_sprites(&mut self, args: &[Expression]) -> Result<DataType, String> {
+        self.uses_collision_sprites = true;
+        for arg in [&args[1], &args[2], &args[4], &args[5]] {
+            let dtype = self.generate_expression(arg)?;
+            self.output.push("  PHA".to_string()); // Low
+            if dtype == DataType::Word || dtype == DataType::Int {
+                self.output.push("  TXA".to_string());
+            } else {
+                self.output.push("  LDA #0".to_string());
+            }
+            self.output.push("  PHA".to_string()); // High
+        }
+        for (arg, zp) in [(&args[0], 0x14), (&args[3], 0x16)] {
+            let state_addr = self.get_static_address(arg)?;
+            self.output
+                .push(format!("  LDA #${:02X}", (state_addr & 0xFF) as u8));
+            self.output.push(format!("  STA ${:02X}", zp));
+            self.output
+                .push(format!("  LDA #${:02X}", ((state_addr >> 8) & 0xFF) as u8));
+            self.output.push(format!("  STA ${:02X}", zp + 1));
+        }
         self.output
-            .push(format!("  STA ${:04X}", VBLANK_BUFFER_START + 3)); // Low
+            .push("  JSR Runtime_Collision_Sprites".to_string());
+        // Clean up stack (4 args * 2 bytes = 8)
+        self.output.push("  TSX".to_string());
+        self.output.push("  TXA".to_string());
+        self.output.push("  CLC".to_string());
+        self.output.push("  ADC #8".to_string());
+        self.output.push("  TAX".to_string());
+        self.output.push("  TXS".to_string());
+        self.output.push("  LDX #0".to_string());
+        Ok(DataType::Bool)
+    }
 
-        // Copy Data
+    // Runtime_Collision_Sprites: A's hitbox against B's hurtbox at their
+    // current animation frames. The boxes follow the tiles in the metasprite
+    // data (x, y, w, h each); a zero-sized box never overlaps. Both are moved
+    // to their sprite's position and handed to Runtime_Collision_Rect.
+    fn generate_collision_sprites_helper(&mut self) {
+        // Collision_SpriteBox: ($02) = AnimState -> ($04) = current frame's
+        // metasprite, Y = offset of its hitbox
+        self.output.push("Collision_SpriteBox:".to_string());
         self.output.push("  LDY #0".to_string());
-        self.output.push("Scroll_CopyLoop:".to_string());
-        self.output.push("  LDA ($00), Y".to_string());
-        self.output
-            .push(format!("  STA ${:04X}, Y", VBLANK_BUFFER_START + 4));
+        self.output.push("  LDA ($02), Y".to_string());
+        self.output.push("  STA $04".to_string());
         self.output.push("  INY".to_string());
-        self.output.push("  CPY #30".to_string());
-        self.output.push("  BNE Scroll_CopyLoop".to_string());
-
-        // Set Flag
-        self.output.push("  LDA #1".to_string());
-        self.output
-            .push(format!("  STA ${:04X}", VBLANK_BUFFER_START));
-
-        self.output.push("Scroll_LoadColumn_Done:".to_string());
-        self.output.push("  RTS".to_string());
-        self.output.push("".to_string());
-
-        self.output.push("Runtime_Scroll_LoadRow:".to_string());
-
-        // Check Buffer
-        self.output
-            .push(format!("  LDA ${:04X}", VBLANK_BUFFER_START));
-        self.output.push("  BNE Scroll_LoadRow_Done".to_string());
-
-        // Save Data Ptr (A/X) -> $00/$01
-        self.output.push("  STA $00".to_string());
-        self.output.push("  STX $01".to_string());
-
-        // Pop Virtual Y -> $02/$03
-        self.output.push("  PLA".to_string());
-        self.output.push("  STA $03".to_string()); // High
+        self.output.push("  LDA ($02), Y".to_string());
+        self.output.push("  STA $05".to_string());
+        self.output.push("  INY".to_string());
+        self.output.push("  LDA ($02), Y".to_string()); // Frame index
+        self.output.push("  STA $06".to_string());
+        self.output.push("  ASL".to_string());
+        self.output.push("  CLC".to_string());
+        self.output.push("  ADC $06".to_string());
+        self.output.push("  ADC #2".to_string());
+        self.output.push("  TAY".to_string());
+        self.output.push("  LDA ($04), Y".to_string());
+        self.output.push("  PHA".to_string());
+        self.output.push("  INY".to_string());
+        self.output.push("  LDA ($04), Y".to_string());
+        self.output.push("  STA $05".to_string());
+        self.output.push("  PLA".to_string());
+        self.output.push("  STA $04".to_string());
+        // Boxes start after Count(1) + Tiles(N * 4)
+        self.output.push("  LDY #0".to_string());
+        self.output.push("  LDA ($04), Y".to_string());
+        self.output.push("  ASL".to_string());
+        self.output.push("  ASL".to_string());
+        self.output.push("  CLC".to_string());
+        self.output.push("  ADC #1".to_string());
+        self.output.push("  TAY".to_string());
+        self.output.push("  RTS".to_string());
+        self.output.push("Collision_SpritesMiss:".to_string());
+        self.output.push("  JMP Collision_False".to_string());
+
+        self.output.push("Runtime_Collision_Sprites:".to_string());
+        // $08/$09 = A's hitbox, $0A/$0B = B's hurtbox (4 bytes further)
+        for (state, boxp, skip) in [(0x14, 0x08, 0), (0x16, 0x0A, 4)] {
+            self.output.push(format!("  LDA ${:02X}", state));
+            self.output.push("  STA $02".to_string());
+            self.output.push(format!("  LDA ${:02X}", state + 1));
+            self.output.push("  STA $03".to_string());
+            self.output.push("  JSR Collision_SpriteBox".to_string());
+            self.output.push("  TYA".to_string());
+            self.output.push("  CLC".to_string());
+            if skip != 0 {
+                self.output.push(format!("  ADC #{}", skip));
+            }
+            self.output.push("  ADC $04".to_string());
+            self.output.push(format!("  STA ${:02X}", boxp));
+            self.output.push("  LDA $05".to_string());
+            self.output.push("  ADC #0".to_string());
+            self.output.push(format!("  STA ${:02X}", boxp + 1));
+            for offset in [2, 3] {
+                self.output.push(format!("  LDY #{}", offset));
+                self.output.push(format!("  LDA (${:02X}), Y", boxp));
+                self.output.push("  BEQ Collision_SpritesMiss".to_string());
+            }
+        }
+
+        // Push x1, y1, w1, h1, x2, y2, w2, h2 for Runtime_Collision_Rect.
+        // X stays on the entry frame: yB $0103, xB $0105, yA $0107, xA $0109
+        self.output.push("  TSX".to_string());
+        for (boxp, x_hi, y_hi) in [(0x08, 0x0109, 0x0107), (0x0A, 0x0105, 0x0103)] {
+            for (offset, hi) in [(0, x_hi), (1, y_hi)] {
+                // Sign-extend the box offset into $07
+                self.output.push(format!("  LDY #{}", offset));
+                self.output.push(format!("  LDA (${:02X}), Y", boxp));
+                self.output.push("  STA $06".to_string());
+                self.output.push("  ASL".to_string());
+                self.output.push("  LDA #0".to_string());
+                self.output.push("  SBC #0".to_string());
+                self.output.push("  EOR #$FF".to_string());
+                self.output.push("  STA $07".to_string());
+                self.output.push("  CLC".to_string());
+                self.output.push("  LDA $06".to_string());
+                self.output.push(format!("  ADC ${:04X}, X", hi + 1));
+                self.output.push("  PHA".to_string());
+                self.output.push("  LDA $07".to_string());
+                self.output.push(format!("  ADC ${:04X}, X", hi));
+                self.output.push("  PHA".to_string());
+            }
+            for offset in [2, 3] {
+                self.output.push(format!("  LDY #{}", offset));
+                self.output.push(format!("  LDA (${:02X}), Y", boxp));
+                self.output.push("  PHA".to_string());
+                self.output.push("  LDA #0".to_string());
+                self.output.push("  PHA".to_string());
+            }
+        }
+        self.output.push("  JSR Runtime_Collision_Rect".to_string());
+        // Drop the 16 bytes, keeping the result
+        self.output.push("  TAY".to_string());
+        self.output.push("  TSX".to_string());
+        self.output.push("  TXA".to_string());
+        self.output.push("  CLC".to_string());
+        self.output.push("  ADC #16".to_string());
+        self.output.push("  TAX".to_string());
+        self.output.push("  TXS".to_string());
+        self.output.push("  TYA".to_string());
+        self.output.push("  LDX #0".to_string());
+        self.output.push("  RTS".to_string());
+    }
+
+    // Runtime_Collision_Flags: the flags of the tile at a world pixel. With a
+    // WORLD map that is room (x / 256, y / 240), as Camera.Follow and
+    // World.Enter lay rooms out; without one, the startup nametable. Off the
+    // map and in empty rooms everything is solid.
+    fn generate_collision_flags_helper(&mut self) {
+        let table = self.layout.addr(SECTION_SCREEN_TABLE);
+        let flags = self.layout.addr(SECTION_COLLISION);
+        self.output.push("Runtime_Collision_Flags:".to_string());
+        // X = room y, $02 = y inside it
+        self.output.push("  LDX #0".to_string());
+        self.output.push("Collision_FlagsDiv:".to_string());
+        self.output.push("  LDA $02".to_string());
+        self.output.push("  SEC".to_string());
+        self.output.push("  SBC #240".to_string());
+        self.output.push("  TAY".to_string());
+        self.output.push("  LDA $03".to_string());
+        self.output.push("  SBC #0".to_string());
+        self.output.push("  BCC Collision_FlagsRoom".to_string());
+        self.output.push("  STA $03".to_string());
+        self.output.push("  STY $02".to_string());
+        self.output.push("  INX".to_string());
+        self.output.push("  JMP Collision_FlagsDiv".to_string());
+        self.output.push("Collision_FlagsRoom:".to_string());
+        if self.layout.get(SECTION_WORLD).is_some() {
+            // World section: width, height, format, then the raw room map
+            let world = self.layout.addr(SECTION_WORLD);
+            self.output.push(format!("  CPX ${:04X}", world + 1));
+            self.output.push("  BCS Collision_FlagsOff".to_string());
+            self.output.push("  LDA $01".to_string());
+            self.output.push(format!("  CMP ${:04X}", world));
+            self.output.push("  BCS Collision_FlagsOff".to_string());
+            self.output.push("  STA $0A".to_string());
+            self.output.push("  LDA #0".to_string());
+            self.output.push("  STA $0B".to_string());
+            self.output.push("  TXA".to_string());
+            self.output.push("  TAY".to_string());
+            self.output.push("  BEQ Collision_FlagsFound".to_string());
+            self.output.push("Collision_FlagsMul:".to_string());
+            self.output.push("  CLC".to_string());
+            self.output.push("  LDA $0A".to_string());
+            self.output.push(format!("  ADC ${:04X}", world));
+            self.output.push("  STA $0A".to_string());
+            self.output.push("  BCC Collision_FlagsNext".to_string());
+            self.output.push("  INC $0B".to_string());
+            self.output.push("Collision_FlagsNext:".to_string());
+            self.output.push("  DEY".to_string());
+            self.output.push("  BNE Collision_FlagsMul".to_string());
+            self.output.push("Collision_FlagsFound:".to_string());
+            self.output.push("  CLC".to_string());
+            self.output.push("  LDA $0A".to_string());
+            self.output.push(format!("  ADC #{}", (world + 3) & 0xFF));
+            self.output.push("  STA $0A".to_string());
+            self.output.push("  LDA $0B".to_string());
+            self.output.push(format!("  ADC #{}", (world + 3) >> 8));
+            self.output.push("  STA $0B".to_string());
+            self.output.push("  LDY #0".to_string());
+            self.output.push("  LDA ($0A), Y".to_string());
+            // Empty rooms ($FF) are not a screen id
+            self.output.push(format!("  CMP #{}", self.screens.len()));
+            self.output.push("  BCS Collision_FlagsOff".to_string());
+            self.generate_screen_bank_switch();
+            self.output.push("  STA $0A".to_string());
+            self.output.push("  ASL".to_string());
+            self.output.push("  ADC $0A".to_string());
+            self.output.push("  TAY".to_string());
+            self.output.push(format!("  LDA ${:04X}, Y", table));
+            self.output.push("  STA $0A".to_string());
+            self.output.push(format!("  LDA ${:04X}, Y", table + 1));
+            self.output.push("  STA $0B".to_string());
+        } else {
+            let nametable = self.layout.addr(SECTION_NAMETABLE);
+            self.output.push("  TXA".to_string());
+            self.output.push("  ORA $01".to_string());
+            self.output.push("  BNE Collision_FlagsOff".to_string());
+            self.output
+                .push(format!("  LDA #${:02X}", nametable & 0xFF));
+            self.output.push("  STA $0A".to_string());
+            self.output.push(format!("  LDA #${:02X}", nametable >> 8));
+            self.output.push("  STA $0B".to_string());
+        }
+        // Tile at row * 32 + column of the screen
+        self.output.push("  LDA $02".to_string());
+        self.output.push("  LSR".to_string());
+        self.output.push("  LSR".to_string());
+        self.output.push("  LSR".to_string());
+        self.output.push("  STA $05".to_string());
+        self.output.push("  LSR".to_string());
+        self.output.push("  LSR".to_string());
+        self.output.push("  LSR".to_string());
+        self.output.push("  CLC".to_string());
+        self.output.push("  ADC $0B".to_string());
+        self.output.push("  STA $0B".to_string());
+        self.output.push("  LDA $05".to_string());
+        self.output.push("  AND #7".to_string());
+        self.output.push("  ASL".to_string());
+        self.output.push("  ASL".to_string());
+        self.output.push("  ASL".to_string());
+        self.output.push("  ASL".to_string());
+        self.output.push("  ASL".to_string());
+        self.output.push("  STA $04".to_string());
+        self.output.push("  LDA $00".to_string());
+        self.output.push("  LSR".to_string());
+        self.output.push("  LSR".to_string());
+        self.output.push("  LSR".to_string());
+        self.output.push("  ORA $04".to_string());
+        self.output.push("  CLC".to_string());
+        self.output.push("  ADC $0A".to_string());
+        self.output.push("  STA $0A".to_string());
+        self.output.push("  BCC Collision_FlagsTile".to_string());
+        self.output.push("  INC $0B".to_string());
+        self.output.push("Collision_FlagsTile:".to_string());
+        self.output.push("  LDY #0".to_string());
+        self.output.push("  LDA ($0A), Y".to_string());
+        self.output.push("  TAY".to_string());
+        self.output.push(format!("  LDA ${:04X}, Y", flags));
+        self.output.push("  LDX #0".to_string());
+        self.output.push("  RTS".to_string());
+        self.output.push("Collision_FlagsOff:".to_string());
+        self.output.push(format!("  LDA #{}", collision::SOLID));
+        self.output.push("  LDX #0".to_string());
+        self.output.push("  RTS".to_string());
+    }
+
+    // Physics.MoveAndCollide(body, dx, dy): dx/dy in 1/16 pixel -> contacts
+    fn generate_physics_move(&mut self, args: &[Expression]) -> Result<DataType, String> {
+        if self.layout.get(SECTION_COLLISION).is_none() {
+            return Err(
+                "Physics.MoveAndCollide requires the collision table (compile with project assets)"
+                    .to_string(),
+            );
+        }
+        self.uses_collision_flags = true;
+        self.uses_physics = true;
+        let body_addr = self.get_static_address(&args[0])?;
+        self.generate_expression(&args[1])?;
+        self.output.push("  PHA".to_string());
+        self.generate_expression(&args[2])?;
+        self.output
+            .push(format!("  STA ${:04X}", PHYSICS_STATE + 9));
         self.output.push("  PLA".to_string());
+        self.output
+            .push(format!("  STA ${:04X}", PHYSICS_STATE + 8));
+        self.output
+            .push(format!("  LDA #${:02X}", (body_addr & 0xFF) as u8));
+        self.output.push("  STA $0E".to_string());
+        self.output
+            .push(format!("  LDA #${:02X}", ((body_addr >> 8) & 0xFF) as u8));
+        self.output.push("  STA $0F".to_string());
+        self.output.push("  JSR Runtime_Physics_Move".to_string());
+        Ok(DataType::Byte)
+    }
+
+    // Runtime_Physics_Move: moves the Body at ($0E) by dx, then dy, 1/16
+    // pixel fixed point. Each axis moves at most 8 pixels, so checking the
+    // leading edge after the step cannot skip over a tile. Solid tiles stop
+    // it on both axes, one-way tiles only when landing from above, and slope
+    // tiles (bits 5-7) are floors under the body's bottom centre. Returns the
+    // Contact flags.
+    fn generate_physics_helpers(&mut self) {
+        let ps = PHYSICS_STATE;
+        let out = &mut self.output;
+        out.push("Runtime_Physics_Move:".to_string());
+        out.push("  LDY #7".to_string());
+        out.push("Physics_Load:".to_string());
+        out.push("  LDA ($0E), Y".to_string());
+        out.push(format!("  STA ${:04X}, Y", ps));
+        out.push("  DEY".to_string());
+        out.push("  BPL Physics_Load".to_string());
+        out.push("  LDA #0".to_string());
+        out.push(format!("  STA ${:04X}", ps + 10));
+        out.push("  JSR Physics_MoveX".to_string());
+        out.push("  JSR Physics_MoveY".to_string());
+        out.push("Physics_Done:".to_string());
+        out.push("  LDY #5".to_string());
+        out.push("Physics_Store:".to_string());
+        out.push(format!("  LDA ${:04X}, Y", ps));
+        out.push("  STA ($0E), Y".to_string());
+        out.push("  DEY".to_string());
+        out.push("  BPL Physics_Store".to_string());
+        out.push(format!("  LDA ${:04X}", ps + 10));
+        out.push("  LDX #0".to_string());
+        out.push("  RTS".to_string());
+
+        // Vertical: test the leading row over the body's width
+        out.push("Physics_MoveY:".to_string());
+        out.push("  JSR Physics_Foot".to_string());
+        out.push("  LDA $08".to_string());
+        out.push(format!("  STA ${:04X}", ps + 14));
+        out.push("  LDA $09".to_string());
+        out.push(format!("  STA ${:04X}", ps + 15));
+        out.push(format!("  LDA ${:04X}", ps + 9));
+        out.push("  LDX #1".to_string());
+        out.push("  JSR Physics_Step".to_string());
+        out.push(format!("  STA ${:04X}", ps + 12));
+        out.push("  LDY #0".to_string());
+        out.push("  CMP #$80".to_string());
+        out.push("  BCC Physics_AddY".to_string());
+        out.push("  DEY".to_string());
+        out.push("Physics_AddY:".to_string());
+        out.push("  CLC".to_string());
+        out.push(format!("  ADC ${:04X}", ps + 2));
+        out.push(format!("  STA ${:04X}", ps + 2));
+        out.push("  TYA".to_string());
+        out.push(format!("  ADC ${:04X}", ps + 3));
+        out.push(format!("  STA ${:04X}", ps + 3));
+        out.push(format!("  LDA ${:04X}", ps + 6));
+        out.push("  SEC".to_string());
+        out.push("  SBC #1".to_string());
+        out.push("  STA $0C".to_string());
+        out.push(format!("  LDA ${:04X}", ps + 12));
+        out.push("  BMI Physics_Up".to_string());
+        // A slope under the bottom centre decides the floor on its own
+        out.push("  JSR Physics_Land".to_string());
+        out.push("  BCS Physics_MoveYDone".to_string());
+        out.push("  JSR Physics_Foot".to_string());
+        out.push(format!("  LDA ${:04X}", ps));
+        out.push("  STA $06".to_string());
+        out.push(format!("  LDA ${:04X}", ps + 1));
+        out.push("  STA $07".to_string());
+        out.push("  JSR Physics_ScanX".to_string());
+        out.push("  TAX".to_string());
+        out.push(format!("  AND #{}", collision::SOLID));
+        out.push("  BNE Physics_Floor".to_string());
+        out.push("  TXA".to_string());
+        out.push(format!("  AND #{}", collision::ONE_WAY));
+        out.push("  BEQ Physics_MoveYDone".to_string());
+        // One-way: only if the old bottom was above the tile
+        out.push("  LDA $08".to_string());
+        out.push("  AND #$F8".to_string());
+        out.push("  STA $0D".to_string());
+        out.push(format!("  LDA ${:04X}", ps + 14));
+        out.push("  CMP $0D".to_string());
+        out.push(format!("  LDA ${:04X}", ps + 15));
+        out.push("  SBC $09".to_string());
+        out.push("  BCC Physics_Floor".to_string());
+        out.push("Physics_MoveYDone:".to_string());
+        out.push("  RTS".to_string());
+        out.push("Physics_Floor:".to_string());
+        out.push("  LDA $08".to_string());
+        out.push("  AND #$F8".to_string());
+        out.push("  SEC".to_string());
+        out.push(format!("  SBC ${:04X}", ps + 7));
+        out.push(format!("  STA ${:04X}", ps + 2));
+        out.push("  LDA $09".to_string());
+        out.push("  SBC #0".to_string());
+        out.push(format!("  STA ${:04X}", ps + 3));
+        out.push("  LDA #0".to_string());
+        out.push(format!("  STA ${:04X}", ps + 5));
+        out.push(format!("  LDA #{}", collision::CONTACT_FLOOR));
+        out.push(format!("  ORA ${:04X}", ps + 10));
+        out.push(format!("  STA ${:04X}", ps + 10));
+        // The ground may continue as a slope above the tile landed on
+        out.push("  JMP Physics_Land".to_string());
+        out.push("Physics_Up:".to_string());
+        out.push(format!("  LDA ${:04X}", ps));
+        out.push("  STA $06".to_string());
+        out.push(format!("  LDA ${:04X}", ps + 1));
+        out.push("  STA $07".to_string());
+        out.push(format!("  LDA ${:04X}", ps + 2));
+        out.push("  STA $08".to_string());
+        out.push(format!("  LDA ${:04X}", ps + 3));
+        out.push("  STA $09".to_string());
+        out.push("  JSR Physics_ScanX".to_string());
+        out.push(format!("  AND #{}", collision::SOLID));
+        out.push("  BEQ Physics_MoveYDone".to_string());
+        out.push("  LDA $08".to_string());
+        out.push("  ORA #7".to_string());
+        out.push("  CLC".to_string());
+        out.push("  ADC #1".to_string());
+        out.push(format!("  STA ${:04X}", ps + 2));
+        out.push("  LDA $09".to_string());
+        out.push("  ADC #0".to_string());
+        out.push(format!("  STA ${:04X}", ps + 3));
+        out.push("  LDA #0".to_string());
+        out.push(format!("  STA ${:04X}", ps + 5));
+        out.push(format!("  LDA #{}", collision::CONTACT_CEILING));
+        out.push(format!("  ORA ${:04X}", ps + 10));
+        out.push(format!("  STA ${:04X}", ps + 10));
+        out.push("  RTS".to_string());
+
+        // Horizontal: test the leading column over the body's height
+        out.push("Physics_MoveX:".to_string());
+        out.push(format!("  LDA ${:04X}", ps + 8));
+        out.push("  LDX #0".to_string());
+        out.push("  JSR Physics_Step".to_string());
+        out.push(format!("  STA ${:04X}", ps + 12));
+        out.push("  BNE Physics_MoveXStep".to_string());
+        out.push("  RTS".to_string());
+        out.push("Physics_MoveXStep:".to_string());
+        // On a slope the bottom dips into the ground beside it, so the tile
+        // row it is in is left out of the wall test
+        out.push("  JSR Physics_Foot".to_string());
+        out.push("  JSR Physics_Probe".to_string());
+        out.push("  AND #$E0".to_string());
+        out.push(format!("  STA ${:04X}", ps + 13));
+        out.push(format!("  LDA ${:04X}", ps + 12));
+        out.push("  LDY #0".to_string());
+        out.push("  CMP #$80".to_string());
+        out.push("  BCC Physics_AddX".to_string());
+        out.push("  DEY".to_string());
+        out.push("Physics_AddX:".to_string());
+        out.push("  CLC".to_string());
+        out.push(format!("  ADC ${:04X}", ps));
+        out.push(format!("  STA ${:04X}", ps));
+        out.push("  TYA".to_string());
+        out.push(format!("  ADC ${:04X}", ps + 1));
+        out.push(format!("  STA ${:04X}", ps + 1));
+        out.push(format!("  LDA ${:04X}", ps));
+        out.push("  STA $06".to_string());
+        out.push(format!("  LDA ${:04X}", ps + 1));
+        out.push("  STA $07".to_string());
+        out.push(format!("  LDA ${:04X}", ps + 12));
+        out.push("  BMI Physics_Wall".to_string());
+        out.push("  CLC".to_string());
+        out.push("  LDA $06".to_string());
+        out.push(format!("  ADC ${:04X}", ps + 6));
+        out.push("  STA $06".to_string());
+        out.push("  BCC Physics_RightEdge".to_string());
+        out.push("  INC $07".to_string());
+        out.push("Physics_RightEdge:".to_string());
+        out.push("  LDA $06".to_string());
+        out.push("  BNE Physics_RightLow".to_string());
+        out.push("  DEC $07".to_string());
+        out.push("Physics_RightLow:".to_string());
+        out.push("  DEC $06".to_string());
+        out.push("Physics_Wall:".to_string());
+        out.push(format!("  LDA ${:04X}", ps + 2));
+        out.push("  STA $08".to_string());
+        out.push(format!("  LDA ${:04X}", ps + 3));
+        out.push("  STA $09".to_string());
+        out.push(format!("  LDX ${:04X}", ps + 7));
+        out.push("  DEX".to_string());
+        out.push(format!("  LDA ${:04X}", ps + 13));
+        out.push("  BEQ Physics_WallScan".to_string());
+        out.push(format!("  LDA ${:04X}", ps + 2));
+        out.push("  AND #7".to_string());
+        out.push("  STA $0D".to_string());
+        out.push("  TXA".to_string());
+        out.push("  CLC".to_string());
+        out.push("  ADC $0D".to_string());
+        out.push("  AND #$F8".to_string());
+        out.push("  BEQ Physics_MoveXDone".to_string());
+        out.push("  SEC".to_string());
+        out.push("  SBC $0D".to_string());
+        out.push("  TAX".to_string());
+        out.push("  DEX".to_string());
+        out.push("Physics_WallScan:".to_string());
+        out.push("  STX $0C".to_string());
+        out.push("  JSR Physics_ScanY".to_string());
+        out.push(format!("  AND #{}", collision::SOLID));
+        out.push("  BEQ Physics_MoveXDone".to_string());
+        out.push(format!("  LDA ${:04X}", ps + 12));
+        out.push("  BMI Physics_HitLeft".to_string());
+        // Right edge back to the pixel before the tile
+        out.push("  LDA $06".to_string());
+        out.push("  AND #$F8".to_string());
+        out.push("  SEC".to_string());
+        out.push(format!("  SBC ${:04X}", ps + 6));
+        out.push(format!("  STA ${:04X}", ps));
+        out.push("  LDA $07".to_string());
+        out.push("  SBC #0".to_string());
+        out.push(format!("  STA ${:04X}", ps + 1));
+        out.push(format!("  LDA #{}", collision::CONTACT_RIGHT));
+        out.push("  BNE Physics_HitX".to_string());
+        out.push("Physics_HitLeft:".to_string());
+        out.push("  LDA $06".to_string());
+        out.push("  ORA #7".to_string());
+        out.push("  CLC".to_string());
+        out.push("  ADC #1".to_string());
+        out.push(format!("  STA ${:04X}", ps));
+        out.push("  LDA $07".to_string());
+        out.push("  ADC #0".to_string());
+        out.push(format!("  STA ${:04X}", ps + 1));
+        out.push(format!("  LDA #{}", collision::CONTACT_LEFT));
+        out.push("Physics_HitX:".to_string());
+        out.push(format!("  ORA ${:04X}", ps + 10));
+        out.push(format!("  STA ${:04X}", ps + 10));
+        out.push("  LDA #0".to_string());
+        out.push(format!("  STA ${:04X}", ps + 4));
+        out.push("Physics_MoveXDone:".to_string());
+        out.push("  RTS".to_string());
+
+        // A = velocity, X = axis: adds the fraction to sub x/y, returns the
+        // whole pixels (-8 to 8)
+        out.push("Physics_Step:".to_string());
+        out.push("  PHA".to_string());
+        out.push("  AND #$0F".to_string());
+        out.push("  CLC".to_string());
+        out.push(format!("  ADC ${:04X}, X", ps + 4));
+        out.push("  TAY".to_string());
+        out.push("  AND #$0F".to_string());
+        out.push(format!("  STA ${:04X}, X", ps + 4));
+        out.push("  PLA".to_string());
+        for _ in 0..4 {
+            out.push("  CMP #$80".to_string());
+            out.push("  ROR".to_string());
+        }
+        out.push("  CPY #16".to_string());
+        out.push("  ADC #0".to_string());
+        out.push("  RTS".to_string());
+
+        // $06/$07 = bottom centre x, $08/$09 = bottom row
+        out.push("Physics_Foot:".to_string());
+        out.push(format!("  LDA ${:04X}", ps + 6));
+        out.push("  LSR".to_string());
+        out.push("  CLC".to_string());
+        out.push(format!("  ADC ${:04X}", ps));
+        out.push("  STA $06".to_string());
+        out.push(format!("  LDA ${:04X}", ps + 1));
+        out.push("  ADC #0".to_string());
+        out.push("  STA $07".to_string());
+        out.push(format!("  LDA ${:04X}", ps + 7));
+        out.push("  SEC".to_string());
+        out.push("  SBC #1".to_string());
+        out.push("  CLC".to_string());
+        out.push(format!("  ADC ${:04X}", ps + 2));
+        out.push("  STA $08".to_string());
+        out.push(format!("  LDA ${:04X}", ps + 3));
+        out.push("  ADC #0".to_string());
+        out.push("  STA $09".to_string());
+        out.push("  RTS".to_string());
+
+        // Flags of the tile at $06/$07, $08/$09
+        out.push("Physics_Probe:".to_string());
+        out.push("  LDA $06".to_string());
+        out.push("  STA $00".to_string());
+        out.push("  LDA $07".to_string());
+        out.push("  STA $01".to_string());
+        out.push("  LDA $08".to_string());
+        out.push("  STA $02".to_string());
+        out.push("  LDA $09".to_string());
+        out.push("  STA $03".to_string());
+        out.push("  JMP Runtime_Collision_Flags".to_string());
+
+        // Flags of every tile from $06/$07 (ScanX) or $08/$09 (ScanY) to
+        // $0C pixels further, OR'd together
+        for (axis, lo, hi) in [("X", "$06", "$07"), ("Y", "$08", "$09")] {
+            out.push(format!("Physics_Scan{}:", axis));
+            out.push("  LDA #0".to_string());
+            out.push(format!("  STA ${:04X}", ps + 11));
+            out.push(format!("Physics_Scan{}Loop:", axis));
+            out.push("  JSR Physics_Probe".to_string());
+            out.push(format!("  ORA ${:04X}", ps + 11));
+            out.push(format!("  STA ${:04X}", ps + 11));
+            out.push("  LDA $0C".to_string());
+            out.push(format!("  BEQ Physics_Scan{}Done", axis));
+            out.push("  CMP #8".to_string());
+            out.push(format!("  BCC Physics_Scan{}Last", axis));
+            out.push("  LDA #8".to_string());
+            out.push(format!("Physics_Scan{}Last:", axis));
+            out.push("  STA $0D".to_string());
+            out.push("  LDA $0C".to_string());
+            out.push("  SEC".to_string());
+            out.push("  SBC $0D".to_string());
+            out.push("  STA $0C".to_string());
+            out.push("  CLC".to_string());
+            out.push(format!("  LDA {}", lo));
+            out.push("  ADC $0D".to_string());
+            out.push(format!("  STA {}", lo));
+            out.push(format!("  BCC Physics_Scan{}Loop", axis));
+            out.push(format!("  INC {}", hi));
+            out.push(format!("  JMP Physics_Scan{}Loop", axis));
+            out.push(format!("Physics_Scan{}Done:", axis));
+            out.push(format!("  LDA ${:04X}", ps + 11));
+            out.push("  RTS".to_string());
+        }
+
+        // Carry set if the bottom centre is on or below the surface of a
+        // slope tile, after standing the body on it
+        out.push("Physics_Land:".to_string());
+        out.push("  JSR Physics_Foot".to_string());
+        out.push("  JSR Physics_Probe".to_string());
+        for _ in 0..collision::SLOPE_SHIFT {
+            out.push("  LSR".to_string());
+        }
+        out.push("  BEQ Physics_LandNone".to_string());
+        out.push(format!("  CMP #{}", collision::SLOPES.len() + 1));
+        out.push("  BCS Physics_LandNone".to_string());
+        // Floor height at the column, as collision::slope_height
+        out.push("  TAX".to_string());
+        out.push("  LDA $06".to_string());
+        out.push("  AND #7".to_string());
+        out.push("  CPX #3".to_string());
+        out.push("  BCC Physics_Slope45".to_string());
+        out.push("  LSR".to_string());
+        out.push("Physics_Slope45:".to_string());
+        out.push("  STA $0D".to_string());
+        out.push("  CPX #2".to_string());
+        out.push("  BEQ Physics_Fall8".to_string());
+        out.push("  CPX #5".to_string());
+        out.push("  BEQ Physics_Fall8".to_string());
+        out.push("  CPX #6".to_string());
+        out.push("  BEQ Physics_Fall4".to_string());
+        out.push("  LDA #1".to_string());
+        out.push("  CPX #4".to_string());
+        out.push("  BNE Physics_Rise".to_string());
+        out.push("  LDA #5".to_string());
+        out.push("Physics_Rise:".to_string());
+        out.push("  CLC".to_string());
+        out.push("  ADC $0D".to_string());
+        out.push("  BNE Physics_Height".to_string());
+        out.push("Physics_Fall8:".to_string());
+        out.push("  LDA #8".to_string());
+        out.push("  BNE Physics_Fall".to_string());
+        out.push("Physics_Fall4:".to_string());
+        out.push("  LDA #4".to_string());
+        out.push("Physics_Fall:".to_string());
+        out.push("  SEC".to_string());
+        out.push("  SBC $0D".to_string());
+        out.push("Physics_Height:".to_string());
+        out.push("  STA $0D".to_string());
+        // Surface bottom row = last row of the tile - height
+        out.push("  LDA $08".to_string());
+        out.push("  ORA #7".to_string());
+        out.push("  SEC".to_string());
+        out.push("  SBC $0D".to_string());
+        out.push("  STA $0A".to_string());
+        out.push("  LDA $09".to_string());
+        out.push("  SBC #0".to_string());
+        out.push("  STA $0B".to_string());
+        out.push("  LDA $08".to_string());
+        out.push("  CMP $0A".to_string());
+        out.push("  LDA $09".to_string());
+        out.push("  SBC $0B".to_string());
+        out.push("  BCC Physics_LandNone".to_string());
+        out.push(format!("  LDX ${:04X}", ps + 7));
+        out.push("  DEX".to_string());
+        out.push("  STX $0D".to_string());
+        out.push("  LDA $0A".to_string());
+        out.push("  SEC".to_string());
+        out.push("  SBC $0D".to_string());
+        out.push(format!("  STA ${:04X}", ps + 2));
+        out.push("  LDA $0B".to_string());
+        out.push("  SBC #0".to_string());
+        out.push(format!("  STA ${:04X}", ps + 3));
+        out.push("  LDA #0".to_string());
+        out.push(format!("  STA ${:04X}", ps + 5));
+        out.push(format!(
+            "  LDA #{}",
+            collision::CONTACT_FLOOR | collision::CONTACT_SLOPE
+        ));
+        out.push(format!("  ORA ${:04X}", ps + 10));
+        out.push(format!("  STA ${:04X}", ps + 10));
+        out.push("  SEC".to_string());
+        out.push("  RTS".to_string());
+        out.push("Physics_LandNone:".to_string());
+        out.push("  CLC".to_string());
+        out.push("  RTS".to_string());
+    }
+
+    fn generate_scroll_helpers(&mut self) {
+        self.output.push("; --- Scroll Helpers ---".to_string());
+
+        self.output.push("Runtime_Scroll_LoadColumn:".to_string());
+
+        // Save Data Ptr (A/X) -> $00/$01
+        self.output.push("  STA $00".to_string());
+        self.output.push("  STX $01".to_string());
+
+        // Read Virtual X -> $02/$03 (caller pops it)
+        self.output.push("  TSX".to_string());
+        self.output.push("  LDA $0103, X".to_string());
+        self.output.push("  STA $03".to_string()); // High
+        self.output.push("  LDA $0104, X".to_string());
+        self.output.push("  STA $02".to_string()); // Low
+
+        // Calculate Target High ($05)
+        // Base $20 or $24 based on X High Bit 0
+        self.output.push("  LDA $03".to_string());
+        self.output.push("  AND #1".to_string());
+        self.output.push("  BEQ Scroll_Base2000".to_string());
+        self.output.push("  LDA #$24".to_string());
+        self.output.push("  JMP Scroll_BaseStore".to_string());
+        self.output.push("Scroll_Base2000:".to_string());
+        self.output.push("  LDA #$20".to_string());
+        self.output.push("Scroll_BaseStore:".to_string());
+        self.output.push("  STA $05".to_string());
+
+        // Calculate Target Low ($04) = (X Low / 8)
+        self.output.push("  LDA $02".to_string());
+        self.output.push("  LSR".to_string());
+        self.output.push("  LSR".to_string());
+        self.output.push("  LSR".to_string());
+        self.output.push("  STA $04".to_string());
+
+        // Queue 30 tiles going down (vertical increment)
+        self.output.push("  LDA #$9E".to_string());
+        self.output.push("  JSR PpuQueue_Reserve".to_string());
+        self.output.push("  LDA $05".to_string());
+        self.output
+            .push(format!("  STA ${:04X}, X", PPU_QUEUE_START + 1)); // High
+        self.output.push("  LDA $04".to_string());
+        self.output
+            .push(format!("  STA ${:04X}, X", PPU_QUEUE_START + 2)); // Low
+
+        // Copy Data
+        self.output.push("  LDY #0".to_string());
+        self.output.push("Scroll_CopyLoop:".to_string());
+        self.output.push("  LDA ($00), Y".to_string());
+        self.output
+            .push(format!("  STA ${:04X}, X", PPU_QUEUE_START + 3));
+        self.output.push("  INX".to_string());
+        self.output.push("  INY".to_string());
+        self.output.push("  CPY #30".to_string());
+        self.output.push("  BNE Scroll_CopyLoop".to_string());
+        self.output.push("  JSR PpuQueue_Commit".to_string());
+
+        self.output.push("Scroll_LoadColumn_Done:".to_string());
+        self.output.push("  RTS".to_string());
+        self.output.push("".to_string());
+
+        self.output.push("Runtime_Scroll_LoadRow:".to_string());
+
+        // Save Data Ptr (A/X) -> $00/$01
+        self.output.push("  STA $00".to_string());
+        self.output.push("  STX $01".to_string());
+
+        // Read Virtual Y -> $02/$03 (caller pops it)
+        self.output.push("  TSX".to_string());
+        self.output.push("  LDA $0103, X".to_string());
+        self.output.push("  STA $03".to_string()); // High
+        self.output.push("  LDA $0104, X".to_string());
         self.output.push("  STA $02".to_string()); // Low
 
         // Gap Skip: If Y Low >= 240, add 16 to skip attribute area
@@ -2106,84 +3180,612 @@ impl CodeGenerator {
         self.output.push("  ASL".to_string());
         self.output.push("  STA $04".to_string());
 
-        // Write Header
-        self.output.push("  LDA #1".to_string()); // Type 1 (Row)
-        self.output
-            .push(format!("  STA ${:04X}", VBLANK_BUFFER_START + 1)); // Type
+        // Queue 32 tiles going across
+        self.output.push("  LDA #32".to_string());
+        self.output.push("  JSR PpuQueue_Reserve".to_string());
         self.output.push("  LDA $05".to_string());
         self.output
-            .push(format!("  STA ${:04X}", VBLANK_BUFFER_START + 2)); // High
+            .push(format!("  STA ${:04X}, X", PPU_QUEUE_START + 1)); // High
         self.output.push("  LDA $04".to_string());
         self.output
-            .push(format!("  STA ${:04X}", VBLANK_BUFFER_START + 3)); // Low
+            .push(format!("  STA ${:04X}, X", PPU_QUEUE_START + 2)); // Low
 
         // Copy Data
         self.output.push("  LDY #0".to_string());
         self.output.push("Scroll_RowCopyLoop:".to_string());
         self.output.push("  LDA ($00), Y".to_string());
         self.output
-            .push(format!("  STA ${:04X}, Y", VBLANK_BUFFER_START + 4));
+            .push(format!("  STA ${:04X}, X", PPU_QUEUE_START + 3));
+        self.output.push("  INX".to_string());
         self.output.push("  INY".to_string());
         self.output.push("  CPY #32".to_string()); // 32 tiles for row
         self.output.push("  BNE Scroll_RowCopyLoop".to_string());
-
-        // Set Flag
-        self.output.push("  LDA #1".to_string());
-        self.output
-            .push(format!("  STA ${:04X}", VBLANK_BUFFER_START));
+        self.output.push("  JSR PpuQueue_Commit".to_string());
 
         self.output.push("Scroll_LoadRow_Done:".to_string());
         self.output.push("  RTS".to_string());
         self.output.push("".to_string());
     }
 
-    fn generate_pool_helpers(&mut self) {
-        self.output.push("; --- Pool Helpers ---".to_string());
+    // The actor table: a pool of `actor_slots` slots of `actor_stride` bytes
+    // at `actor_ram`, then the loop state (slot, slot address) and the room
+    // spawner's (list address, room x, room top y). UpdateAll and DrawAll
+    // call the handler of each active slot's kind, passing the slot.
+    fn generate_actor_helpers(&mut self) -> Result<(), String> {
+        if self.actors.is_empty() {
+            return Ok(());
+        }
+        self.output.push("; --- Actor Helpers ---".to_string());
+        let base = self.actor_ram;
+        let stride = self.actor_stride;
+        let slots = self.actor_slots;
+        let slot = base + slots as u16 * stride;
+        let ptr = slot + 1;
+
+        for (loop_name, update) in [("Update", true), ("Draw", false)] {
+            self.output.push(format!("Runtime_Actors_{}:", loop_name));
+            self.output.push("  LDA #0".to_string());
+            self.output.push(format!("  STA ${:04X}", slot));
+            self.output
+                .push(format!("  LDA #${:02X}", (base & 0xFF) as u8));
+            self.output.push(format!("  STA ${:04X}", ptr));
+            self.output
+                .push(format!("  LDA #${:02X}", (base >> 8) as u8));
+            self.output.push(format!("  STA ${:04X}", ptr + 1));
+            self.output.push(format!("Actors_{}_Loop:", loop_name));
+            self.output.push(format!("  LDA ${:04X}", ptr));
+            self.output.push("  STA $0E".to_string());
+            self.output.push(format!("  LDA ${:04X}", ptr + 1));
+            self.output.push("  STA $0F".to_string());
+            self.output.push("  LDY #0".to_string());
+            self.output.push("  LDA ($0E), Y".to_string());
+            self.output.push(format!("  BEQ Actors_{}_Next", loop_name));
+            self.output.push("  INY".to_string());
+            self.output.push("  LDA ($0E), Y".to_string()); // Kind
+            self.output
+                .push(format!("  JSR Actors_{}_Dispatch", loop_name));
+            self.output.push(format!("Actors_{}_Next:", loop_name));
+            self.output.push(format!("  LDA ${:04X}", ptr));
+            self.output.push("  CLC".to_string());
+            self.output.push(format!("  ADC #{}", stride));
+            self.output.push(format!("  STA ${:04X}", ptr));
+            self.output.push(format!("  BCC Actors_{}_Step", loop_name));
+            self.output.push(format!("  INC ${:04X}", ptr + 1));
+            self.output.push(format!("Actors_{}_Step:", loop_name));
+            self.output.push(format!("  INC ${:04X}", slot));
+            self.output.push(format!("  LDA ${:04X}", slot));
+            self.output.push(format!("  CMP #{}", slots));
+            self.output.push(format!("  BCC Actors_{}_Loop", loop_name));
+            self.output.push("  RTS".to_string());
+
+            // A = kind; jumps through the kind's entry in the handler table
+            let table = self.layout.addr(SECTION_ACTOR_HANDLERS)
+                + if update {
+                    0
+                } else {
+                    2 * self.actors.len() as u16
+                };
+            self.output.push(format!("Actors_{}_Dispatch:", loop_name));
+            self.output.push("  ASL".to_string());
+            self.output.push("  TAX".to_string());
+            self.output.push(format!("  LDA ${:04X}, X", table));
+            self.output.push("  STA $0E".to_string());
+            self.output.push(format!("  LDA ${:04X}, X", table + 1));
+            self.output.push("  STA $0F".to_string());
+            self.output.push("  JMP ($000E)".to_string());
+            self.output.push(format!("Actors_{}_None:", loop_name));
+            self.output.push("  RTS".to_string());
+
+            // Handlers taking the slot are entered through a stub storing it
+            let actors = self.actors.clone();
+            for (kind, actor) in actors.iter().enumerate() {
+                let handler = if update { &actor.update } else { &actor.draw };
+                let Some(handler) = handler else {
+                    self.actor_handlers
+                        .push(format!("Actors_{}_None", loop_name));
+                    continue;
+                };
+                let target = if self.sub_banks.contains_key(handler) {
+                    format!("Far_{}", handler)
+                } else {
+                    handler.clone()
+                };
+                let param = self
+                    .sub_signatures
+                    .get(handler)
+                    .and_then(|sig| sig.first())
+                    .map(|(addr, _)| *addr);
+                let Some(addr) = param else {
+                    self.actor_handlers.push(target);
+                    continue;
+                };
+                let entry = format!("Actors_{}_Kind{}", loop_name, kind);
+                self.output.push(format!("{}:", entry));
+                self.output.push(format!("  LDA ${:04X}", slot));
+                self.output.push(format!("  STA ${:04X}", addr));
+                self.output.push(format!("  JMP {}", target));
+                self.actor_handlers.push(entry);
+            }
+        }
 
-        self.output.push("Runtime_Pool_Spawn:".to_string());
-        self.output.push("  LDA #0".to_string());
-        self.output.push("  STA $08".to_string());
-        self.output.push("  STA $09".to_string());
+        // Actors_Locate: A = slot -> ($0E) its address
+        self.output.push("Actors_Locate:".to_string());
+        self.output.push("  TAX".to_string());
+        self.output
+            .push(format!("  LDA #${:02X}", (base & 0xFF) as u8));
+        self.output.push("  STA $0E".to_string());
+        self.output
+            .push(format!("  LDA #${:02X}", (base >> 8) as u8));
+        self.output.push("  STA $0F".to_string());
+        self.output.push("  CPX #0".to_string());
+        self.output.push("  BEQ Actors_Located".to_string());
+        self.output.push("Actors_Locate_Loop:".to_string());
+        self.output.push("  LDA $0E".to_string());
+        self.output.push("  CLC".to_string());
+        self.output.push(format!("  ADC #{}", stride));
+        self.output.push("  STA $0E".to_string());
+        self.output.push("  BCC Actors_Locate_Next".to_string());
+        self.output.push("  INC $0F".to_string());
+        self.output.push("Actors_Locate_Next:".to_string());
+        self.output.push("  DEX".to_string());
+        self.output.push("  BNE Actors_Locate_Loop".to_string());
+        self.output.push("Actors_Located:".to_string());
+        self.output.push("  RTS".to_string());
 
-        self.output.push("Pool_Spawn_Loop:".to_string());
-        self.output.push("  LDA $08".to_string());
-        self.output.push("  CMP $04".to_string());
-        self.output.push("  LDA $09".to_string());
-        self.output.push("  SBC $05".to_string());
-        self.output.push("  BCS Pool_Full".to_string());
+        self.output.push("Runtime_Actors_Clear:".to_string());
+        self.output
+            .push(format!("  LDA #${:02X}", (base & 0xFF) as u8));
+        self.output.push("  STA $0E".to_string());
+        self.output
+            .push(format!("  LDA #${:02X}", (base >> 8) as u8));
+        self.output.push("  STA $0F".to_string());
+        self.output.push(format!("  LDX #{}", slots));
+        self.output.push("  LDY #0".to_string());
+        self.output.push("Actors_Clear_Loop:".to_string());
+        self.output.push("  TYA".to_string());
+        self.output.push("  STA ($0E), Y".to_string());
+        self.output.push("  LDA $0E".to_string());
+        self.output.push("  CLC".to_string());
+        self.output.push(format!("  ADC #{}", stride));
+        self.output.push("  STA $0E".to_string());
+        self.output.push("  BCC Actors_Clear_Next".to_string());
+        self.output.push("  INC $0F".to_string());
+        self.output.push("Actors_Clear_Next:".to_string());
+        self.output.push("  DEX".to_string());
+        self.output.push("  BNE Actors_Clear_Loop".to_string());
+        self.output.push("  RTS".to_string());
 
+        // A = slot; out of range (the $FF of a failed spawn) is ignored
+        self.output.push("Runtime_Actors_Despawn:".to_string());
+        self.output.push(format!("  CMP #{}", slots));
+        self.output.push("  BCS Actors_Despawned".to_string());
+        self.output.push("  JSR Actors_Locate".to_string());
         self.output.push("  LDY #0".to_string());
-        self.output.push("  LDA ($02), Y".to_string());
-        self.output.push("  BEQ Pool_Found".to_string());
+        self.output.push("  TYA".to_string());
+        self.output.push("  STA ($0E), Y".to_string());
+        self.output.push("Actors_Despawned:".to_string());
+        self.output.push("  RTS".to_string());
 
-        self.output.push("  LDA $02".to_string());
-        self.output.push("  CLC".to_string());
-        self.output.push("  ADC $06".to_string());
+        // Actors_Claim: a free slot from the pool at ($02), its index in $08;
+        // carry set when the table is full
+        self.output.push("Actors_Claim:".to_string());
+        self.output
+            .push(format!("  LDA #${:02X}", (base & 0xFF) as u8));
         self.output.push("  STA $02".to_string());
-        self.output.push("  LDA $03".to_string());
-        self.output.push("  ADC #0".to_string());
+        self.output
+            .push(format!("  LDA #${:02X}", (base >> 8) as u8));
         self.output.push("  STA $03".to_string());
-
-        self.output.push("  INC $08".to_string());
-        self.output.push("  BNE Pool_Inc_Skip".to_string());
-        self.output.push("  INC $09".to_string());
-        self.output.push("Pool_Inc_Skip:".to_string());
-        self.output.push("  JMP Pool_Spawn_Loop".to_string());
-
-        self.output.push("Pool_Found:".to_string());
-        self.output.push("  LDA #1".to_string());
-        self.output.push("  STA ($02), Y".to_string());
-        self.output.push("  LDA $08".to_string());
-        self.output.push("  LDX $09".to_string());
+        self.output.push(format!("  LDA #{}", slots));
+        self.output.push("  STA $04".to_string());
+        self.output.push("  LDA #0".to_string());
+        self.output.push("  STA $05".to_string());
+        self.output.push(format!("  LDA #{}", stride));
+        self.output.push("  STA $06".to_string());
+        self.output.push("  JSR Runtime_Pool_Spawn".to_string());
+        self.output.push("  CMP #$FF".to_string());
         self.output.push("  RTS".to_string());
-
-        self.output.push("Pool_Full:".to_string());
+        self.output.push("Actors_Full:".to_string());
         self.output.push("  LDA #$FF".to_string());
-        self.output.push("  LDX #$FF".to_string());
+        self.output.push("  LDX #0".to_string());
         self.output.push("  RTS".to_string());
 
-        self.output.push("Runtime_Pool_Despawn:".to_string());
-        self.output.push("  PLA".to_string());
+        // Actor_Spawn_<Type>: x in $0A/$0B, y in $0C/$0D; fills the header
+        // and the spawn values of the fields, zeroing the rest
+        let classes = self.actor_classes.clone();
+        let actors = self.actors.clone();
+        for (kind, actor) in actors.iter().enumerate() {
+            let mut bytes = vec![0u8; stride as usize];
+            bytes[1] = kind as u8;
+            bytes[2] = actors::class_mask(actor, &classes);
+            let (w, h) = match &actor.size {
+                Some((w, h)) => (self.resolve_constant(w)?, self.resolve_constant(h)?),
+                None => (actors::DEFAULT_SIZE as i32, actors::DEFAULT_SIZE as i32),
+            };
+            if !(0..=255).contains(&w) || !(0..=255).contains(&h) {
+                return Err(format!(
+                    "ACTOR '{}' SIZE {}, {} is out of range (0-255)",
+                    actor.name, w, h
+                ));
+            }
+            bytes[7] = w as u8;
+            bytes[8] = h as u8;
+            let members = self
+                .symbol_table
+                .resolve(&actors::struct_name(&actor.name))
+                .and_then(|sym| sym.members.clone())
+                .unwrap_or_default();
+            for (name, _, value) in &actor.fields {
+                let (Some(value), Some((_, dtype, offset))) =
+                    (value, members.iter().find(|(n, _, _)| n == name))
+                else {
+                    continue;
+                };
+                let value = self.resolve_constant(value).map_err(|e| {
+                    format!("ACTOR '{}' field '{}' spawn value: {}", actor.name, name, e)
+                })?;
+                bytes[*offset as usize] = (value & 0xFF) as u8;
+                if *dtype == DataType::Word {
+                    bytes[*offset as usize + 1] = ((value >> 8) & 0xFF) as u8;
+                }
+            }
+
+            self.output.push(format!("Actor_Spawn_{}:", actor.name));
+            self.output.push("  JSR Actors_Claim".to_string());
+            self.output.push("  BCS Actors_Full".to_string());
+            self.output.push("  LDY #1".to_string());
+            for (i, byte) in bytes.iter().enumerate().skip(1) {
+                if i > 1 {
+                    self.output.push("  INY".to_string());
+                }
+                match i {
+                    3..=6 => self.output.push(format!("  LDA ${:02X}", 0x0A + i - 3)),
+                    _ => self.output.push(format!("  LDA #${:02X}", byte)),
+                }
+                self.output.push("  STA ($02), Y".to_string());
+            }
+            self.output.push("  LDA $08".to_string());
+            self.output.push("  LDX #0".to_string());
+            self.output.push("  RTS".to_string());
+        }
+
+        // Actors_LoadBox: the box of the slot at ($0E) -> $00-$05 (x, y,
+        // width, height)
+        self.output.push("Actors_LoadBox:".to_string());
+        self.output.push("  LDX #0".to_string());
+        self.output.push("  LDY #3".to_string());
+        self.output.push("Actors_LoadBox_Loop:".to_string());
+        self.output.push("  LDA ($0E), Y".to_string());
+        self.output.push("  STA $00, X".to_string());
+        self.output.push("  INX".to_string());
+        self.output.push("  INY".to_string());
+        self.output.push("  CPY #9".to_string());
+        self.output.push("  BNE Actors_LoadBox_Loop".to_string());
+        self.output.push("  RTS".to_string());
+
+        // Actors_BoxHit: carry set when the box in $00-$05 overlaps the slot
+        // at ($0C) on both axes
+        self.output.push("Actors_BoxHit:".to_string());
+        for (pos, size, a_pos, a_size) in [(3u8, 7u8, 0x00u8, 0x04u8), (5, 8, 0x02, 0x05)] {
+            // a < b + b size
+            self.output.push(format!("  LDY #{}", size));
+            self.output.push("  LDA ($0C), Y".to_string());
+            self.output.push(format!("  LDY #{}", pos));
+            self.output.push("  CLC".to_string());
+            self.output.push("  ADC ($0C), Y".to_string());
+            self.output.push("  STA $06".to_string());
+            self.output.push("  INY".to_string());
+            self.output.push("  LDA ($0C), Y".to_string());
+            self.output.push("  ADC #0".to_string());
+            self.output.push("  STA $07".to_string());
+            self.output.push(format!("  LDA ${:02X}", a_pos));
+            self.output.push("  CMP $06".to_string());
+            self.output.push(format!("  LDA ${:02X}", a_pos + 1));
+            self.output.push("  SBC $07".to_string());
+            self.output.push("  BCS Actors_Apart".to_string());
+            // b < a + a size
+            self.output.push(format!("  LDA ${:02X}", a_pos));
+            self.output.push("  CLC".to_string());
+            self.output.push(format!("  ADC ${:02X}", a_size));
+            self.output.push("  STA $06".to_string());
+            self.output.push(format!("  LDA ${:02X}", a_pos + 1));
+            self.output.push("  ADC #0".to_string());
+            self.output.push("  STA $07".to_string());
+            self.output.push(format!("  LDY #{}", pos));
+            self.output.push("  LDA ($0C), Y".to_string());
+            self.output.push("  CMP $06".to_string());
+            self.output.push("  INY".to_string());
+            self.output.push("  LDA ($0C), Y".to_string());
+            self.output.push("  SBC $07".to_string());
+            self.output.push("  BCS Actors_Apart".to_string());
+        }
+        self.output.push("  SEC".to_string());
+        self.output.push("  RTS".to_string());
+        self.output.push("Actors_Apart:".to_string());
+        self.output.push("  CLC".to_string());
+        self.output.push("  RTS".to_string());
+
+        // $0A = slot, $0B = classes: the first other active slot of one of
+        // the classes overlapping it, or $FF
+        self.output.push("Runtime_Actors_Collide:".to_string());
+        self.output.push("  LDA $0A".to_string());
+        self.output.push(format!("  CMP #{}", slots));
+        self.output.push("  BCS Actors_Collide_None".to_string());
+        self.output.push("  JSR Actors_Locate".to_string());
+        self.output.push("  JSR Actors_LoadBox".to_string());
+        self.output
+            .push(format!("  LDA #${:02X}", (base & 0xFF) as u8));
+        self.output.push("  STA $0C".to_string());
+        self.output
+            .push(format!("  LDA #${:02X}", (base >> 8) as u8));
+        self.output.push("  STA $0D".to_string());
+        self.output.push("  LDA #0".to_string());
+        self.output.push("  STA $09".to_string());
+        self.output.push("Actors_Collide_Loop:".to_string());
+        self.output.push("  LDA $09".to_string());
+        self.output.push("  CMP $0A".to_string());
+        self.output.push("  BEQ Actors_Collide_Next".to_string());
+        self.output.push("  LDY #0".to_string());
+        self.output.push("  LDA ($0C), Y".to_string());
+        self.output.push("  BEQ Actors_Collide_Next".to_string());
+        self.output.push("  LDY #2".to_string());
+        self.output.push("  LDA ($0C), Y".to_string());
+        self.output.push("  AND $0B".to_string());
+        self.output.push("  BEQ Actors_Collide_Next".to_string());
+        self.output.push("  JSR Actors_BoxHit".to_string());
+        self.output.push("  BCS Actors_Collide_Hit".to_string());
+        self.output.push("Actors_Collide_Next:".to_string());
+        self.output.push("  LDA $0C".to_string());
+        self.output.push("  CLC".to_string());
+        self.output.push(format!("  ADC #{}", stride));
+        self.output.push("  STA $0C".to_string());
+        self.output.push("  BCC Actors_Collide_Step".to_string());
+        self.output.push("  INC $0D".to_string());
+        self.output.push("Actors_Collide_Step:".to_string());
+        self.output.push("  INC $09".to_string());
+        self.output.push("  LDA $09".to_string());
+        self.output.push(format!("  CMP #{}", slots));
+        self.output.push("  BCC Actors_Collide_Loop".to_string());
+        self.output.push("Actors_Collide_None:".to_string());
+        self.output.push("  LDA #$FF".to_string());
+        self.output.push("  LDX #0".to_string());
+        self.output.push("  RTS".to_string());
+        self.output.push("Actors_Collide_Hit:".to_string());
+        self.output.push("  LDA $09".to_string());
+        self.output.push("  LDX #0".to_string());
+        self.output.push("  RTS".to_string());
+
+        // $0A, $0B = slots: TRUE when their boxes overlap
+        self.output.push("Runtime_Actors_Overlap:".to_string());
+        self.output.push("  LDA $0A".to_string());
+        self.output.push(format!("  CMP #{}", slots));
+        self.output.push("  BCS Actors_Overlap_None".to_string());
+        self.output.push("  LDA $0B".to_string());
+        self.output.push(format!("  CMP #{}", slots));
+        self.output.push("  BCS Actors_Overlap_None".to_string());
+        self.output.push("  JSR Actors_Locate".to_string());
+        self.output.push("  LDA $0E".to_string());
+        self.output.push("  STA $0C".to_string());
+        self.output.push("  LDA $0F".to_string());
+        self.output.push("  STA $0D".to_string());
+        self.output.push("  LDA $0A".to_string());
+        self.output.push("  JSR Actors_Locate".to_string());
+        self.output.push("  JSR Actors_LoadBox".to_string());
+        self.output.push("  JSR Actors_BoxHit".to_string());
+        self.output.push("  LDA #0".to_string());
+        self.output.push("  BCC Actors_Overlap_Done".to_string());
+        self.output.push("  LDA #$FF".to_string());
+        self.output.push("Actors_Overlap_Done:".to_string());
+        self.output.push("  LDX #0".to_string());
+        self.output.push("  RTS".to_string());
+        self.output.push("Actors_Overlap_None:".to_string());
+        self.output.push("  LDA #0".to_string());
+        self.output.push("  LDX #0".to_string());
+        self.output.push("  RTS".to_string());
+        if !self.uses_room_spawns {
+            self.output.push("".to_string());
+            return Ok(());
+        }
+        // Runtime_World_Spawn: $08/$09 = room x/y; spawns the room's objects
+        // from the world_objects section, ignoring rooms off the map
+        let table = self.layout.addr(SECTION_WORLD_OBJECTS);
+        let offsets = table + 2;
+        let list = ptr + 2;
+        let room_x = ptr + 4;
+        let top = ptr + 5;
+        self.output.push("Runtime_World_Spawn:".to_string());
+        self.output.push("  LDA $08".to_string());
+        self.output.push(format!("  CMP ${:04X}", table));
+        self.output.push("  BCS World_SpawnDone".to_string());
+        self.output.push(format!("  STA ${:04X}", room_x));
+        self.output.push("  LDA $09".to_string());
+        self.output.push(format!("  CMP ${:04X}", table + 1));
+        self.output.push("  BCC World_SpawnRoom".to_string());
+        self.output.push("World_SpawnDone:".to_string());
+        self.output.push("  RTS".to_string());
+        self.output.push("World_SpawnRoom:".to_string());
+        // Room index y * width + x, then its entry in the offset table
+        self.output.push("  LDA $08".to_string());
+        self.output.push("  STA $0A".to_string());
+        self.output.push("  LDA #0".to_string());
+        self.output.push("  STA $0B".to_string());
+        self.output.push("  LDY $09".to_string());
+        self.output.push("  BEQ World_SpawnIndexed".to_string());
+        self.output.push("World_SpawnMul:".to_string());
+        self.output.push("  CLC".to_string());
+        self.output.push("  LDA $0A".to_string());
+        self.output.push(format!("  ADC ${:04X}", table));
+        self.output.push("  STA $0A".to_string());
+        self.output.push("  BCC World_SpawnMulNext".to_string());
+        self.output.push("  INC $0B".to_string());
+        self.output.push("World_SpawnMulNext:".to_string());
+        self.output.push("  DEY".to_string());
+        self.output.push("  BNE World_SpawnMul".to_string());
+        self.output.push("World_SpawnIndexed:".to_string());
+        self.output.push("  ASL $0A".to_string());
+        self.output.push("  ROL $0B".to_string());
+        self.output.push("  CLC".to_string());
+        self.output.push("  LDA $0A".to_string());
+        self.output
+            .push(format!("  ADC #${:02X}", (offsets & 0xFF) as u8));
+        self.output.push("  STA $0A".to_string());
+        self.output.push("  LDA $0B".to_string());
+        self.output
+            .push(format!("  ADC #${:02X}", (offsets >> 8) as u8));
+        self.output.push("  STA $0B".to_string());
+        self.output.push("  LDY #0".to_string());
+        self.output.push("  CLC".to_string());
+        self.output.push("  LDA ($0A), Y".to_string());
+        self.output
+            .push(format!("  ADC #${:02X}", (table & 0xFF) as u8));
+        self.output.push(format!("  STA ${:04X}", list));
+        self.output.push("  INY".to_string());
+        self.output.push("  LDA ($0A), Y".to_string());
+        self.output
+            .push(format!("  ADC #${:02X}", (table >> 8) as u8));
+        self.output.push(format!("  STA ${:04X}", list + 1));
+        // The room's top edge, y * 240 = y * 256 - y * 16
+        self.output.push("  LDA $09".to_string());
+        self.output.push("  ASL".to_string());
+        self.output.push("  ASL".to_string());
+        self.output.push("  ASL".to_string());
+        self.output.push("  ASL".to_string());
+        self.output.push("  STA $06".to_string());
+        self.output.push("  LDA $09".to_string());
+        self.output.push("  LSR".to_string());
+        self.output.push("  LSR".to_string());
+        self.output.push("  LSR".to_string());
+        self.output.push("  LSR".to_string());
+        self.output.push("  STA $07".to_string());
+        self.output.push("  SEC".to_string());
+        self.output.push("  LDA #0".to_string());
+        self.output.push("  SBC $06".to_string());
+        self.output.push(format!("  STA ${:04X}", top));
+        self.output.push("  LDA $09".to_string());
+        self.output.push("  SBC $07".to_string());
+        self.output.push(format!("  STA ${:04X}", top + 1));
+        // Each object: kind, x, y, field count, (offset, byte) pairs
+        self.output.push("World_SpawnNext:".to_string());
+        self.output.push(format!("  LDA ${:04X}", list));
+        self.output.push("  STA $0E".to_string());
+        self.output.push(format!("  LDA ${:04X}", list + 1));
+        self.output.push("  STA $0F".to_string());
+        self.output.push("  LDY #0".to_string());
+        self.output.push("  LDA ($0E), Y".to_string());
+        self.output.push("  CMP #$FF".to_string());
+        self.output.push("  BNE World_SpawnObject".to_string());
+        self.output.push("  RTS".to_string());
+        self.output.push("World_SpawnObject:".to_string());
+        self.output.push("  PHA".to_string());
+        self.output.push("  INY".to_string());
+        self.output.push("  LDA ($0E), Y".to_string());
+        self.output.push("  STA $0A".to_string());
+        self.output.push(format!("  LDA ${:04X}", room_x));
+        self.output.push("  STA $0B".to_string());
+        self.output.push("  INY".to_string());
+        self.output.push("  CLC".to_string());
+        self.output.push("  LDA ($0E), Y".to_string());
+        self.output.push(format!("  ADC ${:04X}", top));
+        self.output.push("  STA $0C".to_string());
+        self.output.push(format!("  LDA ${:04X}", top + 1));
+        self.output.push("  ADC #0".to_string());
+        self.output.push("  STA $0D".to_string());
+        self.output.push("  PLA".to_string());
+        self.output.push("  JSR World_SpawnKind".to_string());
+        // Fields are written into the new slot at ($02), skipped when full
+        self.output.push("  STA $08".to_string());
+        self.output.push("  LDY #3".to_string());
+        self.output.push("  LDA ($0E), Y".to_string());
+        self.output.push("  STA $09".to_string());
+        self.output.push("  INY".to_string());
+        self.output.push("World_SpawnField:".to_string());
+        self.output.push("  LDA $09".to_string());
+        self.output.push("  BEQ World_SpawnAdvance".to_string());
+        self.output.push("  LDA ($0E), Y".to_string());
+        self.output.push("  STA $04".to_string());
+        self.output.push("  INY".to_string());
+        self.output.push("  LDA ($0E), Y".to_string());
+        self.output.push("  INY".to_string());
+        self.output.push("  LDX $08".to_string());
+        self.output.push("  CPX #$FF".to_string());
+        self.output.push("  BEQ World_SpawnSkip".to_string());
+        self.output.push("  STY $05".to_string());
+        self.output.push("  LDY $04".to_string());
+        self.output.push("  STA ($02), Y".to_string());
+        self.output.push("  LDY $05".to_string());
+        self.output.push("World_SpawnSkip:".to_string());
+        self.output.push("  DEC $09".to_string());
+        self.output.push("  JMP World_SpawnField".to_string());
+        self.output.push("World_SpawnAdvance:".to_string());
+        self.output.push("  TYA".to_string());
+        self.output.push("  CLC".to_string());
+        self.output.push(format!("  ADC ${:04X}", list));
+        self.output.push(format!("  STA ${:04X}", list));
+        self.output.push("  BCC World_SpawnNext".to_string());
+        self.output.push(format!("  INC ${:04X}", list + 1));
+        self.output.push("  JMP World_SpawnNext".to_string());
+
+        // World_SpawnKind: A = kind -> Actor_Spawn_<Type>
+        self.output.push("World_SpawnKind:".to_string());
+        for (kind, actor) in actors.iter().enumerate() {
+            self.output.push(format!("  CMP #{}", kind));
+            self.output.push(format!("  BNE World_SpawnKind{}", kind));
+            self.output
+                .push(format!("  JMP Actor_Spawn_{}", actor.name));
+            self.output.push(format!("World_SpawnKind{}:", kind));
+        }
+        self.output.push("  LDA #$FF".to_string());
+        self.output.push("  RTS".to_string());
+        self.output.push("".to_string());
+        Ok(())
+    }
+
+    fn generate_pool_helpers(&mut self) {
+        self.output.push("; --- Pool Helpers ---".to_string());
+
+        self.output.push("Runtime_Pool_Spawn:".to_string());
+        self.output.push("  LDA #0".to_string());
+        self.output.push("  STA $08".to_string());
+        self.output.push("  STA $09".to_string());
+
+        self.output.push("Pool_Spawn_Loop:".to_string());
+        self.output.push("  LDA $08".to_string());
+        self.output.push("  CMP $04".to_string());
+        self.output.push("  LDA $09".to_string());
+        self.output.push("  SBC $05".to_string());
+        self.output.push("  BCS Pool_Full".to_string());
+
+        self.output.push("  LDY #0".to_string());
+        self.output.push("  LDA ($02), Y".to_string());
+        self.output.push("  BEQ Pool_Found".to_string());
+
+        self.output.push("  LDA $02".to_string());
+        self.output.push("  CLC".to_string());
+        self.output.push("  ADC $06".to_string());
+        self.output.push("  STA $02".to_string());
+        self.output.push("  LDA $03".to_string());
+        self.output.push("  ADC #0".to_string());
+        self.output.push("  STA $03".to_string());
+
+        self.output.push("  INC $08".to_string());
+        self.output.push("  BNE Pool_Inc_Skip".to_string());
+        self.output.push("  INC $09".to_string());
+        self.output.push("Pool_Inc_Skip:".to_string());
+        self.output.push("  JMP Pool_Spawn_Loop".to_string());
+
+        self.output.push("Pool_Found:".to_string());
+        self.output.push("  LDA #1".to_string());
+        self.output.push("  STA ($02), Y".to_string());
+        self.output.push("  LDA $08".to_string());
+        self.output.push("  LDX $09".to_string());
+        self.output.push("  RTS".to_string());
+
+        self.output.push("Pool_Full:".to_string());
+        self.output.push("  LDA #$FF".to_string());
+        self.output.push("  LDX #$FF".to_string());
+        self.output.push("  RTS".to_string());
+
+        self.output.push("Runtime_Pool_Despawn:".to_string());
+        self.output.push("  PLA".to_string());
         self.output.push("  STA $0A".to_string());
         self.output.push("  PLA".to_string());
         self.output.push("  STA $0B".to_string());
@@ -2762,145 +4364,113 @@ impl CodeGenerator {
         self.output.push("".to_string());
         self.output.push("; --- User Data ---".to_string());
         self.output.push("USER_DATA_START:".to_string());
+        let mut bank = None;
         for decl in &program.declarations {
-            if let TopLevel::Data(label, exprs) = decl {
-                if let Some(l) = label {
-                    self.output.push(format!("{}:", l));
-                }
-                for expr in exprs {
-                    match expr {
-                        Expression::Integer(val) => {
-                            if (-128..=255).contains(val) {
+            if let TopLevel::Bank(b) = decl {
+                bank = *b;
+                continue;
+            }
+            // DATA and asset tables declared after `BANK n` live in that bank
+            if let Some(b) = bank {
+                std::mem::swap(&mut self.output, self.bank_output.entry(b).or_default());
+            }
+            self.generate_user_data_decl(decl)?;
+            if let Some(b) = bank {
+                std::mem::swap(&mut self.output, self.bank_output.entry(b).or_default());
+            }
+        }
+        self.output.push("".to_string());
+        Ok(())
+    }
+
+    fn generate_user_data_decl(&mut self, decl: &TopLevel) -> Result<(), String> {
+        if let TopLevel::Data(label, exprs) = decl {
+            if let Some(l) = label {
+                self.output.push(format!("{}:", l));
+            }
+            for expr in exprs {
+                match expr {
+                    Expression::Integer(val) => {
+                        if (-128..=255).contains(val) {
+                            self.output
+                                .push(format!("  db ${:02X}", (val & 0xFF) as u8));
+                        } else {
+                            let low = (val & 0xFF) as u8;
+                            let high = ((val >> 8) & 0xFF) as u8;
+                            self.output
+                                .push(format!("  db ${:02X}, ${:02X}", low, high));
+                        }
+                    }
+                    Expression::StringLiteral(s) => {
+                        let bytes: Vec<String> = s.bytes().map(|b| format!("${:02X}", b)).collect();
+                        self.output.push(format!("  db {}, $00", bytes.join(", ")));
+                    }
+                    Expression::UnaryOp(UnaryOperator::Negate, operand) => {
+                        if let Expression::Integer(val) = **operand {
+                            let neg_val = -val;
+                            if (-128..=255).contains(&neg_val) {
                                 self.output
-                                    .push(format!("  db ${:02X}", (val & 0xFF) as u8));
+                                    .push(format!("  db ${:02X}", (neg_val & 0xFF) as u8));
                             } else {
-                                let low = (val & 0xFF) as u8;
-                                let high = ((val >> 8) & 0xFF) as u8;
+                                let low = (neg_val & 0xFF) as u8;
+                                let high = ((neg_val >> 8) & 0xFF) as u8;
                                 self.output
                                     .push(format!("  db ${:02X}, ${:02X}", low, high));
                             }
+                        } else {
+                            return Err("DATA error".to_string());
                         }
-                        Expression::StringLiteral(s) => {
-                            let bytes: Vec<String> =
-                                s.bytes().map(|b| format!("${:02X}", b)).collect();
-                            self.output.push(format!("  db {}, $00", bytes.join(", ")));
-                        }
-                        Expression::UnaryOp(UnaryOperator::Negate, operand) => {
-                            if let Expression::Integer(val) = **operand {
-                                let neg_val = -val;
-                                if (-128..=255).contains(&neg_val) {
-                                    self.output
-                                        .push(format!("  db ${:02X}", (neg_val & 0xFF) as u8));
-                                } else {
-                                    let low = (neg_val & 0xFF) as u8;
-                                    let high = ((neg_val >> 8) & 0xFF) as u8;
-                                    self.output
-                                        .push(format!("  db ${:02X}, ${:02X}", low, high));
-                                }
-                            } else {
-                                return Err("DATA error".to_string());
-                            }
-                        }
-                        _ => return Err("DATA statement only supports literals".to_string()),
                     }
+                    _ => return Err("DATA statement only supports literals".to_string()),
                 }
-            } else if let TopLevel::Metasprite(name, tiles) = decl {
-                self.output.push(format!("{}:", name));
-                self.output.push(format!("  db ${:02X}", tiles.len() as u8));
-                for tile in tiles {
-                    let x = self.resolve_constant(&tile.x)? as i8;
-                    let y = self.resolve_constant(&tile.y)? as i8;
-                    let t = self.resolve_constant(&tile.tile)? as u8;
-                    let a = self.resolve_constant(&tile.attr)? as u8;
-                    self.output.push(format!(
-                        "  db ${:02X}, ${:02X}, ${:02X}, ${:02X}",
-                        x as u8, y as u8, t, a
-                    ));
-                }
-            } else if let TopLevel::Animation(name, frames, loops) = decl {
-                self.output.push(format!("{}:", name));
-                self.output
-                    .push(format!("  db ${:02X}", frames.len() as u8));
-                self.output
-                    .push(format!("  db ${:02X}", if *loops { 1 } else { 0 }));
-                for (i, frame) in frames.iter().enumerate() {
-                    let lbl = format!("{}_f{}", name, i);
-                    self.output
-                        .push(format!("{}: WORD {}", lbl, frame.metasprite));
-                    self.output.push(format!("  db ${:02X}", frame.duration));
-                }
-            } else if let TopLevel::Metatile(name, tiles, attr) = decl {
-                self.output.push(format!("{}:", name));
-                self.output.push(format!(
-                    "  db ${:02X}, ${:02X}, ${:02X}, ${:02X}, ${:02X}",
-                    tiles[0], tiles[1], tiles[2], tiles[3], attr
-                ));
-            } else if let TopLevel::World(width, height, data) = decl {
-                self.output.push("World_Map:".to_string());
-                self.output.push(format!(
-                    "  db ${:02X}, ${:02X}",
-                    width & 0xFF,
-                    (width >> 8) & 0xFF
-                ));
-                self.output.push(format!(
-                    "  db ${:02X}, ${:02X}",
-                    height & 0xFF,
-                    (height >> 8) & 0xFF
-                ));
-                let mut line = String::from("  db ");
-                for (i, val) in data.iter().enumerate() {
-                    if i > 0 && i % 16 == 0 {
-                        self.output.push(line);
-                        line = String::from("  db ");
-                    } else if i > 0 {
-                        line.push_str(", ");
-                    }
-                    line.push_str(&format!("${:02X}", val));
+            }
+        } else if let TopLevel::Metasprite(name, _, _, _) | TopLevel::Animation(name, _, _) = decl {
+            // Placed in the sprites section
+            self.output
+                .push(format!("; {} @ ${:04X}", name, self.sprite_addrs[name]));
+        } else if let TopLevel::Metatile(name, tiles, attr) = decl {
+            self.output.push(format!("{}:", name));
+            self.output.push(format!(
+                "  db ${:02X}, ${:02X}, ${:02X}, ${:02X}, ${:02X}",
+                tiles[0], tiles[1], tiles[2], tiles[3], attr
+            ));
+        } else if let TopLevel::World(width, height, data) = decl {
+            self.output.push("World_Map:".to_string());
+            self.output.push(format!(
+                "  db ${:02X}, ${:02X}",
+                width & 0xFF,
+                (width >> 8) & 0xFF
+            ));
+            self.output.push(format!(
+                "  db ${:02X}, ${:02X}",
+                height & 0xFF,
+                (height >> 8) & 0xFF
+            ));
+            let mut line = String::from("  db ");
+            for (i, val) in data.iter().enumerate() {
+                if i > 0 && i % 16 == 0 {
+                    self.output.push(line);
+                    line = String::from("  db ");
+                } else if i > 0 {
+                    line.push_str(", ");
                 }
-                self.output.push(line);
+                // Empty rooms (-1) are stored as $FF
+                line.push_str(&format!("${:02X}", val & 0xFF));
             }
+            self.output.push(line);
         }
-        self.output.push("".to_string());
         Ok(())
     }
 
     fn resolve_constant(&self, expr: &Expression) -> Result<i32, String> {
-        match expr {
-            Expression::Integer(val) => Ok(*val),
-            Expression::UnaryOp(UnaryOperator::Negate, operand) => {
-                let val = self.resolve_constant(operand)?;
-                Ok(-val)
-            }
-            Expression::Identifier(name) => {
-                if let Some(sym) = self.symbol_table.resolve(name) {
-                    if let Some(val) = sym.value {
-                        Ok(val)
-                    } else {
-                        Err(format!("Symbol '{}' is not a constant value", name))
-                    }
-                } else {
-                    Err(format!("Undefined symbol '{}'", name))
-                }
-            }
-            Expression::BinaryOp(l, op, r) => {
-                let lv = self.resolve_constant(l)?;
-                let rv = self.resolve_constant(r)?;
-                match op {
-                    BinaryOperator::Add => Ok(lv + rv),
-                    BinaryOperator::Subtract => Ok(lv - rv),
-                    BinaryOperator::Multiply => Ok(lv * rv),
-                    BinaryOperator::Divide => Ok(lv / rv),
-                    _ => Err("Unsupported constant operator".to_string()),
-                }
-            }
-            _ => Err("Not a constant expression".to_string()),
-        }
+        self.symbol_table.constant_value(expr)
     }
 
     fn generate_data_tables(&mut self, program: &Program) -> Result<(), String> {
         self.output.push("".to_string());
         self.output.push("; --- Data Tables ---".to_string());
-        self.output.push(".ORG $FF00".to_string());
+        self.output
+            .push(format!(".ORG ${:04X}", POINTER_TABLE_ADDR));
         let mut current_addr = 0xFF00;
         self.output
             .push("InitDefaultRTI: WORD DefaultRTI".to_string());
@@ -2924,7 +4494,11 @@ impl CodeGenerator {
                 current_addr += 2;
             }
             if let TopLevel::Sub(name, _, _) = decl {
-                self.output.push(format!("Ptr_{}: WORD {}", name, name));
+                if self.sub_banks.contains_key(name) {
+                    self.output.push(format!("Ptr_{}: WORD Far_{}", name, name));
+                } else {
+                    self.output.push(format!("Ptr_{}: WORD {}", name, name));
+                }
                 self.data_table_offsets.insert(name.clone(), current_addr);
                 current_addr += 2;
             }
@@ -2958,7 +4532,7 @@ impl CodeGenerator {
 
     fn generate_vectors(&mut self, _program: &Program) -> Result<(), String> {
         self.output.push("".to_string());
-        self.output.push(".ORG $FFFA".to_string());
+        self.output.push(format!(".ORG ${:04X}", VECTORS_ADDR));
         self.output.push("VecNMI: WORD TrampolineNMI".to_string());
         self.output.push("VecReset: WORD Startup".to_string());
         self.output.push("VecIRQ: WORD TrampolineIRQ".to_string());
@@ -2966,18 +4540,2345 @@ impl CodeGenerator {
         Ok(())
     }
 
-    fn generate_controller_helpers(&mut self) {
-        self.output.push("; --- Controller Helpers ---".to_string());
-        self.output.push("Runtime_Controller_Read:".to_string());
-        self.output.push("  LDA #$01".to_string());
-        self.output.push("  STA $4016".to_string());
-        self.output.push("  LDA #$00".to_string());
-        self.output.push("  STA $4016".to_string());
-        self.output.push("  LDA $10".to_string()); // Load old held
-        self.output.push("  STA $11".to_string()); // Save to last
-        self.output.push("  LDA #0".to_string());
-        self.output.push("  STA $10".to_string()); // Clear held
-        self.output.push("  LDX #8".to_string());
+    fn collect_banks(&mut self, program: &Program) {
+        self.current_bank = None;
+        self.sub_banks.clear();
+        self.bank_entries.clear();
+        self.bank_output.clear();
+        self.scanline_handlers.clear();
+        let mut bank = None;
+        for decl in &program.declarations {
+            match decl {
+                TopLevel::Bank(b) => bank = *b,
+                TopLevel::Sub(name, _, _) => {
+                    if let Some(b) = bank {
+                        self.sub_banks.insert(name.clone(), b);
+                        self.bank_entries.entry(b).or_default().push(name.clone());
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    // SUBs and top-level ASM after `BANK n` are emitted into that bank's
+    // output; everything else stays in the fixed bank.
+    fn generate_banked(&mut self, decl: &TopLevel) -> Result<(), String> {
+        let bank = self
+            .current_bank
+            .filter(|_| matches!(decl, TopLevel::Sub(..) | TopLevel::Asm(_)));
+        if let Some(b) = bank {
+            std::mem::swap(&mut self.output, self.bank_output.entry(b).or_default());
+        }
+        let start = self.output.len();
+        let result = self.generate_top_level(decl);
+        self.route_far_calls(start);
+        if let Some(b) = bank {
+            std::mem::swap(&mut self.output, self.bank_output.entry(b).or_default());
+        }
+        result
+    }
+
+    /// Rewrites `JSR Sub` into `JSR Far_Sub` when Sub lives in a bank other
+    /// than the one being generated.
+    fn route_far_calls(&mut self, start: usize) {
+        if self.sub_banks.is_empty() {
+            return;
+        }
+        for line in &mut self.output[start..] {
+            let target = match line.trim().strip_prefix("JSR ") {
+                Some(t) => t.trim().to_string(),
+                None => continue,
+            };
+            if let Some(bank) = self.sub_banks.get(&target) {
+                if self.current_bank != Some(*bank) {
+                    *line = format!("  JSR Far_{}", target);
+                }
+            }
+        }
+    }
+
+    // Save.Commit stores a checksum of the DIM SAVE region after it; on a
+    // fresh or corrupted battery RAM Save.Verify returns FALSE.
+    fn generate_save_helpers(&mut self) {
+        if self.save_pointer == SAVE_RAM_START {
+            return;
+        }
+        self.output.push("; --- Save RAM Helpers ---".to_string());
+        // $00 = sum, $01 = rotating XOR over $6000..save_pointer. Both are
+        // seeded so that all-zero RAM does not verify.
+        self.output.push("Runtime_Save_Checksum:".to_string());
+        self.output.push("  LDA #$00".to_string());
+        self.output.push("  STA $02".to_string());
+        self.output
+            .push(format!("  LDA #${:02X}", SAVE_RAM_START >> 8));
+        self.output.push("  STA $03".to_string());
+        self.output.push("  LDA #$A5".to_string());
+        self.output.push("  STA $00".to_string());
+        self.output.push("  LDA #$5A".to_string());
+        self.output.push("  STA $01".to_string());
+        self.output.push("  LDY #$00".to_string());
+        self.output.push("SaveSum_Loop:".to_string());
+        self.output.push("  LDA $03".to_string());
+        self.output
+            .push(format!("  CMP #${:02X}", self.save_pointer >> 8));
+        self.output.push("  BNE SaveSum_Byte".to_string());
+        self.output
+            .push(format!("  CPY #${:02X}", self.save_pointer & 0xFF));
+        self.output.push("  BEQ SaveSum_Done".to_string());
+        self.output.push("SaveSum_Byte:".to_string());
+        self.output.push("  LDA ($02), Y".to_string());
+        self.output.push("  PHA".to_string());
+        self.output.push("  CLC".to_string());
+        self.output.push("  ADC $00".to_string());
+        self.output.push("  STA $00".to_string());
+        self.output.push("  PLA".to_string());
+        self.output.push("  EOR $01".to_string());
+        self.output.push("  ASL".to_string());
+        self.output.push("  ADC #$00".to_string());
+        self.output.push("  STA $01".to_string());
+        self.output.push("  INY".to_string());
+        self.output.push("  BNE SaveSum_Loop".to_string());
+        self.output.push("  INC $03".to_string());
+        self.output.push("  JMP SaveSum_Loop".to_string());
+        self.output.push("SaveSum_Done:".to_string());
+        self.output.push("  RTS".to_string());
+
+        self.output.push("Runtime_Save_Commit:".to_string());
+        self.output.push("  JSR Runtime_Save_Checksum".to_string());
+        self.output.push("  LDA $00".to_string());
+        self.output
+            .push(format!("  STA ${:04X}", SAVE_CHECKSUM_ADDR));
+        self.output.push("  LDA $01".to_string());
+        self.output
+            .push(format!("  STA ${:04X}", SAVE_CHECKSUM_ADDR + 1));
+        self.output.push("  RTS".to_string());
+
+        // A = $FF when the stored checksum matches, else 0
+        self.output.push("Runtime_Save_Verify:".to_string());
+        self.output.push("  JSR Runtime_Save_Checksum".to_string());
+        self.output.push("  LDX #0".to_string());
+        self.output.push("  LDA $00".to_string());
+        self.output
+            .push(format!("  CMP ${:04X}", SAVE_CHECKSUM_ADDR));
+        self.output.push("  BNE SaveVerify_Bad".to_string());
+        self.output.push("  LDA $01".to_string());
+        self.output
+            .push(format!("  CMP ${:04X}", SAVE_CHECKSUM_ADDR + 1));
+        self.output.push("  BNE SaveVerify_Bad".to_string());
+        self.output.push("  LDA #$FF".to_string());
+        self.output.push("  RTS".to_string());
+        self.output.push("SaveVerify_Bad:".to_string());
+        self.output.push("  LDA #0".to_string());
+        self.output.push("  RTS".to_string());
+    }
+
+    // Password.Encode / Password.Decode for the program's PASSWORD format;
+    // see compiler::password for the layout these routines must match.
+    fn generate_password_helpers(&mut self) {
+        let format = match self.password.clone() {
+            Some(f) => f,
+            None => return,
+        };
+        let p = format.payload_bytes() as u16;
+        let bits = format.char_bits();
+        let count = format.length();
+        let buf = self.password_ram;
+        let chars = buf + p + 2;
+        let alphabet = self.layout.addr(SECTION_PASSWORD_ALPHABET);
+
+        // Global address and width in bytes of each field variable
+        let mut fields = Vec::new();
+        for (name, width) in &format.fields {
+            let sym = self.symbol_table.resolve(name);
+            let addr = sym.and_then(|s| s.address).unwrap_or(0);
+            let wide = sym.is_some_and(|s| matches!(s.data_type, DataType::Word | DataType::Int));
+            fields.push((addr, wide, *width));
+        }
+
+        self.output.push("; --- Password Helpers ---".to_string());
+        // Rotates carry into the top of buf[X..0]; bit 0 of buf[0] ends in carry
+        self.output.push("Password_Shift:".to_string());
+        self.output.push(format!("  ROR ${:04X}, X", buf));
+        self.output.push("  DEX".to_string());
+        self.output.push("  BPL Password_Shift".to_string());
+        self.output.push("  RTS".to_string());
+        // Y bits of $00/$01, LSB first, into the top of the payload
+        self.output.push("Password_Push:".to_string());
+        self.output.push("  LSR $01".to_string());
+        self.output.push("  ROR $00".to_string());
+        self.output.push(format!("  LDX #${:02X}", p - 1));
+        self.output.push("  JSR Password_Shift".to_string());
+        self.output.push("  DEY".to_string());
+        self.output.push("  BNE Password_Push".to_string());
+        self.output.push("  RTS".to_string());
+        // Y bits from the bottom of the payload into the top of $00/$01
+        self.output.push("Password_Pull:".to_string());
+        self.output.push("  CLC".to_string());
+        self.output.push(format!("  LDX #${:02X}", p - 1));
+        self.output.push("  JSR Password_Shift".to_string());
+        self.output.push("  ROR $01".to_string());
+        self.output.push("  ROR $00".to_string());
+        self.output.push("  DEY".to_string());
+        self.output.push("  BNE Password_Pull".to_string());
+        self.output.push("  RTS".to_string());
+        self.output.push("Password_Align:".to_string());
+        self.output.push("  LSR $01".to_string());
+        self.output.push("  ROR $00".to_string());
+        self.output.push("  DEY".to_string());
+        self.output.push("  BNE Password_Align".to_string());
+        self.output.push("  RTS".to_string());
+        // A = $02 = $02 * 5 + 1
+        self.output.push("Password_NextKey:".to_string());
+        self.output.push("  LDA $02".to_string());
+        self.output.push("  ASL".to_string());
+        self.output.push("  ASL".to_string());
+        self.output.push("  CLC".to_string());
+        self.output.push("  ADC $02".to_string());
+        self.output.push("  CLC".to_string());
+        self.output.push("  ADC #1".to_string());
+        self.output.push("  STA $02".to_string());
+        self.output.push("  RTS".to_string());
+        // A = sum(payload) ^ seed
+        self.output.push("Password_Checksum:".to_string());
+        self.output.push("  LDA #0".to_string());
+        self.output.push(format!("  LDX #${:02X}", p - 1));
+        self.output.push("Password_SumLoop:".to_string());
+        self.output.push("  CLC".to_string());
+        self.output.push(format!("  ADC ${:04X}, X", buf));
+        self.output.push("  DEX".to_string());
+        self.output.push("  BPL Password_SumLoop".to_string());
+        self.output.push(format!("  EOR #${:02X}", CHECKSUM_SEED));
+        self.output.push("  RTS".to_string());
+
+        // Returns a STRING pointer to the password in A/X
+        self.output.push("Runtime_Password_Encode:".to_string());
+        for (addr, wide, width) in &fields {
+            self.output.push(format!("  LDA ${:04X}", addr));
+            self.output.push("  STA $00".to_string());
+            if *wide {
+                self.output.push(format!("  LDA ${:04X}", addr + 1));
+            } else {
+                self.output.push("  LDA #0".to_string());
+            }
+            self.output.push("  STA $01".to_string());
+            self.output.push(format!("  LDY #{}", width));
+            self.output.push("  JSR Password_Push".to_string());
+        }
+        let pad = p as usize * 8 - format.field_bits();
+        if pad > 0 {
+            self.output.push("  LDA #0".to_string());
+            self.output.push("  STA $00".to_string());
+            self.output.push("  STA $01".to_string());
+            self.output.push(format!("  LDY #{}", pad));
+            self.output.push("  JSR Password_Push".to_string());
+        }
+        self.output.push("  JSR Password_Checksum".to_string());
+        self.output.push(format!("  STA ${:04X}", buf + p));
+        self.output.push(format!("  LDA #${:02X}", format.key));
+        self.output.push("  STA $02".to_string());
+        self.output.push("  LDA #0".to_string());
+        self.output.push("  STA $03".to_string());
+        self.output.push(format!("  LDX #${:02X}", p));
+        self.output.push("PwEnc_Obfuscate:".to_string());
+        self.output.push("  JSR Password_NextKey".to_string());
+        self.output.push(format!("  EOR ${:04X}, X", buf));
+        self.output.push("  CLC".to_string());
+        self.output.push("  ADC $03".to_string());
+        self.output.push(format!("  STA ${:04X}, X", buf));
+        self.output.push("  STA $03".to_string());
+        self.output.push("  DEX".to_string());
+        self.output.push("  BPL PwEnc_Obfuscate".to_string());
+        self.output.push("  LDA #0".to_string());
+        self.output.push("  STA $04".to_string());
+        self.output.push("PwEnc_Char:".to_string());
+        self.output.push("  LDA #0".to_string());
+        self.output.push("  STA $05".to_string());
+        self.output.push(format!("  LDY #{}", bits));
+        self.output.push("PwEnc_Bit:".to_string());
+        self.output.push("  CLC".to_string());
+        self.output.push(format!("  LDX #${:02X}", p));
+        self.output.push("  JSR Password_Shift".to_string());
+        self.output.push("  ROR $05".to_string());
+        self.output.push("  DEY".to_string());
+        self.output.push("  BNE PwEnc_Bit".to_string());
+        self.output.push("  LDA $05".to_string());
+        for _ in bits..8 {
+            self.output.push("  LSR".to_string());
+        }
+        self.output.push("  TAY".to_string());
+        self.output.push(format!("  LDA ${:04X}, Y", alphabet));
+        self.output.push("  LDX $04".to_string());
+        self.output.push(format!("  STA ${:04X}, X", chars));
+        self.output.push("  INC $04".to_string());
+        self.output.push("  LDA $04".to_string());
+        self.output.push(format!("  CMP #{}", count));
+        self.output.push("  BNE PwEnc_Char".to_string());
+        self.output.push("  LDA #0".to_string());
+        self.output
+            .push(format!("  STA ${:04X}", chars + count as u16));
+        self.output.push(format!("  LDA #${:02X}", chars & 0xFF));
+        self.output.push(format!("  LDX #${:02X}", chars >> 8));
+        self.output.push("  RTS".to_string());
+
+        // A/X = STRING pointer; returns TRUE and sets the fields when valid
+        self.output.push("Runtime_Password_Decode:".to_string());
+        self.output.push("  STA $06".to_string());
+        self.output.push("  STX $07".to_string());
+        self.output.push("  LDA #0".to_string());
+        self.output.push(format!("  LDX #${:02X}", p + 1));
+        self.output.push("PwDec_Clear:".to_string());
+        self.output.push(format!("  STA ${:04X}, X", buf));
+        self.output.push("  DEX".to_string());
+        self.output.push("  BPL PwDec_Clear".to_string());
+        self.output.push("  STA $04".to_string());
+        self.output.push("  JMP PwDec_Char".to_string());
+        self.output.push("PwDec_Bad:".to_string());
+        self.output.push("  LDA #0".to_string());
+        self.output.push("  LDX #0".to_string());
+        self.output.push("  RTS".to_string());
+        self.output.push("PwDec_Char:".to_string());
+        self.output.push("  LDY $04".to_string());
+        self.output.push("  LDA ($06), Y".to_string());
+        self.output.push("  BEQ PwDec_Bad".to_string());
+        self.output
+            .push(format!("  LDX #${:02X}", format.alphabet.len() - 1));
+        self.output.push("PwDec_Find:".to_string());
+        self.output.push(format!("  CMP ${:04X}, X", alphabet));
+        self.output.push("  BEQ PwDec_Found".to_string());
+        self.output.push("  DEX".to_string());
+        self.output.push("  BPL PwDec_Find".to_string());
+        self.output.push("  JMP PwDec_Bad".to_string());
+        self.output.push("PwDec_Found:".to_string());
+        self.output.push("  STX $05".to_string());
+        self.output.push(format!("  LDY #{}", bits));
+        self.output.push("PwDec_Bit:".to_string());
+        self.output.push("  LSR $05".to_string());
+        self.output.push(format!("  LDX #${:02X}", p + 1));
+        self.output.push("  JSR Password_Shift".to_string());
+        self.output.push("  DEY".to_string());
+        self.output.push("  BNE PwDec_Bit".to_string());
+        self.output.push("  INC $04".to_string());
+        self.output.push("  LDA $04".to_string());
+        self.output.push(format!("  CMP #{}", count));
+        self.output.push("  BNE PwDec_Char".to_string());
+        // Too long?
+        self.output.push("  TAY".to_string());
+        self.output.push("  LDA ($06), Y".to_string());
+        self.output.push("  BNE PwDec_Bad".to_string());
+        // Align byte 0 to the bottom; the spill byte keeps the padding bits
+        let extra = (format.byte_len() + 1) * 8 - count * bits;
+        self.output.push(format!("  LDY #{}", extra));
+        self.output.push("PwDec_Pad:".to_string());
+        self.output.push("  CLC".to_string());
+        self.output.push(format!("  LDX #${:02X}", p + 1));
+        self.output.push("  JSR Password_Shift".to_string());
+        self.output.push("  DEY".to_string());
+        self.output.push("  BNE PwDec_Pad".to_string());
+        self.output.push(format!("  LDA ${:04X}", buf + p + 1));
+        self.output.push("  BEQ PwDec_Deobfuscate".to_string());
+        self.output.push("  JMP PwDec_Bad".to_string());
+        self.output.push("PwDec_Deobfuscate:".to_string());
+        self.output.push(format!("  LDA #${:02X}", format.key));
+        self.output.push("  STA $02".to_string());
+        self.output.push("  LDA #0".to_string());
+        self.output.push("  STA $03".to_string());
+        self.output.push(format!("  LDX #${:02X}", p));
+        self.output.push("PwDec_Obfuscated:".to_string());
+        self.output.push("  JSR Password_NextKey".to_string());
+        self.output.push("  STA $05".to_string());
+        self.output.push(format!("  LDA ${:04X}, X", buf));
+        self.output.push("  TAY".to_string());
+        self.output.push("  SEC".to_string());
+        self.output.push("  SBC $03".to_string());
+        self.output.push("  EOR $05".to_string());
+        self.output.push(format!("  STA ${:04X}, X", buf));
+        self.output.push("  STY $03".to_string());
+        self.output.push("  DEX".to_string());
+        self.output.push("  BPL PwDec_Obfuscated".to_string());
+        self.output.push("  JSR Password_Checksum".to_string());
+        self.output.push(format!("  CMP ${:04X}", buf + p));
+        self.output.push("  BEQ PwDec_Fields".to_string());
+        self.output.push("  JMP PwDec_Bad".to_string());
+        self.output.push("PwDec_Fields:".to_string());
+        for (addr, wide, width) in &fields {
+            self.output.push("  LDA #0".to_string());
+            self.output.push("  STA $00".to_string());
+            self.output.push("  STA $01".to_string());
+            self.output.push(format!("  LDY #{}", width));
+            self.output.push("  JSR Password_Pull".to_string());
+            if *width < 16 {
+                self.output.push(format!("  LDY #{}", 16 - width));
+                self.output.push("  JSR Password_Align".to_string());
+            }
+            self.output.push("  LDA $00".to_string());
+            self.output.push(format!("  STA ${:04X}", addr));
+            if *wide {
+                self.output.push("  LDA $01".to_string());
+                self.output.push(format!("  STA ${:04X}", addr + 1));
+            }
+        }
+        self.output.push("  LDA #$FF".to_string());
+        self.output.push("  LDX #0".to_string());
+        self.output.push("  RTS".to_string());
+    }
+
+    // CHR-RAM starts out as garbage; fill pattern tables $0000-$1FFF from
+    // the `chr` section while rendering is still off. A banked section is
+    // mapped in for the upload, then bank 0 again.
+    fn generate_chr_upload(&mut self) {
+        let compression = match self.chr_ram {
+            Some(c) => c,
+            None => return,
+        };
+        let addr = self.layout.addr(SECTION_CHR);
+        let bank = self.layout.bank(SECTION_CHR);
+        let set_prg = format!("{}_SetPRG", self.mapper.name());
+        if let Some(bank) = bank {
+            self.output.push(format!("  LDA #${:02X}", bank));
+            self.output.push(format!("  JSR {}", set_prg));
+        }
+        self.output.push("  LDA $2002".to_string());
+        self.output.push("  LDA #$00".to_string());
+        self.output.push("  STA $2006".to_string());
+        self.output.push("  STA $2006".to_string());
+        self.output.push(format!("  LDA #${:02X}", addr & 0xFF));
+        self.output.push("  STA $02".to_string());
+        self.output.push(format!("  LDA #${:02X}", addr >> 8));
+        self.output.push("  STA $03".to_string());
+        match compression {
+            Compression::None => {
+                // The raw section is padded to whole pages
+                let size = self.layout.get(SECTION_CHR).map(|p| p.size).unwrap_or(0);
+                let pages = size.min(0x2000) / 256;
+                if pages > 0 {
+                    self.output.push(format!("  LDX #${:02X}", pages));
+                    self.output.push("  LDY #$00".to_string());
+                    self.output.push("ChrUploadLoop:".to_string());
+                    self.output.push("  LDA ($02), Y".to_string());
+                    self.output.push("  STA $2007".to_string());
+                    self.output.push("  INY".to_string());
+                    self.output.push("  BNE ChrUploadLoop".to_string());
+                    self.output.push("  INC $03".to_string());
+                    self.output.push("  DEX".to_string());
+                    self.output.push("  BNE ChrUploadLoop".to_string());
+                }
+            }
+            _ => {
+                let routine = Self::ppu_decompressor(compression);
+                self.output.push(format!("  JSR {}", routine));
+            }
+        }
+        if bank.is_some() {
+            self.output.push("  LDA #$00".to_string());
+            self.output.push(format!("  JSR {}", set_prg));
+        }
+    }
+
+    // Stream decoder for a stored (never `Auto`) compressed format
+    fn ppu_decompressor(compression: Compression) -> &'static str {
+        match compression {
+            Compression::Rle => "Runtime_Rle_ToPpu",
+            Compression::Metatile => "Runtime_Metatile_ToPpu",
+            _ => "Runtime_Lz_ToPpu",
+        }
+    }
+
+    fn generate_chr_helpers(&mut self) {
+        if self.chr_ram.is_none() {
+            return;
+        }
+        self.output.push("; --- CHR-RAM Helpers ---".to_string());
+        // ($04) = source tiles, $06/$07 = PPU address, $08 = tile count.
+        // Queues two tiles (32 bytes) or one (16 bytes) per entry; a full
+        // queue waits for the NMI to drain it.
+        self.output.push("Runtime_CHR_Load:".to_string());
+        self.output.push("  LDA $08".to_string());
+        self.output.push("  BEQ ChrLoad_Done".to_string());
+        self.output.push("  LDA #16".to_string());
+        self.output.push("  LDY $08".to_string());
+        self.output.push("  CPY #2".to_string());
+        self.output.push("  BCC ChrLoad_Len".to_string());
+        self.output.push("  LDA #32".to_string());
+        self.output.push("ChrLoad_Len:".to_string());
+        self.output.push("  STA $09".to_string());
+        self.output.push("  JSR PpuQueue_Reserve".to_string());
+        self.output.push("  LDA $07".to_string());
+        self.output
+            .push(format!("  STA ${:04X}, X", PPU_QUEUE_START + 1));
+        self.output.push("  LDA $06".to_string());
+        self.output
+            .push(format!("  STA ${:04X}, X", PPU_QUEUE_START + 2));
+        self.output.push("  LDY #$00".to_string());
+        self.output.push("ChrLoad_Copy:".to_string());
+        self.output.push("  LDA ($04), Y".to_string());
+        self.output
+            .push(format!("  STA ${:04X}, X", PPU_QUEUE_START + 3));
+        self.output.push("  INX".to_string());
+        self.output.push("  INY".to_string());
+        self.output.push("  CPY $09".to_string());
+        self.output.push("  BNE ChrLoad_Copy".to_string());
+        self.output.push("  JSR PpuQueue_Commit".to_string());
+        // Advance source and destination past the queued bytes
+        for ptr in [4, 6] {
+            self.output.push("  CLC".to_string());
+            self.output.push(format!("  LDA ${:02X}", ptr));
+            self.output.push("  ADC $09".to_string());
+            self.output.push(format!("  STA ${:02X}", ptr));
+            self.output.push(format!("  LDA ${:02X}", ptr + 1));
+            self.output.push("  ADC #0".to_string());
+            self.output.push(format!("  STA ${:02X}", ptr + 1));
+        }
+        self.output.push("  DEC $08".to_string());
+        self.output.push("  LDA $09".to_string());
+        self.output.push("  CMP #32".to_string());
+        self.output.push("  BNE Runtime_CHR_Load".to_string());
+        self.output.push("  DEC $08".to_string());
+        self.output.push("  JMP Runtime_CHR_Load".to_string());
+        self.output.push("ChrLoad_Done:".to_string());
+        self.output.push("  RTS".to_string());
+    }
+
+    // Decoders shared by the CHR-RAM upload and the startup nametable. Both
+    // read the stream at ($02) and write to $2007 with rendering off.
+    fn generate_decompress_helpers(&mut self) {
+        let mut formats = vec![
+            self.chr_ram.unwrap_or(Compression::None),
+            self.nametable_compression,
+        ];
+        if self.uses_screen_load {
+            formats.extend(self.screens.iter().copied());
+        }
+        let uses = |c: Compression| formats.contains(&c);
+        if !uses(Compression::Rle) && !uses(Compression::Lz) && !uses(Compression::Metatile) {
+            return;
+        }
+        self.output
+            .push("; --- Decompression Helpers ---".to_string());
+        if uses(Compression::Metatile) {
+            self.generate_metatile_decoder();
+        }
+        if !uses(Compression::Rle) && !uses(Compression::Lz) {
+            return;
+        }
+        if uses(Compression::Rle) {
+            // ($02) = RLE stream, written to $2007 until the $00 terminator
+            self.output.push("Runtime_Rle_ToPpu:".to_string());
+            self.output.push("  JSR Rle_Read".to_string());
+            self.output.push("  TAX".to_string());
+            self.output.push("  BEQ Rle_Done".to_string());
+            self.output.push("  BMI Rle_Repeat".to_string());
+            self.output.push("Rle_Literal:".to_string());
+            self.output.push("  JSR Rle_Read".to_string());
+            self.output.push("  STA $2007".to_string());
+            self.output.push("  DEX".to_string());
+            self.output.push("  BNE Rle_Literal".to_string());
+            self.output.push("  JMP Runtime_Rle_ToPpu".to_string());
+            self.output.push("Rle_Repeat:".to_string());
+            self.output.push("  TXA".to_string());
+            self.output.push("  AND #$7F".to_string());
+            self.output.push("  TAX".to_string());
+            self.output.push("  JSR Rle_Read".to_string());
+            self.output.push("Rle_RepeatLoop:".to_string());
+            self.output.push("  STA $2007".to_string());
+            self.output.push("  DEX".to_string());
+            self.output.push("  BNE Rle_RepeatLoop".to_string());
+            self.output.push("  JMP Runtime_Rle_ToPpu".to_string());
+            self.output.push("Rle_Done:".to_string());
+            self.output.push("  RTS".to_string());
+        }
+        if uses(Compression::Lz) {
+            // ($02) = LZ stream, $04/$05 = its start (copy offsets are
+            // relative to it), $06/$07 = copy source, $08 = copy length
+            self.output.push("Runtime_Lz_ToPpu:".to_string());
+            self.output.push("  LDA $02".to_string());
+            self.output.push("  STA $04".to_string());
+            self.output.push("  LDA $03".to_string());
+            self.output.push("  STA $05".to_string());
+            self.output.push("Lz_Next:".to_string());
+            self.output.push("  JSR Rle_Read".to_string());
+            self.output.push("  TAX".to_string());
+            self.output.push("  BEQ Lz_Done".to_string());
+            self.output.push("  BMI Lz_Copy".to_string());
+            self.output.push("  CMP #$40".to_string());
+            self.output.push("  BCS Lz_Run".to_string());
+            self.output.push("Lz_Literal:".to_string());
+            self.output.push("  JSR Rle_Read".to_string());
+            self.output.push("  STA $2007".to_string());
+            self.output.push("  DEX".to_string());
+            self.output.push("  BNE Lz_Literal".to_string());
+            self.output.push("  JMP Lz_Next".to_string());
+            self.output.push("Lz_Run:".to_string());
+            self.output.push("  AND #$3F".to_string());
+            self.output.push("  CLC".to_string());
+            self.output.push("  ADC #3".to_string());
+            self.output.push("  TAX".to_string());
+            self.output.push("  JSR Rle_Read".to_string());
+            self.output.push("Lz_RunLoop:".to_string());
+            self.output.push("  STA $2007".to_string());
+            self.output.push("  DEX".to_string());
+            self.output.push("  BNE Lz_RunLoop".to_string());
+            self.output.push("  JMP Lz_Next".to_string());
+            self.output.push("Lz_Copy:".to_string());
+            self.output.push("  AND #$7F".to_string());
+            self.output.push("  CLC".to_string());
+            self.output.push("  ADC #4".to_string());
+            self.output.push("  STA $08".to_string());
+            // Rle_Read leaves the carry alone, so it chains into the high byte
+            self.output.push("  JSR Rle_Read".to_string());
+            self.output.push("  CLC".to_string());
+            self.output.push("  ADC $04".to_string());
+            self.output.push("  STA $06".to_string());
+            self.output.push("  JSR Rle_Read".to_string());
+            self.output.push("  ADC $05".to_string());
+            self.output.push("  STA $07".to_string());
+            self.output.push("  LDY #$00".to_string());
+            self.output.push("Lz_CopyLoop:".to_string());
+            self.output.push("  LDA ($06), Y".to_string());
+            self.output.push("  STA $2007".to_string());
+            self.output.push("  INY".to_string());
+            self.output.push("  CPY $08".to_string());
+            self.output.push("  BNE Lz_CopyLoop".to_string());
+            self.output.push("  JMP Lz_Next".to_string());
+            self.output.push("Lz_Done:".to_string());
+            self.output.push("  RTS".to_string());
+        }
+        // A = *($02)++
+        self.output.push("Rle_Read:".to_string());
+        self.output.push("  LDY #$00".to_string());
+        self.output.push("  LDA ($02), Y".to_string());
+        self.output.push("  INC $02".to_string());
+        self.output.push("  BNE Rle_ReadDone".to_string());
+        self.output.push("  INC $03".to_string());
+        self.output.push("Rle_ReadDone:".to_string());
+        self.output.push("  RTS".to_string());
+    }
+
+    // ($02) = metatile map. Each grid row is read twice, for the top and the
+    // bottom tile row; $08 counts the grid rows left.
+    fn generate_metatile_decoder(&mut self) {
+        let table = self.layout.addr(SECTION_METATILES);
+        let count = self.layout.get(SECTION_METATILES).map_or(0, |p| p.size / 4) as u16;
+        let plane = |corner: u16| table + corner * count;
+        self.output.push("Runtime_Metatile_ToPpu:".to_string());
+        self.output.push("  LDA #15".to_string());
+        self.output.push("  STA $08".to_string());
+        self.output.push("Metatile_Row:".to_string());
+        for (half, label) in [(0, "Metatile_Top"), (2, "Metatile_Bottom")] {
+            self.output.push("  LDY #$00".to_string());
+            self.output.push(format!("{}:", label));
+            self.output.push("  LDA ($02), Y".to_string());
+            self.output.push("  TAX".to_string());
+            self.output.push(format!("  LDA ${:04X}, X", plane(half)));
+            self.output.push("  STA $2007".to_string());
+            self.output
+                .push(format!("  LDA ${:04X}, X", plane(half + 1)));
+            self.output.push("  STA $2007".to_string());
+            self.output.push("  INY".to_string());
+            self.output.push("  CPY #16".to_string());
+            self.output.push(format!("  BNE {}", label));
+        }
+        self.output.push("  LDA $02".to_string());
+        self.output.push("  CLC".to_string());
+        self.output.push("  ADC #16".to_string());
+        self.output.push("  STA $02".to_string());
+        self.output.push("  BCC Metatile_NextRow".to_string());
+        self.output.push("  INC $03".to_string());
+        self.output.push("Metatile_NextRow:".to_string());
+        self.output.push("  DEC $08".to_string());
+        self.output.push("  BNE Metatile_Row".to_string());
+        // The attribute bytes follow the grid as they are
+        self.output.push("  LDY #$00".to_string());
+        self.output.push("Metatile_Attrs:".to_string());
+        self.output.push("  LDA ($02), Y".to_string());
+        self.output.push("  STA $2007".to_string());
+        self.output.push("  INY".to_string());
+        self.output.push("  CPY #64".to_string());
+        self.output.push("  BNE Metatile_Attrs".to_string());
+        self.output.push("  RTS".to_string());
+    }
+
+    // Screen.Load(id) / Screen.LoadTo(id, nametable): A = id, X = nametable
+    fn generate_screen_load(&mut self, member: &str, args: &[Expression]) -> Result<(), String> {
+        if self.screens.is_empty() {
+            return Err(
+                "Screen.Load requires the screen table (compile with project assets)".to_string(),
+            );
+        }
+        self.uses_screen_load = true;
+        self.generate_expression(&args[0])?;
+        if member.eq_ignore_ascii_case("LoadTo") {
+            self.output.push("  PHA".to_string());
+            self.generate_expression(&args[1])?;
+            self.output.push("  TAX".to_string());
+            self.output.push("  PLA".to_string());
+        } else {
+            self.output.push("  LDX #0".to_string());
+        }
+        self.output.push("  JSR Runtime_Screen_Load".to_string());
+        Ok(())
+    }
+
+    // Palette.Set(entry, color) / Palette.Load(set) / Palette.FadeIn(frames)
+    // / Palette.FadeOut(frames) / Palette.Cycle(palette, start, length, speed)
+    fn generate_palette_call(&mut self, member: &str, args: &[Expression]) -> Result<(), String> {
+        if member.eq_ignore_ascii_case("Set") {
+            self.generate_expression(&args[1])?;
+            self.output.push("  PHA".to_string());
+            self.generate_expression(&args[0])?;
+            self.output.push("  AND #$1F".to_string());
+            self.output.push("  TAX".to_string());
+            self.output.push("  PLA".to_string());
+            self.output.push("  JSR Runtime_Palette_Set".to_string());
+        } else if member.eq_ignore_ascii_case("Load") {
+            // Sets are 32 bytes each in the palette section
+            let sets = self
+                .layout
+                .get(SECTION_PALETTE)
+                .map_or(1, |p| (p.size / 32).max(1));
+            let id = match &args[0] {
+                Expression::Integer(v) => Some(*v),
+                Expression::Identifier(c) => self.symbol_table.resolve(c).and_then(|s| s.value),
+                _ => None,
+            };
+            if let Some(id) = id.filter(|id| *id < 0 || *id as usize >= sets) {
+                return Err(format!(
+                    "Palette.Load set {} is out of range (0-{})",
+                    id,
+                    sets - 1
+                ));
+            }
+            self.generate_expression(&args[0])?;
+            self.output.push("  JSR Runtime_Palette_Load".to_string());
+        } else if member.eq_ignore_ascii_case("Cycle") {
+            // First entry = palette * 4 + start
+            self.generate_expression(&args[0])?;
+            self.output.push("  ASL".to_string());
+            self.output.push("  ASL".to_string());
+            self.output.push("  PHA".to_string());
+            self.generate_expression(&args[1])?;
+            self.output.push("  STA $00".to_string());
+            self.output.push("  PLA".to_string());
+            self.output.push("  CLC".to_string());
+            self.output.push("  ADC $00".to_string());
+            self.output.push("  PHA".to_string());
+            self.generate_expression(&args[2])?;
+            self.output.push("  PHA".to_string());
+            self.generate_expression(&args[3])?;
+            self.output.push("  TAX".to_string());
+            self.output.push("  PLA".to_string());
+            self.output.push("  STA $01".to_string());
+            self.output.push("  PLA".to_string());
+            self.output.push("  STA $00".to_string());
+            self.output.push("  TXA".to_string());
+            self.output.push("  JSR Runtime_Palette_Cycle".to_string());
+        } else {
+            let step = if member.eq_ignore_ascii_case("FadeIn") {
+                1
+            } else {
+                0xFF
+            };
+            self.generate_expression(&args[0])?;
+            self.output.push(format!("  LDX #${:02X}", step));
+            self.output.push("  JSR Runtime_Palette_Fade".to_string());
+        }
+        Ok(())
+    }
+
+    // HUD.Split(scanline [, HudPosition]): A = line, X = position
+    fn generate_hud_split(&mut self, args: &[Expression]) -> Result<(), String> {
+        if self.hud_tile.is_none() {
+            return Err(
+                "HUD.Split requires an opaque sprite-0 tile (compile with project assets)"
+                    .to_string(),
+            );
+        }
+        if let Some(position) = args.get(1) {
+            self.generate_expression(position)?;
+            self.output.push("  PHA".to_string());
+            self.generate_expression(&args[0])?;
+            self.output.push("  TAY".to_string());
+            self.output.push("  PLA".to_string());
+            self.output.push("  TAX".to_string());
+            self.output.push("  TYA".to_string());
+        } else {
+            self.generate_expression(&args[0])?;
+            self.output.push("  LDX #0".to_string());
+        }
+        self.output.push("  JSR Runtime_Hud_Split".to_string());
+        Ok(())
+    }
+
+    // Actors.*: UpdateAll / DrawAll / Clear run table loops; Spawn, Collide
+    // and Overlap return a slot ($FF for none) or a BOOL
+    fn generate_actors_call(
+        &mut self,
+        member: &str,
+        args: &[Expression],
+    ) -> Result<DataType, String> {
+        if member.eq_ignore_ascii_case("UpdateAll") {
+            self.output.push("  JSR Runtime_Actors_Update".to_string());
+        } else if member.eq_ignore_ascii_case("DrawAll") {
+            self.output.push("  JSR Runtime_Actors_Draw".to_string());
+        } else if member.eq_ignore_ascii_case("Clear") {
+            self.output.push("  JSR Runtime_Actors_Clear".to_string());
+        } else if member.eq_ignore_ascii_case("Despawn") {
+            self.generate_expression(&args[0])?;
+            self.output.push("  JSR Runtime_Actors_Despawn".to_string());
+        } else if member.eq_ignore_ascii_case("Spawn") {
+            let name = match &args[0] {
+                Expression::Identifier(name) if self.actors.iter().any(|a| &a.name == name) => {
+                    name.clone()
+                }
+                _ => return Err("Actors.Spawn first argument must be an ACTOR type".to_string()),
+            };
+            // x, y -> $0A-$0D
+            for arg in &args[1..] {
+                let dtype = self.generate_expression(arg)?;
+                self.output.push("  PHA".to_string()); // Low
+                if dtype == DataType::Word || dtype == DataType::Int {
+                    self.output.push("  TXA".to_string());
+                } else {
+                    self.output.push("  LDA #0".to_string());
+                }
+                self.output.push("  PHA".to_string()); // High
+            }
+            for zp in (0x0A..=0x0D).rev() {
+                self.output.push("  PLA".to_string());
+                self.output.push(format!("  STA ${:02X}", zp));
+            }
+            self.output.push(format!("  JSR Actor_Spawn_{}", name));
+        } else {
+            // Collide(slot, classes) / Overlap(slotA, slotB) -> $0A, $0B
+            self.generate_expression(&args[1])?;
+            self.output.push("  PHA".to_string());
+            self.generate_expression(&args[0])?;
+            self.output.push("  STA $0A".to_string());
+            self.output.push("  PLA".to_string());
+            self.output.push("  STA $0B".to_string());
+            if member.eq_ignore_ascii_case("Overlap") {
+                self.output.push("  JSR Runtime_Actors_Overlap".to_string());
+                return Ok(DataType::Bool);
+            }
+            self.output.push("  JSR Runtime_Actors_Collide".to_string());
+        }
+        Ok(DataType::Byte)
+    }
+
+    // Camera.Follow(x, y) / Camera.Update() / Camera.Mode(4 | 8)
+    fn generate_camera_call(&mut self, member: &str, args: &[Expression]) -> Result<(), String> {
+        if member.eq_ignore_ascii_case("Follow") {
+            // Target in world pixels, a word per axis
+            for (i, arg) in args.iter().enumerate() {
+                let addr = CAMERA_STATE + 2 * i as u16;
+                let dtype = self.generate_expression(arg)?;
+                if dtype == DataType::Byte {
+                    self.output.push("  LDX #0".to_string());
+                }
+                self.output.push(format!("  STA ${:04X}", addr));
+                self.output.push(format!("  STX ${:04X}", addr + 1));
+            }
+        } else if member.eq_ignore_ascii_case("Update") {
+            if self.layout.get(SECTION_WORLD).is_none() {
+                return Err(
+                    "Camera.Update requires a world map (compile with project assets)".to_string(),
+                );
+            }
+            self.uses_camera = true;
+            self.output.push("  JSR Runtime_Camera_Update".to_string());
+        } else {
+            self.generate_expression(&args[0])?;
+            self.output
+                .push(format!("  STA ${:04X}", CAMERA_STATE + 10));
+        }
+        Ok(())
+    }
+
+    // Scrolls the whole world: the camera follows its target at up to 8
+    // pixels per axis and frame, and each tile it crosses queues the column
+    // or row (with attributes) coming into view, read from the room's raw
+    // screen. The mirroring decides how far ahead that is: 64 columns exist
+    // with vertical (or four-screen) mirroring, 60 rows with horizontal.
+    fn generate_camera_helpers(&mut self) {
+        if !self.uses_camera {
+            return;
+        }
+        let target = CAMERA_STATE;
+        let cx = CAMERA_STATE + 4;
+        let cy = CAMERA_STATE + 6;
+        let room_y = CAMERA_STATE + 8;
+        let pixel_y = CAMERA_STATE + 9;
+        let mode = CAMERA_STATE + 10;
+        let placed = CAMERA_STATE + 11;
+        let dx = CAMERA_STATE + 12;
+        let dy = CAMERA_STATE + 13;
+        let count = CAMERA_STATE + 14;
+        // World section: width, height, format, then the raw room map
+        let world = self.layout.addr(SECTION_WORLD);
+        let rooms_lo = (world + 3) & 0xFF;
+        let rooms_hi = (world + 3) >> 8;
+        let table = self.layout.addr(SECTION_SCREEN_TABLE);
+        let screens = self.screens.len();
+        let extra_x = u8::from(self.mirroring != Mirroring::Horizontal);
+        let extra_y = u8::from(self.mirroring != Mirroring::Vertical);
+        let ahead_x = 31 + extra_x;
+        let ahead_y = 29 + extra_y;
+        let columns = 32 + extra_x;
+
+        self.output.push("; --- Camera Helpers ---".to_string());
+        // Runtime_Camera_Update: eases the camera toward the Camera.Follow target
+        // (centred, clamped to the world) and queues what comes into view
+        self.output.push("Runtime_Camera_Update:".to_string());
+        // Desired x = target x - 128, clamped to 0..(W - 1) * 256 -> $00/$01
+        self.output.push("  SEC".to_string());
+        self.output.push(format!("  LDA ${:04X}", target));
+        self.output.push("  SBC #128".to_string());
+        self.output.push("  STA $00".to_string());
+        self.output.push(format!("  LDA ${:04X}", target + 1));
+        self.output.push("  SBC #0".to_string());
+        self.output.push("  STA $01".to_string());
+        self.output.push("  BCS Camera_XLow".to_string());
+        self.output.push("  LDA #0".to_string());
+        self.output.push("  STA $00".to_string());
+        self.output.push("  STA $01".to_string());
+        self.output.push("Camera_XLow:".to_string());
+        self.output.push(format!("  LDX ${:04X}", world));
+        self.output.push("  DEX".to_string());
+        self.output.push("  LDA #0".to_string());
+        self.output.push("  CMP $00".to_string());
+        self.output.push("  TXA".to_string());
+        self.output.push("  SBC $01".to_string());
+        self.output.push("  BCS Camera_XDone".to_string());
+        self.output.push("  LDA #0".to_string());
+        self.output.push("  STA $00".to_string());
+        self.output.push("  STX $01".to_string());
+        self.output.push("Camera_XDone:".to_string());
+        // Desired y = target y - 120 -> $02/$03
+        self.output.push("  SEC".to_string());
+        self.output.push(format!("  LDA ${:04X}", target + 2));
+        self.output.push("  SBC #120".to_string());
+        self.output.push("  STA $02".to_string());
+        self.output.push(format!("  LDA ${:04X}", target + 3));
+        self.output.push("  SBC #0".to_string());
+        self.output.push("  STA $03".to_string());
+        self.output.push("  BCS Camera_YLow".to_string());
+        self.output.push("  LDA #0".to_string());
+        self.output.push("  STA $02".to_string());
+        self.output.push("  STA $03".to_string());
+        self.output.push("Camera_YLow:".to_string());
+        // Rooms are 240 pixels tall: (H - 1) * 256 - (H - 1) * 16 -> $04/$05
+        self.output.push(format!("  LDX ${:04X}", world + 1));
+        self.output.push("  DEX".to_string());
+        self.output.push("  STX $05".to_string());
+        self.output.push("  TXA".to_string());
+        self.output.push("  ASL".to_string());
+        self.output.push("  ASL".to_string());
+        self.output.push("  ASL".to_string());
+        self.output.push("  ASL".to_string());
+        self.output.push("  STA $06".to_string());
+        self.output.push("  TXA".to_string());
+        self.output.push("  LSR".to_string());
+        self.output.push("  LSR".to_string());
+        self.output.push("  LSR".to_string());
+        self.output.push("  LSR".to_string());
+        self.output.push("  STA $07".to_string());
+        self.output.push("  SEC".to_string());
+        self.output.push("  LDA #0".to_string());
+        self.output.push("  SBC $06".to_string());
+        self.output.push("  STA $04".to_string());
+        self.output.push("  LDA $05".to_string());
+        self.output.push("  SBC $07".to_string());
+        self.output.push("  STA $05".to_string());
+        self.output.push("  LDA $04".to_string());
+        self.output.push("  CMP $02".to_string());
+        self.output.push("  LDA $05".to_string());
+        self.output.push("  SBC $03".to_string());
+        self.output.push("  BCS Camera_YDone".to_string());
+        self.output.push("  LDA $04".to_string());
+        self.output.push("  STA $02".to_string());
+        self.output.push("  LDA $05".to_string());
+        self.output.push("  STA $03".to_string());
+        self.output.push("Camera_YDone:".to_string());
+        self.output.push(format!("  LDA ${:04X}", placed));
+        self.output.push("  BNE Camera_Step".to_string());
+        self.output.push("  JMP Camera_Snap".to_string());
+        self.output.push("Camera_Step:".to_string());
+        // At most 8 pixels per axis and frame, so one new column or row at a time
+        self.output.push("  SEC".to_string());
+        self.output.push("  LDA $00".to_string());
+        self.output.push(format!("  SBC ${:04X}", cx));
+        self.output.push("  STA $06".to_string());
+        self.output.push("  LDA $01".to_string());
+        self.output.push(format!("  SBC ${:04X}", cx + 1));
+        self.output.push("  JSR Camera_Clamp".to_string());
+        self.output.push(format!("  STA ${:04X}", dx));
+        self.output.push("  SEC".to_string());
+        self.output.push("  LDA $02".to_string());
+        self.output.push(format!("  SBC ${:04X}", cy));
+        self.output.push("  STA $06".to_string());
+        self.output.push("  LDA $03".to_string());
+        self.output.push(format!("  SBC ${:04X}", cy + 1));
+        self.output.push("  JSR Camera_Clamp".to_string());
+        self.output.push(format!("  STA ${:04X}", dy));
+        // 4-way: y waits while x moves
+        self.output.push(format!("  LDA ${:04X}", mode));
+        self.output.push("  CMP #4".to_string());
+        self.output.push("  BNE Camera_Move".to_string());
+        self.output.push(format!("  LDA ${:04X}", dx));
+        self.output.push("  BEQ Camera_Move".to_string());
+        self.output.push("  LDA #0".to_string());
+        self.output.push(format!("  STA ${:04X}", dy));
+        self.output.push("Camera_Move:".to_string());
+        self.output.push("  JSR Camera_MoveX".to_string());
+        self.output.push("  JSR Camera_MoveY".to_string());
+        self.output.push("  JMP Camera_Scroll".to_string());
+
+        // Camera_Clamp: A/$06 = signed difference (high/low) -> A = -8..8
+        self.output.push("Camera_Clamp:".to_string());
+        self.output.push("  CMP #$80".to_string());
+        self.output.push("  BCS Camera_ClampNeg".to_string());
+        self.output.push("  CMP #0".to_string());
+        self.output.push("  BNE Camera_ClampPos".to_string());
+        self.output.push("  LDA $06".to_string());
+        self.output.push("  CMP #9".to_string());
+        self.output.push("  BCC Camera_ClampDone".to_string());
+        self.output.push("Camera_ClampPos:".to_string());
+        self.output.push("  LDA #8".to_string());
+        self.output.push("  RTS".to_string());
+        self.output.push("Camera_ClampNeg:".to_string());
+        self.output.push("  CMP #$FF".to_string());
+        self.output.push("  BNE Camera_ClampMin".to_string());
+        self.output.push("  LDA $06".to_string());
+        self.output.push("  CMP #$F8".to_string());
+        self.output.push("  BCS Camera_ClampDone".to_string());
+        self.output.push("Camera_ClampMin:".to_string());
+        self.output.push("  LDA #$F8".to_string());
+        self.output.push("Camera_ClampDone:".to_string());
+        self.output.push("  RTS".to_string());
+
+        self.output.push("Camera_MoveX:".to_string());
+        self.output.push(format!("  LDA ${:04X}", dx));
+        self.output.push("  BNE Camera_MoveXGo".to_string());
+        self.output.push("  RTS".to_string());
+        self.output.push("Camera_MoveXGo:".to_string());
+        self.output.push(format!("  LDA ${:04X}", cx));
+        self.output.push("  STA $07".to_string());
+        self.output.push("  CLC".to_string());
+        self.output.push(format!("  ADC ${:04X}", dx));
+        self.output.push(format!("  STA ${:04X}", cx));
+        self.output.push(format!("  LDA ${:04X}", dx));
+        self.output.push("  AND #$80".to_string());
+        self.output.push("  BEQ Camera_MoveXHigh".to_string());
+        self.output.push("  LDA #$FF".to_string());
+        self.output.push("Camera_MoveXHigh:".to_string());
+        self.output.push(format!("  ADC ${:04X}", cx + 1));
+        self.output.push(format!("  STA ${:04X}", cx + 1));
+        self.output.push(format!("  LDA ${:04X}", cx));
+        self.output.push("  EOR $07".to_string());
+        self.output.push("  AND #$F8".to_string());
+        self.output.push("  BNE Camera_MoveXTile".to_string());
+        self.output.push("  RTS".to_string());
+        // Crossed a tile: the column entering on the leading edge, split into
+        // room x ($08) and column (0-31)
+        self.output.push("Camera_MoveXTile:".to_string());
+        self.output.push(format!("  LDA ${:04X}", cx + 1));
+        self.output.push("  STA $08".to_string());
+        self.output.push(format!("  LDA ${:04X}", cx));
+        self.output.push("  LSR".to_string());
+        self.output.push("  LSR".to_string());
+        self.output.push("  LSR".to_string());
+        self.output.push(format!("  LDY ${:04X}", dx));
+        self.output.push("  BMI Camera_MoveXLeft".to_string());
+        self.output.push("  CLC".to_string());
+        self.output.push(format!("  ADC #{}", ahead_x));
+        self.output.push("  CMP #32".to_string());
+        self.output.push("  BCC Camera_MoveXLeft".to_string());
+        self.output.push("  SBC #32".to_string());
+        self.output.push("  INC $08".to_string());
+        self.output.push("Camera_MoveXLeft:".to_string());
+        self.output.push("  STA $0C".to_string());
+        self.output.push("  JMP Camera_Column".to_string());
+
+        self.output.push("Camera_MoveY:".to_string());
+        self.output.push(format!("  LDA ${:04X}", dy));
+        self.output.push("  BNE Camera_MoveYGo".to_string());
+        self.output.push("  RTS".to_string());
+        self.output.push("Camera_MoveYGo:".to_string());
+        self.output.push("  CLC".to_string());
+        self.output.push(format!("  LDA ${:04X}", cy));
+        self.output.push(format!("  ADC ${:04X}", dy));
+        self.output.push(format!("  STA ${:04X}", cy));
+        self.output.push(format!("  LDA ${:04X}", dy));
+        self.output.push("  AND #$80".to_string());
+        self.output.push("  BEQ Camera_MoveYHigh".to_string());
+        self.output.push("  LDA #$FF".to_string());
+        self.output.push("Camera_MoveYHigh:".to_string());
+        self.output.push(format!("  ADC ${:04X}", cy + 1));
+        self.output.push(format!("  STA ${:04X}", cy + 1));
+        // The same step inside the room, wrapping at 240
+        self.output.push(format!("  LDA ${:04X}", pixel_y));
+        self.output.push("  LSR".to_string());
+        self.output.push("  LSR".to_string());
+        self.output.push("  LSR".to_string());
+        self.output.push("  STA $0D".to_string());
+        self.output.push(format!("  LDA ${:04X}", dy));
+        self.output.push("  BMI Camera_MoveUp".to_string());
+        self.output.push("  CLC".to_string());
+        self.output.push(format!("  ADC ${:04X}", pixel_y));
+        self.output.push("  CMP #240".to_string());
+        self.output.push("  BCC Camera_MoveYStore".to_string());
+        self.output.push("  SBC #240".to_string());
+        self.output.push(format!("  INC ${:04X}", room_y));
+        self.output.push("  JMP Camera_MoveYStore".to_string());
+        self.output.push("Camera_MoveUp:".to_string());
+        self.output.push("  CLC".to_string());
+        self.output.push(format!("  ADC ${:04X}", pixel_y));
+        self.output.push("  BCS Camera_MoveYStore".to_string());
+        self.output.push("  ADC #240".to_string());
+        self.output.push(format!("  DEC ${:04X}", room_y));
+        self.output.push("Camera_MoveYStore:".to_string());
+        self.output.push(format!("  STA ${:04X}", pixel_y));
+        self.output.push("  LSR".to_string());
+        self.output.push("  LSR".to_string());
+        self.output.push("  LSR".to_string());
+        self.output.push("  CMP $0D".to_string());
+        self.output.push("  BNE Camera_MoveYTile".to_string());
+        self.output.push("  RTS".to_string());
+        self.output.push("Camera_MoveYTile:".to_string());
+        self.output.push("  STA $0D".to_string());
+        self.output.push(format!("  LDA ${:04X}", room_y));
+        self.output.push("  STA $09".to_string());
+        self.output.push(format!("  LDA ${:04X}", dy));
+        self.output.push("  BMI Camera_MoveYRow".to_string());
+        self.output.push("  LDA $0D".to_string());
+        self.output.push("  CLC".to_string());
+        self.output.push(format!("  ADC #{}", ahead_y));
+        self.output.push("  CMP #30".to_string());
+        self.output.push("  BCC Camera_MoveYDown".to_string());
+        self.output.push("  SBC #30".to_string());
+        self.output.push("  INC $09".to_string());
+        self.output.push("Camera_MoveYDown:".to_string());
+        self.output.push("  STA $0D".to_string());
+        self.output.push("Camera_MoveYRow:".to_string());
+        self.output.push("  JMP Camera_Row".to_string());
+
+        // Camera_Column: $08 = room x, $0C = column; the rows in view, split at
+        // the room boundary
+        self.output.push("Camera_Column:".to_string());
+        self.output.push("  LDA $08".to_string());
+        self.output.push(format!("  CMP ${:04X}", world));
+        self.output.push("  BCS Camera_ColumnDone".to_string());
+        self.output.push(format!("  LDA ${:04X}", room_y));
+        self.output.push("  STA $09".to_string());
+        self.output.push(format!("  LDA ${:04X}", pixel_y));
+        self.output.push("  LSR".to_string());
+        self.output.push("  LSR".to_string());
+        self.output.push("  LSR".to_string());
+        self.output.push("  STA $0D".to_string());
+        self.output.push("  LDA #30".to_string());
+        self.output.push("  SEC".to_string());
+        self.output.push("  SBC $0D".to_string());
+        self.output.push("  STA $0E".to_string());
+        self.output.push("  JSR Camera_ColumnSeg".to_string());
+        self.output.push(format!("  LDA ${:04X}", pixel_y));
+        self.output.push("  LSR".to_string());
+        self.output.push("  LSR".to_string());
+        self.output.push("  LSR".to_string());
+        self.output.push("  CLC".to_string());
+        self.output.push(format!("  ADC #{}", extra_y));
+        self.output.push("  BEQ Camera_ColumnDone".to_string());
+        self.output.push("  STA $0E".to_string());
+        self.output.push("  LDA #0".to_string());
+        self.output.push("  STA $0D".to_string());
+        self.output.push("  INC $09".to_string());
+        self.output.push("  LDA $09".to_string());
+        self.output.push(format!("  CMP ${:04X}", world + 1));
+        self.output.push("  BCS Camera_ColumnDone".to_string());
+        self.output.push("  JMP Camera_ColumnSeg".to_string());
+        self.output.push("Camera_ColumnDone:".to_string());
+        self.output.push("  RTS".to_string());
+
+        // Camera_Row: $09 = room y, $0D = row; the columns in view, split at the
+        // room boundary
+        self.output.push("Camera_Row:".to_string());
+        self.output.push("  LDA $09".to_string());
+        self.output.push(format!("  CMP ${:04X}", world + 1));
+        self.output.push("  BCS Camera_RowDone".to_string());
+        self.output.push(format!("  LDA ${:04X}", cx + 1));
+        self.output.push("  STA $08".to_string());
+        self.output.push(format!("  LDA ${:04X}", cx));
+        self.output.push("  LSR".to_string());
+        self.output.push("  LSR".to_string());
+        self.output.push("  LSR".to_string());
+        self.output.push("  STA $0C".to_string());
+        self.output.push("  LDA #32".to_string());
+        self.output.push("  SEC".to_string());
+        self.output.push("  SBC $0C".to_string());
+        self.output.push("  STA $0E".to_string());
+        self.output.push("  JSR Camera_RowSeg".to_string());
+        self.output.push(format!("  LDA ${:04X}", cx));
+        self.output.push("  LSR".to_string());
+        self.output.push("  LSR".to_string());
+        self.output.push("  LSR".to_string());
+        self.output.push("  CLC".to_string());
+        self.output.push(format!("  ADC #{}", extra_x));
+        self.output.push("  BEQ Camera_RowDone".to_string());
+        self.output.push("  STA $0E".to_string());
+        self.output.push("  LDA #0".to_string());
+        self.output.push("  STA $0C".to_string());
+        self.output.push("  INC $08".to_string());
+        self.output.push("  LDA $08".to_string());
+        self.output.push(format!("  CMP ${:04X}", world));
+        self.output.push("  BCS Camera_RowDone".to_string());
+        self.output.push("  JMP Camera_RowSeg".to_string());
+        self.output.push("Camera_RowDone:".to_string());
+        self.output.push("  RTS".to_string());
+
+        // Camera_Target: $08/$09 = room x/y, $0C = column, $0D = row. Returns
+        // $0A/$0B = source tile, $02/$03 = the room's attribute table, $04/$05 =
+        // PPU address, $07 = nametable high byte and $0F = $40 (fill with zeros)
+        // when the room is empty
+        self.output.push("Camera_Target:".to_string());
+        self.output.push("  LDA $08".to_string());
+        self.output.push("  STA $0A".to_string());
+        self.output.push("  LDA #0".to_string());
+        self.output.push("  STA $0B".to_string());
+        self.output.push("  LDY $09".to_string());
+        self.output.push("  BEQ Camera_TargetRoom".to_string());
+        self.output.push("Camera_TargetMul:".to_string());
+        self.output.push("  CLC".to_string());
+        self.output.push("  LDA $0A".to_string());
+        self.output.push(format!("  ADC ${:04X}", world));
+        self.output.push("  STA $0A".to_string());
+        self.output.push("  BCC Camera_TargetNext".to_string());
+        self.output.push("  INC $0B".to_string());
+        self.output.push("Camera_TargetNext:".to_string());
+        self.output.push("  DEY".to_string());
+        self.output.push("  BNE Camera_TargetMul".to_string());
+        self.output.push("Camera_TargetRoom:".to_string());
+        self.output.push("  CLC".to_string());
+        self.output.push("  LDA $0A".to_string());
+        self.output.push(format!("  ADC #{}", rooms_lo));
+        self.output.push("  STA $0A".to_string());
+        self.output.push("  LDA $0B".to_string());
+        self.output.push(format!("  ADC #{}", rooms_hi));
+        self.output.push("  STA $0B".to_string());
+        self.output.push("  LDY #0".to_string());
+        self.output.push("  LDA ($0A), Y".to_string());
+        self.output.push("  LDX #$40".to_string());
+        self.output.push(format!("  CMP #{}", screens));
+        self.output.push("  BCS Camera_TargetEmpty".to_string());
+        self.generate_screen_bank_switch();
+        self.output.push("  STA $0A".to_string());
+        self.output.push("  ASL".to_string());
+        self.output.push("  ADC $0A".to_string());
+        self.output.push("  TAY".to_string());
+        self.output.push(format!("  LDA ${:04X}, Y", table));
+        self.output.push("  STA $0A".to_string());
+        self.output.push("  CLC".to_string());
+        self.output.push("  ADC #$C0".to_string());
+        self.output.push("  STA $02".to_string());
+        self.output.push(format!("  LDA ${:04X}, Y", table + 1));
+        self.output.push("  STA $0B".to_string());
+        self.output.push("  ADC #$03".to_string());
+        self.output.push("  STA $03".to_string());
+        self.output.push("  LDX #0".to_string());
+        self.output.push("Camera_TargetEmpty:".to_string());
+        self.output.push("  STX $0F".to_string());
+        // Nametable (room y & 1) * 2 + (room x & 1), left to the mirroring
+        self.output.push("  LDA $09".to_string());
+        self.output.push("  AND #1".to_string());
+        self.output.push("  ASL".to_string());
+        self.output.push("  STA $07".to_string());
+        self.output.push("  LDA $08".to_string());
+        self.output.push("  AND #1".to_string());
+        self.output.push("  ORA $07".to_string());
+        self.output.push("  ASL".to_string());
+        self.output.push("  ASL".to_string());
+        self.output.push("  ORA #$20".to_string());
+        self.output.push("  STA $07".to_string());
+        // Offset row * 32 + column
+        self.output.push("  LDA $0D".to_string());
+        self.output.push("  AND #7".to_string());
+        self.output.push("  ASL".to_string());
+        self.output.push("  ASL".to_string());
+        self.output.push("  ASL".to_string());
+        self.output.push("  ASL".to_string());
+        self.output.push("  ASL".to_string());
+        self.output.push("  ORA $0C".to_string());
+        self.output.push("  STA $04".to_string());
+        self.output.push("  LDA $0D".to_string());
+        self.output.push("  LSR".to_string());
+        self.output.push("  LSR".to_string());
+        self.output.push("  LSR".to_string());
+        self.output.push("  STA $05".to_string());
+        self.output.push("  CLC".to_string());
+        self.output.push("  LDA $0A".to_string());
+        self.output.push("  ADC $04".to_string());
+        self.output.push("  STA $0A".to_string());
+        self.output.push("  LDA $0B".to_string());
+        self.output.push("  ADC $05".to_string());
+        self.output.push("  STA $0B".to_string());
+        self.output.push("  LDA $05".to_string());
+        self.output.push("  ORA $07".to_string());
+        self.output.push("  STA $05".to_string());
+        self.output.push("  RTS".to_string());
+
+        // Camera_ColumnSeg: $0E rows from row $0D of one room, as a +32 entry
+        self.output.push("Camera_ColumnSeg:".to_string());
+        self.output.push("  JSR Camera_Target".to_string());
+        self.output.push("  LDA $0E".to_string());
+        self.output.push("  ORA #$80".to_string());
+        self.output.push("  ORA $0F".to_string());
+        self.output.push("  JSR PpuQueue_Reserve".to_string());
+        self.output.push("  LDA $05".to_string());
+        self.output.push("  STA $0101, X".to_string());
+        self.output.push("  LDA $04".to_string());
+        self.output.push("  STA $0102, X".to_string());
+        self.output.push("  LDA $0F".to_string());
+        self.output.push("  BEQ Camera_ColumnCopy".to_string());
+        self.output.push("  LDA #0".to_string());
+        self.output.push("  STA $0103, X".to_string());
+        self.output.push("  INX".to_string());
+        self.output.push("  JMP Camera_ColumnQueued".to_string());
+        self.output.push("Camera_ColumnCopy:".to_string());
+        self.output.push("  LDA $0E".to_string());
+        self.output.push("  STA $06".to_string());
+        self.output.push("  LDY #0".to_string());
+        self.output.push("Camera_ColumnLoop:".to_string());
+        self.output.push("  LDA ($0A), Y".to_string());
+        self.output.push("  STA $0103, X".to_string());
+        self.output.push("  INX".to_string());
+        self.output.push("  LDA $0A".to_string());
+        self.output.push("  CLC".to_string());
+        self.output.push("  ADC #32".to_string());
+        self.output.push("  STA $0A".to_string());
+        self.output.push("  BCC Camera_ColumnNext".to_string());
+        self.output.push("  INC $0B".to_string());
+        self.output.push("Camera_ColumnNext:".to_string());
+        self.output.push("  DEC $06".to_string());
+        self.output.push("  BNE Camera_ColumnLoop".to_string());
+        self.output.push("Camera_ColumnQueued:".to_string());
+        self.output.push("  JSR PpuQueue_Commit".to_string());
+        // Attributes only as the column enters a 4-column group from either side
+        self.output.push("  LDA $0C".to_string());
+        self.output.push("  AND #3".to_string());
+        self.output.push("  BEQ Camera_ColumnAttr".to_string());
+        self.output.push("  CMP #3".to_string());
+        self.output.push("  BNE Camera_ColumnSegDone".to_string());
+        self.output.push("Camera_ColumnAttr:".to_string());
+        self.output.push("  LDA $0D".to_string());
+        self.output.push("  LSR".to_string());
+        self.output.push("  LSR".to_string());
+        self.output.push("  STA $01".to_string());
+        self.output.push("  LDA $0D".to_string());
+        self.output.push("  CLC".to_string());
+        self.output.push("  ADC $0E".to_string());
+        self.output.push("  SEC".to_string());
+        self.output.push("  SBC #1".to_string());
+        self.output.push("  LSR".to_string());
+        self.output.push("  LSR".to_string());
+        self.output.push("  STA $00".to_string());
+        self.output.push("  LDA $0C".to_string());
+        self.output.push("  LSR".to_string());
+        self.output.push("  LSR".to_string());
+        self.output.push("  STA $06".to_string());
+        self.output.push("Camera_ColumnAttrLoop:".to_string());
+        self.output.push("  LDA $01".to_string());
+        self.output.push("  ASL".to_string());
+        self.output.push("  ASL".to_string());
+        self.output.push("  ASL".to_string());
+        self.output.push("  ORA $06".to_string());
+        self.output.push("  STA $04".to_string());
+        self.output.push("  LDA #1".to_string());
+        self.output.push("  ORA $0F".to_string());
+        self.output.push("  JSR PpuQueue_Reserve".to_string());
+        self.output.push("  LDA $07".to_string());
+        self.output.push("  ORA #$03".to_string());
+        self.output.push("  STA $0101, X".to_string());
+        self.output.push("  LDA $04".to_string());
+        self.output.push("  ORA #$C0".to_string());
+        self.output.push("  STA $0102, X".to_string());
+        self.output.push("  LDA #0".to_string());
+        self.output.push("  LDY $0F".to_string());
+        self.output.push("  BNE Camera_ColumnAttrByte".to_string());
+        self.output.push("  LDY $04".to_string());
+        self.output.push("  LDA ($02), Y".to_string());
+        self.output.push("Camera_ColumnAttrByte:".to_string());
+        self.output.push("  STA $0103, X".to_string());
+        self.output.push("  INX".to_string());
+        self.output.push("  JSR PpuQueue_Commit".to_string());
+        self.output.push("  INC $01".to_string());
+        self.output.push("  LDA $00".to_string());
+        self.output.push("  CMP $01".to_string());
+        self.output.push("  BCS Camera_ColumnAttrLoop".to_string());
+        self.output.push("Camera_ColumnSegDone:".to_string());
+        self.output.push("  RTS".to_string());
+
+        // Camera_RowSeg: $0E columns from column $0C of one room's row $0D
+        self.output.push("Camera_RowSeg:".to_string());
+        self.output.push("  JSR Camera_Target".to_string());
+        self.output.push("  LDA $0E".to_string());
+        self.output.push("  ORA $0F".to_string());
+        self.output.push("  JSR PpuQueue_Reserve".to_string());
+        self.output.push("  LDA $05".to_string());
+        self.output.push("  STA $0101, X".to_string());
+        self.output.push("  LDA $04".to_string());
+        self.output.push("  STA $0102, X".to_string());
+        self.output.push("  LDY #0".to_string());
+        self.output.push("  LDA $0F".to_string());
+        self.output.push("  BEQ Camera_RowLoop".to_string());
+        self.output.push("  TYA".to_string());
+        self.output.push("  STA $0103, X".to_string());
+        self.output.push("  INX".to_string());
+        self.output.push("  JMP Camera_RowQueued".to_string());
+        self.output.push("Camera_RowLoop:".to_string());
+        self.output.push("  LDA ($0A), Y".to_string());
+        self.output.push("  STA $0103, X".to_string());
+        self.output.push("  INX".to_string());
+        self.output.push("  INY".to_string());
+        self.output.push("  CPY $0E".to_string());
+        self.output.push("  BNE Camera_RowLoop".to_string());
+        self.output.push("Camera_RowQueued:".to_string());
+        self.output.push("  JSR PpuQueue_Commit".to_string());
+        // Attributes only as the row enters a 4-row group (the last group of a
+        // room has two rows)
+        self.output.push("  LDA $0D".to_string());
+        self.output.push("  CMP #29".to_string());
+        self.output.push("  BEQ Camera_RowAttr".to_string());
+        self.output.push("  AND #3".to_string());
+        self.output.push("  BEQ Camera_RowAttr".to_string());
+        self.output.push("  CMP #3".to_string());
+        self.output.push("  BNE Camera_RowSegDone".to_string());
+        self.output.push("Camera_RowAttr:".to_string());
+        self.output.push("  LDA $0C".to_string());
+        self.output.push("  LSR".to_string());
+        self.output.push("  LSR".to_string());
+        self.output.push("  STA $06".to_string());
+        self.output.push("  LDA $0C".to_string());
+        self.output.push("  CLC".to_string());
+        self.output.push("  ADC $0E".to_string());
+        self.output.push("  SEC".to_string());
+        self.output.push("  SBC #1".to_string());
+        self.output.push("  LSR".to_string());
+        self.output.push("  LSR".to_string());
+        self.output.push("  SEC".to_string());
+        self.output.push("  SBC $06".to_string());
+        self.output.push("  CLC".to_string());
+        self.output.push("  ADC #1".to_string());
+        self.output.push("  STA $00".to_string());
+        self.output.push("  LDA $0D".to_string());
+        self.output.push("  LSR".to_string());
+        self.output.push("  LSR".to_string());
+        self.output.push("  ASL".to_string());
+        self.output.push("  ASL".to_string());
+        self.output.push("  ASL".to_string());
+        self.output.push("  ORA $06".to_string());
+        self.output.push("  STA $04".to_string());
+        self.output.push("  LDA $00".to_string());
+        self.output.push("  ORA $0F".to_string());
+        self.output.push("  JSR PpuQueue_Reserve".to_string());
+        self.output.push("  LDA $07".to_string());
+        self.output.push("  ORA #$03".to_string());
+        self.output.push("  STA $0101, X".to_string());
+        self.output.push("  LDA $04".to_string());
+        self.output.push("  ORA #$C0".to_string());
+        self.output.push("  STA $0102, X".to_string());
+        self.output.push("  LDA $0F".to_string());
+        self.output.push("  BEQ Camera_RowAttrCopy".to_string());
+        self.output.push("  LDA #0".to_string());
+        self.output.push("  STA $0103, X".to_string());
+        self.output.push("  INX".to_string());
+        self.output.push("  JMP Camera_RowAttrQueued".to_string());
+        self.output.push("Camera_RowAttrCopy:".to_string());
+        self.output.push("  LDY $04".to_string());
+        self.output.push("Camera_RowAttrLoop:".to_string());
+        self.output.push("  LDA ($02), Y".to_string());
+        self.output.push("  STA $0103, X".to_string());
+        self.output.push("  INX".to_string());
+        self.output.push("  INY".to_string());
+        self.output.push("  DEC $00".to_string());
+        self.output.push("  BNE Camera_RowAttrLoop".to_string());
+        self.output.push("Camera_RowAttrQueued:".to_string());
+        self.output.push("  JSR PpuQueue_Commit".to_string());
+        self.output.push("Camera_RowSegDone:".to_string());
+        self.output.push("  RTS".to_string());
+
+        // Camera_Snap: first update; jumps to the desired position and queues
+        // every column in view
+        self.output.push("Camera_Snap:".to_string());
+        self.output.push("  LDA $00".to_string());
+        self.output.push(format!("  STA ${:04X}", cx));
+        self.output.push("  LDA $01".to_string());
+        self.output.push(format!("  STA ${:04X}", cx + 1));
+        self.output.push("  LDA $02".to_string());
+        self.output.push(format!("  STA ${:04X}", cy));
+        self.output.push("  LDA $03".to_string());
+        self.output.push(format!("  STA ${:04X}", cy + 1));
+        self.output.push("  LDX #0".to_string());
+        self.output.push("Camera_SnapDiv:".to_string());
+        self.output.push("  LDA $02".to_string());
+        self.output.push("  SEC".to_string());
+        self.output.push("  SBC #240".to_string());
+        self.output.push("  TAY".to_string());
+        self.output.push("  LDA $03".to_string());
+        self.output.push("  SBC #0".to_string());
+        self.output.push("  BCC Camera_SnapRoom".to_string());
+        self.output.push("  STA $03".to_string());
+        self.output.push("  STY $02".to_string());
+        self.output.push("  INX".to_string());
+        self.output.push("  JMP Camera_SnapDiv".to_string());
+        self.output.push("Camera_SnapRoom:".to_string());
+        self.output.push(format!("  STX ${:04X}", room_y));
+        self.output.push("  LDA $02".to_string());
+        self.output.push(format!("  STA ${:04X}", pixel_y));
+        self.output.push(format!("  LDA #{}", columns));
+        self.output.push(format!("  STA ${:04X}", count));
+        self.output.push(format!("  LDA ${:04X}", cx + 1));
+        self.output.push("  STA $08".to_string());
+        self.output.push(format!("  LDA ${:04X}", cx));
+        self.output.push("  LSR".to_string());
+        self.output.push("  LSR".to_string());
+        self.output.push("  LSR".to_string());
+        self.output.push("  STA $0C".to_string());
+        self.output.push("Camera_SnapLoop:".to_string());
+        self.output.push("  JSR Camera_Column".to_string());
+        self.output.push("  INC $0C".to_string());
+        self.output.push("  LDA $0C".to_string());
+        self.output.push("  CMP #32".to_string());
+        self.output.push("  BCC Camera_SnapNext".to_string());
+        self.output.push("  LDA #0".to_string());
+        self.output.push("  STA $0C".to_string());
+        self.output.push("  INC $08".to_string());
+        self.output.push("Camera_SnapNext:".to_string());
+        self.output.push(format!("  DEC ${:04X}", count));
+        self.output.push("  BNE Camera_SnapLoop".to_string());
+        self.output.push("  LDA #1".to_string());
+        self.output.push(format!("  STA ${:04X}", placed));
+        // Camera_Scroll: scroll registers and PPUCTRL nametable bits for the NMI
+        self.output.push("Camera_Scroll:".to_string());
+        self.output.push(format!("  LDA ${:04X}", cx));
+        self.output.push("  STA $E0".to_string());
+        self.output.push(format!("  LDA ${:04X}", pixel_y));
+        self.output.push("  STA $E1".to_string());
+        self.output.push(format!("  LDA ${:04X}", room_y));
+        self.output.push("  AND #1".to_string());
+        self.output.push("  ASL".to_string());
+        self.output.push("  STA $07".to_string());
+        self.output.push(format!("  LDA ${:04X}", cx + 1));
+        self.output.push("  AND #1".to_string());
+        self.output.push("  ORA $07".to_string());
+        self.output.push("  STA $07".to_string());
+        self.output.push("  LDA $F8".to_string());
+        self.output.push("  AND #$FC".to_string());
+        self.output.push("  ORA $07".to_string());
+        self.output.push("  STA $F8".to_string());
+        self.output.push("  RTS".to_string());
+    }
+
+    // World.Enter(roomX, roomY) / World.Transition(direction [, speed])
+    fn generate_world_call(&mut self, member: &str, args: &[Expression]) -> Result<(), String> {
+        if member.eq_ignore_ascii_case("SpawnRoomObjects") {
+            return self.generate_room_spawn(args);
+        }
+        if self.layout.get(SECTION_WORLD).is_none() || self.screens.is_empty() {
+            return Err(format!(
+                "World.{} requires a world map (compile with project assets)",
+                member
+            ));
+        }
+        self.uses_world = true;
+        self.uses_screen_load = true;
+        self.generate_expression(&args[0])?;
+        if member.eq_ignore_ascii_case("Enter") {
+            self.output.push("  PHA".to_string());
+            self.generate_expression(&args[1])?;
+            self.output.push(format!("  STA ${:04X}", WORLD_STATE + 1));
+            self.output.push("  PLA".to_string());
+            self.output.push(format!("  STA ${:04X}", WORLD_STATE));
+            self.output.push("  JSR Runtime_World_Enter".to_string());
+        } else if let Some(Expression::Integer(speed)) = args.get(1) {
+            // Scrolling reuses the camera's column and row streaming
+            self.uses_world_scroll = true;
+            self.uses_camera = true;
+            self.output.push(format!("  LDX #{}", speed));
+            self.output.push("  JSR Runtime_World_Scroll".to_string());
+        } else {
+            self.output.push("  JSR Runtime_World_Flip".to_string());
+        }
+        Ok(())
+    }
+
+    // World.SpawnRoomObjects(roomX, roomY): the actors placed in that room
+    fn generate_room_spawn(&mut self, args: &[Expression]) -> Result<(), String> {
+        if self.layout.get(SECTION_WORLD_OBJECTS).is_none() {
+            return Err(
+                "World.SpawnRoomObjects requires a world map (compile with project assets)"
+                    .to_string(),
+            );
+        }
+        self.uses_room_spawns = true;
+        self.generate_expression(&args[0])?;
+        self.output.push("  PHA".to_string());
+        self.generate_expression(&args[1])?;
+        self.output.push("  STA $09".to_string());
+        self.output.push("  PLA".to_string());
+        self.output.push("  STA $08".to_string());
+        self.output.push("  JSR Runtime_World_Spawn".to_string());
+        Ok(())
+    }
+
+    // Room-to-room movement through the WORLD map. Room (x, y) is shown in
+    // nametable (y & 1) * 2 + (x & 1), the layout Camera.Update scrolls
+    // through. A flip reloads the screen with Screen.Load; a scrolling
+    // transition parks the camera on the old room and moves it one room over,
+    // streaming the new room in a column or row per tile.
+    fn generate_world_helpers(&mut self) {
+        if !self.uses_world {
+            return;
+        }
+        let room_x = WORLD_STATE;
+        let room_y = WORLD_STATE + 1;
+        let speed = WORLD_STATE + 2;
+        let direction = WORLD_STATE + 3;
+        // World section: width, height, format, then the raw room map
+        let world = self.layout.addr(SECTION_WORLD);
+        let rooms_lo = (world + 3) & 0xFF;
+        let rooms_hi = (world + 3) >> 8;
+
+        self.output.push("; --- World Helpers ---".to_string());
+        // Runtime_World_Enter: shows the current room with the scroll at its
+        // top-left corner
+        self.output.push("Runtime_World_Enter:".to_string());
+        self.output.push(format!("  LDA ${:04X}", room_x));
+        self.output.push("  STA $08".to_string());
+        self.output.push(format!("  LDA ${:04X}", room_y));
+        self.output.push("  STA $09".to_string());
+        self.output.push("  JSR World_Room".to_string());
+        self.output.push("  PHA".to_string());
+        if self.uses_camera {
+            self.output.push("  JSR World_Place".to_string());
+        }
+        self.output.push("  LDA #0".to_string());
+        self.output.push("  STA $E0".to_string());
+        self.output.push("  STA $E1".to_string());
+        self.output.push("  LDA $09".to_string());
+        self.output.push("  AND #1".to_string());
+        self.output.push("  ASL".to_string());
+        self.output.push("  STA $07".to_string());
+        self.output.push("  LDA $08".to_string());
+        self.output.push("  AND #1".to_string());
+        self.output.push("  ORA $07".to_string());
+        self.output.push("  STA $07".to_string());
+        self.output.push("  LDA $F8".to_string());
+        self.output.push("  AND #$FC".to_string());
+        self.output.push("  ORA $07".to_string());
+        self.output.push("  STA $F8".to_string());
+        // Empty rooms ($FF) are not a screen id, so Screen.Load skips them
+        self.output.push("  LDX $07".to_string());
+        self.output.push("  PLA".to_string());
+        self.output.push("  JMP Runtime_Screen_Load".to_string());
+
+        // World_Room: $08/$09 = room x/y -> A = its screen id ($FF when empty)
+        self.output.push("World_Room:".to_string());
+        self.output.push("  LDA $08".to_string());
+        self.output.push("  STA $0A".to_string());
+        self.output.push("  LDA #0".to_string());
+        self.output.push("  STA $0B".to_string());
+        self.output.push("  LDY $09".to_string());
+        self.output.push("  BEQ World_RoomFound".to_string());
+        self.output.push("World_RoomMul:".to_string());
+        self.output.push("  CLC".to_string());
+        self.output.push("  LDA $0A".to_string());
+        self.output.push(format!("  ADC ${:04X}", world));
+        self.output.push("  STA $0A".to_string());
+        self.output.push("  BCC World_RoomNext".to_string());
+        self.output.push("  INC $0B".to_string());
+        self.output.push("World_RoomNext:".to_string());
+        self.output.push("  DEY".to_string());
+        self.output.push("  BNE World_RoomMul".to_string());
+        self.output.push("World_RoomFound:".to_string());
+        self.output.push("  CLC".to_string());
+        self.output.push("  LDA $0A".to_string());
+        self.output.push(format!("  ADC #{}", rooms_lo));
+        self.output.push("  STA $0A".to_string());
+        self.output.push("  LDA $0B".to_string());
+        self.output.push(format!("  ADC #{}", rooms_hi));
+        self.output.push("  STA $0B".to_string());
+        self.output.push("  LDY #0".to_string());
+        self.output.push("  LDA ($0A), Y".to_string());
+        self.output.push("  RTS".to_string());
+
+        // World_Neighbour: A = direction (Up, Down, Left, Right) -> $08/$09 =
+        // the room that way; carry set when it is off the map or empty
+        self.output.push("World_Neighbour:".to_string());
+        self.output.push(format!("  LDX ${:04X}", room_x));
+        self.output.push(format!("  LDY ${:04X}", room_y));
+        self.output.push("  CMP #4".to_string());
+        self.output.push("  BCS World_Blocked".to_string());
+        self.output.push("  CMP #3".to_string());
+        self.output.push("  BEQ World_Right".to_string());
+        self.output.push("  CMP #2".to_string());
+        self.output.push("  BEQ World_Left".to_string());
+        self.output.push("  CMP #1".to_string());
+        self.output.push("  BEQ World_Down".to_string());
+        self.output.push("  CPY #0".to_string());
+        self.output.push("  BEQ World_Blocked".to_string());
+        self.output.push("  DEY".to_string());
+        self.output.push("  JMP World_NeighbourRoom".to_string());
+        self.output.push("World_Down:".to_string());
+        self.output.push("  INY".to_string());
+        self.output.push(format!("  CPY ${:04X}", world + 1));
+        self.output.push("  BCS World_Blocked".to_string());
+        self.output.push("  JMP World_NeighbourRoom".to_string());
+        self.output.push("World_Left:".to_string());
+        self.output.push("  CPX #0".to_string());
+        self.output.push("  BEQ World_Blocked".to_string());
+        self.output.push("  DEX".to_string());
+        self.output.push("  JMP World_NeighbourRoom".to_string());
+        self.output.push("World_Right:".to_string());
+        self.output.push("  INX".to_string());
+        self.output.push(format!("  CPX ${:04X}", world));
+        self.output.push("  BCS World_Blocked".to_string());
+        self.output.push("World_NeighbourRoom:".to_string());
+        self.output.push("  STX $08".to_string());
+        self.output.push("  STY $09".to_string());
+        self.output.push("  JSR World_Room".to_string());
+        self.output.push("  CMP #$FF".to_string());
+        self.output.push("  BEQ World_Blocked".to_string());
+        self.output.push("  CLC".to_string());
+        self.output.push("  RTS".to_string());
+        self.output.push("World_Blocked:".to_string());
+        self.output.push("  SEC".to_string());
+        self.output.push("  RTS".to_string());
+
+        // Runtime_World_Flip: A = direction; cuts straight to the neighbouring
+        // room, ignoring moves off the map or into empty rooms
+        self.output.push("Runtime_World_Flip:".to_string());
+        self.output.push("  JSR World_Neighbour".to_string());
+        self.output.push("  BCS World_FlipDone".to_string());
+        self.output.push("  LDA $08".to_string());
+        self.output.push(format!("  STA ${:04X}", room_x));
+        self.output.push("  LDA $09".to_string());
+        self.output.push(format!("  STA ${:04X}", room_y));
+        self.output.push("  JMP Runtime_World_Enter".to_string());
+        self.output.push("World_FlipDone:".to_string());
+        self.output.push("  RTS".to_string());
+
+        if !self.uses_camera {
+            return;
+        }
+        let cx = CAMERA_STATE + 4;
+        let cy = CAMERA_STATE + 6;
+        let camera_room_y = CAMERA_STATE + 8;
+        let pixel_y = CAMERA_STATE + 9;
+        let placed = CAMERA_STATE + 11;
+        let dx = CAMERA_STATE + 12;
+        let dy = CAMERA_STATE + 13;
+        // World_Place: parks the camera on the current room, x * 256 / y * 240
+        self.output.push("World_Place:".to_string());
+        self.output.push("  LDA #0".to_string());
+        self.output.push(format!("  STA ${:04X}", cx));
+        self.output.push(format!("  STA ${:04X}", pixel_y));
+        self.output.push(format!("  LDA ${:04X}", room_x));
+        self.output.push(format!("  STA ${:04X}", cx + 1));
+        self.output.push(format!("  LDA ${:04X}", room_y));
+        self.output.push(format!("  STA ${:04X}", camera_room_y));
+        self.output.push("  ASL".to_string());
+        self.output.push("  ASL".to_string());
+        self.output.push("  ASL".to_string());
+        self.output.push("  ASL".to_string());
+        self.output.push("  STA $06".to_string());
+        self.output.push(format!("  LDA ${:04X}", room_y));
+        self.output.push("  LSR".to_string());
+        self.output.push("  LSR".to_string());
+        self.output.push("  LSR".to_string());
+        self.output.push("  LSR".to_string());
+        self.output.push("  STA $07".to_string());
+        self.output.push("  SEC".to_string());
+        self.output.push("  LDA #0".to_string());
+        self.output.push("  SBC $06".to_string());
+        self.output.push(format!("  STA ${:04X}", cy));
+        self.output.push(format!("  LDA ${:04X}", room_y));
+        self.output.push("  SBC $07".to_string());
+        self.output.push(format!("  STA ${:04X}", cy + 1));
+        self.output.push("  LDA #1".to_string());
+        self.output.push(format!("  STA ${:04X}", placed));
+        self.output.push("  RTS".to_string());
+
+        if !self.uses_world_scroll {
+            return;
+        }
+        let budget = PPU_QUEUE_SCRATCH + 3;
+        let extra_x = self.mirroring != Mirroring::Horizontal;
+        let extra_y = self.mirroring != Mirroring::Vertical;
+        // Runtime_World_Scroll: A = direction, X = pixels per frame (1, 2, 4
+        // or 8, so a step never crosses more than one tile)
+        self.output.push("Runtime_World_Scroll:".to_string());
+        self.output.push(format!("  STA ${:04X}", direction));
+        self.output.push(format!("  STX ${:04X}", speed));
+        self.output.push("  JSR World_Neighbour".to_string());
+        self.output.push("  BCC World_ScrollGo".to_string());
+        self.output.push("  RTS".to_string());
+        self.output.push("World_ScrollGo:".to_string());
+        self.output.push("  JSR World_Place".to_string());
+        self.output.push("  LDA $08".to_string());
+        self.output.push(format!("  STA ${:04X}", room_x));
+        self.output.push("  LDA $09".to_string());
+        self.output.push(format!("  STA ${:04X}", room_y));
+        self.output.push("  LDA #0".to_string());
+        self.output.push(format!("  STA ${:04X}", dx));
+        self.output.push(format!("  STA ${:04X}", dy));
+        // Up and Left step backwards
+        self.output.push(format!("  LDA ${:04X}", speed));
+        self.output.push(format!("  LDX ${:04X}", direction));
+        self.output.push("  CPX #1".to_string());
+        self.output.push("  BEQ World_ScrollV".to_string());
+        self.output.push("  CPX #3".to_string());
+        self.output.push("  BEQ World_ScrollH".to_string());
+        self.output.push("  EOR #$FF".to_string());
+        self.output.push("  CLC".to_string());
+        self.output.push("  ADC #1".to_string());
+        self.output.push("  CPX #0".to_string());
+        self.output.push("  BEQ World_ScrollV".to_string());
+        // The camera streams what enters past its look-ahead, so with a
+        // spare nametable on the axis the new room's first column or row
+        // goes out up front
+        self.output.push("World_ScrollH:".to_string());
+        self.output.push(format!("  STA ${:04X}", dx));
+        if extra_x {
+            self.output.push("  CPX #3".to_string());
+            self.output.push("  BNE World_ScrollLoop".to_string());
+            self.output.push(format!("  LDA ${:04X}", room_x));
+            self.output.push("  STA $08".to_string());
+            self.output.push("  LDA #0".to_string());
+            self.output.push("  STA $0C".to_string());
+            self.output.push("  JSR Camera_Column".to_string());
+        }
+        self.output.push("  JMP World_ScrollLoop".to_string());
+        self.output.push("World_ScrollV:".to_string());
+        self.output.push(format!("  STA ${:04X}", dy));
+        if extra_y {
+            self.output.push("  CPX #1".to_string());
+            self.output.push("  BNE World_ScrollLoop".to_string());
+            self.output.push(format!("  LDA ${:04X}", room_y));
+            self.output.push("  STA $09".to_string());
+            self.output.push("  LDA #0".to_string());
+            self.output.push("  STA $0D".to_string());
+            self.output.push("  JSR Camera_Row".to_string());
+        }
+        // One step per frame until the camera lines up with the new room
+        self.output.push("World_ScrollLoop:".to_string());
+        self.output.push("  JSR Camera_MoveX".to_string());
+        self.output.push("  JSR Camera_MoveY".to_string());
+        self.output.push("  JSR Camera_Scroll".to_string());
+        // Wait for the frame: the NMI's queue flush refills the byte budget.
+        // With NMI off, wait for vblank and flush here.
+        self.output.push("  LDA #0".to_string());
+        self.output.push(format!("  STA ${:04X}", budget));
+        self.output.push("  LDA $F8".to_string());
+        self.output.push("  BMI World_ScrollWait".to_string());
+        self.output.push("World_ScrollVblank:".to_string());
+        self.output.push("  BIT $2002".to_string());
+        self.output.push("  BPL World_ScrollVblank".to_string());
+        self.output.push("  JSR PpuQueue_Flush".to_string());
+        self.output.push("  LDA $2002".to_string());
+        self.output.push("  LDA $E0".to_string());
+        self.output.push("  STA $2005".to_string());
+        self.output.push("  LDA $E1".to_string());
+        self.output.push("  STA $2005".to_string());
+        self.output.push("World_ScrollWait:".to_string());
+        self.output.push(format!("  LDA ${:04X}", budget));
+        self.output.push("  BEQ World_ScrollWait".to_string());
+        self.output.push(format!("  LDA ${:04X}", cx));
+        self.output.push(format!("  ORA ${:04X}", pixel_y));
+        self.output.push("  BNE World_ScrollLoop".to_string());
+        self.output.push("  RTS".to_string());
+    }
+
+    // Uploads a whole screen with rendering off. NMI is held off so the
+    // handler cannot touch the PPU mid-upload, and both switches happen in
+    // vblank; $F8/$F9 (PPUCTRL/PPUMASK shadows) and the scroll are restored.
+    fn generate_screen_helpers(&mut self) {
+        if !self.uses_screen_load {
+            return;
+        }
+        let table = self.layout.addr(SECTION_SCREEN_TABLE);
+        self.output.push("; --- Screen Helpers ---".to_string());
+        self.output.push("Runtime_Screen_Load:".to_string());
+        // Unknown ids are ignored
+        self.output.push(format!("  CMP #{}", self.screens.len()));
+        self.output.push("  BCS Screen_Load_Done".to_string());
+        self.generate_screen_bank_switch();
+        self.output.push("  STA $00".to_string());
+        self.output.push("  ASL".to_string());
+        self.output.push("  ADC $00".to_string());
+        self.output.push("  TAY".to_string());
+        self.output.push(format!("  LDA ${:04X}, Y", table));
+        self.output.push("  STA $02".to_string());
+        self.output.push(format!("  LDA ${:04X}, Y", table + 1));
+        self.output.push("  STA $03".to_string());
+        self.output.push(format!("  LDA ${:04X}, Y", table + 2));
+        self.output.push("  STA $01".to_string()); // Format
+                                                   // Nametable n lives at $2000 + n * $400
+        self.output.push("  TXA".to_string());
+        self.output.push("  AND #$03".to_string());
+        self.output.push("  ASL".to_string());
+        self.output.push("  ASL".to_string());
+        self.output.push("  ORA #$20".to_string());
+        self.output.push("  STA $00".to_string());
+
+        // NMI off, +1 increment; rendering goes off at the next vblank
+        self.output.push("  LDA $F8".to_string());
+        self.output.push("  AND #$7B".to_string());
+        self.output.push("  STA $2000".to_string());
+        self.output.push("  BIT $2002".to_string());
+        self.output.push("Screen_Load_Off:".to_string());
+        self.output.push("  BIT $2002".to_string());
+        self.output.push("  BPL Screen_Load_Off".to_string());
+        self.output.push("  LDA #$00".to_string());
+        self.output.push("  STA $2001".to_string());
+        self.output.push("  LDA $00".to_string());
+        self.output.push("  STA $2006".to_string());
+        self.output.push("  LDA #$00".to_string());
+        self.output.push("  STA $2006".to_string());
+
+        let mut formats: Vec<Compression> = Vec::new();
+        for format in &self.screens {
+            if *format != Compression::None && !formats.contains(format) {
+                formats.push(*format);
+            }
+        }
+        self.output.push("  LDA $01".to_string());
+        for format in formats {
+            let skip = self.new_label();
+            self.output.push(format!("  CMP #{}", format as u8));
+            self.output.push(format!("  BNE {}", skip));
+            self.output
+                .push(format!("  JSR {}", Self::ppu_decompressor(format)));
+            self.output.push("  JMP Screen_Load_On".to_string());
+            self.output.push(format!("{}:", skip));
+        }
+        self.output.push("  LDX #$04".to_string());
+        self.output.push("  LDY #$00".to_string());
+        self.output.push("Screen_Load_Raw:".to_string());
+        self.output.push("  LDA ($02), Y".to_string());
+        self.output.push("  STA $2007".to_string());
+        self.output.push("  INY".to_string());
+        self.output.push("  BNE Screen_Load_Raw".to_string());
+        self.output.push("  INC $03".to_string());
+        self.output.push("  DEX".to_string());
+        self.output.push("  BNE Screen_Load_Raw".to_string());
+
+        self.output.push("Screen_Load_On:".to_string());
+        self.output.push("  BIT $2002".to_string());
+        self.output.push("Screen_Load_Vblank:".to_string());
+        self.output.push("  BIT $2002".to_string());
+        self.output.push("  BPL Screen_Load_Vblank".to_string());
+        self.output.push("  LDA $F8".to_string());
+        self.output.push("  STA $2000".to_string());
+        self.output.push("  LDA $E0".to_string());
+        self.output.push("  STA $2005".to_string());
+        self.output.push("  LDA $E1".to_string());
+        self.output.push("  STA $2005".to_string());
+        self.output.push("  LDA $F9".to_string());
+        self.output.push("  STA $2001".to_string());
+        self.output.push("Screen_Load_Done:".to_string());
+        self.output.push("  RTS".to_string());
+    }
+
+    // CHR.Load(bank, tileStart, count): tiles come from 8 KB bank `bank` of
+    // the project CHR data and land at the same tile index in CHR-RAM.
+    fn generate_chr_load(&mut self, args: &[Expression]) -> Result<(), String> {
+        let compression = self
+            .chr_ram
+            .ok_or("CHR.Load requires a CHR-RAM cartridge (set chr_ram in project.json)")?;
+        // Bank n of the CHR data starts at base + n * $2000. With a
+        // compressed startup image only banks 1+ are kept raw, in `chr_tiles`.
+        let base = match compression {
+            Compression::None => self.layout.addr(SECTION_CHR),
+            _ => {
+                if args[0] == Expression::Integer(0) {
+                    return Err(
+                        "CHR.Load cannot read bank 0 when the startup CHR is compressed"
+                            .to_string(),
+                    );
+                }
+                self.layout.addr(SECTION_CHR_TILES).wrapping_sub(0x2000)
+            }
+        };
+
+        self.generate_expression(&args[2])?;
+        self.output.push("  PHA".to_string());
+        self.generate_expression(&args[0])?;
+        self.output.push("  PHA".to_string());
+        let rtype = self.generate_expression(&args[1])?;
+        self.output.push("  STA $06".to_string());
+        if rtype == DataType::Word || rtype == DataType::Int {
+            self.output.push("  STX $07".to_string());
+        } else {
+            self.output.push("  LDA #0".to_string());
+            self.output.push("  STA $07".to_string());
+        }
+        // PPU address = tile * 16
+        for _ in 0..4 {
+            self.output.push("  ASL $06".to_string());
+            self.output.push("  ROL $07".to_string());
+        }
+        // Source = base + bank * $2000 + tile * 16
+        self.output.push("  CLC".to_string());
+        self.output.push("  LDA $06".to_string());
+        self.output.push(format!("  ADC #${:02X}", base & 0xFF));
+        self.output.push("  STA $04".to_string());
+        self.output.push("  LDA $07".to_string());
+        self.output.push(format!("  ADC #${:02X}", base >> 8));
+        self.output.push("  STA $05".to_string());
+        self.output.push("  PLA".to_string());
+        for _ in 0..5 {
+            self.output.push("  ASL".to_string());
+        }
+        self.output.push("  CLC".to_string());
+        self.output.push("  ADC $05".to_string());
+        self.output.push("  STA $05".to_string());
+        self.output.push("  PLA".to_string());
+        self.output.push("  STA $08".to_string());
+        self.uses_ppu_queue = true;
+        self.output.push("  JSR Runtime_CHR_Load".to_string());
+        Ok(())
+    }
+
+    fn generate_mapper_init(&mut self) {
+        if self.mapper == Mapper::Mmc1 {
+            // Control $0E: vertical mirroring ($0F horizontal), 16 KB PRG at
+            // $8000 with the last bank fixed at $C000, 8 KB CHR
+            let control = if self.mirroring == Mirroring::Horizontal {
+                0x0F
+            } else {
+                0x0E
+            };
+            self.output.push("  LDA #$80".to_string());
+            self.output.push("  STA $8000".to_string());
+            self.output.push(format!("  LDA #${:02X}", control));
+            self.output.push("  JSR MMC1_WriteControl".to_string());
+            self.output.push("  LDA #$00".to_string());
+            self.output.push("  JSR MMC1_WriteCHR0".to_string());
+            self.output.push("  LDA #$00".to_string());
+            self.output.push("  JSR MMC1_SetPRG".to_string());
+        } else if self.mapper == Mapper::Mmc3 {
+            // Mirroring, PRG-RAM on, scanline IRQ off
+            let mirroring = u8::from(self.mirroring == Mirroring::Horizontal);
+            self.output.push(format!("  LDA #${:02X}", mirroring));
+            self.output.push("  STA $A000".to_string());
+            self.output.push("  LDA #$80".to_string());
+            self.output.push("  STA $A001".to_string());
+            self.output.push("  STA $E000".to_string());
+            // R0-R1: 2 KB CHR, R2-R5: 1 KB CHR, R6-R7: 8 KB PRG at $8000/$A000
+            for (register, bank) in [0u8, 2, 4, 5, 6, 7, 0, 1].iter().enumerate() {
+                self.output.push(format!("  LDA #${:02X}", register));
+                self.output.push("  STA $8000".to_string());
+                self.output.push(format!("  LDA #${:02X}", bank));
+                self.output.push("  STA $8001".to_string());
+            }
+        } else if self.mapper.bus_conflicts() {
+            let label = if self.mapper == Mapper::Cnrom {
+                "SetCHR"
+            } else {
+                "SetPRG"
+            };
+            self.output.push("  LDA #$00".to_string());
+            self.output
+                .push(format!("  JSR {}_{}", self.mapper.name(), label));
+        }
+    }
+
+    // MMC3 register writes are two-step (select, then data). Interrupts keep
+    // the interrupted code's selection (shadowed in $1E) and reselect it on
+    // exit.
+    fn generate_bank_select_save(&mut self) {
+        if self.mapper == Mapper::Mmc3 {
+            self.output.push("  LDA $1E".to_string());
+            self.output.push("  PHA".to_string());
+        }
+    }
+
+    fn generate_bank_select_restore(&mut self) {
+        if self.mapper == Mapper::Mmc3 {
+            self.output.push("  PLA".to_string());
+            self.output.push("  STA $1E".to_string());
+            self.output.push("  STA $8000".to_string());
+        }
+    }
+
+    fn generate_bank_helpers(&mut self) {
+        match self.mapper {
+            Mapper::Nrom => return,
+            Mapper::Mmc1 => self.generate_mmc1_helpers(),
+            Mapper::Mmc3 => self.generate_mmc3_helpers(),
+            Mapper::Uxrom | Mapper::Cnrom | Mapper::Axrom => self.generate_discrete_helpers(),
+        }
+        if self.mapper.switchable_banks() > 0 {
+            self.generate_far_trampolines();
+        }
+    }
+
+    // UxROM, CNROM and AxROM latch whatever is written anywhere in $8000-$FFFF.
+    // The write goes through the bank table so the ROM byte under it matches.
+    fn generate_discrete_helpers(&mut self) {
+        let table = self.layout.addr(SECTION_BANK_TABLE);
+        let name = self.mapper.name();
+        self.output
+            .push(format!("; --- {} Bank Switching ---", name));
+        let label = if self.mapper == Mapper::Cnrom {
+            "SetCHR"
+        } else {
+            // A = bank, shadowed in $1D for the trampolines
+            "SetPRG"
+        };
+        self.output.push(format!("{}_{}:", name, label));
+        if label == "SetPRG" {
+            self.output.push("  STA $1D".to_string());
+        }
+        self.output.push("  TAY".to_string());
+        self.output.push(format!("  STA ${:04X}, Y", table));
+        self.output.push("  RTS".to_string());
+    }
+
+    fn generate_mmc3_helpers(&mut self) {
+        self.output
+            .push("; --- MMC3 Bank Switching ---".to_string());
+        // A = bank, mapped at $8000 through R6
+        self.output.push("MMC3_SetPRG:".to_string());
+        self.output.push("  STA $1D".to_string());
+        self.output.push("  LDA #$06".to_string());
+        self.output.push("  STA $1E".to_string());
+        self.output.push("  STA $8000".to_string());
+        self.output.push("  LDA $1D".to_string());
+        self.output.push("  STA $8001".to_string());
+        self.output.push("  RTS".to_string());
+
+        // A = 8 KB CHR bank, spread over R0-R5 in 1 KB units
+        self.output.push("MMC3_SetCHR:".to_string());
+        self.output.push("  ASL".to_string());
+        self.output.push("  ASL".to_string());
+        self.output.push("  ASL".to_string());
+        self.output.push("  TAY".to_string());
+        for (register, offset) in [(0u8, 0u8), (1, 2), (2, 4), (3, 5), (4, 6), (5, 7)] {
+            self.output.push(format!("  LDA #${:02X}", register));
+            self.output.push("  STA $1E".to_string());
+            self.output.push("  STA $8000".to_string());
+            self.output.push("  TYA".to_string());
+            if offset > 0 {
+                self.output.push("  CLC".to_string());
+                self.output.push(format!("  ADC #${:02X}", offset));
+            }
+            self.output.push("  STA $8001".to_string());
+        }
+        self.output.push("  RTS".to_string());
+
+        // Calls the ON SCANLINE handler selected by $E5
+        self.output.push("Scanline_Dispatch:".to_string());
+        let handlers = self.scanline_handlers.clone();
+        for (i, name) in handlers.iter().enumerate() {
+            let target = if self.sub_banks.contains_key(name) {
+                format!("Far_{}", name)
+            } else {
+                name.clone()
+            };
+            self.output.push("  LDA $E5".to_string());
+            self.output.push(format!("  CMP #${:02X}", i + 1));
+            self.output.push(format!("  BNE Scanline_Next{}", i + 1));
+            self.output.push(format!("  JMP {}", target));
+            self.output.push(format!("Scanline_Next{}:", i + 1));
+        }
+        self.output.push("  RTS".to_string());
+    }
+
+    fn generate_mmc1_helpers(&mut self) {
+        self.output
+            .push("; --- MMC1 Bank Switching ---".to_string());
+        // A = 8 KB CHR bank; in 8 KB mode CHR0 counts 4 KB units
+        self.output.push("MMC1_SetCHR:".to_string());
+        self.output.push("  ASL".to_string());
+        self.output.push("  JMP MMC1_WriteCHR0".to_string());
+        for (label, register) in [
+            ("MMC1_WriteControl", 0x8000),
+            ("MMC1_WriteCHR0", 0xA000),
+            ("MMC1_WriteCHR1", 0xC000),
+        ] {
+            self.output.push(format!("{}:", label));
+            for i in 0..5 {
+                if i > 0 {
+                    self.output.push("  LSR".to_string());
+                }
+                self.output.push(format!("  STA ${:04X}", register));
+            }
+            self.output.push("  RTS".to_string());
+        }
+
+        // A = bank. $1D shadows the selected bank. An interrupt that switches
+        // banks mid-write sets $1E, and the interrupted write starts over.
+        self.output.push("MMC1_SetPRG:".to_string());
+        self.output.push("  STA $1D".to_string());
+        self.output.push("MMC1_SetPRG_Retry:".to_string());
+        self.output.push("  LDA #0".to_string());
+        self.output.push("  STA $1E".to_string());
+        self.output.push("  LDA #$80".to_string());
+        self.output.push("  STA $8000".to_string());
+        self.output.push("  LDA $1D".to_string());
+        for i in 0..5 {
+            if i > 0 {
+                self.output.push("  LSR".to_string());
+            }
+            self.output.push("  STA $E000".to_string());
+        }
+        self.output.push("  LDA $1E".to_string());
+        self.output.push("  BNE MMC1_SetPRG_Retry".to_string());
+        self.output.push("  INC $1E".to_string());
+        self.output.push("  RTS".to_string());
+    }
+
+    fn generate_far_trampolines(&mut self) {
+        let set_prg = format!("{}_SetPRG", self.mapper.name());
+        // Trampolines: switch in the callee's bank, call through its jump
+        // table slot, then restore the caller's bank. A is preserved in Y.
+        let entries: Vec<(u8, Vec<String>)> = self
+            .bank_entries
+            .iter()
+            .map(|(b, subs)| (*b, subs.clone()))
+            .collect();
+        for (bank, subs) in entries {
+            for (i, name) in subs.iter().enumerate() {
+                self.output.push(format!("Far_{}:", name));
+                self.output.push("  LDA $1D".to_string());
+                self.output.push("  PHA".to_string());
+                self.output.push(format!("  LDA #${:02X}", bank));
+                self.output.push(format!("  JSR {}", set_prg));
+                self.output
+                    .push(format!("  JSR ${:04X}", SWITCH_WINDOW + 3 * i as u16));
+                self.output.push("  TAY".to_string());
+                self.output.push("  PLA".to_string());
+                self.output.push(format!("  JSR {}", set_prg));
+                self.output.push("  TYA".to_string());
+                self.output.push("  RTS".to_string());
+            }
+        }
+        self.output.push("".to_string());
+    }
+
+    // Routines reading assets out of a switchable bank keep the caller's
+    // bank: the routine generated from `start` on becomes `{name}_Banked`,
+    // and `{name}` saves $1D, maps `bank` (or leaves that to the routine),
+    // calls it and maps the saved bank back. A and X pass through both ways
+    // and the saved bank lives on the stack, so the NMI can nest it.
+    fn generate_bank_wrapper(&mut self, start: usize, name: &str, bank: Option<u8>) {
+        let label = format!("{}:", name);
+        let banked = format!("{}_Banked", name);
+        if !self.output[start..].contains(&label) {
+            return;
+        }
+        for line in &mut self.output[start..] {
+            if *line == label {
+                *line = format!("{}:", banked);
+            } else if line.split_whitespace().nth(1) == Some(name) {
+                *line = line.replacen(name, &banked, 1);
+            }
+        }
+        let set_prg = format!("{}_SetPRG", self.mapper.name());
+        self.output.push(label);
+        self.output.push("  PHA".to_string());
+        self.output.push("  TXA".to_string());
+        self.output.push("  PHA".to_string());
+        self.output.push("  LDA $1D".to_string());
+        self.output.push("  PHA".to_string());
+        if let Some(bank) = bank {
+            self.output.push(format!("  LDA #${:02X}", bank));
+            self.output.push(format!("  JSR {}", set_prg));
+        }
+        self.output.push("  TSX".to_string());
+        self.output.push("  LDA $0103, X".to_string());
+        self.output.push("  PHA".to_string());
+        self.output.push("  LDA $0102, X".to_string());
+        self.output.push("  TAX".to_string());
+        self.output.push("  PLA".to_string());
+        self.output.push(format!("  JSR {}", banked));
+        self.output.push("  PHA".to_string());
+        self.output.push("  TXA".to_string());
+        self.output.push("  PHA".to_string());
+        self.output.push("  TSX".to_string());
+        self.output.push("  LDA $0103, X".to_string());
+        self.output.push(format!("  JSR {}", set_prg));
+        self.output.push("  PLA".to_string());
+        self.output.push("  TAX".to_string());
+        self.output.push("  PLA".to_string());
+        self.output.push("  TAY".to_string());
+        self.output.push("  PLA".to_string());
+        self.output.push("  PLA".to_string());
+        self.output.push("  PLA".to_string());
+        self.output.push("  TYA".to_string());
+        self.output.push("  RTS".to_string());
+    }
+
+    // Screen_Bank: A = screen id; maps the screen's bank unless it is in the
+    // fixed one ($FF). A and X are kept.
+    fn generate_screen_bank_helper(&mut self) {
+        let banks = self.layout.addr(SECTION_SCREEN_BANKS);
+        self.output.push("Screen_Bank:".to_string());
+        self.output.push("  PHA".to_string());
+        self.output.push("  TAY".to_string());
+        self.output.push(format!("  LDA ${:04X}, Y", banks));
+        self.output.push("  BMI Screen_BankFixed".to_string());
+        self.output
+            .push(format!("  JSR {}_SetPRG", self.mapper.name()));
+        self.output.push("Screen_BankFixed:".to_string());
+        self.output.push("  PLA".to_string());
+        self.output.push("  RTS".to_string());
+    }
+
+    // Maps the bank of the screen id in A, when screens are banked
+    fn generate_screen_bank_switch(&mut self) {
+        if self.layout.get(SECTION_SCREEN_BANKS).is_some() {
+            self.output.push("  JSR Screen_Bank".to_string());
+        }
+    }
+
+    // Each switchable bank is a separate `.BANK n` block at $8000 that opens
+    // with a jump table, so trampolines never need the bank's own labels.
+    fn generate_switchable_banks(&mut self) {
+        let banks: Vec<u8> = self
+            .bank_output
+            .keys()
+            .chain(self.bank_entries.keys())
+            .copied()
+            .collect::<std::collections::BTreeSet<u8>>()
+            .into_iter()
+            .collect();
+        for bank in banks {
+            self.output.push(format!(".BANK {}", bank));
+            self.output.push(format!(".ORG ${:04X}", SWITCH_WINDOW));
+            if let Some(subs) = self.bank_entries.get(&bank) {
+                for name in subs {
+                    self.output.push(format!("  JMP {}", name));
+                }
+            }
+            if let Some(lines) = self.bank_output.remove(&bank) {
+                self.output.extend(lines);
+            }
+            self.output.push("".to_string());
+        }
+    }
+
+    fn generate_controller_helpers(&mut self) {
+        self.output.push("; --- Controller Helpers ---".to_string());
+        self.output.push("Runtime_Controller_Read:".to_string());
+        self.output.push("  LDA #$01".to_string());
+        self.output.push("  STA $4016".to_string());
+        self.output.push("  LDA #$00".to_string());
+        self.output.push("  STA $4016".to_string());
+        self.output.push("  LDA $10".to_string()); // Load old held
+        self.output.push("  STA $11".to_string()); // Save to last
+        self.output.push("  LDA #0".to_string());
+        self.output.push("  STA $10".to_string()); // Clear held
+        self.output.push("  LDX #8".to_string());
         self.output.push("Controller_ReadLoop:".to_string());
         self.output.push("  LDA $4016".to_string());
         self.output.push("  LSR".to_string()); // Bit0 -> Carry
@@ -2998,42 +6899,114 @@ impl CodeGenerator {
         self.output.push("".to_string());
     }
 
+    // Flip flags of Sprite.Draw / Animation.Draw -> $07 (OAM attribute bits)
+    fn generate_flip_flags(&mut self, flags: &Expression) -> Result<(), String> {
+        self.generate_expression(flags)?;
+        self.output.push("  AND #$C0".to_string());
+        self.output.push("  STA $07".to_string());
+        Ok(())
+    }
+
     fn generate_sprite_helpers(&mut self) {
         self.output.push("; --- Sprite Helpers ---".to_string());
+        let zp = |offset: u8| format!("${:02X}", SPRITE_STATE + offset);
+        let reserved = zp(0);
+        let layer = zp(1);
+        let fixed = zp(2);
+        let stable = zp(3);
+        let low = zp(4);
+        let up = zp(5);
+        let parity = zp(6);
+        let crowded = zp(7);
+        let crowded_last = zp(8);
+        let band = format!("${:02X}, X", SPRITE_BANDS);
 
         // Runtime_SpriteClear
-        // Resets $19 (OAM Ptr) and fills OAM with $FF (Offscreen)
-        // Implements OAM Cycling (Flicker) if $1C is set.
-        // Uses $1A (OAM Base Offset), $1B (Sprites Drawn Count), $1C (Flicker Enable)
+        // Fills OAM with $FF (Offscreen) and starts a frame: the fixed layer
+        // restarts at slot 0 (1 under a HUD split's sprite 0), stable sprites above the reserved slots and
+        // crowded ones from slot 63 down, or (every other frame) upward from
+        // last frame's crowded block, so their order reverses each frame.
+        // A band counter over 8 marks its lines crowded for the next frame.
         self.output.push("Runtime_SpriteClear:".to_string());
-        self.output.push("  LDA $1C".to_string());
-        self.output.push("  BEQ SpriteClear_NoShuffle".to_string());
-        self.output.push("  LDA $1A".to_string());
-        self.output.push("  CLC".to_string());
-        self.output.push("  ADC #44".to_string()); // 11 slots
-        self.output.push("  STA $1A".to_string());
-        self.output.push("  JMP SpriteClear_SetPtr".to_string());
-        self.output.push("SpriteClear_NoShuffle:".to_string());
+        self.output.push("  LDX #31".to_string());
+        self.output.push("SpriteClear_Bands:".to_string());
+        self.output.push(format!("  LDA {}", band));
+        self.output.push("  AND #$7F".to_string());
+        self.output.push("  CMP #9".to_string());
         self.output.push("  LDA #0".to_string());
-        self.output.push("  STA $1A".to_string());
-        self.output.push("SpriteClear_SetPtr:".to_string());
-        self.output.push("  LDA $1A".to_string());
-        self.output.push("  STA $19".to_string()); // Set Ptr to Base
-        self.output.push("  LDA #0".to_string());
-        self.output.push("  STA $1B".to_string()); // Reset Count
+        self.output.push("  BCC SpriteClear_Calm".to_string());
+        self.output.push("  LDA #$80".to_string());
+        self.output.push("SpriteClear_Calm:".to_string());
+        self.output.push(format!("  STA {}", band));
+        self.output.push("  DEX".to_string());
+        self.output.push("  BPL SpriteClear_Bands".to_string());
 
+        self.output.push(format!("  LDA {}", crowded));
+        self.output.push(format!("  STA {}", crowded_last));
+        self.output.push("  LDA #0".to_string());
+        self.output.push(format!("  STA {}", crowded));
+        self.output.push(format!("  STA {}", layer));
+        // With a HUD split sprite 0 is taken: the layers start at slot 1
+        let first_free = if self.hud_tile.is_some() {
+            self.output.push(format!("  LDA ${:02X}", HUD_STATE));
+            self.output.push("  BEQ SpriteClear_Fixed".to_string());
+            self.output.push("  LDA #1".to_string());
+            self.output.push("SpriteClear_Fixed:".to_string());
+            self.output.push(format!("  STA {}", fixed));
+            self.output.push(format!("  CMP {}", reserved));
+            self.output.push("  BCS SpriteClear_Stable".to_string());
+            self.output.push(format!("  LDA {}", reserved));
+            self.output.push("SpriteClear_Stable:".to_string());
+            self.output.push(format!("  STA {}", stable));
+            stable.clone()
+        } else {
+            self.output.push(format!("  STA {}", fixed));
+            self.output.push(format!("  LDA {}", reserved));
+            self.output.push(format!("  STA {}", stable));
+            reserved.clone()
+        };
+        self.output.push("  LDA #64".to_string());
+        self.output.push(format!("  STA {}", low));
+        self.output.push(format!("  STA {}", up));
+        self.output.push(format!("  LDA {}", parity));
+        self.output.push("  EOR #1".to_string());
+        self.output.push(format!("  STA {}", parity));
+        self.output.push("  BEQ SpriteClear_Fill".to_string());
+        // Odd frame: crowded sprites climb from 64 - last count (not into
+        // the reserved slots)
+        self.output.push("  LDA #64".to_string());
+        self.output.push("  SEC".to_string());
+        self.output.push(format!("  SBC {}", crowded_last));
+        self.output.push(format!("  CMP {}", first_free));
+        self.output.push("  BCS SpriteClear_Block".to_string());
+        self.output.push(format!("  LDA {}", first_free));
+        self.output.push("SpriteClear_Block:".to_string());
+        self.output.push(format!("  STA {}", low));
+        self.output.push(format!("  STA {}", up));
+
+        self.output.push("SpriteClear_Fill:".to_string());
         self.output.push("  LDA #$FF".to_string());
         self.output.push("  LDX #0".to_string());
         self.output.push("SpriteClear_Loop:".to_string());
         self.output.push("  STA $0200, X".to_string());
         self.output.push("  INX".to_string());
         self.output.push("  BNE SpriteClear_Loop".to_string());
-        self.output.push("  RTS".to_string());
+        if self.hud_tile.is_some() {
+            self.output.push("  JMP Hud_PlaceSprite".to_string());
+        } else {
+            self.output.push("  RTS".to_string());
+        }
 
         // Runtime_SpriteDraw
         // Args: X ($14), Y ($15), MetaPtr ($16/$17)
-        // Uses: $02/$03 (Ptr), $04 (Count), $05 (Temp X), $06 (Temp Y), $19 (OAM Offset), $1B (Total Count)
+        // Uses: $02/$03 (Ptr), $04 (Count), $05 (Temp X), $06 (Temp Y), $0A (Crowded), $0B (Band)
+        // Runtime_SpriteDrawFlags also takes Flip.H/Flip.V in $07 and mirrors
+        // the tiles around the bounding box, whose min+max sums follow the
+        // metasprite's hitbox and hurtbox ($08/$09)
         self.output.push("Runtime_SpriteDraw:".to_string());
+        self.output.push("  LDA #0".to_string());
+        self.output.push("  STA $07".to_string());
+        self.output.push("Runtime_SpriteDrawFlags:".to_string());
         self.output.push("  LDA $16".to_string());
         self.output.push("  STA $02".to_string());
         self.output.push("  LDA $17".to_string());
@@ -3043,78 +7016,745 @@ impl CodeGenerator {
         self.output.push("  LDY #0".to_string());
         self.output.push("  LDA ($02), Y".to_string());
         self.output.push("  STA $04".to_string()); // Count
-        self.output.push("  INY".to_string()); // Y=1
 
-        self.output.push("  LDX $19".to_string()); // Load current OAM ptr
+        // Flip Sums (Offset 1 + Count * 4 + 8)
+        self.output.push("  LDX $07".to_string());
+        self.output.push("  BEQ SpriteDraw_Start".to_string());
+        self.output.push("  ASL".to_string());
+        self.output.push("  ASL".to_string());
+        self.output.push("  CLC".to_string());
+        self.output.push("  ADC #9".to_string());
+        self.output.push("  TAY".to_string());
+        self.output.push("  LDA ($02), Y".to_string());
+        self.output.push("  STA $08".to_string());
+        self.output.push("  INY".to_string());
+        self.output.push("  LDA ($02), Y".to_string());
+        self.output.push("  STA $09".to_string());
+        self.output.push("SpriteDraw_Start:".to_string());
+        self.output.push("  LDY #1".to_string());
 
         self.output.push("SpriteDraw_Loop:".to_string());
         self.output.push("  LDA $04".to_string());
-        self.output.push("  BEQ SpriteDraw_Done".to_string());
+        self.output.push("  BNE SpriteDraw_Tile".to_string());
+        self.output.push("  RTS".to_string());
+        self.output.push("SpriteDraw_Tile:".to_string());
         self.output.push("  DEC $04".to_string());
 
-        // Check Limit (64 sprites)
-        self.output.push("  LDA $1B".to_string());
+        // Read Rel X (Flip.H: sum - x)
+        self.output.push("  LDA ($02), Y".to_string());
+        self.output.push("  BIT $07".to_string());
+        self.output.push("  BVC SpriteDraw_NoFlipX".to_string());
+        self.output.push("  EOR #$FF".to_string());
+        self.output.push("  SEC".to_string());
+        self.output.push("  ADC $08".to_string());
+        self.output.push("SpriteDraw_NoFlipX:".to_string());
+        self.output.push("  CLC".to_string());
+        self.output.push("  ADC $14".to_string()); // + Screen X
+        self.output.push("  STA $05".to_string()); // Store Screen X
+        self.output.push("  INY".to_string());
+
+        // Read Rel Y (Flip.V: sum - y)
+        self.output.push("  LDA ($02), Y".to_string());
+        self.output.push("  BIT $07".to_string());
+        self.output.push("  BPL SpriteDraw_NoFlipY".to_string());
+        self.output.push("  EOR #$FF".to_string());
+        self.output.push("  SEC".to_string());
+        self.output.push("  ADC $09".to_string());
+        self.output.push("SpriteDraw_NoFlipY:".to_string());
+        self.output.push("  CLC".to_string());
+        self.output.push("  ADC $15".to_string()); // + Screen Y
+        self.output.push("  STA $06".to_string()); // Store Screen Y
+        self.output.push("  INY".to_string());
+
+        // Read Tile
+        self.output.push("  LDA ($02), Y".to_string());
+        self.output.push("  PHA".to_string()); // Push Tile
+        self.output.push("  INY".to_string());
+
+        // Read Attr, toggling the flip bits
+        self.output.push("  LDA ($02), Y".to_string());
+        self.output.push("  EOR $07".to_string());
+        self.output.push("  PHA".to_string()); // Push Attr
+        self.output.push("  INY".to_string());
+
+        // Count the tile in the 8-line bands it covers (lines Y+1 to Y+8,
+        // or Y+16 for 8x16 sprites); $0A bit 7 = one was crowded last frame
+        self.output.push("  LDA #0".to_string());
+        self.output.push("  STA $0A".to_string());
+        self.output.push("  LDA $06".to_string());
+        self.output.push("  CMP #$EF".to_string());
+        self.output.push("  BCS SpriteDraw_Place".to_string()); // Offscreen
+        self.output.push("  ADC #1".to_string());
+        self.output.push("  LSR".to_string());
+        self.output.push("  LSR".to_string());
+        self.output.push("  LSR".to_string());
+        self.output.push("  STA $0B".to_string());
+        self.output.push("  TAX".to_string());
+        self.output.push(format!("  INC {}", band));
+        self.output.push(format!("  LDA {}", band));
+        self.output.push("  STA $0A".to_string());
+        let bottoms: &[u8] = if self.tall_sprites { &[8, 16] } else { &[8] };
+        for (i, bottom) in bottoms.iter().enumerate() {
+            let last = i + 1 == bottoms.len();
+            let next = if last {
+                "SpriteDraw_Place".to_string()
+            } else {
+                format!("SpriteDraw_Band{}", i + 2)
+            };
+            self.output.push("  LDA $06".to_string());
+            self.output.push("  CLC".to_string());
+            self.output.push(format!("  ADC #{}", bottom));
+            self.output.push("  LSR".to_string());
+            self.output.push("  LSR".to_string());
+            self.output.push("  LSR".to_string());
+            self.output.push("  CMP $0B".to_string());
+            self.output.push(format!("  BEQ {}", next));
+            if !last {
+                self.output.push("  STA $0B".to_string());
+            }
+            self.output.push("  TAX".to_string());
+            self.output.push(format!("  INC {}", band));
+            self.output.push(format!("  LDA {}", band));
+            self.output.push("  ORA $0A".to_string());
+            self.output.push("  STA $0A".to_string());
+            if !last {
+                self.output.push(format!("{}:", next));
+            }
+        }
+
+        // Pick the OAM slot: fixed layer while reserved slots remain, then
+        // crowded sprites (with flicker on) from the top, others from below
+        self.output.push("SpriteDraw_Place:".to_string());
+        self.output.push(format!("  LDA {}", layer));
+        self.output.push("  BEQ SpriteDraw_Normal".to_string());
+        self.output.push(format!("  LDA {}", fixed));
+        self.output.push(format!("  CMP {}", reserved));
+        self.output.push("  BCS SpriteDraw_Normal".to_string());
+        self.output.push(format!("  INC {}", fixed));
+        self.output.push("  BCC SpriteDraw_Write".to_string());
+        self.output.push("SpriteDraw_Normal:".to_string());
+        self.output.push("  LDA $1C".to_string());
+        self.output.push("  BEQ SpriteDraw_Stable".to_string());
+        self.output.push("  BIT $0A".to_string());
+        self.output.push("  BMI SpriteDraw_Crowded".to_string());
+        self.output.push("SpriteDraw_Stable:".to_string());
+        self.output.push(format!("  LDA {}", stable));
+        self.output.push(format!("  CMP {}", low));
+        self.output.push("  BCS SpriteDraw_Skip".to_string());
+        self.output.push(format!("  INC {}", stable));
+        self.output.push("  BCC SpriteDraw_Write".to_string());
+        self.output.push("SpriteDraw_Crowded:".to_string());
+        self.output.push(format!("  LDA {}", up));
         self.output.push("  CMP #64".to_string());
-        self.output.push("  BCS SpriteDraw_Full".to_string());
+        self.output.push("  BCS SpriteDraw_Descend".to_string());
+        self.output.push(format!("  INC {}", up));
+        self.output.push("  BCC SpriteDraw_Counted".to_string());
+        self.output.push("SpriteDraw_Descend:".to_string());
+        self.output.push(format!("  LDA {}", stable));
+        self.output.push(format!("  CMP {}", low));
+        self.output.push("  BCS SpriteDraw_Skip".to_string());
+        self.output.push(format!("  DEC {}", low));
+        self.output.push(format!("  LDA {}", low));
+        self.output.push("SpriteDraw_Counted:".to_string());
+        self.output.push(format!("  INC {}", crowded));
+
+        // Write to OAM (A = slot)
+        self.output.push("SpriteDraw_Write:".to_string());
+        self.output.push("  ASL".to_string());
+        self.output.push("  ASL".to_string());
+        self.output.push("  TAX".to_string());
+        self.output.push("  LDA $06".to_string()); // Y
+        self.output.push("  STA $0200, X".to_string());
+        self.output.push("  PLA".to_string()); // Attr (Popped first)
+        self.output.push("  STA $0202, X".to_string());
+        self.output.push("  PLA".to_string()); // Tile
+        self.output.push("  STA $0201, X".to_string());
+        self.output.push("  LDA $05".to_string()); // Screen X
+        self.output.push("  STA $0203, X".to_string());
+        self.output.push("  JMP SpriteDraw_Loop".to_string());
+
+        // No free slot: drop the tile
+        self.output.push("SpriteDraw_Skip:".to_string());
+        self.output.push("  PLA".to_string());
+        self.output.push("  PLA".to_string());
+        self.output.push("  JMP SpriteDraw_Loop".to_string());
+        self.output.push("".to_string());
+    }
+
+    // Palette runtime: Palette.* edit the base colors in zero page and shade
+    // them into the shown buffer, which the NMI uploads while it is dirty.
+    // Fades and cycles advance after the vblank work, so each step is shown
+    // the frame after it is computed.
+    fn generate_palette_helpers(&mut self) {
+        self.output.push("; --- Palette Helpers ---".to_string());
+        let zp = |offset: u8| format!("${:02X}", PALETTE_STATE + offset);
+        let dirty = zp(0);
+        let sent = zp(1);
+        let level = zp(2);
+        let step = zp(3);
+        let speed = zp(4);
+        let counter = zp(5);
+        let first = zp(6);
+        let last = zp(7);
+        let cycle_speed = zp(8);
+        let cycle_counter = zp(9);
+
+        // Palette_Flush (NMI): uploads all 32 colors when dirty and records
+        // what that took out of the queue's byte budget
+        self.output.push("Palette_Flush:".to_string());
+        self.output.push(format!("  LDA {}", dirty));
+        self.output.push("  BNE Palette_Upload".to_string());
+        self.output.push(format!("  STA {}", sent));
+        self.output.push("  RTS".to_string());
+        self.output.push("Palette_Upload:".to_string());
+        self.output.push("  LDA #0".to_string());
+        self.output.push(format!("  STA {}", dirty));
+        self.output.push("  LDA $F8".to_string()); // +1 increment
+        self.output.push("  AND #$FB".to_string());
+        self.output.push("  STA $2000".to_string());
+        self.output.push("  LDA $2002".to_string());
+        self.output.push("  LDA #$3F".to_string());
+        self.output.push("  STA $2006".to_string());
+        self.output.push("  LDA #$00".to_string());
+        self.output.push("  STA $2006".to_string());
+        for i in 0..32 {
+            self.output
+                .push(format!("  LDA ${:02X}", PALETTE_SHOWN + i));
+            self.output.push("  STA $2007".to_string());
+        }
+        // Leave the palette so the backdrop does not show a stray color
+        self.output.push("  LDA #$3F".to_string());
+        self.output.push("  STA $2006".to_string());
+        self.output.push("  LDA #$00".to_string());
+        self.output.push("  STA $2006".to_string());
+        self.output.push("  STA $2006".to_string());
+        self.output.push("  STA $2006".to_string());
+        self.output.push(format!("  LDA #{}", PALETTE_UPLOAD_COST));
+        self.output.push(format!("  STA {}", sent));
+        self.output.push("  RTS".to_string());
+        self.output.push("".to_string());
+
+        // Palette_Update (NMI): one fade step every `speed` frames until the
+        // level reaches 0 or 4, then rotates the cycle range
+        self.output.push("Palette_Update:".to_string());
+        self.output.push(format!("  LDA {}", step));
+        self.output.push("  BEQ Palette_Cycle".to_string());
+        self.output.push(format!("  DEC {}", counter));
+        self.output.push("  BNE Palette_Cycle".to_string());
+        self.output.push(format!("  LDA {}", speed));
+        self.output.push(format!("  STA {}", counter));
+        self.output.push("  CLC".to_string());
+        self.output.push(format!("  LDA {}", level));
+        self.output.push(format!("  ADC {}", step));
+        self.output.push(format!("  STA {}", level));
+        self.output.push("  BEQ Palette_FadeDone".to_string());
+        self.output.push("  CMP #4".to_string());
+        self.output.push("  BNE Palette_FadeStep".to_string());
+        self.output.push("Palette_FadeDone:".to_string());
+        self.output.push("  LDA #0".to_string());
+        self.output.push(format!("  STA {}", step));
+        self.output.push("Palette_FadeStep:".to_string());
+        self.output.push("  JSR Palette_Compose".to_string());
+        self.output.push("Palette_Cycle:".to_string());
+        self.output.push(format!("  LDA {}", cycle_speed));
+        self.output.push("  BEQ Palette_UpdateDone".to_string());
+        self.output.push(format!("  DEC {}", cycle_counter));
+        self.output.push("  BNE Palette_UpdateDone".to_string());
+        self.output.push(format!("  STA {}", cycle_counter));
+        // The last color wraps around to the first entry
+        self.output.push(format!("  LDX {}", last));
+        self.output.push(format!("  LDY ${:02X}, X", PALETTE_BASE));
+        self.output.push("Palette_Rotate:".to_string());
+        self.output.push(format!("  CPX {}", first));
+        self.output.push("  BEQ Palette_Rotated".to_string());
+        self.output
+            .push(format!("  LDA ${:02X}, X", PALETTE_BASE - 1));
+        self.output.push(format!("  STA ${:02X}, X", PALETTE_BASE));
+        self.output.push("  DEX".to_string());
+        self.output.push("  JMP Palette_Rotate".to_string());
+        self.output.push("Palette_Rotated:".to_string());
+        self.output.push(format!("  STY ${:02X}, X", PALETTE_BASE));
+        self.output.push("  JMP Palette_Compose".to_string());
+        self.output.push("Palette_UpdateDone:".to_string());
+        self.output.push("  RTS".to_string());
+        self.output.push("".to_string());
+
+        // Palette_Compose: shades every base color into the shown buffer
+        self.output.push("Palette_Compose:".to_string());
+        self.output.push("  LDX #31".to_string());
+        self.output.push("Palette_ComposeLoop:".to_string());
+        self.output.push(format!("  LDA ${:02X}, X", PALETTE_BASE));
+        self.output.push("  JSR Palette_Shade".to_string());
+        self.output.push(format!("  STA ${:02X}, X", PALETTE_SHOWN));
+        self.output.push("  DEX".to_string());
+        self.output.push("  BPL Palette_ComposeLoop".to_string());
+        self.output.push("  LDA #1".to_string());
+        self.output.push(format!("  STA {}", dirty));
+        self.output.push("  RTS".to_string());
+        self.output.push("".to_string());
+
+        // Palette_Shade: A = color, one brightness row ($10) darker per
+        // level below 4, and black ($0F) once it drops under $00. Uses Y.
+        self.output.push("Palette_Shade:".to_string());
+        self.output.push(format!("  LDY {}", level));
+        self.output.push("Palette_ShadeLoop:".to_string());
+        self.output.push("  CPY #4".to_string());
+        self.output.push("  BCS Palette_Shaded".to_string());
+        self.output.push("  SEC".to_string());
+        self.output.push("  SBC #$10".to_string());
+        self.output.push("  BCC Palette_Black".to_string());
+        self.output.push("  INY".to_string());
+        self.output.push("  BNE Palette_ShadeLoop".to_string());
+        self.output.push("Palette_Black:".to_string());
+        self.output.push("  LDA #$0F".to_string());
+        self.output.push("Palette_Shaded:".to_string());
+        self.output.push("  RTS".to_string());
+        self.output.push("".to_string());
+
+        // Runtime_Palette_Set: X = entry (0-31), A = color
+        self.output.push("Runtime_Palette_Set:".to_string());
+        self.output.push("  AND #$3F".to_string());
+        self.output.push(format!("  STA ${:02X}, X", PALETTE_BASE));
+        self.output.push("  JSR Palette_Shade".to_string());
+        self.output.push(format!("  STA ${:02X}, X", PALETTE_SHOWN));
+        self.output.push("  LDA #1".to_string());
+        self.output.push(format!("  STA {}", dirty));
+        self.output.push("  RTS".to_string());
+        self.output.push("".to_string());
+
+        // Runtime_Palette_Load: A = set id; copies its 32 colors from the
+        // palette section into the base colors
+        let palettes = self.layout.addr(SECTION_PALETTE);
+        self.output.push("Runtime_Palette_Load:".to_string());
+        self.output.push("  STA $00".to_string());
+        self.output.push("  LDA #0".to_string());
+        self.output.push("  STA $01".to_string());
+        self.output.push("  LDX #5".to_string());
+        self.output.push("Palette_LoadShift:".to_string());
+        self.output.push("  ASL $00".to_string());
+        self.output.push("  ROL $01".to_string());
+        self.output.push("  DEX".to_string());
+        self.output.push("  BNE Palette_LoadShift".to_string());
+        self.output.push("  CLC".to_string());
+        self.output.push("  LDA $00".to_string());
+        self.output.push(format!("  ADC #${:02X}", palettes & 0xFF));
+        self.output.push("  STA $00".to_string());
+        self.output.push("  LDA $01".to_string());
+        self.output.push(format!("  ADC #${:02X}", palettes >> 8));
+        self.output.push("  STA $01".to_string());
+        self.output.push("  LDX #31".to_string());
+        self.output.push("  LDY #31".to_string());
+        self.output.push("Palette_LoadCopy:".to_string());
+        self.output.push("  LDA ($00),Y".to_string());
+        self.output.push(format!("  STA ${:02X}, X", PALETTE_BASE));
+        self.output.push("  DEY".to_string());
+        self.output.push("  DEX".to_string());
+        self.output.push("  BPL Palette_LoadCopy".to_string());
+        self.output.push("  JMP Palette_Compose".to_string());
+        self.output.push("".to_string());
+
+        // Runtime_Palette_Fade: A = frames for the whole fade (4 steps),
+        // X = step (1 = in, $FF = out). Fading in from full brightness starts
+        // from black; fading out at black does nothing.
+        self.output.push("Runtime_Palette_Fade:".to_string());
+        self.output.push("  LSR".to_string());
+        self.output.push("  LSR".to_string());
+        self.output.push("  BNE Palette_FadeSpeed".to_string());
+        self.output.push("  LDA #1".to_string());
+        self.output.push("Palette_FadeSpeed:".to_string());
+        self.output.push(format!("  STA {}", speed));
+        self.output.push(format!("  STA {}", counter));
+        self.output.push(format!("  LDA {}", level));
+        self.output.push("  CPX #1".to_string());
+        self.output.push("  BEQ Palette_FadeIn".to_string());
+        self.output.push("  CMP #0".to_string());
+        self.output.push("  BNE Palette_FadeGo".to_string());
+        self.output.push("  RTS".to_string());
+        self.output.push("Palette_FadeIn:".to_string());
+        self.output.push("  CMP #4".to_string());
+        self.output.push("  BNE Palette_FadeGo".to_string());
+        self.output.push("  LDA #0".to_string());
+        self.output.push(format!("  STA {}", level));
+        self.output.push("  JSR Palette_Compose".to_string());
+        self.output.push("  LDX #1".to_string());
+        self.output.push("Palette_FadeGo:".to_string());
+        self.output.push(format!("  STX {}", step));
+        self.output.push("  RTS".to_string());
+        self.output.push("".to_string());
+
+        // Runtime_Palette_Cycle: $00 = first entry, $01 = length, A = frames
+        // per step (0 stops). The range ends at its sub-palette's last color.
+        self.output.push("Runtime_Palette_Cycle:".to_string());
+        self.output.push("  LDX #0".to_string());
+        self.output.push(format!("  STX {}", cycle_speed));
+        self.output.push("  PHA".to_string());
+        self.output.push("  LDA $00".to_string());
+        self.output.push("  AND #$1F".to_string());
+        self.output.push(format!("  STA {}", first));
+        self.output.push("  ORA #3".to_string());
+        self.output.push("  STA $02".to_string());
+        self.output.push("  LDA $01".to_string());
+        self.output.push("  AND #$07".to_string());
+        self.output.push("  BNE Palette_CycleLength".to_string());
+        self.output.push("  LDA #1".to_string());
+        self.output.push("Palette_CycleLength:".to_string());
+        self.output.push("  CLC".to_string());
+        self.output.push(format!("  ADC {}", first));
+        self.output.push("  SEC".to_string());
+        self.output.push("  SBC #1".to_string());
+        self.output.push("  CMP $02".to_string());
+        self.output.push("  BCC Palette_CycleLast".to_string());
+        self.output.push("  LDA $02".to_string());
+        self.output.push("Palette_CycleLast:".to_string());
+        self.output.push(format!("  STA {}", last));
+        self.output.push("  PLA".to_string());
+        self.output.push(format!("  STA {}", cycle_counter));
+        self.output.push(format!("  STA {}", cycle_speed));
+        self.output.push("  RTS".to_string());
+        self.output.push("".to_string());
+    }
+
+    // Sprite-0 split (`compiler::hud`): the region above the split line is
+    // scrolled by the NMI as usual, the one below it once sprite 0 hits.
+    // The HUD shows nametable 0 where it sits on screen; the playfield keeps
+    // the Scroll.Set / camera scroll in $E0/$E1 and the PPUCTRL nametable.
+    fn generate_hud_helpers(&mut self) {
+        let Some(tile) = self.hud_tile else {
+            return;
+        };
+        let line = format!("${:02X}", HUD_STATE);
+        let position = format!("${:02X}", HUD_STATE + 1);
+        self.output.push("; --- HUD Helpers ---".to_string());
+
+        // Runtime_Hud_Split: A = split line (0 = none), X = HudPosition
+        // Variable lines above 200 are clamped so the hit timeout (line + 28)
+        // can neither wrap nor run into vblank
+        self.output.push("Runtime_Hud_Split:".to_string());
+        self.output.push(format!("  STX {}", position));
+        self.output.push("  CMP #201".to_string());
+        self.output.push("  BCC Hud_LineOk".to_string());
+        self.output.push("  LDA #200".to_string());
+        self.output.push("Hud_LineOk:".to_string());
+        self.output.push(format!("  STA {}", line));
+        self.output.push("  CMP #0".to_string());
+        self.output.push("  BNE Hud_PlaceSprite".to_string());
+        self.output.push("  LDA #$FF".to_string()); // Hide sprite 0
+        self.output.push("  STA $0200".to_string());
+        self.output.push("  RTS".to_string());
+
+        // Hud_PlaceSprite: sprite 0 behind the background, its first line
+        // on the line before the split (also the tail of Runtime_SpriteClear)
+        self.output.push("Hud_PlaceSprite:".to_string());
+        self.output.push(format!("  LDA {}", line));
+        self.output.push("  BEQ Hud_Placed".to_string());
+        self.output.push("  SEC".to_string());
+        self.output.push("  SBC #2".to_string());
+        self.output.push("  STA $0200".to_string());
+        self.output.push(format!("  LDA #${:02X}", tile));
+        self.output.push("  STA $0201".to_string());
+        self.output.push("  LDA #$20".to_string());
+        self.output.push("  STA $0202".to_string());
+        self.output.push(format!("  LDA #{}", hud::SPRITE_X));
+        self.output.push("  STA $0203".to_string());
+        self.output.push("Hud_Placed:".to_string());
+        self.output.push("  RTS".to_string());
+        self.output.push("".to_string());
+
+        // Hud_Split (NMI tail, after the scroll is set): $00 = nametable,
+        // $01 = y, $02 = x for below the split, then waits for the hit
+        self.output.push("Hud_Split:".to_string());
+        self.output.push(format!("  LDA {}", line));
+        self.output.push("  BNE Hud_Active".to_string());
+        self.output.push("  RTS".to_string());
+        self.output.push("Hud_Active:".to_string());
+        self.output.push(format!("  LDX {}", position));
+        self.output.push("  BNE Hud_Bottom".to_string());
+        // Top: the HUD from (0, 0), then the playfield continues at the row
+        // it would show on that line, wrapping into the other nametable
+        self.output.push("  LDA $F8".to_string());
+        self.output.push("  AND #$FC".to_string());
+        self.output.push("  STA $2000".to_string());
+        self.output.push("  LDA #0".to_string());
+        self.output.push("  STA $2005".to_string());
+        self.output.push("  STA $2005".to_string());
+        self.output.push("  LDA $F8".to_string());
+        self.output.push("  AND #$03".to_string());
+        self.output.push("  STA $00".to_string());
+        self.output.push("  LDA $E1".to_string());
+        self.output.push("  CLC".to_string());
+        self.output.push(format!("  ADC {}", line));
+        self.output.push("  BCS Hud_WrapHigh".to_string());
+        self.output.push("  CMP #240".to_string());
+        self.output.push("  BCC Hud_Row".to_string());
+        self.output.push("  SBC #240".to_string());
+        self.output.push("  JMP Hud_Wrap".to_string());
+        self.output.push("Hud_WrapHigh:".to_string());
+        self.output.push("  ADC #15".to_string()); // + 256 - 240 with carry
+        self.output.push("Hud_Wrap:".to_string());
+        self.output.push("  TAX".to_string());
+        self.output.push("  LDA $00".to_string());
+        self.output.push("  EOR #$02".to_string());
+        self.output.push("  STA $00".to_string());
+        self.output.push("  TXA".to_string());
+        self.output.push("Hud_Row:".to_string());
+        self.output.push("  STA $01".to_string());
+        self.output.push("  LDA $E0".to_string());
+        self.output.push("  STA $02".to_string());
+        self.output.push("  JMP Hud_Address".to_string());
+        // Bottom: the playfield as set, then the HUD's own rows
+        self.output.push("Hud_Bottom:".to_string());
+        self.output.push("  LDA #0".to_string());
+        self.output.push("  STA $00".to_string());
+        self.output.push("  STA $02".to_string());
+        self.output.push(format!("  LDA {}", line));
+        self.output.push("  STA $01".to_string());
+        // $2006 pair: nametable << 2 | y >> 6, then (y & $F8) << 2 | x >> 3
+        self.output.push("Hud_Address:".to_string());
+        self.output.push("  LDA $01".to_string());
+        self.output.push("  AND #$F8".to_string());
+        self.output.push("  ASL".to_string());
+        self.output.push("  ASL".to_string());
+        self.output.push("  STA $03".to_string());
+        self.output.push("  LDA $02".to_string());
+        self.output.push("  LSR".to_string());
+        self.output.push("  LSR".to_string());
+        self.output.push("  LSR".to_string());
+        self.output.push("  ORA $03".to_string());
+        self.output.push("  STA $03".to_string());
+        self.output.push("  LDA $01".to_string());
+        self.output.push("  CLC".to_string());
+        self.output.push("  ROL".to_string());
+        self.output.push("  ROL".to_string());
+        self.output.push("  ROL".to_string());
+        self.output.push("  AND #$03".to_string());
+        self.output.push("  STA $04".to_string());
+        self.output.push("  LDA $00".to_string());
+        self.output.push("  ASL".to_string());
+        self.output.push("  ASL".to_string());
+        self.output.push("  ORA $04".to_string());
+        self.output.push("  STA $00".to_string());
+        // Wait out last frame's hit, then poll ~110 cycles per line. A miss
+        // (rendering off, transparent background) gives up 28 lines after
+        // the split, before the next vblank.
+        self.output.push(format!("  LDA {}", line));
+        self.output.push("  CLC".to_string());
+        self.output.push("  ADC #28".to_string());
+        self.output.push("  TAY".to_string());
+        self.output.push("Hud_WaitClear:".to_string());
+        self.output.push("  BIT $2002".to_string());
+        self.output.push("  BVS Hud_WaitClear".to_string());
+        self.output.push("Hud_WaitLine:".to_string());
+        self.output.push("  LDX #10".to_string());
+        self.output.push("Hud_WaitHit:".to_string());
+        self.output.push("  BIT $2002".to_string());
+        self.output.push("  BVS Hud_Hit".to_string());
+        self.output.push("  DEX".to_string());
+        self.output.push("  BNE Hud_WaitHit".to_string());
+        self.output.push("  DEY".to_string());
+        self.output.push("  BNE Hud_WaitLine".to_string());
+        self.output.push("  RTS".to_string());
+        self.output.push("Hud_Hit:".to_string());
+        self.output.push("  LDA $00".to_string());
+        self.output.push("  STA $2006".to_string());
+        self.output.push("  LDA $01".to_string());
+        self.output.push("  STA $2005".to_string());
+        self.output.push("  LDA $02".to_string());
+        self.output.push("  STA $2005".to_string());
+        self.output.push("  LDA $03".to_string());
+        self.output.push("  STA $2006".to_string());
+        self.output.push("  RTS".to_string());
+        self.output.push("".to_string());
+    }
 
-        // Read Rel X
-        self.output.push("  LDA ($02), Y".to_string());
-        self.output.push("  CLC".to_string());
-        self.output.push("  ADC $14".to_string()); // + Screen X
-        self.output.push("  STA $05".to_string()); // Store Screen X
+    fn generate_ppu_queue_helpers(&mut self) {
+        self.output.push("; --- PPU Queue Helpers ---".to_string());
+        let head = PPU_QUEUE_HEAD;
+        let tail = PPU_QUEUE_TAIL;
+        let ctrl = PPU_QUEUE_SCRATCH;
+        let size = PPU_QUEUE_SCRATCH + 1;
+        let snapshot = PPU_QUEUE_SCRATCH + 2;
+        let budget = PPU_QUEUE_SCRATCH + 3;
+        let len = PPU_QUEUE_SCRATCH + 4;
+        let entry = |offset: u16| format!("${:04X}, X", PPU_QUEUE_START + offset);
+
+        // PpuQueue_Reserve: A = flags | length (bit 7 vertical +32, bit 6
+        // fill with one repeated byte; copies carry at most 32 bytes).
+        // Returns X = entry index with the control byte written; the caller
+        // stores the address at +1/+2 and data from +3, then commits with X
+        // past the data. Entries never wrap: a zero control byte sends the
+        // reader back to the start of the ring.
+        self.output.push("PpuQueue_Reserve:".to_string());
+        self.output.push(format!("  STA ${:04X}", ctrl));
+        self.output.push("  AND #$3F".to_string());
+        self.output.push("  TAY".to_string());
+        self.output.push(format!("  LDA ${:04X}", ctrl));
+        self.output.push("  AND #$40".to_string());
+        self.output.push("  BEQ PpuQueue_Sized".to_string());
+        self.output.push("  LDY #1".to_string());
+        self.output.push("PpuQueue_Sized:".to_string());
         self.output.push("  INY".to_string());
-
-        // Read Rel Y
-        self.output.push("  LDA ($02), Y".to_string());
-        self.output.push("  CLC".to_string());
-        self.output.push("  ADC $15".to_string()); // + Screen Y
-        self.output.push("  STA $06".to_string()); // Store Screen Y
         self.output.push("  INY".to_string());
-
-        // Read Tile
-        self.output.push("  LDA ($02), Y".to_string());
-        self.output.push("  PHA".to_string()); // Push Tile
         self.output.push("  INY".to_string());
+        self.output.push(format!("  STY ${:04X}", size));
+        self.output.push("PpuQueue_Retry:".to_string());
+        // The NMI only ever advances the head, so a stale copy is safe
+        self.output.push(format!("  LDA ${:04X}", head));
+        self.output.push(format!("  STA ${:04X}", snapshot));
+        self.output.push(format!("  LDX ${:04X}", tail));
+        self.output.push(format!("  CPX ${:04X}", snapshot));
+        self.output.push("  BCC PpuQueue_Below".to_string());
+        self.output.push("  TXA".to_string());
+        self.output.push("  CLC".to_string());
+        self.output.push(format!("  ADC ${:04X}", size));
+        self.output.push(format!("  CMP #${:02X}", PPU_QUEUE_SIZE));
+        self.output.push("  BCC PpuQueue_Fits".to_string());
+        // No room before the end of the ring: wrap if the start is free
+        self.output.push(format!("  LDA ${:04X}", size));
+        self.output.push(format!("  CMP ${:04X}", snapshot));
+        self.output.push("  BCS PpuQueue_Full".to_string());
+        self.output.push("  LDA #0".to_string());
+        self.output.push(format!("  STA {}", entry(0)));
+        self.output.push("  TAX".to_string());
+        self.output.push("  JMP PpuQueue_Fits".to_string());
+        self.output.push("PpuQueue_Below:".to_string());
+        self.output.push("  TXA".to_string());
+        self.output.push("  CLC".to_string());
+        self.output.push(format!("  ADC ${:04X}", size));
+        self.output.push(format!("  CMP ${:04X}", snapshot));
+        self.output.push("  BCC PpuQueue_Fits".to_string());
+        // Full: flag the overflow, then wait for the NMI to drain the ring.
+        // With NMI off, or inside the NMI handler, drain it right away.
+        self.output.push("PpuQueue_Full:".to_string());
+        self.output.push("  LDA #$FF".to_string());
+        self.output
+            .push(format!("  STA ${:04X}", PPU_QUEUE_OVERFLOW));
+        self.output.push("  LDA $F8".to_string());
+        self.output.push("  BPL PpuQueue_Drain".to_string());
+        self.output.push(format!("  LDA ${:04X}", PPU_QUEUE_IN_NMI));
+        self.output.push("  BNE PpuQueue_Drain".to_string());
+        self.output.push("PpuQueue_Wait:".to_string());
+        self.output.push(format!("  LDA ${:04X}", head));
+        self.output.push(format!("  CMP ${:04X}", snapshot));
+        self.output.push("  BEQ PpuQueue_Wait".to_string());
+        self.output.push("  JMP PpuQueue_Retry".to_string());
+        self.output.push("PpuQueue_Drain:".to_string());
+        self.output.push("  JSR PpuQueue_Flush".to_string());
+        self.output.push("  JMP PpuQueue_Retry".to_string());
+        self.output.push("PpuQueue_Fits:".to_string());
+        self.output.push(format!("  LDA ${:04X}", ctrl));
+        self.output.push(format!("  STA {}", entry(0)));
+        self.output.push("  RTS".to_string());
+        self.output.push("".to_string());
 
-        // Read Attr
-        self.output.push("  LDA ($02), Y".to_string());
-        self.output.push("  PHA".to_string()); // Push Attr
-        self.output.push("  INY".to_string());
+        // PpuQueue_Commit: X = entry index + data length; publishes the entry
+        self.output.push("PpuQueue_Commit:".to_string());
+        self.output.push("  TXA".to_string());
+        self.output.push("  CLC".to_string());
+        self.output.push("  ADC #3".to_string());
+        self.output.push(format!("  STA ${:04X}", tail));
+        self.output.push("  RTS".to_string());
+        self.output.push("".to_string());
 
-        // Write to OAM
-        // X points to current slot (starting with Y coord at Base)
-        self.output.push("  LDA $06".to_string()); // Y
-        self.output.push("  STA $0200, X".to_string());
+        // PpuQueue_Flush: writes entries until the ring is empty or the next
+        // one would exceed the frame budget. Uses only A/X/Y and the scratch.
+        self.output.push("PpuQueue_Flush:".to_string());
+        self.output.push(format!("  LDA #{}", PPU_QUEUE_BUDGET));
+        self.output.push("  SEC".to_string());
+        self.output
+            .push(format!("  SBC ${:02X}", PALETTE_STATE + 1));
+        self.output.push(format!("  STA ${:04X}", budget));
+        self.output.push(format!("  LDX ${:04X}", head));
+        self.output.push("PpuQueue_Next:".to_string());
+        self.output.push(format!("  CPX ${:04X}", tail));
+        self.output.push("  BEQ PpuQueue_Done".to_string());
+        self.output.push(format!("  LDA {}", entry(0)));
+        self.output.push("  BNE PpuQueue_Entry".to_string());
+        self.output.push("  LDX #0".to_string()); // Wrap marker
+        self.output.push("  JMP PpuQueue_Next".to_string());
+        self.output.push("PpuQueue_Entry:".to_string());
+        self.output.push("  AND #$3F".to_string());
+        self.output.push(format!("  STA ${:04X}", len));
+        self.output.push(format!("  LDA ${:04X}", budget));
+        self.output.push(format!("  CMP ${:04X}", len));
+        self.output.push("  BCC PpuQueue_Done".to_string());
+        self.output.push(format!("  SBC ${:04X}", len));
+        self.output.push(format!("  STA ${:04X}", budget));
+        // Direction: bit 7 selects the +32 (vertical) increment
+        self.output.push("  LDA $F8".to_string());
+        self.output.push("  AND #$FB".to_string());
+        self.output.push(format!("  LDY {}", entry(0)));
+        self.output.push("  BPL PpuQueue_Inc".to_string());
+        self.output.push("  ORA #$04".to_string());
+        self.output.push("PpuQueue_Inc:".to_string());
+        self.output.push("  STA $2000".to_string());
+        self.output.push("  LDA $2002".to_string());
+        self.output.push(format!("  LDA {}", entry(1)));
+        self.output.push("  STA $2006".to_string());
+        self.output.push(format!("  LDA {}", entry(2)));
+        self.output.push("  STA $2006".to_string());
+        self.output.push(format!("  LDY ${:04X}", len));
+        self.output.push(format!("  LDA {}", entry(0)));
+        self.output.push("  AND #$40".to_string());
+        self.output.push("  BNE PpuQueue_Fill".to_string());
+        self.output.push("PpuQueue_Copy:".to_string());
+        self.output.push(format!("  LDA {}", entry(3)));
+        self.output.push("  STA $2007".to_string());
         self.output.push("  INX".to_string());
+        self.output.push("  DEY".to_string());
+        self.output.push("  BNE PpuQueue_Copy".to_string());
+        self.output.push("  JMP PpuQueue_Skip".to_string());
+        self.output.push("PpuQueue_Fill:".to_string());
+        self.output.push(format!("  LDA {}", entry(3)));
+        self.output.push("PpuQueue_FillLoop:".to_string());
+        self.output.push("  STA $2007".to_string());
+        self.output.push("  DEY".to_string());
+        self.output.push("  BNE PpuQueue_FillLoop".to_string());
+        self.output.push("  INX".to_string());
+        self.output.push("PpuQueue_Skip:".to_string());
+        self.output.push("  INX".to_string());
+        self.output.push("  INX".to_string());
+        self.output.push("  INX".to_string());
+        self.output.push("  JMP PpuQueue_Next".to_string());
+        self.output.push("PpuQueue_Done:".to_string());
+        self.output.push(format!("  STX ${:04X}", head));
+        self.output.push("  LDA $F8".to_string());
+        self.output.push("  STA $2000".to_string());
+        self.output.push("  RTS".to_string());
+        self.output.push("".to_string());
 
-        self.output.push("  PLA".to_string()); // Attr (Popped first)
-        self.output.push("  STA $0201, X".to_string()); // Writes to Base+2 (Since X is Base+1)
-
-        self.output.push("  PLA".to_string()); // Tile
-        self.output.push("  STA $0200, X".to_string()); // Writes to Base+1 (Since X is Base+1)
-
-        self.output.push("  INX".to_string()); // X -> Base+2
-        self.output.push("  INX".to_string()); // X -> Base+3
-
-        self.output.push("  LDA $05".to_string()); // Screen X
-        self.output.push("  STA $0200, X".to_string()); // Writes to Base+3
-
-        self.output.push("  INX".to_string()); // X -> Base+4 (Next Sprite Base)
-        self.output.push("  STX $19".to_string()); // Save OAM Ptr
-
-        self.output.push("  INC $1B".to_string()); // Increment Global Sprite Count
-
-        self.output.push("  JMP SpriteDraw_Loop".to_string());
-
-        self.output.push("SpriteDraw_Full:".to_string());
-        // Skip input data (4 bytes per tile) to stay in sync
-        self.output.push("  TYA".to_string());
+        // Runtime_PPU_Fill: $00/$01 = PPU address, $02 = value, $03 = count.
+        // Queued as fill entries of up to 63 bytes each.
+        self.output.push("Runtime_PPU_Fill:".to_string());
+        self.output.push("  LDA $03".to_string());
+        self.output.push("  BEQ PpuFill_Done".to_string());
+        self.output.push("  CMP #64".to_string());
+        self.output.push("  BCC PpuFill_Len".to_string());
+        self.output.push("  LDA #63".to_string());
+        self.output.push("PpuFill_Len:".to_string());
+        self.output.push("  STA $04".to_string());
+        self.output.push("  ORA #$40".to_string());
+        self.output.push("  JSR PpuQueue_Reserve".to_string());
+        self.output.push("  LDA $01".to_string());
+        self.output.push(format!("  STA {}", entry(1)));
+        self.output.push("  LDA $00".to_string());
+        self.output.push(format!("  STA {}", entry(2)));
+        self.output.push("  LDA $02".to_string());
+        self.output.push(format!("  STA {}", entry(3)));
+        self.output.push("  INX".to_string());
+        self.output.push("  JSR PpuQueue_Commit".to_string());
         self.output.push("  CLC".to_string());
-        self.output.push("  ADC #4".to_string());
-        self.output.push("  TAY".to_string());
-        self.output.push("  JMP SpriteDraw_Loop".to_string());
-
-        self.output.push("SpriteDraw_Done:".to_string());
+        self.output.push("  LDA $00".to_string());
+        self.output.push("  ADC $04".to_string());
+        self.output.push("  STA $00".to_string());
+        self.output.push("  LDA $01".to_string());
+        self.output.push("  ADC #0".to_string());
+        self.output.push("  STA $01".to_string());
+        self.output.push("  SEC".to_string());
+        self.output.push("  LDA $03".to_string());
+        self.output.push("  SBC $04".to_string());
+        self.output.push("  STA $03".to_string());
+        self.output.push("  JMP Runtime_PPU_Fill".to_string());
+        self.output.push("PpuFill_Done:".to_string());
         self.output.push("  RTS".to_string());
         self.output.push("".to_string());
     }
@@ -3123,11 +7763,10 @@ impl CodeGenerator {
         self.output.push("; --- Text Helpers ---".to_string());
         // Runtime_Text_Print
         // Inputs: $14 (X), $15 (Y), $16/$17 (Str Ptr), $18 (Offset)
-        // Writes to PPU $2006/$2007
+        // Queues the tiles on the PPU update queue
         // Addr = $2000 + Y*32 + X
         // Y*32 = (Y << 5)
         self.output.push("Runtime_Text_Print:".to_string());
-        self.output.push("  LDA $2002".to_string()); // Reset latch
 
         // Calc Addr
         self.output.push("  LDA #0".to_string());
@@ -3152,22 +7791,50 @@ impl CodeGenerator {
         self.output.push("  ADC #$20".to_string()); // + Base $2000 (High Byte $20)
         self.output.push("  STA $01".to_string()); // High Addr
 
-        // Set PPU Addr
+        // Queue the string in chunks of up to 32 characters
+        self.output.push("  LDY #0".to_string());
+        self.output.push("Text_Chunk:".to_string());
+        self.output.push("  STY $02".to_string()); // Chunk start
+        self.output.push("  LDX #0".to_string());
+        self.output.push("Text_Count:".to_string());
+        self.output.push("  LDA ($16),Y".to_string());
+        self.output.push("  BEQ Text_Counted".to_string());
+        self.output.push("  INY".to_string());
+        self.output.push("  INX".to_string());
+        self.output.push("  CPX #32".to_string());
+        self.output.push("  BNE Text_Count".to_string());
+        self.output.push("Text_Counted:".to_string());
+        self.output.push("  STX $03".to_string()); // Chunk length
+        self.output.push("  TXA".to_string());
+        self.output.push("  BEQ Text_Done".to_string());
+        self.output.push("  JSR PpuQueue_Reserve".to_string());
         self.output.push("  LDA $01".to_string());
-        self.output.push("  STA $2006".to_string());
+        self.output
+            .push(format!("  STA ${:04X}, X", PPU_QUEUE_START + 1));
         self.output.push("  LDA $00".to_string());
-        self.output.push("  STA $2006".to_string());
-
-        // Loop Chars
-        self.output.push("  LDY #0".to_string());
+        self.output
+            .push(format!("  STA ${:04X}, X", PPU_QUEUE_START + 2));
+        self.output.push("  LDY $02".to_string());
         self.output.push("Text_Loop:".to_string());
         self.output.push("  LDA ($16),Y".to_string());
-        self.output.push("  BEQ Text_Done".to_string());
         self.output.push("  CLC".to_string());
         self.output.push("  ADC $18".to_string()); // Add Offset
-        self.output.push("  STA $2007".to_string());
+        self.output
+            .push(format!("  STA ${:04X}, X", PPU_QUEUE_START + 3));
+        self.output.push("  INX".to_string());
         self.output.push("  INY".to_string());
+        self.output.push("  DEC $03".to_string());
         self.output.push("  BNE Text_Loop".to_string());
+        self.output.push("  JSR PpuQueue_Commit".to_string());
+        // Next chunk continues 32 tiles on
+        self.output.push("  CLC".to_string());
+        self.output.push("  LDA $00".to_string());
+        self.output.push("  ADC #32".to_string());
+        self.output.push("  STA $00".to_string());
+        self.output.push("  LDA $01".to_string());
+        self.output.push("  ADC #0".to_string());
+        self.output.push("  STA $01".to_string());
+        self.output.push("  JMP Text_Chunk".to_string());
         self.output.push("Text_Done:".to_string());
         self.output.push("  RTS".to_string());
         self.output.push("".to_string());
@@ -3253,11 +7920,9 @@ impl CodeGenerator {
 
     fn collect_strings_expr(&mut self, expr: &Expression) {
         match expr {
-            Expression::StringLiteral(s) => {
-                if !self.string_literals.contains_key(s) {
-                    let label = self.new_label();
-                    self.string_literals.insert(s.clone(), label);
-                }
+            Expression::StringLiteral(s) if !self.string_literals.contains_key(s) => {
+                let label = self.new_label();
+                self.string_literals.insert(s.clone(), label);
             }
             Expression::BinaryOp(l, _, r) => {
                 self.collect_strings_expr(l);
@@ -3285,6 +7950,8 @@ impl CodeGenerator {
             .insert("USER_DATA_START".to_string(), data_table_addr);
         data_table_addr += 2;
 
+        let mut frames = Vec::new();
+
         for decl in &program.declarations {
             match decl {
                 TopLevel::Dim(name, dtype, _) => {
@@ -3293,42 +7960,45 @@ impl CodeGenerator {
                         .push(format!("; {} @ ${:04X}", name, self.ram_pointer));
                     let size = self.get_type_size(dtype);
                     self.ram_pointer += size;
-                    if self.ram_pointer > 0x0800 {
+                    if self.ram_pointer > VAR_END_RAM {
                         return Err(format!(
-                            "RAM overflow: Variable '{}' allocation exceeded safe memory limit ($07FF)",
-                            name
+                            "RAM overflow: Variable '{}' allocation exceeded safe memory limit (${:04X})",
+                            name,
+                            VAR_END_RAM - 1
                         ));
                     }
                 }
-                TopLevel::Sub(sub_name, params, _) => {
-                    self.symbol_table.enter_scope();
-                    let mut sig_params = Vec::new();
-                    for (param_name, param_type) in params {
-                        self.symbol_table.define(
-                            param_name.clone(),
-                            param_type.clone(),
-                            SymbolKind::Param,
-                        )?;
-                        self.symbol_table
-                            .assign_address(param_name, self.ram_pointer)?;
-                        self.output.push(format!(
-                            "; {}.{} @ ${:04X}",
-                            sub_name, param_name, self.ram_pointer
+                TopLevel::Password(fields, alphabet, key) => {
+                    let format = PasswordFormat::new(fields.clone(), alphabet.as_deref(), *key)?;
+                    self.password_ram = self.ram_pointer;
+                    self.output
+                        .push(format!("; PASSWORD buffers @ ${:04X}", self.ram_pointer));
+                    self.ram_pointer += (format.byte_len() + 1 + format.length() + 1) as u16;
+                    if self.ram_pointer > VAR_END_RAM {
+                        return Err(format!(
+                            "RAM overflow: PASSWORD buffers exceeded safe memory limit (${:04X})",
+                            VAR_END_RAM - 1
+                        ));
+                    }
+                    self.password = Some(format);
+                }
+                TopLevel::SaveDim(name, dtype) => {
+                    self.symbol_table.assign_address(name, self.save_pointer)?;
+                    self.output
+                        .push(format!("; {} @ ${:04X} (SAVE)", name, self.save_pointer));
+                    self.save_pointer += self.get_type_size(dtype);
+                    if self.save_pointer > SAVE_CHECKSUM_ADDR {
+                        return Err(format!(
+                            "Save RAM overflow: Variable '{}' does not fit below ${:04X}",
+                            name, SAVE_CHECKSUM_ADDR
                         ));
-                        sig_params.push((self.ram_pointer, param_type.clone()));
-                        self.ram_pointer += self.get_type_size(param_type);
-                        if self.ram_pointer > 0x0800 {
-                            return Err(format!(
-                                "RAM overflow: Parameter '{}' in sub '{}' exceeded safe memory limit ($07FF)",
-                                param_name, sub_name
-                            ));
-                        }
                     }
-                    self.sub_signatures.insert(sub_name.clone(), sig_params);
+                }
+                TopLevel::Sub(sub_name, params, _) => {
+                    frames.push((sub_name.clone(), params.clone()));
                     self.data_table_offsets
                         .insert(sub_name.clone(), data_table_addr);
                     data_table_addr += 2;
-                    self.symbol_table.exit_scope();
                 }
                 TopLevel::Interrupt(name, _) => {
                     if name.to_uppercase() == "NMI" || name.to_uppercase() == "IRQ" {
@@ -3343,18 +8013,6 @@ impl CodeGenerator {
                         .insert(label.clone(), data_table_addr);
                     data_table_addr += 2;
                 }
-                TopLevel::Metasprite(name, tiles) => {
-                    self.data_table_offsets
-                        .insert(name.clone(), data_table_addr);
-                    // Size: Count(1) + Tiles(N * 4)
-                    data_table_addr += 1 + (tiles.len() as u16) * 4;
-                }
-                TopLevel::Animation(name, frames, _) => {
-                    self.data_table_offsets
-                        .insert(name.clone(), data_table_addr);
-                    // Size: Count(1) + Loop(1) + Frames(N * 3)
-                    data_table_addr += 2 + (frames.len() as u16) * 3;
-                }
                 TopLevel::Metatile(name, _, _) => {
                     self.data_table_offsets
                         .insert(name.clone(), data_table_addr);
@@ -3370,6 +8028,14 @@ impl CodeGenerator {
                 _ => {}
             }
         }
+        let base = self.layout.addr(SECTION_SPRITES);
+        self.sprite_addrs = sprites::offsets(program)
+            .into_iter()
+            .map(|(name, offset)| (name, base + offset))
+            .collect();
+        self.allocate_actor_table()?;
+        self.allocate_sub_frames(program, &frames)?;
+
         let mut sorted_strings: Vec<_> = self.string_literals.iter().collect();
         sorted_strings.sort_by_key(|(k, _)| *k);
         for (_, label) in sorted_strings {
@@ -3382,23 +8048,128 @@ impl CodeGenerator {
         Ok(())
     }
 
+    // Every view of the actor table (Actors and one per ACTOR) shares its
+    // address; eight bytes of state follow the slots.
+    fn allocate_actor_table(&mut self) -> Result<(), String> {
+        if self.actors.is_empty() {
+            return Ok(());
+        }
+        self.actor_stride = self
+            .symbol_table
+            .resolve("Actor")
+            .and_then(|sym| sym.value)
+            .unwrap_or(actors::HEADER_SIZE as i32) as u16;
+        self.actor_ram = self.ram_pointer;
+        self.symbol_table
+            .assign_address("Actors", self.ram_pointer)?;
+        for actor in self.actors.clone() {
+            self.symbol_table
+                .assign_address(&actor.name, self.ram_pointer)?;
+        }
+        self.output.push(format!(
+            "; Actors @ ${:04X}: {} slots of {} bytes",
+            self.ram_pointer, self.actor_slots, self.actor_stride
+        ));
+        self.ram_pointer += self.actor_slots as u16 * self.actor_stride + 8;
+        if self.ram_pointer > VAR_END_RAM {
+            return Err(format!(
+                "RAM overflow: the actor table exceeded safe memory limit (${:04X})",
+                VAR_END_RAM - 1
+            ));
+        }
+        Ok(())
+    }
+
+    // SUB parameters are placed after the globals. Using the call graph, frames
+    // of SUBs that can never be active at the same time share the same bytes.
+    fn allocate_sub_frames(
+        &mut self,
+        program: &Program,
+        frames: &[(String, Vec<(String, DataType)>)],
+    ) -> Result<(), String> {
+        let call_graph = CallGraph::build(program);
+        let base = self.ram_pointer;
+        let mut placed: Vec<(String, u16, u16)> = Vec::new(); // Name, Offset, Size
+        let mut high_water: u16 = 0;
+        let mut total_saved: u16 = 0;
+
+        for (sub_name, params) in frames {
+            let size: u16 = params.iter().map(|(_, t)| self.get_type_size(t)).sum();
+            let mut offset = 0;
+            while let Some((_, o, s)) = placed.iter().find(|(other, o, s)| {
+                offset < o + s && *o < offset + size && call_graph.may_overlap(sub_name, other)
+            }) {
+                offset = o + s;
+            }
+
+            let mut addr = base + offset;
+            let mut sig_params = Vec::new();
+            for (param_name, param_type) in params {
+                self.output
+                    .push(format!("; {}.{} @ ${:04X}", sub_name, param_name, addr));
+                sig_params.push((addr, param_type.clone()));
+                addr += self.get_type_size(param_type);
+                if addr > VAR_END_RAM {
+                    return Err(format!(
+                        "RAM overflow: Parameter '{}' in sub '{}' exceeded safe memory limit (${:04X})",
+                        param_name,
+                        sub_name,
+                        VAR_END_RAM - 1
+                    ));
+                }
+            }
+            self.sub_signatures.insert(sub_name.clone(), sig_params);
+
+            if size > 0 {
+                let new_high = high_water.max(offset + size);
+                let saved = size - (new_high - high_water);
+                high_water = new_high;
+                total_saved += saved;
+                self.output.push(format!(
+                    "; {} frame: {} bytes @ ${:04X}, overlay saves {} bytes",
+                    sub_name,
+                    size,
+                    base + offset,
+                    saved
+                ));
+                placed.push((sub_name.clone(), offset, size));
+            }
+        }
+
+        self.ram_pointer = base + high_water;
+        self.output.push(format!(
+            "; SUB frames: {} bytes, {} bytes saved by overlays",
+            high_water, total_saved
+        ));
+        Ok(())
+    }
+
     fn generate_top_level(&mut self, decl: &TopLevel) -> Result<(), String> {
         self.select_stack_depth = 0;
         match decl {
-            TopLevel::Sub(name, _, body) => {
+            TopLevel::Sub(name, params, body) => {
                 self.output.push(format!("{}:", name));
                 self.symbol_table.enter_scope();
-                self.generate_block(body)?;
+                // Re-bind parameters to the addresses chosen in allocate_memory
+                if let Some(sig) = self.sub_signatures.get(name).cloned() {
+                    for ((param_name, param_type), (addr, _)) in params.iter().zip(sig) {
+                        self.symbol_table.define(
+                            param_name.clone(),
+                            param_type.clone(),
+                            SymbolKind::Param,
+                        )?;
+                        self.symbol_table.assign_address(param_name, addr)?;
+                    }
+                }
+                self.generate_body(name, body)?;
                 self.symbol_table.exit_scope();
-                self.output.push("  RTS".to_string());
                 self.output.push("".to_string());
             }
             TopLevel::Interrupt(name, body) => {
                 self.output.push(format!("{}:", name));
                 self.symbol_table.enter_scope();
-                self.generate_block(body)?;
+                self.generate_body(name, body)?;
                 self.symbol_table.exit_scope();
-                self.output.push("  RTS".to_string());
                 self.output.push("".to_string());
             }
             TopLevel::Asm(lines) => {
@@ -3411,6 +8182,23 @@ impl CodeGenerator {
         Ok(())
     }
 
+    // Bodies within the IR subset go through lowering, optimization and
+    // instruction selection; everything else uses the direct emitter.
+    fn generate_body(&mut self, name: &str, body: &[Statement]) -> Result<(), String> {
+        if self.use_ir {
+            match ir::compile_body(name, body, &self.symbol_table, &self.sub_signatures) {
+                Ok(lines) => {
+                    self.output.extend(lines);
+                    return Ok(());
+                }
+                Err(e) => self.output.push(format!("; IR fallback: {}", e)),
+            }
+        }
+        self.generate_block(body)?;
+        self.output.push("  RTS".to_string());
+        Ok(())
+    }
+
     fn generate_block(&mut self, statements: &[Statement]) -> Result<(), String> {
         for stmt in statements {
             self.generate_statement(stmt)?;
@@ -3445,6 +8233,12 @@ impl CodeGenerator {
                                 }
                             }
                         },
+                        DataType::String => {
+                            // Pointer: low byte in A, high byte in X
+                            self.generate_expression(expr)?;
+                            self.output.push(format!("  STA ${:04X}", addr));
+                            self.output.push(format!("  STX ${:04X}", addr + 1));
+                        }
                         _ => {
                             self.generate_expression(expr)?;
                             self.output.push(format!("  STA ${:04X}", addr));
@@ -3509,6 +8303,7 @@ impl CodeGenerator {
                                     self.output.push("  STA $16".to_string());
                                     self.output.push("  STX $17".to_string());
                                 }
+                                self.uses_ppu_queue = true;
                                 self.output.push("  JSR Runtime_Text_Print".to_string());
                                 return Ok(());
                             } else if member.eq_ignore_ascii_case("SetOffset") {
@@ -3529,7 +8324,14 @@ impl CodeGenerator {
                                 self.generate_expression(&args[2])?;
                                 self.output.push("  STA $16".to_string());
                                 self.output.push("  STX $17".to_string());
-                                self.output.push("  JSR Runtime_SpriteDraw".to_string());
+                                // Arg 3: Flip Flags -> $07
+                                if let Some(flags) = args.get(3) {
+                                    self.generate_flip_flags(flags)?;
+                                    self.output
+                                        .push("  JSR Runtime_SpriteDrawFlags".to_string());
+                                } else {
+                                    self.output.push("  JSR Runtime_SpriteDraw".to_string());
+                                }
                                 return Ok(());
                             } else if member.eq_ignore_ascii_case("Clear") {
                                 self.output.push("  JSR Runtime_SpriteClear".to_string());
@@ -3538,6 +8340,20 @@ impl CodeGenerator {
                                 self.generate_expression(&args[0])?;
                                 self.output.push("  STA $1C".to_string());
                                 return Ok(());
+                            } else if member.eq_ignore_ascii_case("Reserve") {
+                                // Slots 0..n-1 (at most 64), from the next Sprite.Clear
+                                self.generate_expression(&args[0])?;
+                                let fits = self.new_label();
+                                self.output.push("  CMP #65".to_string());
+                                self.output.push(format!("  BCC {}", fits));
+                                self.output.push("  LDA #64".to_string());
+                                self.output.push(format!("{}:", fits));
+                                self.output.push(format!("  STA ${:02X}", SPRITE_STATE));
+                                return Ok(());
+                            } else if member.eq_ignore_ascii_case("Layer") {
+                                self.generate_expression(&args[0])?;
+                                self.output.push(format!("  STA ${:02X}", SPRITE_STATE + 1));
+                                return Ok(());
                             }
                         } else if base_name.eq_ignore_ascii_case("Pool")
                             && member.eq_ignore_ascii_case("Despawn")
@@ -3652,7 +8468,13 @@ impl CodeGenerator {
                                     ((state_addr >> 8) & 0xFF) as u8
                                 ));
                                 self.output.push("  STA $17".to_string());
-                                self.output.push("  JSR Runtime_Anim_Draw".to_string());
+                                // Arg 3: Flip Flags -> $07
+                                if let Some(flags) = args.get(3) {
+                                    self.generate_flip_flags(flags)?;
+                                    self.output.push("  JSR Runtime_Anim_DrawFlags".to_string());
+                                } else {
+                                    self.output.push("  JSR Runtime_Anim_Draw".to_string());
+                                }
                                 return Ok(());
                             }
                         } else if base_name.eq_ignore_ascii_case("Scroll") {
@@ -3679,12 +8501,13 @@ impl CodeGenerator {
                                 }
 
                                 // Arg 1: Array (Address)
-                                self.generate_address_expression(&args[1])?; // $02/$03
-                                self.output.push("  LDA $02".to_string());
-                                self.output.push("  LDX $03".to_string());
+                                self.generate_data_address(&args[1])?;
 
+                                self.uses_ppu_queue = true;
                                 self.output
                                     .push("  JSR Runtime_Scroll_LoadColumn".to_string());
+                                self.output.push("  PLA".to_string());
+                                self.output.push("  PLA".to_string());
                                 return Ok(());
                             } else if member.eq_ignore_ascii_case("LoadRow") {
                                 // Arg 0: Y (Value)
@@ -3701,23 +8524,87 @@ impl CodeGenerator {
                                 }
 
                                 // Arg 1: Array (Address)
-                                self.generate_address_expression(&args[1])?; // $02/$03
-                                self.output.push("  LDA $02".to_string());
-                                self.output.push("  LDX $03".to_string());
+                                self.generate_data_address(&args[1])?;
 
+                                self.uses_ppu_queue = true;
                                 self.output.push("  JSR Runtime_Scroll_LoadRow".to_string());
+                                self.output.push("  PLA".to_string());
+                                self.output.push("  PLA".to_string());
                                 return Ok(());
                             }
+                        } else if base_name.eq_ignore_ascii_case("Save")
+                            && member.eq_ignore_ascii_case("Commit")
+                        {
+                            self.output.push("  JSR Runtime_Save_Commit".to_string());
+                            return Ok(());
+                        } else if base_name.eq_ignore_ascii_case("CHR")
+                            && member.eq_ignore_ascii_case("Load")
+                        {
+                            return self.generate_chr_load(args);
+                        } else if base_name.eq_ignore_ascii_case("Screen") {
+                            return self.generate_screen_load(member, args);
+                        } else if base_name.eq_ignore_ascii_case("Palette") {
+                            return self.generate_palette_call(member, args);
+                        } else if base_name.eq_ignore_ascii_case("HUD") {
+                            return self.generate_hud_split(args);
+                        } else if base_name.eq_ignore_ascii_case("Actors") {
+                            return self.generate_actors_call(member, args).map(|_| ());
+                        } else if base_name.eq_ignore_ascii_case("Camera") {
+                            return self.generate_camera_call(member, args);
+                        } else if base_name.eq_ignore_ascii_case("World") {
+                            return self.generate_world_call(member, args);
+                        } else if base_name.eq_ignore_ascii_case("Physics") {
+                            return self.generate_physics_move(args).map(|_| ());
+                        } else if (base_name.eq_ignore_ascii_case("PRG")
+                            || base_name.eq_ignore_ascii_case("CHR"))
+                            && member.eq_ignore_ascii_case("Bank")
+                        {
+                            let routine = if base_name.eq_ignore_ascii_case("PRG") {
+                                "SetPRG"
+                            } else {
+                                "SetCHR"
+                            };
+                            self.generate_expression(&args[0])?;
+                            self.output
+                                .push(format!("  JSR {}_{}", self.mapper.name(), routine));
+                            return Ok(());
                         } else if base_name.eq_ignore_ascii_case("PPU") {
                             if member.eq_ignore_ascii_case("Ctrl") {
                                 self.generate_expression(&args[0])?;
+                                if self.tall_sprites {
+                                    self.output.push("  ORA #$20".to_string()); // 8x16 sprites
+                                }
                                 self.output.push("  STA $F8".to_string()); // Shadow
                                 self.output.push("  STA $2000".to_string());
                                 return Ok(());
                             } else if member.eq_ignore_ascii_case("Mask") {
                                 self.generate_expression(&args[0])?;
+                                self.output.push("  STA $F9".to_string()); // Shadow
                                 self.output.push("  STA $2001".to_string());
                                 return Ok(());
+                            } else if member.eq_ignore_ascii_case("Fill") {
+                                // Address, value and count -> $00/$01, $02, $03
+                                let addr_type = self.generate_expression(&args[0])?;
+                                self.output.push("  PHA".to_string()); // Low
+                                if addr_type == DataType::Byte {
+                                    self.output.push("  LDA #0".to_string());
+                                } else {
+                                    self.output.push("  TXA".to_string());
+                                }
+                                self.output.push("  PHA".to_string()); // High
+                                self.generate_expression(&args[1])?;
+                                self.output.push("  PHA".to_string());
+                                self.generate_expression(&args[2])?;
+                                self.output.push("  STA $03".to_string());
+                                self.output.push("  PLA".to_string());
+                                self.output.push("  STA $02".to_string());
+                                self.output.push("  PLA".to_string());
+                                self.output.push("  STA $01".to_string());
+                                self.output.push("  PLA".to_string());
+                                self.output.push("  STA $00".to_string());
+                                self.uses_ppu_queue = true;
+                                self.output.push("  JSR Runtime_PPU_Fill".to_string());
+                                return Ok(());
                             }
                         }
                     }
@@ -3918,6 +8805,33 @@ impl CodeGenerator {
                     self.output.push("  STA $05".to_string());
                 }
             }
+            Statement::OnScanline(line, routine) => {
+                let index = match self.scanline_handlers.iter().position(|h| h == routine) {
+                    Some(i) => i + 1,
+                    None => {
+                        self.scanline_handlers.push(routine.clone());
+                        self.scanline_handlers.len()
+                    }
+                };
+                let chain = self.new_label();
+                let done = self.new_label();
+                self.generate_expression(line)?;
+                self.output.push("  LDX $E6".to_string());
+                self.output.push(format!("  BNE {}", chain));
+                // Outside a handler: the NMI arms it from the top of each frame
+                self.output.push("  STA $1F".to_string());
+                self.output.push(format!("  LDA #${:02X}", index));
+                self.output.push("  STA $E4".to_string());
+                self.output.push(format!("  JMP {}", done));
+                // Inside a handler: count on from the current scanline
+                self.output.push(format!("{}:", chain));
+                self.output.push("  STA $C000".to_string());
+                self.output.push("  STA $C001".to_string());
+                self.output.push("  STA $E001".to_string());
+                self.output.push(format!("  LDA #${:02X}", index));
+                self.output.push("  STA $E5".to_string());
+                self.output.push(format!("{}:", done));
+            }
             Statement::WaitVBlank => {
                 let lbl = self.new_label();
                 self.output.push(format!("{}:", lbl));
@@ -4189,6 +9103,21 @@ impl CodeGenerator {
         Ok(())
     }
 
+    // A/X = address of the data `expr` names: a whole array by name, or an
+    // element such as `map(i * 30)` to start from
+    fn generate_data_address(&mut self, expr: &Expression) -> Result<(), String> {
+        if let Expression::Identifier(_) = expr {
+            let addr = self.get_static_address(expr)?;
+            self.output.push(format!("  LDA #${:02X}", addr & 0xFF));
+            self.output.push(format!("  LDX #${:02X}", addr >> 8));
+        } else {
+            self.generate_address_expression(expr)?; // $02/$03
+            self.output.push("  LDA $02".to_string());
+            self.output.push("  LDX $03".to_string());
+        }
+        Ok(())
+    }
+
     fn generate_address_expression(&mut self, expr: &Expression) -> Result<(), String> {
         match expr {
             Expression::Call(callee, args) => {
@@ -4404,6 +9333,67 @@ impl CodeGenerator {
                                 self.output.push("  TXS".to_string());
                                 self.output.push("  LDX #0".to_string());
                                 return Ok(DataType::Byte);
+                            } else if member.eq_ignore_ascii_case("Flags") {
+                                return self.generate_collision_flags(args);
+                            } else if member.eq_ignore_ascii_case("Sprites") {
+                                return self.generate_collision_sprites(args);
+                            }
+                        }
+                        if base_name.eq_ignore_ascii_case("Save")
+                            && member.eq_ignore_ascii_case("Verify")
+                        {
+                            self.output.push("  JSR Runtime_Save_Verify".to_string());
+                            return Ok(DataType::Bool);
+                        }
+                        if base_name.eq_ignore_ascii_case("Camera") {
+                            // Camera.X() / Camera.Y(): top-left corner in the world
+                            let addr = if member.eq_ignore_ascii_case("X") {
+                                CAMERA_STATE + 4
+                            } else {
+                                CAMERA_STATE + 6
+                            };
+                            self.output.push(format!("  LDA ${:04X}", addr));
+                            self.output.push(format!("  LDX ${:04X}", addr + 1));
+                            return Ok(DataType::Word);
+                        }
+                        if base_name.eq_ignore_ascii_case("Physics") {
+                            return self.generate_physics_move(args);
+                        }
+                        if base_name.eq_ignore_ascii_case("Actors") {
+                            return self.generate_actors_call(member, args);
+                        }
+                        if base_name.eq_ignore_ascii_case("World") {
+                            // World.RoomX() / World.RoomY(): the current room
+                            let addr = if member.eq_ignore_ascii_case("RoomX") {
+                                WORLD_STATE
+                            } else {
+                                WORLD_STATE + 1
+                            };
+                            self.output.push(format!("  LDA ${:04X}", addr));
+                            self.output.push("  LDX #0".to_string());
+                            return Ok(DataType::Byte);
+                        }
+                        if base_name.eq_ignore_ascii_case("PPU")
+                            && member.eq_ignore_ascii_case("Overflow")
+                        {
+                            // Read and clear the queue's overflow flag
+                            self.output
+                                .push(format!("  LDA ${:04X}", PPU_QUEUE_OVERFLOW));
+                            self.output.push("  LDX #0".to_string());
+                            self.output
+                                .push(format!("  STX ${:04X}", PPU_QUEUE_OVERFLOW));
+                            return Ok(DataType::Bool);
+                        }
+                        if base_name.eq_ignore_ascii_case("Password") {
+                            if member.eq_ignore_ascii_case("Encode") {
+                                self.output
+                                    .push("  JSR Runtime_Password_Encode".to_string());
+                                return Ok(DataType::String);
+                            } else if member.eq_ignore_ascii_case("Decode") {
+                                self.generate_expression(&args[0])?;
+                                self.output
+                                    .push("  JSR Runtime_Password_Decode".to_string());
+                                return Ok(DataType::Bool);
                             }
                         }
                         if base_name.eq_ignore_ascii_case("Controller") {
@@ -4713,21 +9703,16 @@ impl CodeGenerator {
                         } else {
                             Err("Const no val".to_string())
                         }
-                    } else if sym.kind == SymbolKind::Metasprite {
-                        if let Some(addr) = self.data_table_offsets.get(name) {
-                            self.output.push(format!("  LDA ${:04X}", addr));
-                            self.output.push(format!("  LDX ${:04X}", addr + 1));
-                            Ok(DataType::Word)
-                        } else {
-                            Err("Metasprite table entry missing".to_string())
-                        }
-                    } else if sym.kind == SymbolKind::Animation {
-                        if let Some(addr) = self.data_table_offsets.get(name) {
-                            self.output.push(format!("  LDA ${:04X}", addr));
-                            self.output.push(format!("  LDX ${:04X}", addr + 1));
+                    } else if sym.kind == SymbolKind::Metasprite
+                        || sym.kind == SymbolKind::Animation
+                    {
+                        // The table's address in the sprites section
+                        if let Some(addr) = self.sprite_addrs.get(name) {
+                            self.output.push(format!("  LDA #${:02X}", addr & 0xFF));
+                            self.output.push(format!("  LDX #${:02X}", addr >> 8));
                             Ok(DataType::Word)
                         } else {
-                            Err("Animation table entry missing".to_string())
+                            Err("Sprite table entry missing".to_string())
                         }
                     } else {
                         Err("No addr".to_string())
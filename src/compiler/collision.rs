@@ -0,0 +1,85 @@
+//! Tile collision attributes, read at runtime by `Collision.Flags`.
+//!
+//! Every CHR tile has one byte of flags, stored as the 256-byte `collision`
+//! section indexed by tile id. Flags set on a metatile are OR'd into each of
+//! its four tiles, so a tile shared by several metatiles gets the flags of
+//! all of them. Bits 5-7 hold a tile's slope shape (`SLOPES`), which
+//! `Physics.MoveAndCollide` treats as a floor; slopes are set per tile only.
+
+pub const SOLID: u8 = 0x01;
+pub const HAZARD: u8 = 0x02;
+pub const LADDER: u8 = 0x04;
+pub const ONE_WAY: u8 = 0x08;
+pub const WATER: u8 = 0x10;
+
+/// Members of the `TileFlag` enum.
+pub const FLAGS: [(&str, u8); 5] = [
+    ("Solid", SOLID),
+    ("Hazard", HAZARD),
+    ("Ladder", LADDER),
+    ("OneWay", ONE_WAY),
+    ("Water", WATER),
+];
+
+/// Slope shapes stored in bits 5-7 (shape 0 is flat): 45 degree rising and
+/// falling, then 22.5 degree pairs spanning two tiles (low, high half).
+pub const SLOPES: [&str; 6] = [
+    "Rise45",
+    "Fall45",
+    "Rise22Low",
+    "Rise22High",
+    "Fall22High",
+    "Fall22Low",
+];
+pub const SLOPE_SHIFT: u8 = 5;
+
+/// Floor height (1-8 pixels from the tile's bottom) of slope `shape` at
+/// column `x` (0-7) of the tile.
+pub fn slope_height(shape: u8, x: u8) -> u8 {
+    match shape {
+        1 => x + 1,
+        2 => 8 - x,
+        3 => x / 2 + 1,
+        4 => x / 2 + 5,
+        5 => 8 - x / 2,
+        6 => 4 - x / 2,
+        _ => 0,
+    }
+}
+
+/// Members of the `Contact` enum returned by `Physics.MoveAndCollide`.
+pub const CONTACT_FLOOR: u8 = 0x01;
+pub const CONTACT_CEILING: u8 = 0x02;
+pub const CONTACT_LEFT: u8 = 0x04;
+pub const CONTACT_RIGHT: u8 = 0x08;
+pub const CONTACT_SLOPE: u8 = 0x10;
+pub const CONTACTS: [(&str, u8); 5] = [
+    ("Floor", CONTACT_FLOOR),
+    ("Ceiling", CONTACT_CEILING),
+    ("Left", CONTACT_LEFT),
+    ("Right", CONTACT_RIGHT),
+    ("Slope", CONTACT_SLOPE),
+];
+
+/// Size of the `collision` section: one entry per tile id.
+pub const TABLE_SIZE: usize = 256;
+
+/// Builds the `collision` section from per-tile flags (index = tile id) and
+/// `(tiles, flags)` metatiles.
+pub fn flag_table(tile_flags: &[u8], metatiles: &[([u8; 4], u8)]) -> Result<Vec<u8>, String> {
+    if tile_flags.len() > TABLE_SIZE {
+        return Err(format!(
+            "{} tile collision entries defined (limit {})",
+            tile_flags.len(),
+            TABLE_SIZE
+        ));
+    }
+    let mut table = vec![0u8; TABLE_SIZE];
+    table[..tile_flags.len()].copy_from_slice(tile_flags);
+    for (tiles, flags) in metatiles {
+        for tile in tiles {
+            table[*tile as usize] |= flags;
+        }
+    }
+    Ok(table)
+}
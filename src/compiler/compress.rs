@@ -0,0 +1,350 @@
+//! Asset compression shared by the ROM builder and the 6502 runtime.
+//!
+//! RLE stream format, decoded by `Runtime_Rle_ToPpu`:
+//! - `$00`: end of stream
+//! - `$01-$7F` (n): n literal bytes follow
+//! - `$81-$FF` (n): the next byte repeats `n & $7F` times
+//!
+//! LZ stream format, decoded by `Runtime_Lz_ToPpu`. Copies read earlier bytes
+//! of the stream itself rather than earlier output, so the decoder needs no
+//! RAM window and can write straight to `$2007`:
+//! - `$00`: end of stream
+//! - `$01-$3F` (n): n literal bytes follow
+//! - `$40-$7F` (n): the next byte repeats `(n & $3F) + 3` times
+//! - `$80-$FF` (n): copy `(n & $7F) + 4` bytes from stream offset `lo, hi`
+//!
+//! A screen drawn entirely from metatiles can instead be stored as its
+//! metatile map, decoded by `Runtime_Metatile_ToPpu`: the 16x15 grid of
+//! metatile ids row by row, then the 64 attribute bytes. Each id picks four
+//! tiles from the `metatiles` section (see `metatile_table`).
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Compression {
+    #[default]
+    None,
+    Rle,
+    Lz,
+    /// A screen's metatile map; only screens, see `pack_metatiles`.
+    Metatile,
+    /// Whichever of the above is smallest for the data.
+    Auto,
+}
+
+impl Compression {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Compression::None => "raw",
+            Compression::Rle => "RLE",
+            Compression::Lz => "LZ",
+            Compression::Metatile => "metatile map",
+            Compression::Auto => "auto",
+        }
+    }
+}
+
+/// An asset blob stored in a concrete format (never `Auto`).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Packed {
+    pub format: Compression,
+    pub raw_len: usize,
+    pub data: Vec<u8>,
+}
+
+impl Packed {
+    /// Compression report line, e.g. `nametable: LZ, 1024 -> 212 bytes (21%)`.
+    pub fn report(&self, name: &str) -> String {
+        let percent = (self.data.len() * 100)
+            .checked_div(self.raw_len)
+            .unwrap_or(100);
+        format!(
+            "{}: {}, {} -> {} bytes ({}%)",
+            name,
+            self.format.name(),
+            self.raw_len,
+            self.data.len(),
+            percent
+        )
+    }
+}
+
+/// Stores `data` with `format`, trying every format for `Auto`. A metatile
+/// map needs the screen's grid, so `Metatile` stores the data raw here.
+pub fn pack(data: &[u8], format: Compression) -> Packed {
+    let encode = |format| match format {
+        Compression::Rle => rle_encode(data),
+        Compression::Lz => lz_encode(data),
+        _ => data.to_vec(),
+    };
+    let format = match format {
+        Compression::Auto => [Compression::None, Compression::Rle, Compression::Lz]
+            .into_iter()
+            .min_by_key(|f| encode(*f).len())
+            .unwrap_or(Compression::None),
+        Compression::Metatile => Compression::None,
+        other => other,
+    };
+    Packed {
+        format,
+        raw_len: data.len(),
+        data: encode(format),
+    }
+}
+
+/// Host-side inverse of `pack`.
+pub fn unpack(data: &[u8], format: Compression) -> Result<Vec<u8>, String> {
+    match format {
+        Compression::Rle => rle_decode(data),
+        Compression::Lz => lz_decode(data),
+        Compression::None => Ok(data.to_vec()),
+        Compression::Metatile => Err("Metatile maps unpack with unpack_metatiles".to_string()),
+        Compression::Auto => Err("Cannot unpack data stored as 'auto'".to_string()),
+    }
+}
+
+const GRID_WIDTH: usize = 16;
+const GRID_CELLS: usize = GRID_WIDTH * 15;
+
+/// The `metatiles` section: top-left, top-right, bottom-left and
+/// bottom-right tiles of every metatile, one plane each, so the decoder
+/// indexes all four with the metatile id.
+pub fn metatile_table(tiles: &[[u8; 4]]) -> Vec<u8> {
+    (0..4)
+        .flat_map(|corner| tiles.iter().map(move |t| t[corner]))
+        .collect()
+}
+
+/// Stores a 1024-byte `screen` as the metatile map `grid` (16x15 ids, -1
+/// for an empty cell), if every cell holds one of `tiles` and they
+/// reproduce the screen's tiles.
+pub fn pack_metatiles(screen: &[u8], grid: &[i32], tiles: &[[u8; 4]]) -> Option<Packed> {
+    if grid.len() != GRID_CELLS {
+        return None;
+    }
+    let mut data = Vec::with_capacity(GRID_CELLS + 64);
+    for id in grid {
+        let id = u8::try_from(*id).ok()?;
+        if id as usize >= tiles.len() {
+            return None;
+        }
+        data.push(id);
+    }
+    data.extend_from_slice(screen.get(960..1024)?);
+    let packed = Packed {
+        format: Compression::Metatile,
+        raw_len: screen.len(),
+        data,
+    };
+    (unpack_metatiles(&packed.data, tiles).ok()? == screen).then_some(packed)
+}
+
+/// Host-side decoder, the reference for the 6502 routine.
+pub fn unpack_metatiles(data: &[u8], tiles: &[[u8; 4]]) -> Result<Vec<u8>, String> {
+    if data.len() != GRID_CELLS + 64 {
+        return Err(format!(
+            "Metatile map is {} bytes (expected {})",
+            data.len(),
+            GRID_CELLS + 64
+        ));
+    }
+    let mut out = Vec::with_capacity(1024);
+    for row in data[..GRID_CELLS].chunks(GRID_WIDTH) {
+        for half in [0, 2] {
+            for id in row {
+                let t = tiles
+                    .get(*id as usize)
+                    .ok_or_else(|| format!("Metatile {} is not defined", id))?;
+                out.extend([t[half], t[half + 1]]);
+            }
+        }
+    }
+    out.extend_from_slice(&data[GRID_CELLS..]);
+    Ok(out)
+}
+
+const MAX_RUN: usize = 0x7F;
+
+pub fn rle_encode(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut literals: Vec<u8> = Vec::new();
+    let mut i = 0;
+    while i < data.len() {
+        let run = data[i..]
+            .iter()
+            .take(MAX_RUN)
+            .take_while(|b| **b == data[i])
+            .count();
+        // A run of 3 costs 2 bytes, so shorter ones stay literal
+        if run >= 3 {
+            flush_literals(&mut out, &mut literals);
+            out.push(0x80 | run as u8);
+            out.push(data[i]);
+            i += run;
+        } else {
+            literals.push(data[i]);
+            if literals.len() == MAX_RUN {
+                flush_literals(&mut out, &mut literals);
+            }
+            i += 1;
+        }
+    }
+    flush_literals(&mut out, &mut literals);
+    out.push(0x00);
+    out
+}
+
+fn flush_literals(out: &mut Vec<u8>, literals: &mut Vec<u8>) {
+    if !literals.is_empty() {
+        out.push(literals.len() as u8);
+        out.append(literals);
+    }
+}
+
+/// Host-side decoder, the reference for the 6502 routine.
+pub fn rle_decode(stream: &[u8]) -> Result<Vec<u8>, String> {
+    let mut out = Vec::new();
+    let mut i = 0;
+    loop {
+        let control = *stream.get(i).ok_or("RLE stream ends without terminator")? as usize;
+        i += 1;
+        if control == 0 {
+            return Ok(out);
+        }
+        if control & 0x80 != 0 {
+            let value = *stream.get(i).ok_or("RLE run is missing its value")?;
+            out.extend(std::iter::repeat_n(value, control & 0x7F));
+            i += 1;
+        } else {
+            let literals = stream
+                .get(i..i + control)
+                .ok_or("RLE literal block is truncated")?;
+            out.extend_from_slice(literals);
+            i += control;
+        }
+    }
+}
+
+const LZ_MAX_LITERALS: usize = 0x3F;
+const LZ_MIN_RUN: usize = 3;
+const LZ_MAX_RUN: usize = 0x3F + LZ_MIN_RUN;
+const LZ_MIN_COPY: usize = 4;
+const LZ_MAX_COPY: usize = 0x7F + LZ_MIN_COPY;
+
+pub fn lz_encode(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut literals: Vec<u8> = Vec::new();
+    let mut index = StreamIndex::default();
+    let mut i = 0;
+    while i < data.len() {
+        let run = data[i..]
+            .iter()
+            .take(LZ_MAX_RUN)
+            .take_while(|b| **b == data[i])
+            .count();
+        index.update(&out);
+        let copy = index.best_match(&out, &data[i..]);
+
+        // A run costs 2 bytes and a copy 3; pick whichever covers more
+        let (len, token) = match copy {
+            Some((offset, len)) if len > run => (
+                len,
+                vec![
+                    0x80 | (len - LZ_MIN_COPY) as u8,
+                    (offset & 0xFF) as u8,
+                    (offset >> 8) as u8,
+                ],
+            ),
+            _ if run >= LZ_MIN_RUN => (run, vec![0x40 | (run - LZ_MIN_RUN) as u8, data[i]]),
+            _ => {
+                literals.push(data[i]);
+                if literals.len() == LZ_MAX_LITERALS {
+                    flush_literals(&mut out, &mut literals);
+                }
+                i += 1;
+                continue;
+            }
+        };
+        flush_literals(&mut out, &mut literals);
+        out.extend(token);
+        i += len;
+    }
+    flush_literals(&mut out, &mut literals);
+    out.push(0x00);
+    out
+}
+
+// Stream offsets of every 4-byte sequence emitted so far. Pending literals
+// are not in the stream yet, so they cannot be copied from.
+#[derive(Default)]
+struct StreamIndex {
+    starts: HashMap<[u8; LZ_MIN_COPY], Vec<usize>>,
+    indexed: usize,
+}
+
+impl StreamIndex {
+    fn update(&mut self, out: &[u8]) {
+        while self.indexed + LZ_MIN_COPY <= out.len().min(0x10000) {
+            let mut key = [0u8; LZ_MIN_COPY];
+            key.copy_from_slice(&out[self.indexed..self.indexed + LZ_MIN_COPY]);
+            self.starts.entry(key).or_default().push(self.indexed);
+            self.indexed += 1;
+        }
+    }
+
+    fn best_match(&self, out: &[u8], ahead: &[u8]) -> Option<(usize, usize)> {
+        let key: [u8; LZ_MIN_COPY] = ahead.get(..LZ_MIN_COPY)?.try_into().ok()?;
+        let mut best: Option<(usize, usize)> = None;
+        for &start in self.starts.get(&key)? {
+            let len = out[start..]
+                .iter()
+                .zip(ahead)
+                .take(LZ_MAX_COPY)
+                .take_while(|(a, b)| a == b)
+                .count();
+            if best.is_none_or(|(_, l)| len > l) {
+                best = Some((start, len));
+            }
+        }
+        best
+    }
+}
+
+/// Host-side decoder, the reference for the 6502 routine.
+pub fn lz_decode(stream: &[u8]) -> Result<Vec<u8>, String> {
+    let mut out = Vec::new();
+    let mut i = 0;
+    loop {
+        let control = *stream.get(i).ok_or("LZ stream ends without terminator")? as usize;
+        i += 1;
+        match control {
+            0 => return Ok(out),
+            0x01..=0x3F => {
+                let literals = stream
+                    .get(i..i + control)
+                    .ok_or("LZ literal block is truncated")?;
+                out.extend_from_slice(literals);
+                i += control;
+            }
+            0x40..=0x7F => {
+                let value = *stream.get(i).ok_or("LZ run is missing its value")?;
+                out.extend(std::iter::repeat_n(value, (control & 0x3F) + LZ_MIN_RUN));
+                i += 1;
+            }
+            _ => {
+                let offset = stream
+                    .get(i..i + 2)
+                    .ok_or("LZ copy is missing its offset")?;
+                let start = offset[0] as usize | (offset[1] as usize) << 8;
+                let len = (control & 0x7F) + LZ_MIN_COPY;
+                let source = stream
+                    .get(start..start + len)
+                    .ok_or("LZ copy reads past the stream")?;
+                out.extend_from_slice(source);
+                i += 2;
+            }
+        }
+    }
+}
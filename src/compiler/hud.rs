@@ -0,0 +1,33 @@
+//! Sprite-0 status bar split, armed by `HUD.Split`.
+//!
+//! Sprite 0 sits behind the background with its first line on the line
+//! before the split, at `SPRITE_X`. When that line of the background is
+//! opaque there, the PPU raises the sprite-0 hit flag and the NMI tail,
+//! which has been polling for it, rewrites the scroll during the following
+//! horizontal blank.
+
+/// Sprite 0's x position. The hit is seen up to 11 cycles late and the
+/// scroll writes take about 30 more, so a hit here lands the last write
+/// between dot 257 and 320, where the next line has not been fetched yet.
+pub const SPRITE_X: u8 = 184;
+
+/// The tile sprite 0 is drawn with: its top row must be fully opaque so the
+/// hit happens on that row wherever the background is. Tiles that are
+/// transparent below it (and, for 8x16 sprites, whose lower tile is empty)
+/// are preferred, since the rest of the sprite shows over the playfield.
+/// 8x16 sprites need an even tile.
+pub fn sprite_zero_tile(chr: &[u8], tall: bool) -> Option<u8> {
+    let tile = |t: usize| chr.get(t * 16..t * 16 + 16);
+    let row = |data: &[u8], r: usize| data[r] | data[r + 8];
+    let candidates: Vec<usize> = (0..256)
+        .step_by(if tall { 2 } else { 1 })
+        .filter(|&t| tile(t).is_some_and(|d| row(d, 0) == 0xFF))
+        .collect();
+    let clean = candidates.iter().find(|&&t| {
+        let data = tile(t).unwrap();
+        let below = (1..8).all(|r| row(data, r) == 0);
+        let lower = !tall || tile(t + 1).is_none_or(|d| d.iter().all(|&b| b == 0));
+        below && lower
+    });
+    clean.or(candidates.first()).map(|&t| t as u8)
+}
@@ -0,0 +1,1262 @@
+//! Mid-level IR sitting between the AST and 6502 assembly.
+//!
+//! SUB and interrupt bodies are lowered into basic blocks of three-address
+//! instructions over explicit temporaries. Temporaries are single-assignment,
+//! which keeps constant propagation, dead-store elimination and liveness
+//! simple. After optimization the temporaries are assigned to the accumulator
+//! or to zero-page scratch bytes and the blocks are selected into 6502 code.
+//!
+//! The IR covers the 8-bit subset of the language (BYTE/BOOL/ENUM scalars,
+//! control flow, direct SUB calls, PEEK/POKE and inline ASM). Lowering returns
+//! an error for anything outside of that subset and the code generator falls
+//! back to the direct AST emitter.
+
+use crate::compiler::ast::{BinaryOperator, DataType, Expression, Statement, UnaryOperator};
+use crate::compiler::symbol_table::{SymbolKind, SymbolTable};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+/// First zero-page byte handed out to IR temporaries.
+/// $00-$0F is saved and restored by the NMI/IRQ trampolines.
+pub const TEMP_ZP_START: u8 = 0x00;
+/// Number of zero-page bytes available to IR temporaries.
+pub const TEMP_ZP_COUNT: u8 = 16;
+
+pub type Temp = usize;
+pub type BlockId = usize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operand {
+    Const(u8),
+    Temp(Temp),
+    Mem(u16), // Statically addressed variable
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinOp {
+    Add,
+    Sub,
+    And,
+    Or,
+    Xor,
+    Eq, // Comparisons produce $FF (true) or $00 (false)
+    Ne,
+    Lt, // Unsigned
+    Ge, // Unsigned
+}
+
+impl BinOp {
+    fn is_commutative(self) -> bool {
+        matches!(
+            self,
+            BinOp::Add | BinOp::And | BinOp::Or | BinOp::Xor | BinOp::Eq | BinOp::Ne
+        )
+    }
+
+    fn is_comparison(self) -> bool {
+        matches!(self, BinOp::Eq | BinOp::Ne | BinOp::Lt | BinOp::Ge)
+    }
+
+    fn eval(self, a: u8, b: u8) -> u8 {
+        let flag = |c: bool| if c { 0xFF } else { 0x00 };
+        match self {
+            BinOp::Add => a.wrapping_add(b),
+            BinOp::Sub => a.wrapping_sub(b),
+            BinOp::And => a & b,
+            BinOp::Or => a | b,
+            BinOp::Xor => a ^ b,
+            BinOp::Eq => flag(a == b),
+            BinOp::Ne => flag(a != b),
+            BinOp::Lt => flag(a < b),
+            BinOp::Ge => flag(a >= b),
+        }
+    }
+
+    // Branch taken when the comparison holds after `CMP`.
+    fn branch_if_true(self) -> &'static str {
+        match self {
+            BinOp::Eq => "BEQ",
+            BinOp::Ne => "BNE",
+            BinOp::Lt => "BCC",
+            BinOp::Ge => "BCS",
+            _ => "BNE",
+        }
+    }
+
+    fn branch_if_false(self) -> &'static str {
+        match self {
+            BinOp::Eq => "BNE",
+            BinOp::Ne => "BEQ",
+            BinOp::Lt => "BCS",
+            BinOp::Ge => "BCC",
+            _ => "BEQ",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnOp {
+    Not,
+    Neg,
+    Shl(u8),
+    Shr(u8),
+}
+
+impl UnOp {
+    fn eval(self, a: u8) -> u8 {
+        match self {
+            UnOp::Not => !a,
+            UnOp::Neg => a.wrapping_neg(),
+            UnOp::Shl(n) => a.checked_shl(n as u32).unwrap_or(0),
+            UnOp::Shr(n) => a.checked_shr(n as u32).unwrap_or(0),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Instr {
+    Copy {
+        dst: Temp,
+        src: Operand,
+    },
+    Bin {
+        dst: Temp,
+        op: BinOp,
+        lhs: Operand,
+        rhs: Operand,
+    },
+    Un {
+        dst: Temp,
+        op: UnOp,
+        src: Operand,
+    },
+    Load {
+        dst: Temp,
+        addr: u16,
+    }, // PEEK: never removed, the read may have side effects
+    Store {
+        addr: u16,
+        src: Operand,
+        volatile: bool, // POKE: never removed
+    },
+    Call(String),
+    Asm(Vec<String>),
+}
+
+impl Instr {
+    pub fn def(&self) -> Option<Temp> {
+        match self {
+            Instr::Copy { dst, .. }
+            | Instr::Bin { dst, .. }
+            | Instr::Un { dst, .. }
+            | Instr::Load { dst, .. } => Some(*dst),
+            _ => None,
+        }
+    }
+
+    pub fn uses(&self) -> Vec<Temp> {
+        let ops: Vec<Operand> = match self {
+            Instr::Copy { src, .. } | Instr::Un { src, .. } | Instr::Store { src, .. } => {
+                vec![*src]
+            }
+            Instr::Bin { lhs, rhs, .. } => vec![*lhs, *rhs],
+            _ => vec![],
+        };
+        ops.into_iter()
+            .filter_map(|op| match op {
+                Operand::Temp(t) => Some(t),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Calls and inline assembly may overwrite any zero-page scratch byte.
+    pub fn clobbers_temps(&self) -> bool {
+        matches!(self, Instr::Call(_) | Instr::Asm(_))
+    }
+
+    fn has_side_effects(&self) -> bool {
+        !matches!(
+            self,
+            Instr::Copy { .. } | Instr::Bin { .. } | Instr::Un { .. }
+        )
+    }
+
+    fn reads_mem(&self, addr: u16) -> bool {
+        match self {
+            Instr::Copy { src, .. } | Instr::Un { src, .. } | Instr::Store { src, .. } => {
+                *src == Operand::Mem(addr)
+            }
+            Instr::Bin { lhs, rhs, .. } => *lhs == Operand::Mem(addr) || *rhs == Operand::Mem(addr),
+            Instr::Load { addr: a, .. } => *a == addr,
+            Instr::Call(_) | Instr::Asm(_) => true,
+        }
+    }
+
+    fn operands_mut(&mut self) -> Vec<&mut Operand> {
+        match self {
+            Instr::Copy { src, .. } | Instr::Un { src, .. } | Instr::Store { src, .. } => {
+                vec![src]
+            }
+            Instr::Bin { lhs, rhs, .. } => vec![lhs, rhs],
+            _ => vec![],
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Terminator {
+    Jump(BlockId),
+    Branch {
+        cond: Operand,
+        then_bb: BlockId,
+        else_bb: BlockId,
+    },
+    Return(Option<Operand>),
+}
+
+impl Terminator {
+    fn uses(&self) -> Vec<Temp> {
+        match self {
+            Terminator::Branch {
+                cond: Operand::Temp(t),
+                ..
+            }
+            | Terminator::Return(Some(Operand::Temp(t))) => vec![*t],
+            _ => vec![],
+        }
+    }
+
+    fn successors(&self) -> Vec<BlockId> {
+        match self {
+            Terminator::Jump(b) => vec![*b],
+            Terminator::Branch {
+                then_bb, else_bb, ..
+            } => vec![*then_bb, *else_bb],
+            Terminator::Return(_) => vec![],
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Block {
+    pub instrs: Vec<Instr>,
+    pub term: Terminator,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Function {
+    pub name: String,
+    pub blocks: Vec<Block>,
+    pub temp_count: usize,
+}
+
+/// Where a temporary lives after allocation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Location {
+    A,
+    ZeroPage(u8),
+}
+
+/// Lowers, optimizes and selects a SUB or interrupt body.
+/// Returns the 6502 lines of the body (without the entry label).
+pub fn compile_body(
+    name: &str,
+    body: &[Statement],
+    symbols: &SymbolTable,
+    subs: &HashMap<String, Vec<(u16, DataType)>>,
+) -> Result<Vec<String>, String> {
+    let mut func = lower(name, body, symbols, subs)?;
+    func.optimize();
+    let alloc = func.allocate()?;
+    Ok(func.select(&alloc))
+}
+
+// --- Lowering ---
+
+struct Value {
+    op: Operand,
+    exact: bool, // The 8-bit value equals the full 16-bit value the AST emitter would compute
+}
+
+struct Lowerer<'a> {
+    symbols: &'a SymbolTable,
+    subs: &'a HashMap<String, Vec<(u16, DataType)>>,
+    blocks: Vec<(Vec<Instr>, Option<Terminator>)>,
+    current: BlockId,
+    temp_count: usize,
+}
+
+pub fn lower(
+    name: &str,
+    body: &[Statement],
+    symbols: &SymbolTable,
+    subs: &HashMap<String, Vec<(u16, DataType)>>,
+) -> Result<Function, String> {
+    let mut l = Lowerer {
+        symbols,
+        subs,
+        blocks: vec![(Vec::new(), None)],
+        current: 0,
+        temp_count: 0,
+    };
+    l.lower_block(body)?;
+    let blocks = l
+        .blocks
+        .into_iter()
+        .map(|(instrs, term)| Block {
+            instrs,
+            term: term.unwrap_or(Terminator::Return(None)),
+        })
+        .collect();
+    Ok(Function {
+        name: name.to_string(),
+        blocks,
+        temp_count: l.temp_count,
+    })
+}
+
+fn is_byte_type(dt: &DataType) -> bool {
+    matches!(dt, DataType::Byte | DataType::Bool | DataType::Enum(_))
+}
+
+impl Lowerer<'_> {
+    fn new_temp(&mut self) -> Temp {
+        self.temp_count += 1;
+        self.temp_count - 1
+    }
+
+    fn new_block(&mut self) -> BlockId {
+        self.blocks.push((Vec::new(), None));
+        self.blocks.len() - 1
+    }
+
+    fn emit(&mut self, instr: Instr) {
+        self.blocks[self.current].0.push(instr);
+    }
+
+    fn terminate(&mut self, term: Terminator) {
+        if self.blocks[self.current].1.is_none() {
+            self.blocks[self.current].1 = Some(term);
+        }
+    }
+
+    fn switch_to(&mut self, block: BlockId) {
+        self.current = block;
+    }
+
+    fn byte_variable(&self, name: &str) -> Result<u16, String> {
+        match self.symbols.resolve(name) {
+            Some(sym) if is_byte_type(&sym.data_type) && sym.kind != SymbolKind::Constant => sym
+                .address
+                .ok_or_else(|| format!("IR: '{}' has no address", name)),
+            _ => Err(format!("IR: '{}' is not a byte variable", name)),
+        }
+    }
+
+    fn const_address(&self, expr: &Expression) -> Option<u16> {
+        match expr {
+            Expression::Integer(val) => Some(*val as u16),
+            Expression::Identifier(name) => self
+                .symbols
+                .resolve(name)
+                .filter(|sym| sym.kind == SymbolKind::Constant)
+                .and_then(|sym| sym.value.map(|val| val as u16)),
+            _ => None,
+        }
+    }
+
+    fn lower_block(&mut self, statements: &[Statement]) -> Result<(), String> {
+        for stmt in statements {
+            self.lower_statement(stmt)?;
+        }
+        Ok(())
+    }
+
+    fn lower_statement(&mut self, stmt: &Statement) -> Result<(), String> {
+        match stmt {
+            Statement::Let(Expression::Identifier(name), expr) => {
+                let addr = self.byte_variable(name)?;
+                let value = self.lower_expr(expr)?;
+                self.emit(Instr::Store {
+                    addr,
+                    src: value.op,
+                    volatile: false,
+                });
+            }
+            Statement::Poke(addr_expr, val_expr) => {
+                let addr = self
+                    .const_address(addr_expr)
+                    .ok_or("IR: POKE needs a constant address")?;
+                let value = self.lower_expr(val_expr)?;
+                self.emit(Instr::Store {
+                    addr,
+                    src: value.op,
+                    volatile: true,
+                });
+            }
+            Statement::Call(Expression::Identifier(name), args) => {
+                let params = self
+                    .subs
+                    .get(name)
+                    .ok_or_else(|| format!("IR: unknown sub '{}'", name))?
+                    .clone();
+                if params.len() != args.len() {
+                    return Err(format!("IR: argument mismatch for '{}'", name));
+                }
+                for ((addr, dtype), arg) in params.iter().zip(args) {
+                    if !is_byte_type(dtype) {
+                        return Err(format!("IR: non-byte parameter in '{}'", name));
+                    }
+                    let value = self.lower_expr(arg)?;
+                    self.emit(Instr::Store {
+                        addr: *addr,
+                        src: value.op,
+                        volatile: false,
+                    });
+                }
+                self.emit(Instr::Call(name.clone()));
+            }
+            Statement::If(cond, then_block, else_block) => {
+                let c = self.lower_expr(cond)?;
+                let then_bb = self.new_block();
+                let end_bb = self.new_block();
+                let else_bb = if else_block.is_some() {
+                    self.new_block()
+                } else {
+                    end_bb
+                };
+                self.terminate(Terminator::Branch {
+                    cond: c.op,
+                    then_bb,
+                    else_bb,
+                });
+                self.switch_to(then_bb);
+                self.lower_block(then_block)?;
+                self.terminate(Terminator::Jump(end_bb));
+                if let Some(stmts) = else_block {
+                    self.switch_to(else_bb);
+                    self.lower_block(stmts)?;
+                    self.terminate(Terminator::Jump(end_bb));
+                }
+                self.switch_to(end_bb);
+            }
+            Statement::While(cond, body) => {
+                let cond_bb = self.new_block();
+                let body_bb = self.new_block();
+                let exit_bb = self.new_block();
+                self.terminate(Terminator::Jump(cond_bb));
+                self.switch_to(cond_bb);
+                let c = self.lower_expr(cond)?;
+                self.terminate(Terminator::Branch {
+                    cond: c.op,
+                    then_bb: body_bb,
+                    else_bb: exit_bb,
+                });
+                self.switch_to(body_bb);
+                self.lower_block(body)?;
+                self.terminate(Terminator::Jump(cond_bb));
+                self.switch_to(exit_bb);
+            }
+            Statement::DoWhile(body, cond) => {
+                let body_bb = self.new_block();
+                let exit_bb = self.new_block();
+                self.terminate(Terminator::Jump(body_bb));
+                self.switch_to(body_bb);
+                self.lower_block(body)?;
+                let c = self.lower_expr(cond)?;
+                self.terminate(Terminator::Branch {
+                    cond: c.op,
+                    then_bb: body_bb,
+                    else_bb: exit_bb,
+                });
+                self.switch_to(exit_bb);
+            }
+            Statement::For(var, start, end, step, body) => {
+                let addr = self.byte_variable(var)?;
+                let (step_val, negative) = match step {
+                    None => (1u8, false),
+                    Some(Expression::Integer(v)) => ((*v & 0xFF) as u8, *v < 0),
+                    Some(Expression::UnaryOp(UnaryOperator::Negate, inner)) => match &**inner {
+                        Expression::Integer(v) => ((v.wrapping_neg() & 0xFF) as u8, true),
+                        _ => return Err("IR: FOR step must be constant".to_string()),
+                    },
+                    _ => return Err("IR: FOR step must be constant".to_string()),
+                };
+                let init = self.lower_expr(start)?;
+                self.emit(Instr::Store {
+                    addr,
+                    src: init.op,
+                    volatile: false,
+                });
+
+                let cond_bb = self.new_block();
+                let body_bb = self.new_block();
+                let exit_bb = self.new_block();
+                self.terminate(Terminator::Jump(cond_bb));
+                self.switch_to(cond_bb);
+                let limit = self.lower_expr(end)?;
+                if !limit.exact {
+                    return Err("IR: FOR limit exceeds byte range".to_string());
+                }
+                let c = self.new_temp();
+                // var <= end  ->  end >= var ; var >= end for negative steps
+                let (lhs, rhs) = if negative {
+                    (Operand::Mem(addr), limit.op)
+                } else {
+                    (limit.op, Operand::Mem(addr))
+                };
+                self.emit(Instr::Bin {
+                    dst: c,
+                    op: BinOp::Ge,
+                    lhs,
+                    rhs,
+                });
+                self.terminate(Terminator::Branch {
+                    cond: Operand::Temp(c),
+                    then_bb: body_bb,
+                    else_bb: exit_bb,
+                });
+                self.switch_to(body_bb);
+                self.lower_block(body)?;
+                let next = self.new_temp();
+                self.emit(Instr::Bin {
+                    dst: next,
+                    op: BinOp::Add,
+                    lhs: Operand::Mem(addr),
+                    rhs: Operand::Const(step_val),
+                });
+                self.emit(Instr::Store {
+                    addr,
+                    src: Operand::Temp(next),
+                    volatile: false,
+                });
+                self.terminate(Terminator::Jump(cond_bb));
+                self.switch_to(exit_bb);
+            }
+            Statement::Return(expr) => {
+                let value = match expr {
+                    Some(e) => {
+                        let v = self.lower_expr(e)?;
+                        if !v.exact {
+                            return Err("IR: RETURN value exceeds byte range".to_string());
+                        }
+                        Some(v.op)
+                    }
+                    None => None,
+                };
+                self.terminate(Terminator::Return(value));
+                // Anything after RETURN is unreachable; keep lowering into a fresh block.
+                let dead = self.new_block();
+                self.switch_to(dead);
+            }
+            Statement::Asm(lines) => self.emit(Instr::Asm(lines.clone())),
+            Statement::Comment(_) => {}
+            _ => return Err("IR: unsupported statement".to_string()),
+        }
+        Ok(())
+    }
+
+    fn lower_expr(&mut self, expr: &Expression) -> Result<Value, String> {
+        match expr {
+            Expression::Integer(v) => Ok(Value {
+                op: Operand::Const((*v & 0xFF) as u8),
+                exact: (0..=255).contains(v),
+            }),
+            Expression::Identifier(name) => {
+                if let Some(sym) = self.symbols.resolve(name) {
+                    if sym.kind == SymbolKind::Constant {
+                        if let Some(v) = sym.value {
+                            return Ok(Value {
+                                op: Operand::Const((v & 0xFF) as u8),
+                                exact: (0..=255).contains(&v),
+                            });
+                        }
+                    }
+                }
+                Ok(Value {
+                    op: Operand::Mem(self.byte_variable(name)?),
+                    exact: true,
+                })
+            }
+            Expression::Peek(addr_expr) => {
+                let addr = self
+                    .const_address(addr_expr)
+                    .ok_or("IR: PEEK needs a constant address")?;
+                let dst = self.new_temp();
+                self.emit(Instr::Load { dst, addr });
+                Ok(Value {
+                    op: Operand::Temp(dst),
+                    exact: true,
+                })
+            }
+            Expression::UnaryOp(op, inner) => {
+                let v = self.lower_expr(inner)?;
+                let (op, exact) = match op {
+                    // ~x of a constant is a 16-bit value in the AST emitter
+                    UnaryOperator::Not => {
+                        (UnOp::Not, v.exact && !matches!(v.op, Operand::Const(_)))
+                    }
+                    UnaryOperator::Negate => (UnOp::Neg, false),
+                };
+                let dst = self.new_temp();
+                self.emit(Instr::Un { dst, op, src: v.op });
+                Ok(Value {
+                    op: Operand::Temp(dst),
+                    exact,
+                })
+            }
+            Expression::BinaryOp(l, op, r) => {
+                let lv = self.lower_expr(l)?;
+                let rv = self.lower_expr(r)?;
+                let dst = self.new_temp();
+                let (instr, exact) = match op {
+                    BinaryOperator::ShiftLeft | BinaryOperator::ShiftRight => {
+                        let count = match (rv.op, rv.exact) {
+                            (Operand::Const(n), true) => n,
+                            _ => return Err("IR: shift count must be constant".to_string()),
+                        };
+                        if matches!(op, BinaryOperator::ShiftLeft) {
+                            (
+                                Instr::Un {
+                                    dst,
+                                    op: UnOp::Shl(count),
+                                    src: lv.op,
+                                },
+                                false,
+                            )
+                        } else {
+                            (
+                                Instr::Un {
+                                    dst,
+                                    op: UnOp::Shr(count),
+                                    src: lv.op,
+                                },
+                                lv.exact,
+                            )
+                        }
+                    }
+                    _ => {
+                        let (bop, swap, exact) = match op {
+                            BinaryOperator::Add => (BinOp::Add, false, false),
+                            BinaryOperator::Subtract => (BinOp::Sub, false, false),
+                            BinaryOperator::And => (BinOp::And, false, lv.exact && rv.exact),
+                            BinaryOperator::Or => (BinOp::Or, false, lv.exact && rv.exact),
+                            BinaryOperator::Xor => (BinOp::Xor, false, lv.exact && rv.exact),
+                            BinaryOperator::Equal => (BinOp::Eq, false, true),
+                            BinaryOperator::NotEqual => (BinOp::Ne, false, true),
+                            BinaryOperator::LessThan => (BinOp::Lt, false, true),
+                            BinaryOperator::GreaterThanOrEqual => (BinOp::Ge, false, true),
+                            BinaryOperator::GreaterThan => (BinOp::Lt, true, true),
+                            BinaryOperator::LessThanOrEqual => (BinOp::Ge, true, true),
+                            _ => return Err("IR: unsupported operator".to_string()),
+                        };
+                        if bop.is_comparison() && !(lv.exact && rv.exact) {
+                            return Err("IR: comparison operand exceeds byte range".to_string());
+                        }
+                        let (lhs, rhs) = if swap { (rv.op, lv.op) } else { (lv.op, rv.op) };
+                        (
+                            Instr::Bin {
+                                dst,
+                                op: bop,
+                                lhs,
+                                rhs,
+                            },
+                            exact,
+                        )
+                    }
+                };
+                self.emit(instr);
+                Ok(Value {
+                    op: Operand::Temp(dst),
+                    exact,
+                })
+            }
+            _ => Err("IR: unsupported expression".to_string()),
+        }
+    }
+}
+
+// --- Optimization ---
+
+impl Function {
+    /// Runs the optimization passes until nothing changes.
+    pub fn optimize(&mut self) {
+        loop {
+            let mut changed = self.fold_constants();
+            changed |= self.remove_unreachable_blocks();
+            changed |= self.eliminate_dead_stores();
+            if !changed {
+                break;
+            }
+        }
+        self.canonicalize();
+    }
+
+    /// Propagates constants through temporaries, folds operations on constants
+    /// and turns branches on constant conditions into jumps.
+    pub fn fold_constants(&mut self) -> bool {
+        let mut changed = false;
+        let mut known: HashMap<Temp, u8> = HashMap::new();
+        // Temporaries are single-assignment, so a constant definition holds everywhere.
+        for block in &self.blocks {
+            for instr in &block.instrs {
+                if let Instr::Copy {
+                    dst,
+                    src: Operand::Const(v),
+                } = instr
+                {
+                    known.insert(*dst, *v);
+                }
+            }
+        }
+
+        let subst = |op: &mut Operand, known: &HashMap<Temp, u8>| -> bool {
+            if let Operand::Temp(t) = op {
+                if let Some(v) = known.get(t) {
+                    *op = Operand::Const(*v);
+                    return true;
+                }
+            }
+            false
+        };
+
+        for block in &mut self.blocks {
+            for instr in &mut block.instrs {
+                for op in instr.operands_mut() {
+                    changed |= subst(op, &known);
+                }
+                let folded = match instr {
+                    Instr::Bin {
+                        dst,
+                        op,
+                        lhs: Operand::Const(a),
+                        rhs: Operand::Const(b),
+                    } => Some((*dst, op.eval(*a, *b))),
+                    Instr::Un {
+                        dst,
+                        op,
+                        src: Operand::Const(a),
+                    } => Some((*dst, op.eval(*a))),
+                    _ => None,
+                };
+                if let Some((dst, v)) = folded {
+                    *instr = Instr::Copy {
+                        dst,
+                        src: Operand::Const(v),
+                    };
+                    changed = true;
+                }
+            }
+            match &mut block.term {
+                Terminator::Branch { cond, .. } | Terminator::Return(Some(cond)) => {
+                    changed |= subst(cond, &known);
+                }
+                _ => {}
+            }
+            if let Terminator::Branch {
+                cond: Operand::Const(v),
+                then_bb,
+                else_bb,
+            } = block.term
+            {
+                block.term = Terminator::Jump(if v != 0 { then_bb } else { else_bb });
+                changed = true;
+            }
+        }
+        changed
+    }
+
+    /// Drops blocks that cannot be reached from the entry block.
+    pub fn remove_unreachable_blocks(&mut self) -> bool {
+        let mut reachable = vec![false; self.blocks.len()];
+        let mut work = vec![0];
+        while let Some(b) = work.pop() {
+            if reachable[b] {
+                continue;
+            }
+            reachable[b] = true;
+            work.extend(self.blocks[b].term.successors());
+        }
+        if reachable.iter().all(|r| *r) {
+            return false;
+        }
+
+        let mut remap = vec![0; self.blocks.len()];
+        let mut next = 0;
+        for (i, r) in reachable.iter().enumerate() {
+            if *r {
+                remap[i] = next;
+                next += 1;
+            }
+        }
+        let old = std::mem::take(&mut self.blocks);
+        for (i, mut block) in old.into_iter().enumerate() {
+            if !reachable[i] {
+                continue;
+            }
+            block.term = match block.term {
+                Terminator::Jump(b) => Terminator::Jump(remap[b]),
+                Terminator::Branch {
+                    cond,
+                    then_bb,
+                    else_bb,
+                } => Terminator::Branch {
+                    cond,
+                    then_bb: remap[then_bb],
+                    else_bb: remap[else_bb],
+                },
+                t => t,
+            };
+            self.blocks.push(block);
+        }
+        true
+    }
+
+    /// Computes the set of temporaries live on entry to and exit from each block.
+    pub fn liveness(&self) -> (Vec<HashSet<Temp>>, Vec<HashSet<Temp>>) {
+        let n = self.blocks.len();
+        let mut live_in = vec![HashSet::new(); n];
+        let mut live_out: Vec<HashSet<Temp>> = vec![HashSet::new(); n];
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for b in (0..n).rev() {
+                let mut out = HashSet::new();
+                for s in self.blocks[b].term.successors() {
+                    out.extend(live_in[s].iter().copied());
+                }
+                let mut live = out.clone();
+                live.extend(self.blocks[b].term.uses());
+                for instr in self.blocks[b].instrs.iter().rev() {
+                    if let Some(d) = instr.def() {
+                        live.remove(&d);
+                    }
+                    live.extend(instr.uses());
+                }
+                if live != live_in[b] || out != live_out[b] {
+                    live_in[b] = live;
+                    live_out[b] = out;
+                    changed = true;
+                }
+            }
+        }
+        (live_in, live_out)
+    }
+
+    /// Removes definitions of temporaries that are never read, and stores to a
+    /// variable that are overwritten in the same block before being read.
+    pub fn eliminate_dead_stores(&mut self) -> bool {
+        let mut changed = false;
+        let (_, live_out) = self.liveness();
+        for (b, block) in self.blocks.iter_mut().enumerate() {
+            let mut live = live_out[b].clone();
+            live.extend(block.term.uses());
+            let mut keep = vec![true; block.instrs.len()];
+            for (i, instr) in block.instrs.iter().enumerate().rev() {
+                if let Some(d) = instr.def() {
+                    if !live.contains(&d) && !instr.has_side_effects() {
+                        keep[i] = false;
+                        continue;
+                    }
+                    live.remove(&d);
+                }
+                live.extend(instr.uses());
+            }
+
+            for (i, instr) in block.instrs.iter().enumerate() {
+                if let Instr::Store {
+                    addr,
+                    volatile: false,
+                    ..
+                } = *instr
+                {
+                    for later in &block.instrs[i + 1..] {
+                        if later.reads_mem(addr) {
+                            break;
+                        }
+                        if let Instr::Store { addr: a, .. } = later {
+                            if *a == addr {
+                                keep[i] = false;
+                                break;
+                            }
+                        }
+                    }
+                }
+            }
+
+            if keep.iter().any(|k| !k) {
+                let mut idx = 0;
+                block.instrs.retain(|_| {
+                    idx += 1;
+                    keep[idx - 1]
+                });
+                changed = true;
+            }
+        }
+        changed
+    }
+
+    // Moves temporaries into the left operand of commutative operations, so the
+    // value already held in A can be used directly.
+    fn canonicalize(&mut self) {
+        for block in &mut self.blocks {
+            for instr in &mut block.instrs {
+                if let Instr::Bin { op, lhs, rhs, .. } = instr {
+                    if op.is_commutative()
+                        && matches!(rhs, Operand::Temp(_))
+                        && !matches!(lhs, Operand::Temp(_))
+                    {
+                        std::mem::swap(lhs, rhs);
+                    }
+                }
+            }
+        }
+    }
+
+    // --- Allocation ---
+
+    /// Assigns every temporary to the accumulator (when it is consumed by the
+    /// very next instruction) or to a zero-page scratch byte.
+    pub fn allocate(&self) -> Result<HashMap<Temp, Location>, String> {
+        let mut use_count: HashMap<Temp, usize> = HashMap::new();
+        for block in &self.blocks {
+            for instr in &block.instrs {
+                for t in instr.uses() {
+                    *use_count.entry(t).or_default() += 1;
+                }
+            }
+            for t in block.term.uses() {
+                *use_count.entry(t).or_default() += 1;
+            }
+        }
+
+        let mut alloc = HashMap::new();
+        for block in &self.blocks {
+            for (i, instr) in block.instrs.iter().enumerate() {
+                let Some(d) = instr.def() else { continue };
+                if use_count.get(&d) != Some(&1) {
+                    continue;
+                }
+                let consumed_next = match block.instrs.get(i + 1) {
+                    Some(next) => a_operand(next) == Some(Operand::Temp(d)),
+                    None => match &block.term {
+                        Terminator::Branch { cond, .. } => *cond == Operand::Temp(d),
+                        Terminator::Return(Some(v)) => *v == Operand::Temp(d),
+                        _ => false,
+                    },
+                };
+                if consumed_next {
+                    alloc.insert(d, Location::A);
+                }
+            }
+        }
+
+        // Interference between the remaining temporaries.
+        let (_, live_out) = self.liveness();
+        let mut interferes: HashMap<Temp, HashSet<Temp>> = HashMap::new();
+        for (b, block) in self.blocks.iter().enumerate() {
+            let mut live = live_out[b].clone();
+            live.extend(block.term.uses());
+            for instr in block.instrs.iter().rev() {
+                if let Some(d) = instr.def() {
+                    live.remove(&d);
+                    for &other in &live {
+                        interferes.entry(d).or_default().insert(other);
+                        interferes.entry(other).or_default().insert(d);
+                    }
+                }
+                if instr.clobbers_temps() && !live.is_empty() {
+                    return Err(format!("IR: temporary live across call in '{}'", self.name));
+                }
+                live.extend(instr.uses());
+            }
+        }
+
+        for t in 0..self.temp_count {
+            if alloc.contains_key(&t) || !use_count.contains_key(&t) {
+                continue;
+            }
+            let taken: HashSet<u8> = interferes
+                .get(&t)
+                .map(|s| {
+                    s.iter()
+                        .filter_map(|o| match alloc.get(o) {
+                            Some(Location::ZeroPage(zp)) => Some(*zp),
+                            _ => None,
+                        })
+                        .collect()
+                })
+                .unwrap_or_default();
+            let slot = (TEMP_ZP_START..TEMP_ZP_START + TEMP_ZP_COUNT)
+                .find(|zp| !taken.contains(zp))
+                .ok_or_else(|| format!("IR: out of zero-page temporaries in '{}'", self.name))?;
+            alloc.insert(t, Location::ZeroPage(slot));
+        }
+        Ok(alloc)
+    }
+
+    // --- Instruction selection ---
+
+    fn block_label(&self, b: BlockId) -> String {
+        format!("{}_IR_B{}", self.name, b)
+    }
+
+    /// Selects 6502 instructions for the allocated function.
+    pub fn select(&self, alloc: &HashMap<Temp, Location>) -> Vec<String> {
+        let mut sel = Selector {
+            out: Vec::new(),
+            alloc,
+            name: &self.name,
+            label_counter: 0,
+        };
+        for (b, block) in self.blocks.iter().enumerate() {
+            if b > 0 {
+                sel.out.push(format!("{}:", self.block_label(b)));
+            }
+            // A comparison feeding the branch directly is fused into CMP + Bxx.
+            let fused = match (block.instrs.last(), &block.term) {
+                (
+                    Some(Instr::Bin {
+                        dst, op, lhs, rhs, ..
+                    }),
+                    Terminator::Branch { cond, .. },
+                ) if op.is_comparison()
+                    && *cond == Operand::Temp(*dst)
+                    && alloc.get(dst) == Some(&Location::A) =>
+                {
+                    Some((*op, *lhs, *rhs))
+                }
+                _ => None,
+            };
+            let count = block.instrs.len() - usize::from(fused.is_some());
+            for instr in &block.instrs[..count] {
+                sel.instr(instr);
+            }
+
+            let next = b + 1;
+            match &block.term {
+                Terminator::Jump(target) => {
+                    if *target != next {
+                        sel.out.push(format!("  JMP {}", self.block_label(*target)));
+                    }
+                }
+                Terminator::Branch {
+                    cond,
+                    then_bb,
+                    else_bb,
+                } => {
+                    let (if_true, if_false) = match fused {
+                        Some((op, lhs, rhs)) => {
+                            sel.load_a(lhs);
+                            sel.out.push(format!("  CMP {}", sel.operand(rhs)));
+                            (op.branch_if_true(), op.branch_if_false())
+                        }
+                        None => {
+                            sel.load_a(*cond);
+                            ("BNE", "BEQ")
+                        }
+                    };
+                    // Short branches only hop over a JMP, so block size never matters.
+                    let skip = sel.new_label();
+                    if *then_bb == next {
+                        sel.out.push(format!("  {} {}", if_true, skip));
+                        sel.out
+                            .push(format!("  JMP {}", self.block_label(*else_bb)));
+                    } else {
+                        sel.out.push(format!("  {} {}", if_false, skip));
+                        sel.out
+                            .push(format!("  JMP {}", self.block_label(*then_bb)));
+                    }
+                    sel.out.push(format!("{}:", skip));
+                    if *then_bb != next && *else_bb != next {
+                        sel.out
+                            .push(format!("  JMP {}", self.block_label(*else_bb)));
+                    }
+                }
+                Terminator::Return(value) => {
+                    if let Some(v) = value {
+                        sel.load_a(*v);
+                        sel.out.push("  LDX #0".to_string());
+                    }
+                    sel.out.push("  RTS".to_string());
+                }
+            }
+        }
+        sel.out
+    }
+}
+
+// The operand an instruction expects in the accumulator.
+fn a_operand(instr: &Instr) -> Option<Operand> {
+    match instr {
+        Instr::Copy { src, .. } | Instr::Un { src, .. } | Instr::Store { src, .. } => Some(*src),
+        Instr::Bin { lhs, .. } => Some(*lhs),
+        _ => None,
+    }
+}
+
+struct Selector<'a> {
+    out: Vec<String>,
+    alloc: &'a HashMap<Temp, Location>,
+    name: &'a str,
+    label_counter: usize,
+}
+
+impl Selector<'_> {
+    fn new_label(&mut self) -> String {
+        self.label_counter += 1;
+        format!("{}_IR_L{}", self.name, self.label_counter)
+    }
+
+    fn operand(&self, op: Operand) -> String {
+        match op {
+            Operand::Const(v) => format!("#${:02X}", v),
+            Operand::Mem(addr) => format!("${:04X}", addr),
+            Operand::Temp(t) => match self.alloc.get(&t) {
+                Some(Location::ZeroPage(zp)) => format!("${:02X}", zp),
+                _ => unreachable!("accumulator temporaries are never addressed"),
+            },
+        }
+    }
+
+    fn load_a(&mut self, op: Operand) {
+        if let Operand::Temp(t) = op {
+            if self.alloc.get(&t) == Some(&Location::A) {
+                return;
+            }
+        }
+        self.out.push(format!("  LDA {}", self.operand(op)));
+    }
+
+    fn store_dst(&mut self, dst: Temp) {
+        if let Some(Location::ZeroPage(zp)) = self.alloc.get(&dst) {
+            self.out.push(format!("  STA ${:02X}", zp));
+        }
+    }
+
+    fn instr(&mut self, instr: &Instr) {
+        match instr {
+            Instr::Copy { dst, src } => {
+                self.load_a(*src);
+                self.store_dst(*dst);
+            }
+            Instr::Bin { dst, op, lhs, rhs } => {
+                self.load_a(*lhs);
+                let rhs = self.operand(*rhs);
+                match op {
+                    BinOp::Add => {
+                        self.out.push("  CLC".to_string());
+                        self.out.push(format!("  ADC {}", rhs));
+                    }
+                    BinOp::Sub => {
+                        self.out.push("  SEC".to_string());
+                        self.out.push(format!("  SBC {}", rhs));
+                    }
+                    BinOp::And => self.out.push(format!("  AND {}", rhs)),
+                    BinOp::Or => self.out.push(format!("  ORA {}", rhs)),
+                    BinOp::Xor => self.out.push(format!("  EOR {}", rhs)),
+                    BinOp::Eq | BinOp::Ne | BinOp::Lt | BinOp::Ge => {
+                        let false_lbl = self.new_label();
+                        let done_lbl = self.new_label();
+                        self.out.push(format!("  CMP {}", rhs));
+                        self.out
+                            .push(format!("  {} {}", op.branch_if_false(), false_lbl));
+                        self.out.push("  LDA #$FF".to_string());
+                        self.out.push(format!("  BNE {}", done_lbl));
+                        self.out.push(format!("{}:", false_lbl));
+                        self.out.push("  LDA #$00".to_string());
+                        self.out.push(format!("{}:", done_lbl));
+                    }
+                }
+                self.store_dst(*dst);
+            }
+            Instr::Un { dst, op, src } => {
+                self.load_a(*src);
+                match op {
+                    UnOp::Not => self.out.push("  EOR #$FF".to_string()),
+                    UnOp::Neg => {
+                        self.out.push("  EOR #$FF".to_string());
+                        self.out.push("  CLC".to_string());
+                        self.out.push("  ADC #$01".to_string());
+                    }
+                    UnOp::Shl(n) => {
+                        for _ in 0..(*n).min(8) {
+                            self.out.push("  ASL".to_string());
+                        }
+                    }
+                    UnOp::Shr(n) => {
+                        for _ in 0..(*n).min(8) {
+                            self.out.push("  LSR".to_string());
+                        }
+                    }
+                }
+                self.store_dst(*dst);
+            }
+            Instr::Load { dst, addr } => {
+                self.out.push(format!("  LDA ${:04X}", addr));
+                self.store_dst(*dst);
+            }
+            Instr::Store { addr, src, .. } => {
+                self.load_a(*src);
+                self.out.push(format!("  STA ${:04X}", addr));
+            }
+            Instr::Call(name) => self.out.push(format!("  JSR {}", name)),
+            Instr::Asm(lines) => {
+                for line in lines {
+                    self.out.push(format!("  {}", line));
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Operand::Const(v) => write!(f, "#{}", v),
+            Operand::Temp(t) => write!(f, "t{}", t),
+            Operand::Mem(addr) => write!(f, "[${:04X}]", addr),
+        }
+    }
+}
+
+impl fmt::Display for Function {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "func {}", self.name)?;
+        for (b, block) in self.blocks.iter().enumerate() {
+            writeln!(f, "bb{}:", b)?;
+            for instr in &block.instrs {
+                match instr {
+                    Instr::Copy { dst, src } => writeln!(f, "  t{} = {}", dst, src)?,
+                    Instr::Bin { dst, op, lhs, rhs } => {
+                        writeln!(f, "  t{} = {:?} {}, {}", dst, op, lhs, rhs)?
+                    }
+                    Instr::Un { dst, op, src } => writeln!(f, "  t{} = {:?} {}", dst, op, src)?,
+                    Instr::Load { dst, addr } => writeln!(f, "  t{} = peek ${:04X}", dst, addr)?,
+                    Instr::Store {
+                        addr,
+                        src,
+                        volatile,
+                    } => {
+                        let kw = if *volatile { "poke" } else { "store" };
+                        writeln!(f, "  {} ${:04X}, {}", kw, addr, src)?
+                    }
+                    Instr::Call(name) => writeln!(f, "  call {}", name)?,
+                    Instr::Asm(lines) => writeln!(f, "  asm ({} lines)", lines.len())?,
+                }
+            }
+            match &block.term {
+                Terminator::Jump(t) => writeln!(f, "  jump bb{}", t)?,
+                Terminator::Branch {
+                    cond,
+                    then_bb,
+                    else_bb,
+                } => writeln!(f, "  branch {}, bb{}, bb{}", cond, then_bb, else_bb)?,
+                Terminator::Return(Some(v)) => writeln!(f, "  return {}", v)?,
+                Terminator::Return(None) => writeln!(f, "  return")?,
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,335 @@
+//! PRG-ROM layout.
+//!
+//! Asset blobs (music, samples, nametables, ...) are relocatable sections.
+//! The linker packs them downwards from the pointer table at $FF00, honouring
+//! each section's alignment and lowest legal address, and leaves everything
+//! from the start of the fixed bank up to the lowest section for code. The
+//! interrupt vectors at $FFFA and the pointer table above $FF00 are never
+//! handed out. Sections placed in a switchable bank are packed downwards from
+//! the end of the bank window at $8000 instead, leaving its start to the
+//! bank's code.
+
+use crate::compiler::mapper::{Mapper, SWITCH_WINDOW};
+use std::collections::HashMap;
+
+pub const PRG_START: u16 = 0x8000;
+pub const POINTER_TABLE_ADDR: u16 = 0xFF00;
+pub const VECTORS_ADDR: u16 = 0xFFFA;
+
+/// The DMC can only fetch samples from $C000-$FFFF, on 64-byte boundaries.
+pub const DPCM_MIN_ADDR: u16 = 0xC000;
+pub const DPCM_ALIGN: u16 = 64;
+
+pub const SECTION_PALETTE: &str = "palette";
+pub const SECTION_PERIOD_TABLE: &str = "period_table";
+pub const SECTION_MUSIC: &str = "music";
+pub const SECTION_SAMPLE_TABLE: &str = "sample_table";
+pub const SECTION_NAMETABLE: &str = "nametable";
+pub const SECTION_SFX: &str = "sfx";
+pub const SECTION_ENVELOPES: &str = "envelopes";
+pub const SECTION_DPCM: &str = "dpcm";
+pub const SECTION_BANK_TABLE: &str = "bank_table";
+pub const SECTION_CHR: &str = "chr";
+pub const SECTION_CHR_TILES: &str = "chr_tiles";
+pub const SECTION_PASSWORD_ALPHABET: &str = "password_alphabet";
+pub const SECTION_WORLD: &str = "world";
+pub const SECTION_SCREENS: &str = "screens";
+pub const SECTION_SCREEN_TABLE: &str = "screen_table";
+pub const SECTION_COLLISION: &str = "collision";
+pub const SECTION_WORLD_OBJECTS: &str = "world_objects";
+pub const SECTION_SPRITES: &str = "sprites";
+pub const SECTION_ACTOR_HANDLERS: &str = "actor_handlers";
+pub const SECTION_METATILES: &str = "metatiles";
+/// Bank of each screen ($FF for the fixed bank); only with banked screens.
+pub const SECTION_SCREEN_BANKS: &str = "screen_banks";
+
+/// Banked screens are grouped in one section per bank.
+pub fn banked_screens_section(bank: u8) -> String {
+    format!("{}_{}", SECTION_SCREENS, bank)
+}
+
+/// Screen table entries are 3 bytes, indexed with a single register.
+pub const MAX_SCREENS: usize = 85;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Section {
+    pub name: String,
+    pub size: usize,
+    pub align: u16,
+    pub min_addr: u16,
+    /// Switchable bank holding the section; `None` for the fixed bank.
+    pub bank: Option<u8>,
+}
+
+impl Section {
+    pub fn new(name: &str, size: usize) -> Self {
+        Self {
+            name: name.to_string(),
+            size,
+            align: 1,
+            min_addr: PRG_START,
+            bank: None,
+        }
+    }
+
+    /// A section in switchable bank `bank`, or the fixed bank for `None`.
+    pub fn banked(name: &str, size: usize, bank: Option<u8>) -> Self {
+        Self {
+            bank,
+            ..Self::new(name, size)
+        }
+    }
+
+    /// A section that must start on an `align` boundary at or above `min_addr`.
+    pub fn aligned(name: &str, size: usize, align: u16, min_addr: u16) -> Self {
+        Self {
+            name: name.to_string(),
+            size,
+            align: align.max(1),
+            min_addr,
+            bank: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Placement {
+    pub name: String,
+    pub addr: u16,
+    pub size: usize,
+    pub bank: Option<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Layout {
+    placements: Vec<Placement>,
+    code_start: u16,
+}
+
+impl Layout {
+    /// The fixed map used before the linker existed. Code generated without a
+    /// linked layout (unit tests, tools) still points at these addresses.
+    pub fn legacy() -> Self {
+        let fixed = [
+            (SECTION_PERIOD_TABLE, 0xD000, 0xC0),
+            (SECTION_MUSIC, 0xD100, 0x380),
+            (SECTION_SAMPLE_TABLE, 0xD480, 0x80),
+            (SECTION_NAMETABLE, 0xD500, 0x400),
+            (SECTION_SFX, 0xD900, 0x100),
+            (SECTION_ENVELOPES, 0xDA00, 0x600),
+            (SECTION_PALETTE, 0xE000, 0x20),
+            (SECTION_DPCM, 0xE040, 0x1EC0),
+        ];
+        Self {
+            placements: fixed
+                .iter()
+                .map(|(name, addr, size)| Placement {
+                    name: name.to_string(),
+                    addr: *addr,
+                    size: *size,
+                    bank: None,
+                })
+                .collect(),
+            code_start: PRG_START,
+        }
+    }
+
+    pub fn placements(&self) -> &[Placement] {
+        &self.placements
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Placement> {
+        self.placements.iter().find(|p| p.name == name)
+    }
+
+    /// Start address of a section, falling back to the legacy map.
+    pub fn addr(&self, name: &str) -> u16 {
+        if let Some(p) = self.get(name) {
+            return p.addr;
+        }
+        Self::legacy().get(name).map(|p| p.addr).unwrap_or(0)
+    }
+
+    /// Switchable bank of a section; `None` in the fixed bank or when absent.
+    pub fn bank(&self, name: &str) -> Option<u8> {
+        self.get(name).and_then(|p| p.bank)
+    }
+
+    /// First byte of the fixed bank not available to code.
+    pub fn code_limit(&self) -> u16 {
+        self.placements
+            .iter()
+            .filter(|p| p.size > 0 && p.bank.is_none())
+            .map(|p| p.addr)
+            .min()
+            .unwrap_or(POINTER_TABLE_ADDR)
+    }
+
+    /// Assembler variable definitions (`SECTION_MUSIC = $D100`) so inline ASM
+    /// can refer to the placed sections.
+    pub fn symbols(&self) -> Vec<String> {
+        self.placements
+            .iter()
+            .map(|p| format!("SECTION_{} = ${:04X}", p.name.to_uppercase(), p.addr))
+            .collect()
+    }
+
+    /// Human-readable map, one comment line per section.
+    pub fn report(&self) -> Vec<String> {
+        let mut lines = vec![format!(
+            "; code: ${:04X}-${:04X} ({} bytes available)",
+            self.code_start,
+            self.code_limit() - 1,
+            self.code_limit() - self.code_start
+        )];
+        let mut sorted: Vec<&Placement> = self.placements.iter().collect();
+        sorted.sort_by_key(|p| p.addr);
+        for p in sorted {
+            let bank = p.bank.map(|b| format!("bank {} ", b)).unwrap_or_default();
+            if p.size == 0 {
+                lines.push(format!("; {}: empty", p.name));
+            } else {
+                lines.push(format!(
+                    "; {}: {}${:04X}-${:04X} ({} bytes)",
+                    p.name,
+                    bank,
+                    p.addr,
+                    p.addr as usize + p.size - 1,
+                    p.size
+                ));
+            }
+        }
+        lines
+    }
+}
+
+impl Default for Layout {
+    fn default() -> Self {
+        Self::legacy()
+    }
+}
+
+#[derive(Debug)]
+pub struct Linker {
+    sections: Vec<Section>,
+    code_start: u16,
+    bank_limit: u16,
+}
+
+impl Default for Linker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Linker {
+    pub fn new() -> Self {
+        Self::with_code_start(PRG_START)
+    }
+
+    /// A linker for a fixed bank that starts at `code_start` (e.g. $C000 when
+    /// the lower half of the address space is bank-switched).
+    pub fn with_code_start(code_start: u16) -> Self {
+        Self {
+            sections: Vec::new(),
+            code_start,
+            bank_limit: SWITCH_WINDOW,
+        }
+    }
+
+    /// A linker for `mapper`'s fixed bank and switchable bank window.
+    pub fn for_mapper(mapper: Mapper) -> Self {
+        Self {
+            bank_limit: mapper.bank_limit(),
+            ..Self::with_code_start(mapper.fixed_bank_start())
+        }
+    }
+
+    pub fn add(&mut self, section: Section) {
+        self.sections.push(section);
+    }
+
+    /// Places every fixed section below the pointer table and every banked
+    /// one below the end of its bank window. Sections with a raised lower
+    /// bound (DPCM) go first so they get the high addresses they need; the
+    /// rest follow in the order they were added.
+    pub fn link(&self) -> Result<Layout, String> {
+        let mut order: Vec<&Section> = self.sections.iter().collect();
+        order.sort_by_key(|s| std::cmp::Reverse((s.min_addr, s.align)));
+
+        let mut tops: HashMap<Option<u8>, usize> = HashMap::new();
+        let mut placements = Vec::new();
+        for section in order {
+            let top = tops.entry(section.bank).or_insert(match section.bank {
+                Some(_) => self.bank_limit as usize,
+                None => POINTER_TABLE_ADDR as usize,
+            });
+            if self
+                .sections
+                .iter()
+                .filter(|s| s.name == section.name)
+                .count()
+                > 1
+            {
+                return Err(format!("Duplicate ROM section '{}'", section.name));
+            }
+            if section.size == 0 {
+                placements.push(Placement {
+                    name: section.name.clone(),
+                    addr: *top as u16,
+                    size: 0,
+                    bank: section.bank,
+                });
+                continue;
+            }
+
+            let align = section.align as usize;
+            let min = match section.bank {
+                Some(_) => section.min_addr.max(SWITCH_WINDOW),
+                None => section.min_addr.max(self.code_start),
+            } as usize;
+            let start = top
+                .checked_sub(section.size)
+                .map(|s| s - s % align)
+                .filter(|s| *s >= min);
+            let start = match start {
+                Some(s) => s,
+                None => {
+                    let free = top.saturating_sub(min);
+                    let bank = section
+                        .bank
+                        .map(|b| format!("bank {} ", b))
+                        .unwrap_or_default();
+                    return Err(format!(
+                        "ROM overflow: section '{}' needs {} bytes but only {} are free in {}${:04X}-${:04X}",
+                        section.name,
+                        section.size,
+                        free,
+                        bank,
+                        min,
+                        *top - 1
+                    ));
+                }
+            };
+            placements.push(Placement {
+                name: section.name.clone(),
+                addr: start as u16,
+                size: section.size,
+                bank: section.bank,
+            });
+            *top = start;
+        }
+
+        // Report in declaration order
+        placements.sort_by_key(|p| {
+            self.sections
+                .iter()
+                .position(|s| s.name == p.name)
+                .unwrap_or(usize::MAX)
+        });
+        Ok(Layout {
+            placements,
+            code_start: self.code_start,
+        })
+    }
+}
@@ -0,0 +1,193 @@
+//! Cartridge mapper targets.
+//!
+//! NROM and CNROM are flat 32 KB images. Banked targets keep the runtime and
+//! vectors in a fixed 16 KB region at $C000-$FFFF and assemble `BANK n` code
+//! into the switchable window at $8000: one 16 KB bank on MMC1 and UxROM, one
+//! 8 KB bank on MMC3. AxROM swaps all 32 KB at once, so the fixed region is
+//! repeated in the upper half of every bank. Asset sections go to the fixed
+//! region unless the project places them in a switchable bank.
+
+use crate::compiler::ast::{Program, TopLevel};
+
+/// iNES PRG-ROM unit.
+pub const PRG_BANK_SIZE: usize = 0x4000;
+pub const CHR_BANK_SIZE: usize = 0x2000;
+
+/// Start of the switchable PRG window.
+pub const SWITCH_WINDOW: u16 = 0x8000;
+
+/// Every switchable MMC1 bank ends with a small reset stub, so the console can
+/// boot whichever bank happens to be mapped at $C000 on power-up.
+pub const RESET_STUB_ADDR: u16 = 0xBFF0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Mapper {
+    #[default]
+    Nrom,
+    Mmc1,
+    Uxrom,
+    Cnrom,
+    Mmc3,
+    Axrom,
+}
+
+impl Mapper {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_uppercase().as_str() {
+            "NROM" | "0" => Some(Mapper::Nrom),
+            "MMC1" | "SXROM" | "1" => Some(Mapper::Mmc1),
+            "UXROM" | "UNROM" | "UOROM" | "2" => Some(Mapper::Uxrom),
+            "CNROM" | "3" => Some(Mapper::Cnrom),
+            "MMC3" | "TXROM" | "4" => Some(Mapper::Mmc3),
+            "AXROM" | "ANROM" | "AOROM" | "7" => Some(Mapper::Axrom),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Mapper::Nrom => "NROM",
+            Mapper::Mmc1 => "MMC1",
+            Mapper::Uxrom => "UxROM",
+            Mapper::Cnrom => "CNROM",
+            Mapper::Mmc3 => "MMC3",
+            Mapper::Axrom => "AxROM",
+        }
+    }
+
+    /// iNES mapper number.
+    pub fn number(&self) -> u8 {
+        match self {
+            Mapper::Nrom => 0,
+            Mapper::Mmc1 => 1,
+            Mapper::Uxrom => 2,
+            Mapper::Cnrom => 3,
+            Mapper::Mmc3 => 4,
+            Mapper::Axrom => 7,
+        }
+    }
+
+    /// Number of `BANK n` slots available to user code (0 for none).
+    pub fn switchable_banks(&self) -> usize {
+        match self {
+            Mapper::Nrom | Mapper::Cnrom => 0,
+            // 256 KB PRG = 16 banks, the last one fixed
+            Mapper::Mmc1 | Mapper::Uxrom => 15,
+            // 512 KB PRG = 64 banks, the last two fixed
+            Mapper::Mmc3 => 62,
+            // 256 KB PRG = 8 banks of 32 KB
+            Mapper::Axrom => 8,
+        }
+    }
+
+    /// Size of one switchable PRG bank.
+    pub fn prg_bank_size(&self) -> usize {
+        match self {
+            Mapper::Nrom | Mapper::Mmc1 | Mapper::Uxrom | Mapper::Cnrom => 0x4000,
+            Mapper::Mmc3 => 0x2000,
+            Mapper::Axrom => 0x8000,
+        }
+    }
+
+    /// First address past the usable part of the switchable window.
+    pub fn bank_limit(&self) -> u16 {
+        match self {
+            Mapper::Mmc1 => RESET_STUB_ADDR,
+            // The upper half of every AxROM bank holds the fixed region
+            Mapper::Axrom => 0xC000,
+            _ => SWITCH_WINDOW + self.prg_bank_size() as u16,
+        }
+    }
+
+    /// Lowest address of the bank that is always mapped.
+    pub fn fixed_bank_start(&self) -> u16 {
+        match self {
+            Mapper::Nrom | Mapper::Cnrom => 0x8000,
+            _ => 0xC000,
+        }
+    }
+
+    pub fn max_chr_size(&self) -> usize {
+        match self {
+            Mapper::Nrom | Mapper::Uxrom | Mapper::Axrom => CHR_BANK_SIZE,
+            Mapper::Cnrom => 32 * 1024,
+            Mapper::Mmc1 => 128 * 1024,
+            Mapper::Mmc3 => 256 * 1024,
+        }
+    }
+
+    /// Largest PRG-ROM the target can address.
+    pub fn max_prg_size(&self) -> usize {
+        match self {
+            Mapper::Nrom | Mapper::Cnrom => 32 * 1024,
+            Mapper::Mmc1 | Mapper::Uxrom | Mapper::Axrom => 256 * 1024,
+            Mapper::Mmc3 => 512 * 1024,
+        }
+    }
+
+    /// Whether boards for this mapper carry 8 KB of PRG-RAM at $6000.
+    pub fn has_prg_ram(&self) -> bool {
+        matches!(self, Mapper::Mmc1 | Mapper::Mmc3)
+    }
+
+    /// Number of 8 KB CHR banks `CHR.Bank` can select (1 means no switching).
+    pub fn chr_banks(&self) -> usize {
+        self.max_chr_size() / CHR_BANK_SIZE
+    }
+
+    /// Discrete-logic boards where the ROM drives the data bus during a
+    /// bank write, so the written value must match the byte at that address.
+    pub fn bus_conflicts(&self) -> bool {
+        matches!(self, Mapper::Uxrom | Mapper::Cnrom | Mapper::Axrom)
+    }
+
+    /// Identity table (`table[n] == n`) that bank writes go through on
+    /// bus-conflict boards. Empty when the mapper does not need one.
+    pub fn bank_table(&self) -> Vec<u8> {
+        if !self.bus_conflicts() {
+            return Vec::new();
+        }
+        let prg = match self {
+            Mapper::Uxrom => self.switchable_banks() + 1,
+            _ => self.switchable_banks(),
+        };
+        (0..prg.max(self.chr_banks()) as u8).collect()
+    }
+
+    /// Whether the fixed region is repeated in every PRG bank rather than
+    /// stored once at the end of the image.
+    pub fn mirrors_fixed_region(&self) -> bool {
+        *self == Mapper::Axrom
+    }
+
+    /// Number of `prg_bank_size` banks for a program whose highest `BANK`
+    /// is `highest`, including the fixed ones. Never less than 32 KB.
+    pub fn prg_banks(&self, highest: Option<u8>) -> usize {
+        // 0 on AxROM, where the fixed region lives inside every bank
+        let fixed = 0x4000 / self.prg_bank_size();
+        let minimum = 0x8000 / self.prg_bank_size();
+        match self {
+            Mapper::Nrom | Mapper::Cnrom => minimum,
+            _ => {
+                let needed = highest.map(|b| b as usize + 1 + fixed).unwrap_or(0);
+                needed.next_power_of_two().max(minimum)
+            }
+        }
+    }
+}
+
+/// The mapper selected by the program's `MAPPER` directive (NROM if none).
+pub fn target(program: &Program) -> Result<Mapper, String> {
+    let mut selected: Option<Mapper> = None;
+    for decl in &program.declarations {
+        if let TopLevel::Mapper(name) = decl {
+            let mapper =
+                Mapper::from_name(name).ok_or_else(|| format!("Unknown mapper '{}'", name))?;
+            if selected.is_some_and(|m| m != mapper) {
+                return Err("Conflicting MAPPER directives".to_string());
+            }
+            selected = Some(mapper);
+        }
+    }
+    Ok(selected.unwrap_or_default())
+}
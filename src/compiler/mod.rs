@@ -1,9 +1,20 @@
+pub mod actors;
 pub mod analysis;
 pub mod assembler;
 pub mod ast;
 pub mod audio;
+pub mod callgraph;
+pub mod cartridge;
 pub mod codegen;
+pub mod collision;
+pub mod compress;
+pub mod hud;
+pub mod ir;
 pub mod lexer;
+pub mod linker;
+pub mod mapper;
 pub mod parser;
+pub mod password;
 pub mod preprocessor;
+pub mod sprites;
 pub mod symbol_table;
@@ -1,6 +1,6 @@
 use super::ast::{
-    AnimationFrame, BinaryOperator, DataType, Expression, MetaspriteTile, Program, Statement,
-    TopLevel, UnaryOperator,
+    ActorDecl, AnimationFrame, BinaryOperator, DataType, Expression, MetaspriteTile, Program,
+    SpriteBox, Statement, TopLevel, UnaryOperator,
 };
 use super::lexer::Token;
 
@@ -75,6 +75,15 @@ impl Parser {
         }
 
         if self.match_token(Token::Dim) {
+            // DIM SAVE name ... (a variable may itself be called `save`)
+            let save = matches!(self.peek(), Token::Identifier(s) if s.eq_ignore_ascii_case("SAVE"))
+                && matches!(
+                    self.tokens.get(self.position + 1),
+                    Some(Token::Identifier(_))
+                );
+            if save {
+                self.advance();
+            }
             let name = if let Token::Identifier(n) = self.advance().clone() {
                 n
             } else {
@@ -105,10 +114,19 @@ impl Parser {
 
             let mut init_expr = None;
             if self.match_token(Token::Equal) {
+                if save {
+                    return Err(format!(
+                        "SAVE variable '{}' cannot be initialized (it keeps its saved value)",
+                        name
+                    ));
+                }
                 init_expr = Some(self.parse_expression()?);
             }
 
             self.match_token(Token::Newline);
+            if save {
+                return Ok(TopLevel::SaveDim(name, data_type));
+            }
             return Ok(TopLevel::Dim(name, data_type, init_expr));
         }
 
@@ -373,11 +391,29 @@ impl Parser {
             self.consume(Token::Newline, "Expected newline after METASPRITE name")?;
 
             let mut tiles = Vec::new();
+            let mut hitbox = None;
+            let mut hurtbox = None;
             while !self.check(Token::End) && !self.is_at_end() {
                 if self.match_token(Token::Newline) {
                     continue;
                 }
 
+                // HITBOX x, y, w, h / HURTBOX x, y, w, h
+                if let Token::Identifier(word) = self.peek().clone() {
+                    let is_hitbox = word.eq_ignore_ascii_case("HITBOX");
+                    if is_hitbox || word.eq_ignore_ascii_case("HURTBOX") {
+                        self.advance();
+                        let sprite_box = self.parse_sprite_box()?;
+                        if is_hitbox {
+                            hitbox = Some(sprite_box);
+                        } else {
+                            hurtbox = Some(sprite_box);
+                        }
+                        self.consume(Token::Newline, "Expected newline after box definition")?;
+                        continue;
+                    }
+                }
+
                 self.consume(Token::Tile, "Expected TILE definition inside METASPRITE")?;
 
                 let x = self.parse_expression()?;
@@ -404,7 +440,7 @@ impl Parser {
             self.consume(Token::End, "Expected END METASPRITE")?;
             self.consume(Token::Metasprite, "Expected METASPRITE after END")?;
 
-            return Ok(TopLevel::Metasprite(name, tiles));
+            return Ok(TopLevel::Metasprite(name, tiles, hitbox, hurtbox));
         }
 
         let mut data_label = None;
@@ -461,9 +497,234 @@ impl Parser {
             return Ok(TopLevel::Asm(lines));
         }
 
+        // MAPPER name
+        if let Token::Identifier(word) = self.peek().clone() {
+            if word.eq_ignore_ascii_case("MAPPER") {
+                self.advance();
+                let name = match self.advance().clone() {
+                    Token::Identifier(n) => n,
+                    Token::Integer(n) => n.to_string(),
+                    _ => return Err("Expected mapper name after MAPPER".to_string()),
+                };
+                self.match_token(Token::Newline);
+                return Ok(TopLevel::Mapper(name));
+            }
+
+            // SPRITES 8X16 lexes as 8, X16
+            if word.eq_ignore_ascii_case("SPRITES") {
+                self.advance();
+                let width = self.advance().clone();
+                let height = match (width, self.advance().clone()) {
+                    (Token::Integer(8), Token::Identifier(h)) => h.to_uppercase(),
+                    _ => String::new(),
+                };
+                let height = match height.as_str() {
+                    "X8" => 8,
+                    "X16" => 16,
+                    _ => return Err("Expected 8X8 or 8X16 after SPRITES".to_string()),
+                };
+                self.match_token(Token::Newline);
+                return Ok(TopLevel::Sprites(height));
+            }
+
+            if word.eq_ignore_ascii_case("PASSWORD") {
+                self.advance();
+                return self.parse_password();
+            }
+
+            if word.eq_ignore_ascii_case("ACTOR") {
+                self.advance();
+                return self.parse_actor();
+            }
+
+            // ACTORS n: slots in the actor table
+            if word.eq_ignore_ascii_case("ACTORS") {
+                self.advance();
+                let slots = match self.advance().clone() {
+                    Token::Integer(n) if (1..=255).contains(&n) => n as u8,
+                    _ => return Err("Expected slot count after ACTORS".to_string()),
+                };
+                self.match_token(Token::Newline);
+                return Ok(TopLevel::Actors(slots));
+            }
+
+            // BANK n / BANK FIXED
+            if word.eq_ignore_ascii_case("BANK") {
+                self.advance();
+                let bank = match self.advance().clone() {
+                    Token::Integer(n) if (0..=255).contains(&n) => Some(n as u8),
+                    Token::Identifier(n) if n.eq_ignore_ascii_case("FIXED") => None,
+                    _ => return Err("Expected bank number or FIXED after BANK".to_string()),
+                };
+                self.match_token(Token::Newline);
+                return Ok(TopLevel::Bank(bank));
+            }
+        }
+
         Err(format!("Unexpected token at top level: {:?}", self.peek()))
     }
 
+    // PASSWORD
+    //   name AS bits
+    //   ALPHABET "ABC..."   (optional)
+    //   KEY n               (optional)
+    // END PASSWORD
+    fn parse_password(&mut self) -> Result<TopLevel, String> {
+        self.consume(Token::Newline, "Expected newline after PASSWORD")?;
+        let mut fields = Vec::new();
+        let mut alphabet = None;
+        let mut key = None;
+        while !self.check(Token::End) && !self.is_at_end() {
+            if self.match_token(Token::Newline) {
+                continue;
+            }
+            let name = match self.advance().clone() {
+                Token::Identifier(n) => n,
+                t => return Err(format!("Expected field name in PASSWORD, found {:?}", t)),
+            };
+            if name.eq_ignore_ascii_case("ALPHABET") {
+                match self.advance().clone() {
+                    Token::StringLiteral(s) => alphabet = Some(s),
+                    _ => return Err("Expected string after ALPHABET".to_string()),
+                }
+            } else if name.eq_ignore_ascii_case("KEY") {
+                match self.advance().clone() {
+                    Token::Integer(n) if (0..=255).contains(&n) => key = Some(n as u8),
+                    _ => return Err("Expected a byte value after KEY".to_string()),
+                }
+            } else {
+                self.consume(Token::As, "Expected AS after PASSWORD field name")?;
+                match self.advance().clone() {
+                    Token::Integer(n) if (1..=16).contains(&n) => fields.push((name, n as u8)),
+                    _ => {
+                        return Err(format!(
+                            "Expected bit width (1-16) for PASSWORD field '{}'",
+                            name
+                        ))
+                    }
+                }
+            }
+            self.consume(Token::Newline, "Expected newline after PASSWORD entry")?;
+        }
+        self.consume(Token::End, "Expected END PASSWORD")?;
+        match self.advance().clone() {
+            Token::Identifier(w) if w.eq_ignore_ascii_case("PASSWORD") => {}
+            _ => return Err("Expected PASSWORD after END".to_string()),
+        }
+        self.match_token(Token::Newline);
+        Ok(TopLevel::Password(fields, alphabet, key))
+    }
+
+    // ACTOR Name
+    //   field AS Type [= value]
+    //   SIZE w, h           (optional)
+    //   CLASS a[, b ...]    (optional)
+    //   UPDATE SubName      (optional)
+    //   DRAW SubName        (optional)
+    // END ACTOR
+    fn parse_actor(&mut self) -> Result<TopLevel, String> {
+        let name = match self.advance().clone() {
+            Token::Identifier(n) => n,
+            _ => return Err("Expected identifier after ACTOR".to_string()),
+        };
+        self.consume(Token::Newline, "Expected newline after ACTOR name")?;
+        let mut actor = ActorDecl {
+            name,
+            fields: Vec::new(),
+            update: None,
+            draw: None,
+            classes: Vec::new(),
+            size: None,
+        };
+        while !self.check(Token::End) && !self.is_at_end() {
+            if self.match_token(Token::Newline) {
+                continue;
+            }
+            let word = match self.advance().clone() {
+                Token::Identifier(n) => n,
+                t => return Err(format!("Expected field or clause in ACTOR, found {:?}", t)),
+            };
+            if self.check(Token::As) || self.check(Token::LParen) {
+                actor.fields.push(self.parse_actor_field(word)?);
+            } else if word.eq_ignore_ascii_case("UPDATE") || word.eq_ignore_ascii_case("DRAW") {
+                let handler = match self.advance().clone() {
+                    Token::Identifier(n) => n,
+                    _ => return Err(format!("Expected SUB name after {}", word.to_uppercase())),
+                };
+                if word.eq_ignore_ascii_case("UPDATE") {
+                    actor.update = Some(handler);
+                } else {
+                    actor.draw = Some(handler);
+                }
+            } else if word.eq_ignore_ascii_case("CLASS") {
+                loop {
+                    match self.advance().clone() {
+                        Token::Identifier(n) => actor.classes.push(n),
+                        _ => return Err("Expected class name after CLASS".to_string()),
+                    }
+                    if !self.match_token(Token::Comma) {
+                        break;
+                    }
+                }
+            } else if word.eq_ignore_ascii_case("SIZE") {
+                let w = self.parse_expression()?;
+                self.consume(Token::Comma, "Expected ',' after SIZE width")?;
+                let h = self.parse_expression()?;
+                actor.size = Some((w, h));
+            } else {
+                return Err(format!(
+                    "Unknown ACTOR clause '{}' (field AS Type, SIZE, CLASS, UPDATE, DRAW)",
+                    word
+                ));
+            }
+            self.consume(Token::Newline, "Expected newline after ACTOR entry")?;
+        }
+        self.consume(Token::End, "Expected END ACTOR")?;
+        match self.advance().clone() {
+            Token::Identifier(w) if w.eq_ignore_ascii_case("ACTOR") => {}
+            _ => return Err("Expected ACTOR after END".to_string()),
+        }
+        self.match_token(Token::Newline);
+        Ok(TopLevel::Actor(Box::new(actor)))
+    }
+
+    // field[(n)] AS Type [= value], after the field name
+    fn parse_actor_field(
+        &mut self,
+        name: String,
+    ) -> Result<(String, DataType, Option<Expression>), String> {
+        let mut array_size = None;
+        if self.match_token(Token::LParen) {
+            match self.parse_expression()? {
+                Expression::Integer(val) if val > 0 => array_size = Some(val as usize),
+                _ => return Err("Array size must be a positive integer literal".to_string()),
+            }
+            self.consume(Token::RParen, "Expected ')' after array size")?;
+        }
+        self.consume(Token::As, "Expected AS after field name")?;
+        let mut data_type = self.parse_type()?;
+        if let Some(size) = array_size {
+            data_type = DataType::Array(Box::new(data_type), size);
+        }
+        let value = if self.match_token(Token::Equal) {
+            Some(self.parse_expression()?)
+        } else {
+            None
+        };
+        Ok((name, data_type, value))
+    }
+
+    fn parse_sprite_box(&mut self) -> Result<Box<SpriteBox>, String> {
+        let x = self.parse_expression()?;
+        self.consume(Token::Comma, "Expected ',' after box x")?;
+        let y = self.parse_expression()?;
+        self.consume(Token::Comma, "Expected ',' after box y")?;
+        let w = self.parse_expression()?;
+        self.consume(Token::Comma, "Expected ',' after box width")?;
+        let h = self.parse_expression()?;
+        Ok(Box::new(SpriteBox { x, y, w, h }))
+    }
+
     fn parse_type(&mut self) -> Result<DataType, String> {
         if self.match_token(Token::Byte) {
             return Ok(DataType::Byte);
@@ -613,6 +874,21 @@ impl Parser {
             }
         }
         if self.match_token(Token::On) {
+            if let Token::Identifier(n) = self.peek() {
+                if n.eq_ignore_ascii_case("SCANLINE") {
+                    self.advance();
+                    let line = self.parse_expression()?;
+                    if !self.match_token(Token::Do) {
+                        return Err("Expected DO after scanline number".to_string());
+                    }
+                    let routine = if let Token::Identifier(n) = self.advance().clone() {
+                        n
+                    } else {
+                        return Err("Expected routine name after DO".to_string());
+                    };
+                    return Ok(Statement::OnScanline(line, routine));
+                }
+            }
             let vector = if let Token::Identifier(n) = self.advance().clone() {
                 n
             } else {
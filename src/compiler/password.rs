@@ -0,0 +1,232 @@
+//! Password save format, shared by the generated 6502 routines
+//! (`Password.Encode` / `Password.Decode`) and host-side tools.
+//!
+//! A password is built in four steps:
+//! 1. Fields are packed LSB-first, in declaration order, into `P` payload
+//!    bytes (unused high bits are zero).
+//! 2. A checksum byte, `sum(payload) ^ $5A`, is appended at index `P`.
+//! 3. The bytes are obfuscated from the checksum down to byte 0:
+//!    `key = key * 5 + 1`, `b[i] = (b[i] ^ key) + b[i + 1]` (the
+//!    already-obfuscated neighbour), so changing any field, which always
+//!    changes the checksum, scrambles the whole password.
+//! 4. The byte stream is cut LSB-first into `log2(alphabet)`-bit groups, each
+//!    mapped to an alphabet character (the tile printed by `Text.Print`).
+
+use crate::compiler::ast::{Program, TopLevel};
+
+/// 32 characters without the easily confused I, O, 0 and 1.
+pub const DEFAULT_ALPHABET: &str = "ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+pub const DEFAULT_KEY: u8 = 0xA7;
+pub const CHECKSUM_SEED: u8 = 0x5A;
+/// Payload limit, keeping the runtime buffers and passwords short.
+pub const MAX_BITS: usize = 120;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PasswordFormat {
+    /// Variable name and bit width, in packing order.
+    pub fields: Vec<(String, u8)>,
+    pub alphabet: Vec<u8>,
+    pub key: u8,
+}
+
+impl PasswordFormat {
+    pub fn new(
+        fields: Vec<(String, u8)>,
+        alphabet: Option<&str>,
+        key: Option<u8>,
+    ) -> Result<Self, String> {
+        if fields.is_empty() {
+            return Err("PASSWORD needs at least one field".to_string());
+        }
+        for (name, width) in &fields {
+            if !(1..=16).contains(width) {
+                return Err(format!(
+                    "PASSWORD field '{}' width {} is out of range (1-16 bits)",
+                    name, width
+                ));
+            }
+        }
+        let bits: usize = fields.iter().map(|(_, w)| *w as usize).sum();
+        if bits > MAX_BITS {
+            return Err(format!(
+                "PASSWORD fields use {} bits (limit {})",
+                bits, MAX_BITS
+            ));
+        }
+
+        let alphabet = alphabet.unwrap_or(DEFAULT_ALPHABET).as_bytes().to_vec();
+        let len = alphabet.len();
+        if !len.is_power_of_two() || !(2..=64).contains(&len) {
+            return Err(format!(
+                "PASSWORD alphabet has {} characters (needs 2, 4, 8, 16, 32 or 64)",
+                len
+            ));
+        }
+        for (i, c) in alphabet.iter().enumerate() {
+            if *c == 0 || alphabet[..i].contains(c) {
+                return Err(format!(
+                    "PASSWORD alphabet character '{}' is repeated or invalid",
+                    *c as char
+                ));
+            }
+        }
+
+        Ok(Self {
+            fields,
+            alphabet,
+            key: key.unwrap_or(DEFAULT_KEY),
+        })
+    }
+
+    /// The program's PASSWORD declaration, if it has one.
+    pub fn from_program(program: &Program) -> Option<Result<Self, String>> {
+        program.declarations.iter().find_map(|decl| match decl {
+            TopLevel::Password(fields, alphabet, key) => {
+                Some(Self::new(fields.clone(), alphabet.as_deref(), *key))
+            }
+            _ => None,
+        })
+    }
+
+    pub fn field_bits(&self) -> usize {
+        self.fields.iter().map(|(_, w)| *w as usize).sum()
+    }
+
+    pub fn payload_bytes(&self) -> usize {
+        self.field_bits().div_ceil(8)
+    }
+
+    /// Payload plus checksum.
+    pub fn byte_len(&self) -> usize {
+        self.payload_bytes() + 1
+    }
+
+    pub fn char_bits(&self) -> usize {
+        self.alphabet.len().trailing_zeros() as usize
+    }
+
+    /// Characters in every password of this format.
+    pub fn length(&self) -> usize {
+        (self.byte_len() * 8).div_ceil(self.char_bits())
+    }
+
+    /// Encodes one value per field; values must fit their field width.
+    pub fn encode(&self, values: &[u16]) -> Result<String, String> {
+        if values.len() != self.fields.len() {
+            return Err(format!(
+                "Expected {} values, got {}",
+                self.fields.len(),
+                values.len()
+            ));
+        }
+        let mut bits = Vec::new();
+        for ((name, width), value) in self.fields.iter().zip(values) {
+            if (*value as u32) >> width != 0 {
+                return Err(format!(
+                    "Value {} does not fit in {}-bit field '{}'",
+                    value, width, name
+                ));
+            }
+            bits.extend((0..*width).map(|i| (value >> i) & 1 == 1));
+        }
+
+        let mut bytes = pack(&bits, self.payload_bytes());
+        bytes.push(checksum(&bytes));
+        let mut key = self.key;
+        let mut prev = 0;
+        for b in bytes.iter_mut().rev() {
+            key = next_key(key);
+            *b = (*b ^ key).wrapping_add(prev);
+            prev = *b;
+        }
+
+        let stream = unpack(&bytes);
+        Ok(stream
+            .chunks(self.char_bits())
+            .map(|chunk| {
+                let index = chunk
+                    .iter()
+                    .enumerate()
+                    .fold(0, |acc, (i, bit)| acc | (*bit as usize) << i);
+                self.alphabet[index] as char
+            })
+            .collect())
+    }
+
+    /// Decodes a password back to its field values.
+    pub fn decode(&self, password: &str) -> Result<Vec<u16>, String> {
+        if password.len() != self.length() {
+            return Err(format!(
+                "Password must be {} characters long",
+                self.length()
+            ));
+        }
+        let mut stream = Vec::new();
+        for c in password.bytes() {
+            let index = self
+                .alphabet
+                .iter()
+                .position(|a| *a == c)
+                .ok_or_else(|| format!("'{}' is not in the password alphabet", c as char))?;
+            stream.extend((0..self.char_bits()).map(|i| (index >> i) & 1 == 1));
+        }
+        let len = self.byte_len();
+        if stream[len * 8..].iter().any(|b| *b) {
+            return Err("Invalid password".to_string());
+        }
+
+        let mut bytes = pack(&stream[..len * 8], len);
+        let mut key = self.key;
+        let mut prev = 0;
+        for b in bytes.iter_mut().rev() {
+            key = next_key(key);
+            let obfuscated = *b;
+            *b = obfuscated.wrapping_sub(prev) ^ key;
+            prev = obfuscated;
+        }
+        let sum = bytes.pop().unwrap_or(0);
+        if checksum(&bytes) != sum {
+            return Err("Invalid password".to_string());
+        }
+
+        let payload = unpack(&bytes);
+        let mut values = Vec::new();
+        let mut pos = 0;
+        for (_, width) in &self.fields {
+            let width = *width as usize;
+            let value = payload[pos..pos + width]
+                .iter()
+                .enumerate()
+                .fold(0u16, |acc, (i, bit)| acc | (*bit as u16) << i);
+            values.push(value);
+            pos += width;
+        }
+        Ok(values)
+    }
+}
+
+fn next_key(key: u8) -> u8 {
+    key.wrapping_mul(5).wrapping_add(1)
+}
+
+fn checksum(payload: &[u8]) -> u8 {
+    payload.iter().fold(0u8, |acc, b| acc.wrapping_add(*b)) ^ CHECKSUM_SEED
+}
+
+// LSB-first bits to `len` bytes, zero-padded.
+fn pack(bits: &[bool], len: usize) -> Vec<u8> {
+    let mut bytes = vec![0u8; len];
+    for (i, bit) in bits.iter().enumerate() {
+        if *bit {
+            bytes[i / 8] |= 1 << (i % 8);
+        }
+    }
+    bytes
+}
+
+fn unpack(bytes: &[u8]) -> Vec<bool> {
+    bytes
+        .iter()
+        .flat_map(|b| (0..8).map(move |i| (b >> i) & 1 == 1))
+        .collect()
+}
@@ -295,6 +295,13 @@ fn replace_args_in_statement(stmt: &Statement, mapping: &HashMap<String, Express
             }
             Statement::On(new_vec, new_sub)
         }
+        Statement::OnScanline(line, sub) => {
+            let mut new_sub = sub.clone();
+            if let Some(Expression::Identifier(s)) = mapping.get(sub) {
+                new_sub = s.clone();
+            }
+            Statement::OnScanline(replace_args_in_expression(line.clone(), mapping), new_sub)
+        }
         _ => stmt.clone(),
     }
 }
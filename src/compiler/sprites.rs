@@ -0,0 +1,110 @@
+//! Metasprite and animation tables, placed together as the `sprites` section.
+//!
+//! A metasprite is its tile count, then x, y, tile and attribute per tile,
+//! the hitbox and hurtbox (x, y, width, height), and per axis the sum of the
+//! smallest and largest tile offset, which a flipped tile is mirrored around.
+//! An animation is its frame count and loop flag, then per frame the address
+//! of its metasprite and how many frames it shows. The runtime reads both
+//! from any bank, so the section always lives in the fixed bank.
+
+use crate::compiler::ast::{Program, TopLevel};
+use crate::compiler::symbol_table::SymbolTable;
+
+/// Whether `SPRITES 8X16` (or the project's sprite mode) is in effect.
+pub fn tall(program: &Program) -> bool {
+    program
+        .declarations
+        .iter()
+        .any(|d| matches!(d, TopLevel::Sprites(16)))
+}
+
+/// Offset of each metasprite and animation inside the section.
+pub fn offsets(program: &Program) -> Vec<(String, u16)> {
+    let mut offsets = Vec::new();
+    let mut offset = 0;
+    for decl in &program.declarations {
+        let (name, size) = match decl {
+            // Count, tiles, hitbox, hurtbox, flip sums
+            TopLevel::Metasprite(name, tiles, _, _) => (name, 1 + tiles.len() * 4 + 10),
+            // Count, loop flag, frames
+            TopLevel::Animation(name, frames, _) => (name, 2 + frames.len() * 3),
+            _ => continue,
+        };
+        offsets.push((name.clone(), offset as u16));
+        offset += size;
+    }
+    offsets
+}
+
+/// The section's bytes when it is placed at `base`.
+pub fn table(program: &Program, symbols: &SymbolTable, base: u16) -> Result<Vec<u8>, String> {
+    let offsets = offsets(program);
+    let tall = tall(program);
+    let constant = |expr| symbols.constant_value(expr);
+    let mut data = Vec::new();
+    for decl in &program.declarations {
+        match decl {
+            TopLevel::Metasprite(_, tiles, hitbox, hurtbox) => {
+                data.push(tiles.len() as u8);
+                for tile in tiles {
+                    let mut t = constant(&tile.tile)?;
+                    if tall {
+                        // Even tile pair; bit 0 picks the $1000 pattern table
+                        t = (t & 0xFE) | ((t >> 8) & 1);
+                    }
+                    data.extend([
+                        constant(&tile.x)? as u8,
+                        constant(&tile.y)? as u8,
+                        t as u8,
+                        constant(&tile.attr)? as u8,
+                    ]);
+                }
+                // The hurtbox falls back to the hitbox and a missing box is
+                // zero-sized so it never overlaps anything
+                for sprite_box in [hitbox.as_deref(), hurtbox.as_deref().or(hitbox.as_deref())] {
+                    match sprite_box {
+                        Some(b) => data.extend([
+                            constant(&b.x)? as u8,
+                            constant(&b.y)? as u8,
+                            constant(&b.w)? as u8,
+                            constant(&b.h)? as u8,
+                        ]),
+                        None => data.extend([0; 4]),
+                    }
+                }
+                for axis in 0..2 {
+                    let mut offsets = Vec::new();
+                    for tile in tiles {
+                        let expr = if axis == 0 { &tile.x } else { &tile.y };
+                        offsets.push(constant(expr)? as i8 as i32);
+                    }
+                    let sum = match (offsets.iter().min(), offsets.iter().max()) {
+                        (Some(min), Some(max)) => (min + max) as u8,
+                        _ => 0,
+                    };
+                    data.push(sum);
+                }
+            }
+            TopLevel::Animation(name, frames, loops) => {
+                data.push(frames.len() as u8);
+                data.push(*loops as u8);
+                for frame in frames {
+                    let offset = offsets
+                        .iter()
+                        .find(|(n, _)| *n == frame.metasprite)
+                        .map(|(_, o)| *o)
+                        .ok_or_else(|| {
+                            format!(
+                                "Animation '{}' references undefined metasprite '{}'",
+                                name, frame.metasprite
+                            )
+                        })?;
+                    let addr = base + offset;
+                    data.extend([addr as u8, (addr >> 8) as u8, frame.duration]);
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(data)
+}
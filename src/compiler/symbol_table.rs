@@ -1,4 +1,4 @@
-use crate::compiler::ast::DataType;
+use crate::compiler::ast::{BinaryOperator, DataType, Expression, UnaryOperator};
 use std::collections::HashMap;
 
 #[derive(Debug, Clone, PartialEq)]
@@ -203,6 +203,35 @@ impl SymbolTable {
         }
         Err(format!("Symbol '{}' not found", name))
     }
+
+    /// Value of a constant expression: literals, CONSTs and enum members
+    /// with their values assigned, and + - * / between them.
+    pub fn constant_value(&self, expr: &Expression) -> Result<i32, String> {
+        match expr {
+            Expression::Integer(val) => Ok(*val),
+            Expression::UnaryOp(UnaryOperator::Negate, operand) => {
+                Ok(-self.constant_value(operand)?)
+            }
+            Expression::Identifier(name) => match self.resolve(name) {
+                Some(sym) => sym
+                    .value
+                    .ok_or_else(|| format!("Symbol '{}' is not a constant value", name)),
+                None => Err(format!("Undefined symbol '{}'", name)),
+            },
+            Expression::BinaryOp(l, op, r) => {
+                let lv = self.constant_value(l)?;
+                let rv = self.constant_value(r)?;
+                match op {
+                    BinaryOperator::Add => Ok(lv + rv),
+                    BinaryOperator::Subtract => Ok(lv - rv),
+                    BinaryOperator::Multiply => Ok(lv * rv),
+                    BinaryOperator::Divide => Ok(lv / rv),
+                    _ => Err("Unsupported constant operator".to_string()),
+                }
+            }
+            _ => Err("Not a constant expression".to_string()),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -1,14 +1,22 @@
 use crate::compiler::{
+    actors,
     analysis::SemanticAnalyzer,
     assembler::Assembler,
-    ast::{AnimationFrame, Expression, MetaspriteTile, TopLevel},
+    ast::{AnimationFrame, Expression, MetaspriteTile, PlacedObject, SpriteBox, TopLevel},
     audio,
-    codegen::{CodeGenerator, ENVELOPE_TABLE_ADDR, NAMETABLE_ADDR},
+    cartridge::CartridgeConfig,
+    codegen::CodeGenerator,
+    collision,
+    compress::{self, Compression},
+    hud,
     lexer::Lexer,
+    linker::{self, Linker, Section},
+    mapper,
     parser::Parser,
-    preprocessor,
+    password::PasswordFormat,
+    preprocessor, sprites,
 };
-use crate::server::project::{self, ProjectAssets};
+use crate::server::project::{self, Nametable, ProjectAssets};
 use axum::{
     extract::Path,
     http::StatusCode,
@@ -16,30 +24,46 @@ use axum::{
     Json,
 };
 use serde::Deserialize;
+use std::collections::BTreeMap;
 
 #[derive(Deserialize)]
 pub struct CompileRequest {
     source: Option<String>,
     project_name: Option<String>,
     assets: Option<ProjectAssets>,
+    #[serde(default)]
+    cartridge: Option<CartridgeConfig>,
 }
 
 pub async fn compile(Json(payload): Json<CompileRequest>) -> impl IntoResponse {
     // Spawn a blocking task for the CPU-intensive compilation process
     let result = tokio::task::spawn_blocking(move || {
-        compile_source(payload.source, payload.project_name, payload.assets)
+        compile_project(
+            payload.source,
+            payload.project_name,
+            payload.assets,
+            payload.cartridge,
+        )
     })
     .await;
 
     match result {
         Ok(compile_result) => match compile_result {
-            Ok(rom_data) => {
-                // Return the binary data
+            Ok(output) => {
+                // Return the binary data, with the compression report in a
+                // header (printable ASCII only, so asset names cannot break it)
+                let report: String = output
+                    .compression_report
+                    .join("; ")
+                    .chars()
+                    .filter(|c| c.is_ascii_graphic() || *c == ' ')
+                    .collect();
                 Response::builder()
                     .status(StatusCode::OK)
                     .header("Content-Type", "application/octet-stream")
                     .header("Content-Disposition", "attachment; filename=\"game.nes\"")
-                    .body(axum::body::Body::from(rom_data))
+                    .header("X-Compression-Report", report)
+                    .body(axum::body::Body::from(output.rom))
                     .unwrap()
             }
             Err(err_msg) => {
@@ -63,6 +87,34 @@ pub fn compile_source(
     project_name: Option<String>,
     assets: Option<ProjectAssets>,
 ) -> Result<Vec<u8>, String> {
+    compile_source_with_cartridge(source, project_name, assets, None)
+}
+
+/// Like `compile_source`, with an explicit cartridge config. Without one the
+/// project's `project.json` config is used, or the defaults.
+pub fn compile_source_with_cartridge(
+    source: Option<String>,
+    project_name: Option<String>,
+    assets: Option<ProjectAssets>,
+    cartridge: Option<CartridgeConfig>,
+) -> Result<Vec<u8>, String> {
+    compile_project(source, project_name, assets, cartridge).map(|output| output.rom)
+}
+
+/// A compiled ROM and how its assets were stored.
+pub struct CompileOutput {
+    pub rom: Vec<u8>,
+    /// One line per stored asset, e.g. `nametable: LZ, 1024 -> 212 bytes (21%)`.
+    pub compression_report: Vec<String>,
+}
+
+/// Like `compile_source_with_cartridge`, also returning the compression report.
+pub fn compile_project(
+    source: Option<String>,
+    project_name: Option<String>,
+    assets: Option<ProjectAssets>,
+    cartridge: Option<CartridgeConfig>,
+) -> Result<CompileOutput, String> {
     // Resolve source
     let source_code = if let Some(s) = source {
         s
@@ -88,6 +140,17 @@ pub fn compile_source(
         None
     };
 
+    // Resolve cartridge config
+    let mut cartridge = if let Some(c) = cartridge {
+        c
+    } else if let Some(ref name) = project_name {
+        project::get_project(name)
+            .map(|p| p.metadata.cartridge)
+            .unwrap_or_default()
+    } else {
+        CartridgeConfig::default()
+    };
+
     // 1. Lexing
     let mut lexer = Lexer::new(&source_code);
     let tokens = lexer
@@ -120,6 +183,9 @@ pub fn compile_source(
     // 2d. Inject Assets (Metasprites/Animations/Metatiles/World)
     let mut program = program;
     if let Some(assets) = &resolved_assets {
+        // The runtime reads these tables from whichever bank is mapped, so
+        // they stay fixed; screens, audio and CHR tiles pick their own bank
+        program.declarations.push(TopLevel::Bank(None));
         for ms in &assets.metasprites {
             let tiles: Vec<MetaspriteTile> = ms
                 .tiles
@@ -131,9 +197,20 @@ pub fn compile_source(
                     attr: Expression::Integer(t.attr as i32),
                 })
                 .collect();
-            program
-                .declarations
-                .push(TopLevel::Metasprite(ms.name.clone(), tiles));
+            let sprite_box = |b: &project::SpriteBox| {
+                Box::new(SpriteBox {
+                    x: Expression::Integer(b.x as i32),
+                    y: Expression::Integer(b.y as i32),
+                    w: Expression::Integer(b.w as i32),
+                    h: Expression::Integer(b.h as i32),
+                })
+            };
+            program.declarations.push(TopLevel::Metasprite(
+                ms.name.clone(),
+                tiles,
+                ms.hitbox.as_ref().map(sprite_box),
+                ms.hurtbox.as_ref().map(sprite_box),
+            ));
         }
 
         for anim in &assets.animations {
@@ -158,13 +235,75 @@ pub fn compile_source(
                 .push(TopLevel::Metatile(mt.name.clone(), mt.tiles, mt.attr));
         }
 
+        // The project's sprite size applies like a SPRITES directive
+        if assets.sprite_mode == project::SpriteMode::Tall {
+            program.declarations.push(TopLevel::Sprites(16));
+        }
+
         if let Some(world) = &assets.world {
             program.declarations.push(TopLevel::World(
                 world.width,
                 world.height,
                 world.data.clone(),
             ));
+            for object in &world.objects {
+                program
+                    .declarations
+                    .push(TopLevel::WorldObject(Box::new(PlacedObject {
+                        room: (object.room_x, object.room_y),
+                        actor: object.actor.clone(),
+                        x: object.x,
+                        y: object.y,
+                        params: object.params.clone().into_iter().collect(),
+                    })));
+            }
+        }
+
+        // Screen ids for Screen.Load, in asset order
+        for (id, nt) in assets.nametables.iter().enumerate() {
+            program.declarations.push(TopLevel::Const(
+                nt.constant_name(),
+                Expression::Integer(id as i32),
+            ));
+        }
+
+        // Palette set ids for Palette.Load; the startup set is id 0
+        let mut palette_names: Vec<String> = Vec::new();
+        for (id, (set, _)) in assets.palette_sets().iter().enumerate() {
+            let name = project::palette_constant(*set);
+            if palette_names.contains(&name) {
+                return Err(format!(
+                    "Asset Error: palette set '{}' clashes with another set named {}",
+                    set.unwrap_or_default(),
+                    name
+                ));
+            }
+            palette_names.push(name.clone());
+            program
+                .declarations
+                .push(TopLevel::Const(name, Expression::Integer(id as i32)));
+        }
+    }
+
+    // 2e. The cartridge config can select the mapper for the source
+    if let Some(name) = &cartridge.mapper {
+        let configured = mapper::Mapper::from_name(name)
+            .ok_or_else(|| format!("Cartridge Error: Unknown mapper '{}'", name))?;
+        let declared = mapper::target(&program)?;
+        let has_directive = program
+            .declarations
+            .iter()
+            .any(|d| matches!(d, TopLevel::Mapper(_)));
+        if has_directive && declared != configured {
+            return Err(format!(
+                "Cartridge Error: project.json selects {} but the source uses MAPPER {}",
+                configured.name(),
+                declared.name()
+            ));
         }
+        program
+            .declarations
+            .insert(0, TopLevel::Mapper(name.clone()));
     }
 
     // 3. Analysis
@@ -173,99 +312,525 @@ pub fn compile_source(
         .analyze(&program)
         .map_err(|e| format!("Analysis Error: {:?}", e))?;
 
-    // 4. Codegen
-    let symbol_table = analyzer.symbol_table;
-
-    // Create CodeGenerator (reverted signature)
-    let mut codegen = CodeGenerator::new(symbol_table);
-    let asm_lines = codegen
-        .generate(&program)
-        .map_err(|e| format!("Codegen Error: {:?}", e))?;
-    let asm_source = asm_lines.join("\n");
+    // DIM SAVE variables need the battery to survive power-off
+    if program
+        .declarations
+        .iter()
+        .any(|d| matches!(d, TopLevel::SaveDim(..)))
+    {
+        cartridge.battery = true;
+    }
 
-    // 5. Assembler
-    let assembler = Assembler::new();
+    let target = mapper::target(&program)?;
+    cartridge
+        .validate(target)
+        .map_err(|e| format!("Cartridge Error: {}", e))?;
+
+    // Assets placed in a switchable bank, like BANK n for code
+    let asset_bank = |name: &str, bank: Option<u8>| match bank {
+        Some(n) if target.switchable_banks() == 0 => Err(format!(
+            "Asset Error: '{}' is placed in bank {}, which requires a bank-switching mapper \
+             (e.g. MAPPER MMC1)",
+            name, n
+        )),
+        Some(n) if n as usize >= target.switchable_banks() => Err(format!(
+            "Asset Error: '{}' bank {} is out of range for {} (banks 0-{})",
+            name,
+            n,
+            target.name(),
+            target.switchable_banks() - 1
+        )),
+        _ => Ok(bank),
+    };
+    let audio_bank = asset_bank("audio", resolved_assets.as_ref().and_then(|a| a.audio_bank))?;
+
+    // 4. Asset Sections
+    // Palettes: 32 bytes per set, the startup set first
+    let palette_data = match &resolved_assets {
+        Some(a) => a
+            .palette_sets()
+            .iter()
+            .flat_map(|(_, data)| *data)
+            .collect(),
+        None => vec![0x0F; 32], // Default palette
+    };
 
-    let chr_data = resolved_assets.as_ref().map(|a| a.chr_bank.as_slice());
+    let period_table = audio::generate_period_table();
 
-    // Prepare Injections
-    let mut injections: Vec<(u16, Vec<u8>)> = Vec::new();
-
-    // 1. Palette Data at $E000
-    let palette_data = if let Some(a) = &resolved_assets {
-        let mut data = vec![0x0F; 32];
-        let names = ["BG0", "BG1", "BG2", "BG3", "SP0", "SP1", "SP2", "SP3"];
-        for (i, name) in names.iter().enumerate() {
-            if let Some(pal) = a.palettes.iter().find(|p| p.name == *name) {
-                let start_idx = i * 4;
-                for (j, &color) in pal.colors.iter().enumerate() {
-                    if start_idx + j < 32 {
-                        data[start_idx + j] = color;
-                    }
-                }
-            }
+    // Nametable Data: 960 bytes + 64 bytes attr = 1024 bytes per screen.
+    // Startup always copies screen 0 to the PPU, so an empty one is reserved
+    // when none is defined.
+    let nametables = resolved_assets
+        .as_ref()
+        .map(|a| a.nametables.as_slice())
+        .unwrap_or_default();
+    if nametables.len() > linker::MAX_SCREENS {
+        return Err(format!(
+            "Asset Error: {} nametables defined (limit {})",
+            nametables.len(),
+            linker::MAX_SCREENS
+        ));
+    }
+    // Stored in the smallest format unless the asset picks one. Collision.Tile
+    // reads tiles straight out of ROM, so it needs the raw layout; so do
+    // Camera.Update and scrolling World.Transition, which copy rows and
+    // columns out of every screen, Collision.Flags reading tiles from any
+    // room, and World.Enter looking up rooms.
+    let raw_screens = analyzer.streams_world() || analyzer.reads_collision();
+    let stored_format = |name: &str, format: Option<Compression>, raw: bool| {
+        if !raw {
+            return Ok(format.unwrap_or(Compression::Auto));
+        }
+        if matches!(
+            format,
+            Some(Compression::Rle | Compression::Lz | Compression::Metatile)
+        ) {
+            return Err(format!(
+                "Asset Error: '{}' is read from ROM at runtime (Camera, World, Collision), \
+                 so it cannot be compressed",
+                name
+            ));
         }
-        data
+        Ok(Compression::None)
+    };
+    let nt_format = nametables.first().and_then(|nt| nt.compression);
+    let nt_format = if analyzer.reads_nametable() {
+        if matches!(
+            nt_format,
+            Some(Compression::Rle | Compression::Lz | Compression::Metatile)
+        ) {
+            return Err("Asset Error: Collision.Tile reads the nametable from ROM, \
+                 so it cannot be compressed"
+                .to_string());
+        }
+        Compression::None
     } else {
-        vec![0x0F; 32] // Default palette
+        let name = nametables.first().map_or("", |nt| nt.name.as_str());
+        stored_format(name, nt_format, raw_screens)?
+    };
+    // A screen drawn entirely from metatiles can be stored as its metatile
+    // map, which expands through the project's metatiles (at most 256 ids)
+    let metatile_tiles: Vec<[u8; 4]> = resolved_assets
+        .as_ref()
+        .map(|a| a.metatiles.iter().take(256).map(|m| m.tiles).collect())
+        .unwrap_or_default();
+    let pack_screen = |nt: &Nametable, format: Compression| {
+        let bytes = nt.to_bytes();
+        let map = || compress::pack_metatiles(&bytes, &nt.metatile_grid, &metatile_tiles);
+        match format {
+            Compression::Metatile => map().ok_or_else(|| {
+                format!(
+                    "Asset Error: '{}' is not drawn entirely from metatiles, \
+                     so it cannot be stored as a metatile map",
+                    nt.name
+                )
+            }),
+            Compression::Auto => {
+                let packed = compress::pack(&bytes, format);
+                Ok(match map() {
+                    Some(map) if map.data.len() < packed.data.len() => map,
+                    _ => packed,
+                })
+            }
+            _ => Ok(compress::pack(&bytes, format)),
+        }
+    };
+    let nametable = match nametables.first() {
+        Some(nt) => pack_screen(nt, nt_format)?,
+        None => compress::pack(&[0u8; 1024], nt_format),
+    };
+    let mut compression_report = vec![nametable.report(linker::SECTION_NAMETABLE)];
+
+    // The other screens are only reached through Screen.Load, which finds
+    // them in the screen table: address and storage format per id. Banked
+    // screens are grouped per bank, and the bank table maps each id to its
+    // bank ($FF for the fixed bank).
+    if let Some(nt) = nametables.first().filter(|nt| nt.bank.is_some()) {
+        return Err(format!(
+            "Asset Error: '{}' is the startup screen, so it stays in the fixed bank",
+            nt.name
+        ));
+    }
+    let mut screen_data: BTreeMap<Option<u8>, Vec<u8>> = BTreeMap::new();
+    let mut screen_offsets = Vec::new();
+    let mut screen_formats = vec![nametable.format];
+    let mut screen_banks = vec![0xFF];
+    for nt in nametables.iter().skip(1) {
+        let format = stored_format(&nt.name, nt.compression, raw_screens)?;
+        let packed = pack_screen(nt, format)?;
+        let bank = asset_bank(&nt.name, nt.bank)?;
+        compression_report.push(packed.report(&nt.constant_name()));
+        let data = screen_data.entry(bank).or_default();
+        screen_offsets.push((bank, data.len()));
+        screen_formats.push(packed.format);
+        screen_banks.push(bank.unwrap_or(0xFF));
+        data.extend(packed.data);
+    }
+    if !screen_data.keys().any(Option::is_some) {
+        screen_banks.clear();
+    }
+    let screens_section = |bank: Option<u8>| match bank {
+        Some(n) => linker::banked_screens_section(n),
+        None => linker::SECTION_SCREENS.to_string(),
     };
-    injections.push((0xE000, palette_data));
 
-    // 2. Period Table at $D000
-    let period_table = audio::generate_period_table();
-    injections.push((audio::PERIOD_TABLE_ADDR, period_table));
+    // World room map: width, height and storage format (0 raw, 1 RLE, 2 LZ),
+    // then the room indices (-1, an empty room, becomes $FF)
+    let mut world_data = Vec::new();
+    if let Some(world) = resolved_assets.as_ref().and_then(|a| a.world.as_ref()) {
+        if world.compression == Some(Compression::Metatile) {
+            return Err(
+                "Asset Error: only screens can be stored as a metatile map, not 'world'"
+                    .to_string(),
+            );
+        }
+        if world.width > 255 || world.height > 255 {
+            return Err(format!(
+                "Asset Error: world is {}x{} rooms (at most 255 in each direction)",
+                world.width, world.height
+            ));
+        }
+        // $FF marks an empty room, so room ids stop at 254
+        if let Some(id) = world.data.iter().find(|&&i| !(-1..=254).contains(&i)) {
+            return Err(format!(
+                "Asset Error: world room id {} is out of range (0-254, or -1 for empty)",
+                id
+            ));
+        }
+        let rooms: Vec<u8> = world.data.iter().map(|i| *i as u8).collect();
+        let raw = raw_screens || analyzer.reads_world();
+        let packed = compress::pack(&rooms, stored_format("world", world.compression, raw)?);
+        compression_report.push(packed.report(linker::SECTION_WORLD));
+        world_data = vec![world.width as u8, world.height as u8, packed.format as u8];
+        world_data.extend(packed.data);
+    }
 
-    // 3. Music Data at $D100
-    let music_data =
-        audio::compile_audio_data(&resolved_assets).map_err(|e| format!("Audio Error: {}", e))?;
-    injections.push((audio::MUSIC_DATA_ADDR, music_data));
+    // Collision.Flags: flags per tile id, metatile flags OR'd into their tiles
+    let mut collision_table = Vec::new();
+    if analyzer.reads_collision() {
+        let (tile_flags, metatiles) = match &resolved_assets {
+            Some(assets) => (
+                assets.tile_collision.as_slice(),
+                assets
+                    .metatiles
+                    .iter()
+                    .map(|m| (m.tiles, m.collision))
+                    .collect(),
+            ),
+            None => (&[][..], Vec::new()),
+        };
+        collision_table = collision::flag_table(tile_flags, &metatiles)
+            .map_err(|e| format!("Asset Error: {}", e))?;
+    }
+
+    let metatile_table = if screen_formats.contains(&Compression::Metatile) {
+        compress::metatile_table(&metatile_tiles)
+    } else {
+        Vec::new()
+    };
+
+    // World.SpawnRoomObjects: each room's placed actors
+    let room_spawns = if analyzer.spawns_room_objects() {
+        actors::room_spawn_table(&program, &analyzer.symbol_table)
+    } else {
+        Vec::new()
+    };
 
-    // 3b. Sample Data
+    // Metasprites and animations; animation frames point at metasprites, so
+    // the table is built again once the section is placed
+    let sprite_err = |e: String| format!("Sprite Error: {}", e);
+    let sprites_size = sprites::table(&program, &analyzer.symbol_table, 0)
+        .map_err(sprite_err)?
+        .len();
+
+    // Audio blobs embed absolute pointers, so they are compiled once to
+    // measure them and again once the linker has placed them.
+    let audio_err = |e: String| format!("Audio Error: {}", e);
+    let music_size = audio::compile_audio_data_at(&resolved_assets, audio::MUSIC_DATA_ADDR)
+        .map_err(audio_err)?
+        .len();
     let (samples, sample_table) =
-        audio::compile_samples(&resolved_assets).map_err(|e| format!("Audio Error: {}", e))?;
-    if !samples.is_empty() {
-        injections.push((audio::SAMPLE_DATA_ADDR, samples));
+        audio::compile_samples_at(&resolved_assets, audio::SAMPLE_DATA_ADDR).map_err(audio_err)?;
+    if sample_table.len() > audio::SAMPLE_TABLE_SIZE {
+        return Err(audio_err(format!(
+            "Sample Table exceeds limit of {} bytes (got {})",
+            audio::SAMPLE_TABLE_SIZE,
+            sample_table.len()
+        )));
+    }
+    let envelope_size = audio::compile_envelopes_at(&resolved_assets, audio::ENVELOPE_TABLE_ADDR)
+        .map_err(audio_err)?
+        .len();
+    let sfx_size = audio::compile_sfx_data_at(&resolved_assets, audio::SFX_TABLE_ADDR)
+        .map_err(audio_err)?
+        .len();
+
+    // CHR-RAM boards carry their tiles in PRG. The first 8 KB is uploaded at
+    // startup; with compression the rest stays raw for CHR.Load.
+    let mut chr_startup = Vec::new();
+    let mut chr_tiles = Vec::new();
+    let mut chr_format = Compression::None;
+    if cartridge.chr_ram {
+        let chr = resolved_assets
+            .as_ref()
+            .map(|a| a.chr_bank.clone())
+            .unwrap_or_default();
+        let split = chr.len().min(0x2000);
+        let packed = compress::pack(&chr[..split], cartridge.chr_compression);
+        compression_report.push(packed.report(linker::SECTION_CHR));
+        chr_format = packed.format;
+        if chr_format == Compression::None {
+            chr_startup = chr;
+            chr_startup.resize(chr_startup.len().div_ceil(256) * 256, 0);
+        } else {
+            chr_startup = packed.data;
+            chr_tiles = chr[split..].to_vec();
+        }
+    }
+
+    // HUD.Split draws sprite 0 with a tile whose top row is opaque
+    let hud_tile = if analyzer.splits_hud() {
+        let chr = resolved_assets
+            .as_ref()
+            .map(|a| a.chr_bank.as_slice())
+            .unwrap_or_default();
+        let tall = program
+            .declarations
+            .iter()
+            .any(|d| matches!(d, TopLevel::Sprites(16)));
+        let kind = if tall {
+            "an even CHR tile"
+        } else {
+            "a CHR tile"
+        };
+        let tile = hud::sprite_zero_tile(chr, tall).ok_or_else(|| {
+            format!(
+                "Asset Error: HUD.Split needs {} whose top row is fully opaque for sprite 0",
+                kind
+            )
+        })?;
+        Some(tile)
+    } else {
+        None
+    };
+
+    // Password.Decode searches the alphabet, so it lives at a known address
+    let password_alphabet = match PasswordFormat::from_program(&program) {
+        Some(format) => format?.alphabet,
+        None => Vec::new(),
+    };
+
+    let mut linker = Linker::for_mapper(target);
+    linker.add(Section::aligned(
+        linker::SECTION_DPCM,
+        samples.len(),
+        linker::DPCM_ALIGN,
+        linker::DPCM_MIN_ADDR,
+    ));
+    linker.add(Section::new(linker::SECTION_PALETTE, palette_data.len()));
+    linker.add(Section::new(
+        linker::SECTION_PERIOD_TABLE,
+        period_table.len(),
+    ));
+    linker.add(Section::banked(
+        linker::SECTION_MUSIC,
+        music_size,
+        audio_bank,
+    ));
+    linker.add(Section::new(
+        linker::SECTION_SAMPLE_TABLE,
+        sample_table.len(),
+    ));
+    linker.add(Section::banked(linker::SECTION_SFX, sfx_size, audio_bank));
+    linker.add(Section::banked(
+        linker::SECTION_ENVELOPES,
+        envelope_size,
+        audio_bank,
+    ));
+    linker.add(Section::new(
+        linker::SECTION_NAMETABLE,
+        nametable.data.len(),
+    ));
+    for (bank, data) in &screen_data {
+        linker.add(Section::banked(&screens_section(*bank), data.len(), *bank));
+    }
+    if !screen_banks.is_empty() {
+        linker.add(Section::new(
+            linker::SECTION_SCREEN_BANKS,
+            screen_banks.len(),
+        ));
+    }
+    linker.add(Section::new(
+        linker::SECTION_SCREEN_TABLE,
+        screen_formats.len() * 3,
+    ));
+    if !world_data.is_empty() {
+        linker.add(Section::new(linker::SECTION_WORLD, world_data.len()));
+    }
+    if !collision_table.is_empty() {
+        linker.add(Section::new(
+            linker::SECTION_COLLISION,
+            collision_table.len(),
+        ));
+    }
+    if !room_spawns.is_empty() {
+        linker.add(Section::new(
+            linker::SECTION_WORLD_OBJECTS,
+            room_spawns.len(),
+        ));
+    }
+    if !metatile_table.is_empty() {
+        linker.add(Section::new(
+            linker::SECTION_METATILES,
+            metatile_table.len(),
+        ));
+    }
+    if sprites_size > 0 {
+        linker.add(Section::new(linker::SECTION_SPRITES, sprites_size));
     }
-    injections.push((audio::SAMPLE_TABLE_ADDR, sample_table));
+    let handlers_size = actors::handler_table_size(&program);
+    if handlers_size > 0 {
+        linker.add(Section::new(linker::SECTION_ACTOR_HANDLERS, handlers_size));
+    }
+    let bank_table = target.bank_table();
+    if !bank_table.is_empty() {
+        linker.add(Section::new(linker::SECTION_BANK_TABLE, bank_table.len()));
+    }
+    if !chr_startup.is_empty() {
+        linker.add(Section::banked(
+            linker::SECTION_CHR,
+            chr_startup.len(),
+            cartridge.chr_prg_bank,
+        ));
+    }
+    if !chr_tiles.is_empty() {
+        linker.add(Section::banked(
+            linker::SECTION_CHR_TILES,
+            chr_tiles.len(),
+            cartridge.chr_prg_bank,
+        ));
+    }
+    if !password_alphabet.is_empty() {
+        linker.add(Section::new(
+            linker::SECTION_PASSWORD_ALPHABET,
+            password_alphabet.len(),
+        ));
+    }
+    let layout = linker.link().map_err(|e| format!("Linker Error: {}", e))?;
 
-    // 3c. Envelope Data
+    let music_data =
+        audio::compile_audio_data_at(&resolved_assets, layout.addr(linker::SECTION_MUSIC))
+            .map_err(audio_err)?;
+    let (samples, sample_table) =
+        audio::compile_samples_at(&resolved_assets, layout.addr(linker::SECTION_DPCM))
+            .map_err(audio_err)?;
     let envelope_data =
-        audio::compile_envelopes(&resolved_assets).map_err(|e| format!("Audio Error: {}", e))?;
-    injections.push((ENVELOPE_TABLE_ADDR, envelope_data));
-
-    // 3d. SFX Data
-    let sfx_data =
-        audio::compile_sfx_data(&resolved_assets).map_err(|e| format!("Audio Error: {}", e))?;
-    injections.push((audio::SFX_TABLE_ADDR, sfx_data));
-
-    // 4. Nametable Data at $D500 (NAMETABLE_ADDR)
-    // We only support one nametable for now (Nametable 0)
-    if let Some(a) = &resolved_assets {
-        if let Some(nt) = a.nametables.first() {
-            // Nametable data is 960 bytes + 64 bytes attr = 1024 bytes
-            // Check if data is valid length
-            let mut nt_data = nt.data.clone();
-            if nt_data.len() < 960 {
-                nt_data.resize(960, 0);
-            }
-            let mut attr_data = nt.attrs.clone();
-            if attr_data.len() < 64 {
-                attr_data.resize(64, 0);
-            }
+        audio::compile_envelopes_at(&resolved_assets, layout.addr(linker::SECTION_ENVELOPES))
+            .map_err(audio_err)?;
+    let sfx_data = audio::compile_sfx_data_at(&resolved_assets, layout.addr(linker::SECTION_SFX))
+        .map_err(audio_err)?;
+    let sprite_data = sprites::table(
+        &program,
+        &analyzer.symbol_table,
+        layout.addr(linker::SECTION_SPRITES),
+    )
+    .map_err(sprite_err)?;
+    let screen_addrs = std::iter::once(layout.addr(linker::SECTION_NAMETABLE) as usize).chain(
+        screen_offsets
+            .iter()
+            .map(|(bank, offset)| layout.addr(&screens_section(*bank)) as usize + offset),
+    );
+    let screen_table: Vec<u8> = screen_addrs
+        .zip(&screen_formats)
+        .flat_map(|(addr, format)| [addr as u8, (addr >> 8) as u8, *format as u8])
+        .collect();
+
+    let blobs = vec![
+        (linker::SECTION_PALETTE, palette_data),
+        (linker::SECTION_PERIOD_TABLE, period_table),
+        (linker::SECTION_MUSIC, music_data),
+        (linker::SECTION_DPCM, samples),
+        (linker::SECTION_SAMPLE_TABLE, sample_table),
+        (linker::SECTION_ENVELOPES, envelope_data),
+        (linker::SECTION_SFX, sfx_data),
+        (linker::SECTION_NAMETABLE, nametable.data),
+        (linker::SECTION_SCREEN_TABLE, screen_table),
+        (linker::SECTION_SCREEN_BANKS, screen_banks),
+        (linker::SECTION_METATILES, metatile_table),
+        (linker::SECTION_WORLD, world_data),
+        (linker::SECTION_COLLISION, collision_table),
+        (linker::SECTION_WORLD_OBJECTS, room_spawns),
+        (linker::SECTION_SPRITES, sprite_data),
+        (linker::SECTION_BANK_TABLE, bank_table),
+        (linker::SECTION_CHR, chr_startup),
+        (linker::SECTION_CHR_TILES, chr_tiles),
+        (linker::SECTION_PASSWORD_ALPHABET, password_alphabet),
+    ];
+    let handlers_addr = layout.addr(linker::SECTION_ACTOR_HANDLERS);
+    let blobs = blobs
+        .into_iter()
+        .map(|(name, data)| (name.to_string(), data))
+        .chain(
+            screen_data
+                .into_iter()
+                .map(|(bank, data)| (screens_section(bank), data)),
+        );
+    let mut sections: Vec<(String, Option<u8>, u16, Vec<u8>)> = blobs
+        .filter(|(_, data)| !data.is_empty())
+        .map(|(name, data)| (name.clone(), layout.bank(&name), layout.addr(&name), data))
+        .collect();
+
+    // 5. Codegen
+    let symbol_table = analyzer.symbol_table;
 
-            let mut full_nt = Vec::with_capacity(1024);
-            full_nt.extend_from_slice(&nt_data[..960]);
-            full_nt.extend_from_slice(&attr_data[..64]);
+    // Create CodeGenerator (reverted signature)
+    let mut codegen = CodeGenerator::new(symbol_table);
+    codegen.set_use_ir(true);
+    codegen.set_layout(layout);
+    codegen.set_mirroring(cartridge.mirroring);
+    codegen.set_nametable_compression(nametable.format);
+    codegen.set_compression_report(compression_report.clone());
+    codegen.set_screens(screen_formats);
+    if cartridge.chr_ram {
+        codegen.set_chr_ram(Some(chr_format));
+    }
+    if let Some(tile) = hud_tile {
+        codegen.set_hud_tile(tile);
+    }
+    let asm_lines = codegen
+        .generate(&program)
+        .map_err(|e| format!("Codegen Error: {:?}", e))?;
+    let asm_source = asm_lines.join("\n");
 
-            injections.push((NAMETABLE_ADDR, full_nt));
-        }
+    // 6. Assembler
+    let assembler = Assembler::with_cartridge(target, cartridge);
+
+    // Actor handler addresses are only known once the code is assembled
+    if !codegen.actor_handlers().is_empty() {
+        let handlers: Vec<u8> = assembler
+            .label_addresses(&asm_source, codegen.actor_handlers())
+            .map_err(|e| format!("Assembler Error: {:?}", e))?
+            .iter()
+            .flat_map(|addr| addr.to_le_bytes())
+            .collect();
+        sections.push((
+            linker::SECTION_ACTOR_HANDLERS.to_string(),
+            None,
+            handlers_addr,
+            handlers,
+        ));
     }
 
+    let chr_data = resolved_assets.as_ref().map(|a| a.chr_bank.as_slice());
+
     let rom = assembler
-        .assemble(&asm_source, chr_data, injections)
+        .assemble_sections(&asm_source, chr_data, sections)
         .map_err(|e| format!("Assembler Error: {:?}", e))?;
 
-    Ok(rom)
+    Ok(CompileOutput {
+        rom,
+        compression_report,
+    })
 }
 
 // Project API Handlers
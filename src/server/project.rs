@@ -1,4 +1,7 @@
+use crate::compiler::cartridge::CartridgeConfig;
+use crate::compiler::compress::Compression;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::fs;
 use std::path::Path;
 use std::time::{SystemTime, UNIX_EPOCH};
@@ -8,15 +11,47 @@ pub struct ProjectMetadata {
     pub name: String,
     pub created_at: u64, // Timestamp
     pub modified_at: u64,
+    #[serde(default)]
+    pub cartridge: CartridgeConfig,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Palette {
     pub name: String,
     pub colors: [u8; 4],
+    /// Named set for `Palette.Load`; unset (or empty) for the startup set.
+    #[serde(default)]
+    pub set: Option<String>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+/// Sub-palette names in PPU order, $3F00 through $3F1C.
+pub const PALETTE_NAMES: [&str; 8] = ["BG0", "BG1", "BG2", "BG3", "SP0", "SP1", "SP2", "SP3"];
+
+impl Palette {
+    fn set_name(&self) -> Option<&str> {
+        self.set.as_deref().filter(|s| !s.is_empty())
+    }
+}
+
+/// Program constant holding a palette set id: "Night Sky" ->
+/// `PALETTE_NIGHT_SKY`, the startup set is `PALETTE_DEFAULT`.
+pub fn palette_constant(set: Option<&str>) -> String {
+    format!("PALETTE_{}", constant_suffix(set.unwrap_or("Default")))
+}
+
+fn constant_suffix(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_uppercase()
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct Nametable {
     pub name: String,
     pub data: Vec<u8>,
@@ -24,6 +59,30 @@ pub struct Nametable {
     pub attrs: Vec<u8>,
     #[serde(default)]
     pub metatile_grid: Vec<i32>,
+    /// Storage format in PRG; the smallest one is picked when unset.
+    #[serde(default)]
+    pub compression: Option<Compression>,
+    /// Switchable PRG bank holding the screen; the fixed bank when unset.
+    /// The startup screen (the first one) is always fixed.
+    #[serde(default)]
+    pub bank: Option<u8>,
+}
+
+impl Nametable {
+    /// The 960 tile bytes followed by the 64 attribute bytes, zero-padded.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![0u8; 1024];
+        let n = self.data.len().min(960);
+        bytes[..n].copy_from_slice(&self.data[..n]);
+        let n = self.attrs.len().min(64);
+        bytes[960..960 + n].copy_from_slice(&self.attrs[..n]);
+        bytes
+    }
+
+    /// Program constant holding the screen id: "Level 1" -> `SCREEN_LEVEL_1`.
+    pub fn constant_name(&self) -> String {
+        format!("SCREEN_{}", constant_suffix(&self.name))
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -71,6 +130,9 @@ pub struct Metatile {
     pub name: String,
     pub tiles: [u8; 4], // TopLeft, TopRight, BottomLeft, BottomRight
     pub attr: u8,       // Palette index (0-3)
+    /// Collision flags OR'd into each of the four tiles (`compiler::collision`).
+    #[serde(default)]
+    pub collision: u8,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -81,10 +143,36 @@ pub struct SpriteTile {
     pub attr: u8,
 }
 
+/// Collision rectangle in pixels, relative to the metasprite origin.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct SpriteBox {
+    pub x: i8,
+    pub y: i8,
+    pub w: u8,
+    pub h: u8,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Metasprite {
     pub name: String,
     pub tiles: Vec<SpriteTile>,
+    /// Area that deals damage. Tested by `Collision.Sprites`.
+    #[serde(default)]
+    pub hitbox: Option<SpriteBox>,
+    /// Area that takes damage. Falls back to `hitbox` when unset.
+    #[serde(default)]
+    pub hurtbox: Option<SpriteBox>,
+}
+
+/// OAM sprite size. With `Tall` every metasprite tile is an 8x16 sprite: an
+/// even tile on top of the next one.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SpriteMode {
+    #[default]
+    #[serde(rename = "8x8")]
+    Small,
+    #[serde(rename = "8x16")]
+    Tall,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -123,9 +211,27 @@ pub struct WorldLayout {
     pub width: u32,
     pub height: u32,
     pub data: Vec<i32>, // Index into nametables vector. -1 for empty.
+    #[serde(default)]
+    pub compression: Option<Compression>,
+    /// Enemies, items, doors... spawned by `World.SpawnRoomObjects`.
+    #[serde(default)]
+    pub objects: Vec<WorldObject>,
 }
 
+/// An ACTOR placed in a world room. `x`/`y` are pixels inside the room;
+/// `params` set the actor's fields, overriding their spawn values.
 #[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct WorldObject {
+    pub room_x: u32,
+    pub room_y: u32,
+    pub actor: String,
+    pub x: u8,
+    pub y: u8,
+    #[serde(default)]
+    pub params: BTreeMap<String, i32>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct ProjectAssets {
     pub chr_bank: Vec<u8>,
     pub palettes: Vec<Palette>,
@@ -146,6 +252,41 @@ pub struct ProjectAssets {
     pub metasprites: Vec<Metasprite>,
     #[serde(default)]
     pub animations: Vec<Animation>,
+    /// Collision flags per CHR tile id (`compiler::collision`); missing tiles
+    /// have none.
+    #[serde(default)]
+    pub tile_collision: Vec<u8>,
+    /// Sprite size for the whole project (`SPRITES 8X16` in source).
+    #[serde(default)]
+    pub sprite_mode: SpriteMode,
+    /// Switchable PRG bank holding the music, sound effects and envelopes;
+    /// the fixed bank when unset. DPCM samples always stay fixed, since the
+    /// DMC only fetches from $C000-$FFFF.
+    #[serde(default)]
+    pub audio_bank: Option<u8>,
+}
+
+impl ProjectAssets {
+    /// Palette sets in ROM order: the startup set, then each named set where
+    /// it first appears. A set is 32 bytes; missing sub-palettes are $0F.
+    pub fn palette_sets(&self) -> Vec<(Option<&str>, [u8; 32])> {
+        let mut sets: Vec<(Option<&str>, [u8; 32])> = vec![(None, [0x0F; 32])];
+        for pal in &self.palettes {
+            if !sets.iter().any(|(s, _)| *s == pal.set_name()) {
+                sets.push((pal.set_name(), [0x0F; 32]));
+            }
+        }
+        // In reverse, so the first palette of a name wins as before sets existed
+        for pal in self.palettes.iter().rev() {
+            let Some(slot) = PALETTE_NAMES.iter().position(|n| *n == pal.name) else {
+                continue;
+            };
+            if let Some((_, data)) = sets.iter_mut().find(|(s, _)| *s == pal.set_name()) {
+                data[slot * 4..slot * 4 + 4].copy_from_slice(&pal.colors);
+            }
+        }
+        sets
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -196,6 +337,7 @@ pub fn create_project(name: &str) -> Result<(), String> {
         name: name.to_string(),
         created_at: now,
         modified_at: now,
+        cartridge: CartridgeConfig::default(),
     };
     let meta_json = serde_json::to_string_pretty(&metadata).map_err(|e| e.to_string())?;
     fs::write(project_path.join("project.json"), meta_json).map_err(|e| e.to_string())?;
@@ -204,19 +346,10 @@ pub fn create_project(name: &str) -> Result<(), String> {
     let default_source = "CONST BG_COLOR = $0F\n\nSUB Main()\n    ' Set Palette Address $3F00\n    POKE($2006, $3F)\n    POKE($2006, $00)\n    ' Write Color\n    POKE($2007, BG_COLOR)\nEND SUB";
     fs::write(project_path.join("main.swiss"), default_source).map_err(|e| e.to_string())?;
 
-    // Default assets
+    // Default assets: 4KB of empty CHR, everything else starts empty
     let default_assets = ProjectAssets {
-        chr_bank: vec![0; 4096], // 4KB empty CHR
-        palettes: vec![],        // Start empty
-        nametables: vec![],      // Start empty
-        audio_tracks: vec![],    // Start empty
-        envelopes: vec![],       // Start empty
-        samples: vec![],         // Start empty
-        sound_effects: vec![],   // Start empty
-        metatiles: vec![],       // Start empty
-        world: None,
-        metasprites: vec![], // Start empty
-        animations: vec![],  // Start empty
+        chr_bank: vec![0; 4096],
+        ..Default::default()
     };
     let assets_json = serde_json::to_string_pretty(&default_assets).map_err(|e| e.to_string())?;
     fs::write(project_path.join("assets.json"), assets_json).map_err(|e| e.to_string())?;
@@ -100,6 +100,10 @@ document.addEventListener('DOMContentLoaded', () => {
             });
 
             if (response.ok) {
+                const report = response.headers.get('X-Compression-Report');
+                if (report) {
+                    console.info('Asset compression: ' + report);
+                }
                 const blob = await response.blob();
                 if (download) {
                     // Download the blob
@@ -1,4 +1,10 @@
 
+// Collision flag bits, lowest first (compiler TileFlag enum)
+const COLLISION_FLAGS = ['Solid', 'Hazard', 'Ladder', 'OneWay', 'Water'];
+// Slope shapes in bits 5-7 (compiler collision::SLOPES), 0 = flat
+const COLLISION_SLOPES = ['Flat', 'Rise45', 'Fall45', 'Rise22Low', 'Rise22High', 'Fall22High', 'Fall22Low'];
+const SLOPE_SHIFT = 5;
+
 class CHREditor {
     constructor() {
         this.container = document.getElementById('chr-editor-root');
@@ -103,6 +109,31 @@ class CHREditor {
 
         this.container.appendChild(tools);
 
+        // Collision flags of the current tile (Collision.Flags)
+        const flagRow = document.createElement('div');
+        flagRow.className = 'chr-tools';
+        this.flagBoxes = COLLISION_FLAGS.map((name, bit) => {
+            const label = document.createElement('label');
+            const box = document.createElement('input');
+            box.type = 'checkbox';
+            box.onchange = () => this.setCollisionFlag(1 << bit, box.checked);
+            label.appendChild(box);
+            label.appendChild(document.createTextNode(name));
+            flagRow.appendChild(label);
+            return box;
+        });
+        this.slopeSelect = document.createElement('select');
+        COLLISION_SLOPES.forEach((name, shape) => {
+            const opt = document.createElement('option');
+            opt.value = shape;
+            opt.textContent = name;
+            this.slopeSelect.appendChild(opt);
+        });
+        this.slopeSelect.onchange = () => this.setSlope(parseInt(this.slopeSelect.value));
+        flagRow.appendChild(document.createTextNode('Slope: '));
+        flagRow.appendChild(this.slopeSelect);
+        this.container.appendChild(flagRow);
+
 
         // Canvas Wrapper
         const wrapper = document.createElement('div');
@@ -391,6 +422,7 @@ class CHREditor {
         }
         this.updateRenderPalette();
         this.currentTileIndex = 0;
+        this.updateCollisionFlags();
         this.render();
     }
 
@@ -411,9 +443,39 @@ class CHREditor {
         if (index > 255) index = 0;
         this.currentTileIndex = index;
         this.lblTile.textContent = 'Tile $' + index.toString(16).toUpperCase().padStart(2, '0');
+        this.updateCollisionFlags();
         this.render();
     }
 
+    tileCollision() {
+        if (!this.assets.tile_collision) this.assets.tile_collision = [];
+        const flags = this.assets.tile_collision;
+        while (flags.length <= this.currentTileIndex) flags.push(0);
+        return flags;
+    }
+
+    setCollisionFlag(mask, on) {
+        if (!this.assets) return;
+        const flags = this.tileCollision();
+        const i = this.currentTileIndex;
+        flags[i] = on ? (flags[i] | mask) : (flags[i] & ~mask);
+    }
+
+    setSlope(shape) {
+        if (!this.assets) return;
+        const flags = this.tileCollision();
+        const i = this.currentTileIndex;
+        flags[i] = (flags[i] & ((1 << SLOPE_SHIFT) - 1)) | (shape << SLOPE_SHIFT);
+    }
+
+    updateCollisionFlags() {
+        const flags = (this.assets && this.assets.tile_collision || [])[this.currentTileIndex] || 0;
+        this.flagBoxes.forEach((box, bit) => {
+            box.checked = (flags & (1 << bit)) !== 0;
+        });
+        this.slopeSelect.value = flags >> SLOPE_SHIFT;
+    }
+
     updateColorSelection() {
         this.colorButtons.forEach((btn, i) => {
             if (i === this.currentColorIndex) btn.classList.add('selected');
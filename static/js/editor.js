@@ -194,10 +194,12 @@ class SwissEditor {
 
         try {
             if (this.emulator) {
+                this.persistSRAM();
                 if (this.emulator.free) this.emulator.free();
             }
             this.emulator = new this.EmulatorClass();
             this.emulator.load_rom(romData);
+            this.restoreSRAM();
             this.emulator.set_sample_rate(this.audioContext.sampleRate);
             this.nextStartTime = this.audioContext.currentTime;
 
@@ -277,6 +279,7 @@ class SwissEditor {
             btnClose.style.backgroundColor = '#d9534f';
             btnClose.onclick = () => {
                 this.emulatorRunning = false;
+                this.persistSRAM();
                 overlay.style.display = 'none';
                 if(this.audioContext) this.audioContext.suspend();
             };
@@ -438,11 +441,39 @@ class SwissEditor {
         } catch(e) { return null; }
     }
 
+    sramKey() {
+        const project = window.projectManager ? window.projectManager.currentProject : null;
+        return 'swissarmynes-sram:' + (project || 'scratch');
+    }
+
+    // Battery-backed save RAM is kept in localStorage between sessions
+    restoreSRAM() {
+        if (!this.emulator || typeof this.emulator.has_battery !== 'function') return;
+        if (!this.emulator.has_battery()) return;
+        const saved = localStorage.getItem(this.sramKey());
+        if (!saved) return;
+        this.emulator.set_sram(Uint8Array.from(atob(saved), c => c.charCodeAt(0)));
+    }
+
+    persistSRAM() {
+        if (!this.emulator || !this.wasmMemory) return;
+        if (typeof this.emulator.has_battery !== 'function' || !this.emulator.has_battery()) return;
+        try {
+            const ptr = this.emulator.get_sram();
+            const len = this.emulator.get_sram_len();
+            const bytes = new Uint8Array(this.wasmMemory.buffer, ptr, len);
+            let bin = '';
+            for (let i = 0; i < bytes.length; i++) bin += String.fromCharCode(bytes[i]);
+            localStorage.setItem(this.sramKey(), btoa(bin));
+        } catch(e) { console.error("Failed to save SRAM:", e); }
+    }
+
     emulatorLoop() {
         if (!this.emulatorRunning) return;
 
         // Debug Verification (Phase 34)
         if (this.frameCount++ % 60 === 0) {
+             this.persistSRAM();
              const s = this.getDebugState();
              if (s) {
                  console.log(`[DEBUG] PC: $${s.pc.toString(16).toUpperCase()} A: $${s.acc.toString(16).toUpperCase()}`);
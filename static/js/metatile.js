@@ -101,6 +101,22 @@ class MetatileEditor {
         palContainer.appendChild(this.palSelect);
         rightCol.appendChild(palContainer);
 
+        // Collision flags, applied to all four tiles (COLLISION_FLAGS from chr.js)
+        const flagContainer = document.createElement('div');
+        flagContainer.style.marginTop = '10px';
+        flagContainer.appendChild(document.createTextNode('Collision: '));
+        this.flagBoxes = COLLISION_FLAGS.map((name, bit) => {
+            const label = document.createElement('label');
+            const box = document.createElement('input');
+            box.type = 'checkbox';
+            box.onchange = () => this.setCollisionFlag(1 << bit, box.checked);
+            label.appendChild(box);
+            label.appendChild(document.createTextNode(name));
+            flagContainer.appendChild(label);
+            return box;
+        });
+        rightCol.appendChild(flagContainer);
+
         // Help Text
         const help = document.createElement('p');
         help.textContent = 'Click quadrants to assign the currently selected tile from the Graphics tab.';
@@ -133,7 +149,8 @@ class MetatileEditor {
         const newMeta = {
             name: `Meta ${this.assets.metatiles.length}`,
             tiles: [0, 0, 0, 0], // TopLeft, TopRight, BottomLeft, BottomRight
-            attr: 0
+            attr: 0,
+            collision: 0
         };
         this.assets.metatiles.push(newMeta);
         this.renderList();
@@ -195,11 +212,21 @@ class MetatileEditor {
         window.dispatchEvent(new CustomEvent('metatile-changed', { detail: { index: this.currentMetatileIndex } }));
     }
 
+    setCollisionFlag(mask, on) {
+        if (!this.assets || this.currentMetatileIndex < 0) return;
+        const meta = this.assets.metatiles[this.currentMetatileIndex];
+        const flags = meta.collision || 0;
+        meta.collision = on ? (flags | mask) : (flags & ~mask);
+    }
+
     selectMetatile(index) {
         this.currentMetatileIndex = index;
         const meta = this.assets.metatiles[index];
         this.nameInput.value = meta.name;
         this.palSelect.value = meta.attr;
+        this.flagBoxes.forEach((box, bit) => {
+            box.checked = ((meta.collision || 0) & (1 << bit)) !== 0;
+        });
 
         // Highlight in list
         Array.from(this.listEl.children).forEach((li, i) => {
@@ -10,7 +10,10 @@ class PaletteEditor {
         ];
 
         // 4 Background Palettes (4 colors each) + 4 Sprite Palettes (4 colors each)
+        this.paletteNames = ["BG0", "BG1", "BG2", "BG3", "SP0", "SP1", "SP2", "SP3"];
         this.palettes = [];
+        this.assets = null;
+        this.currentSet = null;
         this.selectedColorIndex = 0x0D; // Default black
 
         this.container = document.getElementById('palette-editor-root');
@@ -23,34 +26,74 @@ class PaletteEditor {
     }
 
     onProjectLoaded(assets) {
-        // Initialize palettes from assets or defaults
-        this.palettes = [];
-        const names = ["BG0", "BG1", "BG2", "BG3", "SP0", "SP1", "SP2", "SP3"];
+        this.assets = assets;
+        this.currentSet = null; // Startup set
 
         // Ensure assets.palettes exists
         if (!assets.palettes) {
             assets.palettes = [];
         }
 
-        // Re-order assets.palettes to match canonical order (BG0..SP3)
+        // Re-order assets.palettes to match canonical order (BG0..SP3), startup set first.
         // This ensures other editors (like Metatile/World) can access by index safely.
+        // Named sets (Palette.Load in code) follow, 8 entries each.
+        const setNames = [];
+        assets.palettes.forEach(p => {
+            if (p.set && !setNames.includes(p.set)) setNames.push(p.set);
+        });
         const sortedPalettes = [];
-        names.forEach(name => {
-            let found = assets.palettes.find(p => p.name === name);
-            if (!found) {
-                found = { name: name, colors: [0x0F, 0x00, 0x10, 0x20] }; // Default grays
-            }
-            sortedPalettes.push(found);
-            this.palettes.push(found);
+        [null, ...setNames].forEach(set => {
+            this.paletteNames.forEach(name => {
+                let found = assets.palettes.find(p => p.name === name && (p.set || null) === set);
+                if (!found) {
+                    found = { name: name, colors: [0x0F, 0x00, 0x10, 0x20] }; // Default grays
+                    if (set) found.set = set;
+                }
+                sortedPalettes.push(found);
+            });
         });
 
         // Update the source of truth
         assets.palettes.length = 0;
         sortedPalettes.forEach(p => assets.palettes.push(p));
 
+        this.selectSet(null);
+    }
+
+    setNames() {
+        const names = [];
+        if (this.assets) {
+            this.assets.palettes.forEach(p => {
+                if (p.set && !names.includes(p.set)) names.push(p.set);
+            });
+        }
+        return names;
+    }
+
+    selectSet(set) {
+        this.currentSet = set;
+        this.palettes = this.assets ? this.assets.palettes.filter(p => (p.set || null) === set) : [];
         this.render();
     }
 
+    // New sets start as a copy of the one being edited
+    addSet() {
+        const name = prompt("Palette Set Name:", "Night");
+        if (!name || name === "Default" || this.setNames().includes(name)) return;
+        this.palettes.forEach(p => {
+            this.assets.palettes.push({ name: p.name, colors: [...p.colors], set: name });
+        });
+        this.selectSet(name);
+    }
+
+    deleteSet() {
+        if (!this.currentSet || !confirm(`Delete palette set ${this.currentSet}?`)) return;
+        const kept = this.assets.palettes.filter(p => p.set !== this.currentSet);
+        this.assets.palettes.length = 0;
+        kept.forEach(p => this.assets.palettes.push(p));
+        this.selectSet(null);
+    }
+
     render() {
         if (!this.container) return;
         this.container.innerHTML = '';
@@ -118,8 +161,10 @@ class PaletteEditor {
                     // But we might want to notify that data changed?
                     // For now, saving will just read the current state.
 
-                    // Dispatch event for other editors (like CHR editor)
-                    window.dispatchEvent(new CustomEvent('palette-changed'));
+                    // Dispatch event for other editors (like CHR editor); they show the startup set
+                    if (!this.currentSet) {
+                        window.dispatchEvent(new CustomEvent('palette-changed'));
+                    }
                 };
 
                 row.appendChild(swatch);
@@ -131,6 +176,28 @@ class PaletteEditor {
         const subHeader = document.createElement('h3');
         subHeader.textContent = "Project Palettes";
         wrapper.appendChild(subHeader);
+
+        // 3. Palette Sets: Default is loaded at startup, the rest by Palette.Load(PALETTE_<NAME>)
+        const setsDiv = document.createElement('div');
+        setsDiv.className = 'palette-sets';
+        const setSelect = document.createElement('select');
+        [null, ...this.setNames()].forEach(set => {
+            const option = document.createElement('option');
+            option.value = set || '';
+            option.textContent = set || 'Default';
+            option.selected = set === this.currentSet;
+            setSelect.appendChild(option);
+        });
+        setSelect.onchange = () => this.selectSet(setSelect.value || null);
+        const addBtn = document.createElement('button');
+        addBtn.textContent = 'New Set';
+        addBtn.onclick = () => this.addSet();
+        const delBtn = document.createElement('button');
+        delBtn.textContent = 'Delete Set';
+        delBtn.disabled = !this.currentSet;
+        delBtn.onclick = () => this.deleteSet();
+        setsDiv.append('Set: ', setSelect, addBtn, delBtn);
+        wrapper.appendChild(setsDiv);
         wrapper.appendChild(subPalettesDiv);
 
         this.container.appendChild(wrapper);
@@ -238,6 +238,7 @@ class ProjectManager {
             const spriteData = window.spriteEditor.getData();
             this.assets.metasprites = spriteData.metasprites;
             this.assets.animations = spriteData.animations;
+            this.assets.sprite_mode = spriteData.sprite_mode;
         }
 
         const payload = {
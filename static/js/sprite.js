@@ -7,6 +7,7 @@ class SpriteEditor {
         this.currentAnimation = null;
         this.selectedTileIndex = 0;
         this.selectedAttr = 0;
+        this.spriteMode = '8x8'; // Project-wide: '8x16' tiles are an even tile over the next one
         this.zoom = 2;
 
         this.initUI();
@@ -49,6 +50,14 @@ class SpriteEditor {
                             </label>
                             <span class="info">Click to place tile. Right-click to remove.</span>
                         </div>
+                        <div class="toolbar">
+                            ${['hitbox', 'hurtbox'].map(box => `
+                                <label>${box === 'hitbox' ? 'Hitbox' : 'Hurtbox'}:
+                                    ${['x', 'y', 'w', 'h'].map(field => `<input type="number" class="sprite-box-input" data-box="${box}" data-field="${field}" title="${field}" style="width: 45px;">`).join('')}
+                                </label>
+                            `).join('')}
+                            <span class="info">Empty width = no box. Hurtbox defaults to the hitbox.</span>
+                        </div>
                         <div class="canvas-wrapper" style="flex:1; background: #333; overflow: auto; position: relative;">
                             <canvas id="metasprite-canvas" width="512" height="512" style="cursor: crosshair;"></canvas>
                         </div>
@@ -78,6 +87,12 @@ class SpriteEditor {
                 </div>
 
                 <div class="sidebar right-sidebar">
+                    <label>Sprite Size:
+                        <select id="sprite-mode-select">
+                            <option value="8x8">8x8</option>
+                            <option value="8x16">8x16</option>
+                        </select>
+                    </label>
                     <h3>CHR Bank</h3>
                     <canvas id="sprite-chr-picker" width="128" height="128" style="border: 1px solid #555; cursor: pointer;"></canvas>
                     <div id="selected-tile-preview">Selected: 00</div>
@@ -91,6 +106,7 @@ class SpriteEditor {
         document.getElementById('btn-del-metasprite').onclick = () => this.deleteMetasprite();
         document.getElementById('metasprite-name').onchange = (e) => this.renameMetasprite(e.target.value);
         document.getElementById('sprite-attr-select').onchange = (e) => this.selectedAttr = parseInt(e.target.value);
+        document.getElementById('sprite-mode-select').onchange = (e) => this.setSpriteMode(e.target.value);
 
         document.getElementById('btn-add-animation').onclick = () => this.addAnimation();
         document.getElementById('btn-del-animation').onclick = () => this.deleteAnimation();
@@ -99,6 +115,10 @@ class SpriteEditor {
             if(this.currentAnimation) this.currentAnimation.does_loop = e.target.checked;
         };
 
+        document.querySelectorAll('.sprite-box-input').forEach(input => {
+            input.onchange = () => this.updateBox(input.dataset.box);
+        });
+
         const msCanvas = document.getElementById('metasprite-canvas');
         msCanvas.onmousedown = (e) => this.handleCanvasClick(e);
         msCanvas.oncontextmenu = (e) => { e.preventDefault(); this.handleCanvasClick(e, true); };
@@ -122,6 +142,7 @@ class SpriteEditor {
     loadData(assets) {
         this.metasprites = assets.metasprites || [];
         this.animations = assets.animations || [];
+        this.setSpriteMode(assets.sprite_mode || '8x8');
 
         // Ensure valid data structures
         this.metasprites.forEach(ms => {
@@ -142,10 +163,24 @@ class SpriteEditor {
     getData() {
         return {
             metasprites: this.metasprites,
-            animations: this.animations
+            animations: this.animations,
+            sprite_mode: this.spriteMode
         };
     }
 
+    // 8x16 mode places tall tiles on a 16px row grid and only picks even tiles
+    setSpriteMode(mode) {
+        this.spriteMode = mode;
+        document.getElementById('sprite-mode-select').value = mode;
+        if(this.isTall()) this.selectedTileIndex &= ~1;
+        this.renderChrPicker();
+        this.renderMetasprite();
+    }
+
+    isTall() {
+        return this.spriteMode === '8x16';
+    }
+
     renderMetaspriteList() {
         const list = document.getElementById('metasprite-list');
         list.innerHTML = '';
@@ -178,6 +213,10 @@ class SpriteEditor {
         document.getElementById('animation-editor-view').style.display = 'none';
 
         document.getElementById('metasprite-name').value = ms.name;
+        document.querySelectorAll('.sprite-box-input').forEach(input => {
+            const box = ms[input.dataset.box];
+            input.value = box ? box[input.dataset.field] : '';
+        });
 
         this.renderMetaspriteList();
         this.renderAnimationList(); // To clear selection
@@ -235,12 +274,26 @@ class SpriteEditor {
         }
     }
 
+    // Hitbox/hurtbox rectangles, relative to the metasprite origin. A zero
+    // width or height removes the box.
+    updateBox(box) {
+        if(!this.currentMetasprite) return;
+        const value = (field, min, max) => {
+            const input = document.querySelector(`.sprite-box-input[data-box="${box}"][data-field="${field}"]`);
+            return Math.max(min, Math.min(max, parseInt(input.value) || 0));
+        };
+        const rect = { x: value('x', -128, 127), y: value('y', -128, 127), w: value('w', 0, 255), h: value('h', 0, 255) };
+        this.currentMetasprite[box] = (rect.w > 0 && rect.h > 0) ? rect : null;
+        this.renderMetasprite();
+    }
+
     handleChrPick(e) {
         const rect = e.target.getBoundingClientRect();
         const x = Math.floor((e.clientX - rect.left) / 8); // 8px tiles, scale 1 (image is 128x128)
         const y = Math.floor((e.clientY - rect.top) / 8);
         if(x >= 0 && x < 16 && y >= 0 && y < 16) {
             this.selectedTileIndex = y * 16 + x;
+            if(this.isTall()) this.selectedTileIndex &= ~1;
             document.getElementById('selected-tile-preview').textContent = `Selected: $${this.selectedTileIndex.toString(16).toUpperCase().padStart(2,'0')}`;
             this.renderChrPicker(); // To show selection
         }
@@ -263,7 +316,7 @@ class SpriteEditor {
         const y = Math.floor(this.selectedTileIndex / 16) * 8;
         ctx.strokeStyle = 'red';
         ctx.lineWidth = 1;
-        ctx.strokeRect(x, y, 8, 8);
+        ctx.strokeRect(x, y, this.isTall() ? 16 : 8, 8);
     }
 
     handleCanvasClick(e, isRightClick = false) {
@@ -292,7 +345,8 @@ class SpriteEditor {
 
         // Center is 256,256
         const relX = Math.floor((rawX - 256) / (8 * zoom)) * 8;
-        const relY = Math.floor((rawY - 256) / (8 * zoom)) * 8;
+        const rowHeight = this.isTall() ? 16 : 8;
+        const relY = Math.floor((rawY - 256) / (rowHeight * zoom)) * rowHeight;
 
         // relX, relY are now snapped to 8px grid, relative to center.
         // Example: Center click -> 0,0.
@@ -347,7 +401,16 @@ class SpriteEditor {
             const screenX = cx + (tile.x * zoom);
             const screenY = cy + (tile.y * zoom);
 
-            this.drawTile(ctx, tile.tile, screenX, screenY, zoom, tile.attr);
+            this.drawSprite(ctx, tile.tile, screenX, screenY, zoom, tile.attr);
+        });
+
+        // Draw Boxes (hurtbox first so an identical hitbox stays visible)
+        const ms = this.currentMetasprite;
+        [[ms.hurtbox || ms.hitbox, '#0C0'], [ms.hitbox, '#F00']].forEach(([box, color]) => {
+            if(!box) return;
+            ctx.strokeStyle = color;
+            ctx.lineWidth = 1;
+            ctx.strokeRect(cx + box.x * zoom + 0.5, cy + box.y * zoom + 0.5, box.w * zoom - 1, box.h * zoom - 1);
         });
     }
 
@@ -365,6 +428,16 @@ class SpriteEditor {
         return ['#00000000', '#F00', '#0F0', '#00F']; // Default debug
     }
 
+    // One OAM sprite: a single tile, or in 8x16 mode the even tile over the next
+    drawSprite(ctx, tileIdx, x, y, scale, attr) {
+        if(!this.isTall()) {
+            this.drawTile(ctx, tileIdx, x, y, scale, attr);
+            return;
+        }
+        this.drawTile(ctx, tileIdx & ~1, x, y, scale, attr);
+        this.drawTile(ctx, tileIdx | 1, x, y + 8 * scale, scale, attr);
+    }
+
     drawTile(ctx, tileIdx, x, y, scale, attr) {
         // This duplicates some logic from MapEditor/CHREditor but keeps it self contained for now
         // Ideally we have a shared Renderer service
@@ -492,7 +565,7 @@ class SpriteEditor {
                 ms.tiles.forEach(tile => {
                     const screenX = cx + (tile.x * zoom);
                     const screenY = cy + (tile.y * zoom);
-                    this.drawTile(ctx, tile.tile, screenX, screenY, zoom, tile.attr);
+                    this.drawSprite(ctx, tile.tile, screenX, screenY, zoom, tile.attr);
                 });
             }
 
@@ -45,6 +45,24 @@ class WorldEditor {
         this.mapList.className = 'map-list';
         sidebar.appendChild(this.mapList);
 
+        // Objects: ACTOR types placed in rooms (World.SpawnRoomObjects)
+        const objects = document.createElement('div');
+        objects.style.padding = '10px';
+        objects.innerHTML = '<h3>Objects</h3><p style="font-size: 12px; color: #888;">Click to place an ACTOR, right-click to remove one.</p>';
+        this.objectMode = document.createElement('input');
+        this.objectMode.type = 'checkbox';
+        const modeLabel = document.createElement('label');
+        modeLabel.appendChild(this.objectMode);
+        modeLabel.appendChild(document.createTextNode(' Place objects'));
+        objects.appendChild(modeLabel);
+        this.objectActor = document.createElement('input');
+        this.objectActor.type = 'text';
+        this.objectActor.placeholder = 'ACTOR type';
+        this.objectActor.style.width = '100%';
+        this.objectActor.style.marginTop = '6px';
+        objects.appendChild(this.objectActor);
+        sidebar.appendChild(objects);
+
         layout.appendChild(sidebar);
 
         // Canvas Area
@@ -62,9 +80,12 @@ class WorldEditor {
         this.container.appendChild(layout);
 
         // Events
-        this.canvas.addEventListener('mousedown', (e) => this.handleMouse(e));
+        this.canvas.addEventListener('mousedown', (e) => {
+            if (this.objectMode.checked) this.handleObject(e);
+            else this.handleMouse(e);
+        });
         this.canvas.addEventListener('mousemove', (e) => {
-            if (e.buttons === 1) this.handleMouse(e);
+            if (e.buttons === 1 && !this.objectMode.checked) this.handleMouse(e);
         });
 
         // Prevent context menu on canvas
@@ -81,6 +102,7 @@ class WorldEditor {
             };
         }
         this.world = this.assets.world;
+        if (!this.world.objects) this.world.objects = [];
 
         // Validation
         const size = this.world.width * this.world.height;
@@ -169,6 +191,39 @@ class WorldEditor {
         }
     }
 
+    handleObject(e) {
+        if (!this.world) return;
+
+        const rect = this.canvas.getBoundingClientRect();
+        const px = Math.floor((e.clientX - rect.left) / this.scale);
+        const py = Math.floor((e.clientY - rect.top) / this.scale);
+        const roomX = Math.floor(px / this.mapWidth);
+        const roomY = Math.floor(py / this.mapHeight);
+        if (roomX >= this.world.width || roomY >= this.world.height) return;
+        const x = px % this.mapWidth;
+        const y = py % this.mapHeight;
+
+        if (e.button === 2) {
+            // Remove the nearest object within 16 pixels in this room
+            let nearest = -1;
+            let best = 16 * 16;
+            this.world.objects.forEach((obj, i) => {
+                if (obj.room_x !== roomX || obj.room_y !== roomY) return;
+                const d = (obj.x - x) ** 2 + (obj.y - y) ** 2;
+                if (d <= best) {
+                    best = d;
+                    nearest = i;
+                }
+            });
+            if (nearest >= 0) this.world.objects.splice(nearest, 1);
+        } else if (e.button === 0) {
+            const actor = this.objectActor.value.trim();
+            if (!actor) return;
+            this.world.objects.push({ room_x: roomX, room_y: roomY, actor, x, y, params: {} });
+        }
+        this.render();
+    }
+
     getMapThumbnail(index) {
         if (index < 0) return null;
         if (this.mapThumbnails.has(index)) {
@@ -305,6 +360,17 @@ class WorldEditor {
             this.ctx.lineTo(this.canvas.width, r * ch);
         }
         this.ctx.stroke();
+
+        // Placed objects: a marker with the ACTOR type's initial
+        this.ctx.font = '8px sans-serif';
+        for (const obj of this.world.objects || []) {
+            const x = (obj.room_x * this.mapWidth + obj.x) * this.scale;
+            const y = (obj.room_y * this.mapHeight + obj.y) * this.scale;
+            this.ctx.fillStyle = '#e04040';
+            this.ctx.fillRect(x - 3, y - 3, 7, 7);
+            this.ctx.fillStyle = '#fff';
+            this.ctx.fillText(obj.actor.charAt(0), x + 5, y + 3);
+        }
     }
 
     getData() {
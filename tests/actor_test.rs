@@ -0,0 +1,221 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use crate::common::{analyze, compile_with, generate, parse};
+    use swissarmynes::compiler::callgraph::CallGraph;
+    use swissarmynes::compiler::codegen::CodeGenerator;
+    use swissarmynes::compiler::lexer::Lexer;
+    use swissarmynes::compiler::parser::Parser;
+
+    const GAME: &str = r#"
+ACTORS 4
+DIM hits AS BYTE
+DIM a AS BYTE
+ACTOR Goomba
+  hp AS BYTE = 3
+  speed AS WORD = 300
+  UPDATE GoombaUpdate
+  DRAW GoombaDraw
+  CLASS Enemy
+  SIZE 16, 12
+END ACTOR
+ACTOR Coin
+  value AS BYTE = 5
+  UPDATE CoinUpdate
+  CLASS Pickup
+END ACTOR
+SUB GoombaUpdate(slot AS BYTE)
+  Goomba(slot).hp = Goomba(slot).hp - 1
+  IF Goomba(slot).hp = 0 THEN
+    Actors.Despawn(slot)
+  END IF
+END SUB
+SUB GoombaDraw()
+END SUB
+SUB CoinUpdate(slot AS BYTE)
+  IF Actors.Collide(slot, ActorClass.Enemy) <> $FF THEN
+    hits = hits + Coin(slot).value
+  END IF
+END SUB
+SUB Main()
+  a = Actors.Spawn(Goomba, 10, Actors(0).y + 4)
+  Actors.UpdateAll()
+  Actors.DrawAll()
+END SUB
+"#;
+
+    #[test]
+    fn test_actor_table_views() {
+        let asm = generate(GAME);
+        // Header (9) + the largest ACTOR's fields (hp, speed)
+        assert!(asm.contains("; Actors @ $05C2: 4 slots of 12 bytes"));
+
+        // Goomba(slot).hp reads offset 9 of the shared table
+        let analyzer = analyze(GAME).unwrap();
+        let goomba = analyzer.symbol_table.resolve("Actor.Goomba").unwrap();
+        let members = goomba.members.clone().unwrap();
+        assert!(members.iter().any(|(n, _, o)| n == "hp" && *o == 9));
+        assert!(members.iter().any(|(n, _, o)| n == "speed" && *o == 10));
+        assert_eq!(goomba.value, Some(12));
+
+        assert!(compile_with(GAME, None, None).is_ok());
+    }
+
+    #[test]
+    fn test_actor_handler_table_rom() {
+        let rom = compile_with(GAME, None, None).expect("Compile failed");
+        let at = |addr: u16| 16 + (addr - 0x8000) as usize;
+        let word = |addr: u16| u16::from_le_bytes([rom[at(addr)], rom[at(addr) + 1]]);
+        // ASL, TAX, LDA table, X, STA $0E: the table the UPDATE dispatch reads
+        let dispatch = rom
+            .windows(7)
+            .position(|w| w[..3] == [0x0A, 0xAA, 0xBD] && w[5..] == [0x85, 0x0E])
+            .expect("Dispatch not in ROM");
+        let table = u16::from_le_bytes([rom[dispatch + 3], rom[dispatch + 4]]);
+        // Goomba's UPDATE entry is its stub, which loads the slot
+        assert_eq!(rom[at(word(table))], 0xAD);
+        // Coin has no DRAW handler: its entry returns at once
+        assert_eq!(rom[at(word(table + 6))], 0x60);
+        assert_ne!(word(table + 4), word(table + 6));
+    }
+
+    #[test]
+    fn test_actor_dispatch_and_spawn() {
+        let asm = generate(GAME);
+
+        // UpdateAll jumps through the kind's entry in the handler table
+        let update = &asm[asm.find("Actors_Update_Dispatch:").unwrap()..];
+        assert!(update.starts_with(
+            "Actors_Update_Dispatch:\n  ASL\n  TAX\n  LDA $0000, X\n  STA $0E\n  LDA $0001, X\n  STA $0F\n  JMP ($000E)\nActors_Update_None:\n  RTS"
+        ));
+        // Handlers taking the slot are entered through a stub that passes it
+        let frame = |sub: &str| {
+            let marker = format!("; {}.slot @ $", sub);
+            let at = asm.find(&marker).unwrap() + marker.len();
+            asm[at..at + 4].to_string()
+        };
+        assert!(asm.contains(&format!(
+            "Actors_Update_Kind0:\n  LDA $05F2\n  STA ${}\n  JMP GoombaUpdate",
+            frame("GoombaUpdate")
+        )));
+        assert!(asm.contains(&format!(
+            "Actors_Update_Kind1:\n  LDA $05F2\n  STA ${}\n  JMP CoinUpdate",
+            frame("CoinUpdate")
+        )));
+        // The DRAW table follows the UPDATE one
+        let draw = &asm[asm.find("Actors_Draw_Dispatch:").unwrap()..];
+        assert!(draw.starts_with("Actors_Draw_Dispatch:\n  ASL\n  TAX\n  LDA $0004, X"));
+
+        let analyzer = analyze(GAME).unwrap();
+        let mut codegen = CodeGenerator::new(analyzer.symbol_table);
+        codegen.generate(&parse(GAME)).unwrap();
+        // A handler without parameters is entered directly
+        assert_eq!(
+            codegen.actor_handlers(),
+            [
+                "Actors_Update_Kind0",
+                "Actors_Update_Kind1",
+                "GoombaDraw",
+                "Actors_Draw_None"
+            ]
+        );
+
+        // Spawn writes kind, class, position, size and the field defaults
+        let spawn = &asm[asm.find("Actor_Spawn_Goomba:").unwrap()..];
+        let spawn = &spawn[..spawn.find("RTS").unwrap()];
+        let stored: Vec<&str> = spawn
+            .lines()
+            .filter(|l| l.starts_with("  LDA"))
+            .map(|l| &l[6..])
+            .collect();
+        assert_eq!(
+            stored,
+            [
+                "#$00", "#$01", "$0A", "$0B", "$0C", "$0D", "#$10", "#$0C", "#$03", "#$2C", "#$01",
+                "$08"
+            ]
+        );
+        let coin = &asm[asm.find("Actor_Spawn_Coin:").unwrap()..];
+        assert!(coin.contains("  LDA #$01\n  STA ($02), Y\n  INY\n  LDA #$02\n  STA ($02), Y"));
+    }
+
+    #[test]
+    fn test_actor_handlers_keep_their_frames() {
+        let program = parse(GAME);
+        let graph = CallGraph::build(&program);
+        assert!(graph.reaches("Main", "GoombaUpdate"));
+        assert!(graph.reaches("Main", "CoinUpdate"));
+        assert!(graph.reaches("Main", "GoombaDraw"));
+        // Handlers run inside Main's frame, but never inside each other's
+        assert!(graph.may_overlap("Main", "GoombaUpdate"));
+        assert!(!graph.may_overlap("GoombaUpdate", "CoinUpdate"));
+    }
+
+    #[test]
+    fn test_actor_errors() {
+        let cases = [
+            (
+                "ACTOR A\n  hp AS BYTE\n  hp AS BYTE\nEND ACTOR\n",
+                "ACTOR 'A' field 'hp' is already defined",
+            ),
+            (
+                "ACTOR A\n  name AS STRING = 1\nEND ACTOR\n",
+                "ACTOR 'A' field 'name' cannot have a spawn value",
+            ),
+            (
+                "ACTOR A\n  UPDATE Tick\nEND ACTOR\nSUB Tick(a AS BYTE, b AS BYTE)\nEND SUB\n",
+                "ACTOR 'A' UPDATE handler 'Tick' must take no arguments or one BYTE (the slot)",
+            ),
+            (
+                "ACTOR A\n  DRAW Missing\nEND ACTOR\n",
+                "ACTOR 'A' DRAW handler 'Missing' is not a SUB",
+            ),
+            (
+                "ACTORS 65\nACTOR A\nEND ACTOR\n",
+                "ACTORS 65 is out of range (1-64)",
+            ),
+            (
+                "ACTOR A\n  buf(250) AS BYTE\nEND ACTOR\n",
+                "ACTOR fields take 259 bytes per slot (at most 255)",
+            ),
+            (
+                "ACTOR A\nEND ACTOR\nSUB Main()\n  Actors.Spawn(Main, 0, 0)\nEND SUB\n",
+                "Actors.Spawn first argument must be an ACTOR type",
+            ),
+            (
+                "ACTOR A\nEND ACTOR\nSUB Main()\n  Actors.Collide(0)\nEND SUB\n",
+                "Actors.Collide expects 2 arguments (slot, classes)",
+            ),
+            (
+                "ACTOR A\nEND ACTOR\nSUB Main()\n  Actors.Tick()\nEND SUB\n",
+                "Unknown Actors command 'Tick'",
+            ),
+            (
+                "SUB Main()\n  Actors.UpdateAll()\nEND SUB\n",
+                "Actors.UpdateAll requires an ACTOR declaration",
+            ),
+        ];
+        for (source, message) in cases {
+            let errors = analyze(source)
+                .err()
+                .unwrap_or_else(|| panic!("{} accepted", source));
+            assert!(errors.iter().any(|e| e.contains(message)), "{:?}", errors);
+        }
+
+        // Kinds index the handler tables two bytes at a time
+        let many: String = (0..129)
+            .map(|i| format!("ACTOR A{}\nEND ACTOR\n", i))
+            .collect();
+        let errors = analyze(&many).err().expect("129 kinds accepted");
+        assert!(errors
+            .iter()
+            .any(|e| e.contains("129 ACTOR types declared (at most 128)")));
+
+        let tokens = Lexer::new("ACTOR A\n  SPEED 2\nEND ACTOR\n")
+            .tokenize()
+            .unwrap();
+        let err = Parser::new(tokens).parse().expect_err("Clause accepted");
+        assert!(err.contains("Unknown ACTOR clause 'SPEED'"), "{}", err);
+    }
+}
@@ -5,6 +5,7 @@ mod tests {
     use swissarmynes::compiler::codegen::CodeGenerator;
     use swissarmynes::compiler::lexer::Lexer;
     use swissarmynes::compiler::parser::Parser;
+    use swissarmynes::compiler::sprites;
 
     #[test]
     fn test_animation_compilation() {
@@ -44,6 +45,11 @@ mod tests {
         let mut analyzer = SemanticAnalyzer::new();
         analyzer.analyze(&program).expect("Analysis failed");
 
+        // Count=2, Loop=1, then metasprite address and duration per frame;
+        // Idle and Run take 15 bytes each
+        let table = sprites::table(&program, &analyzer.symbol_table, 0).expect("Table failed");
+        assert_eq!(table[30..], [2, 1, 0, 0, 10, 15, 0, 5]);
+
         let symbol_table = analyzer.symbol_table;
         let mut codegen = CodeGenerator::new(symbol_table);
         let asm_lines = codegen.generate(&program).expect("Codegen failed");
@@ -51,15 +57,7 @@ mod tests {
         println!("{}", asm_source);
 
         // Verify Assembly
-        assert!(asm_source.contains("PlayerRun:"));
-        // Count=2, Loop=1. Might be on different lines or same.
-        // The generator output:
-        // PlayerRun:
-        //   db $02
-        //   db $01
-        assert!(asm_source.contains("PlayerRun:"));
-        assert!(asm_source.contains("db $02"));
-        assert!(asm_source.contains("db $01"));
+        assert!(asm_source.contains("; PlayerRun @ $001E"));
 
         // Check for Helpers
         assert!(asm_source.contains("Runtime_Anim_Update:"));
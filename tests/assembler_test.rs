@@ -1,6 +1,7 @@
 #[cfg(test)]
 mod tests {
     use swissarmynes::compiler::assembler::Assembler;
+    use swissarmynes::compiler::mapper::Mapper;
 
     #[test]
     fn test_overlap_detection() {
@@ -37,4 +38,30 @@ mod tests {
             "Assembler should detect overlap between injections"
         );
     }
+
+    #[test]
+    fn test_banked_section_overlap() {
+        let assembler = Assembler::with_mapper(Mapper::Mmc1);
+        let source = ".ORG $C000\nLDA #0\n.BANK 1\n.ORG $8000\nLDA #1\nSTA $00\n";
+        let section = |bank, addr| vec![("music".to_string(), bank, addr, vec![0xAA; 4])];
+
+        // Clear of bank 1's code: lands $3000 into the bank
+        let rom = assembler
+            .assemble_sections(source, None, section(Some(1), 0xB000))
+            .expect("Assemble failed");
+        assert_eq!(&rom[16 + 0x4000 + 0x3000..][..4], &[0xAA; 4]);
+
+        let err = assembler
+            .assemble_sections(source, None, section(Some(1), 0x8002))
+            .unwrap_err();
+        assert!(err.contains("Bank 1 overflow"), "{}", err);
+        let err = assembler
+            .assemble_sections(source, None, section(Some(1), 0xC000))
+            .unwrap_err();
+        assert!(err.contains("outside the bank 1 window"), "{}", err);
+        let err = Assembler::new()
+            .assemble_sections(".ORG $8000\nLDA #0\n", None, section(Some(1), 0x9000))
+            .unwrap_err();
+        assert!(err.contains("has no switchable PRG banks"), "{}", err);
+    }
 }
@@ -37,17 +37,8 @@ mod tests {
         };
 
         let assets = ProjectAssets {
-            chr_bank: vec![],
-            palettes: vec![],
-            nametables: vec![],
             audio_tracks: vec![track1],
-            envelopes: vec![],
-            samples: vec![],
-            sound_effects: vec![],
-            metatiles: vec![],
-            world: None,
-            metasprites: vec![],
-            animations: vec![],
+            ..Default::default()
         };
 
         let blob = compile_audio_data(&Some(assets)).expect("Compilation failed");
@@ -110,17 +101,8 @@ mod tests {
         };
 
         let assets = ProjectAssets {
-            chr_bank: vec![],
-            palettes: vec![],
-            nametables: vec![],
             audio_tracks: vec![track1],
-            envelopes: vec![],
-            samples: vec![],
-            sound_effects: vec![],
-            metatiles: vec![],
-            world: None,
-            metasprites: vec![],
-            animations: vec![],
+            ..Default::default()
         };
 
         let blob = compile_audio_data(&Some(assets)).expect("Compilation failed");
@@ -180,17 +162,8 @@ mod tests {
         };
 
         let assets = ProjectAssets {
-            chr_bank: vec![],
-            palettes: vec![],
-            nametables: vec![],
             audio_tracks: vec![track1, track2],
-            envelopes: vec![],
-            samples: vec![],
-            sound_effects: vec![],
-            metatiles: vec![],
-            world: None,
-            metasprites: vec![],
-            animations: vec![],
+            ..Default::default()
         };
 
         let result = compile_audio_data(&Some(assets));
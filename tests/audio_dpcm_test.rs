@@ -25,16 +25,7 @@ fn test_compile_samples() {
 
     let assets = ProjectAssets {
         samples: vec![sample1, sample2],
-        chr_bank: vec![],
-        palettes: vec![],
-        nametables: vec![],
-        audio_tracks: vec![],
-        envelopes: vec![],
-        sound_effects: vec![],
-        metatiles: vec![],
-        world: None,
-        metasprites: vec![],
-        animations: vec![],
+        ..Default::default()
     };
 
     let (samples_blob, table_blob) = audio::compile_samples(&Some(assets)).unwrap();
@@ -89,16 +80,7 @@ fn test_compile_audio_dmc() {
 
     let assets = ProjectAssets {
         audio_tracks: vec![track],
-        chr_bank: vec![],
-        palettes: vec![],
-        nametables: vec![],
-        samples: vec![],
-        envelopes: vec![],
-        sound_effects: vec![],
-        metatiles: vec![],
-        world: None,
-        metasprites: vec![],
-        animations: vec![],
+        ..Default::default()
     };
 
     let blob = audio::compile_audio_data(&Some(assets)).unwrap();
@@ -36,17 +36,8 @@ fn test_audio_priority_compilation() {
     };
 
     let assets = ProjectAssets {
-        chr_bank: vec![],
-        palettes: vec![],
-        nametables: vec![],
         audio_tracks: vec![track1, track2],
-        envelopes: vec![],
-        samples: vec![],
-        sound_effects: vec![],
-        metatiles: vec![],
-        world: None,
-        metasprites: vec![],
-        animations: vec![],
+        ..Default::default()
     };
 
     let blob = compile_audio_data(&Some(assets)).unwrap();
@@ -0,0 +1,160 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use crate::common::{analyze, generate_with, layout, nametable};
+    use swissarmynes::compiler::cartridge::{CartridgeConfig, Mirroring};
+    use swissarmynes::compiler::compress::Compression;
+    use swissarmynes::compiler::linker::SECTION_WORLD;
+    use swissarmynes::server::api::compile_source_with_cartridge;
+    use swissarmynes::server::project::{Nametable, ProjectAssets, WorldLayout};
+
+    const SOURCE: &str = "SUB Main()\n  Camera.Follow(300, 200)\n  Camera.Update()\nEND SUB\n";
+
+    fn assets(nametables: Vec<Nametable>) -> ProjectAssets {
+        ProjectAssets {
+            nametables,
+            world: Some(WorldLayout {
+                width: 2,
+                height: 2,
+                data: vec![0, 1, -1, 1],
+                compression: None,
+                objects: vec![],
+            }),
+            ..Default::default()
+        }
+    }
+
+    fn generate(source: &str, world: bool, mirroring: Mirroring) -> Result<String, String> {
+        generate_with(source, |codegen| {
+            if world {
+                codegen.set_layout(layout(&[(SECTION_WORLD, 7)]));
+            }
+            codegen.set_mirroring(mirroring);
+            codegen.set_screens(vec![Compression::None, Compression::None]);
+        })
+    }
+
+    #[test]
+    fn test_camera_streams_raw_world() {
+        let nametables = vec![
+            nametable("Start", 0x21, None),
+            nametable("Cave", 0x42, None),
+        ];
+        let rom = compile_source_with_cartridge(
+            Some(SOURCE.to_string()),
+            None,
+            Some(assets(nametables.clone())),
+            None,
+        )
+        .expect("Compile failed");
+        let prg = &rom[16..];
+        let has = |data: &[u8]| prg.windows(data.len()).any(|w| w == data);
+        // Both screens and the room map stay raw (the repeating tiles would
+        // otherwise be packed); the empty room is $FF
+        assert!(has(&nametables[0].to_bytes()));
+        assert!(has(&nametables[1].to_bytes()));
+        assert!(has(&[2, 2, 0, 0, 1, 0xFF, 1]));
+
+        let nametables = vec![
+            nametable("Start", 0x21, None),
+            nametable("Cave", 0x42, Some(Compression::Rle)),
+        ];
+        let err = compile_source_with_cartridge(
+            Some(SOURCE.to_string()),
+            None,
+            Some(assets(nametables)),
+            None,
+        )
+        .unwrap_err();
+        assert!(
+            err.contains("'Cave' is read from ROM at runtime"),
+            "{}",
+            err
+        );
+
+        let cartridge = CartridgeConfig {
+            mirroring: Mirroring::Horizontal,
+            ..Default::default()
+        };
+        let nametables = vec![
+            nametable("Start", 0x21, None),
+            nametable("Cave", 0x42, None),
+        ];
+        compile_source_with_cartridge(
+            Some(SOURCE.to_string()),
+            None,
+            Some(assets(nametables)),
+            Some(cartridge),
+        )
+        .expect("Compile failed");
+    }
+
+    #[test]
+    fn test_camera_lookahead_follows_mirroring() {
+        // Vertical: 64 columns, so the entering column is 32 ahead; 30 rows
+        let asm = generate(SOURCE, true, Mirroring::Vertical).expect("Codegen failed");
+        assert!(asm.contains("Runtime_Camera_Update:"));
+        assert!(asm.contains("  JSR Runtime_Camera_Update"));
+        assert!(asm.contains("  ADC #32\n  CMP #32"));
+        assert!(asm.contains("  ADC #29\n  CMP #30"));
+        assert!(asm.contains("  LDA #33\n"));
+        // Columns go out as +32 entries, attributes one byte at a time
+        assert!(asm.contains("  ORA #$80\n  ORA $0F\n  JSR PpuQueue_Reserve"));
+        assert!(asm.contains("  LDA #1\n  ORA $0F\n  JSR PpuQueue_Reserve"));
+
+        let asm = generate(SOURCE, true, Mirroring::Horizontal).expect("Codegen failed");
+        assert!(asm.contains("  ADC #31\n  CMP #32"));
+        assert!(asm.contains("  ADC #30\n  CMP #30"));
+        assert!(asm.contains("  LDA #32\n"));
+
+        let asm = generate(SOURCE, true, Mirroring::FourScreen).expect("Codegen failed");
+        assert!(asm.contains("  ADC #32\n  CMP #32"));
+        assert!(asm.contains("  ADC #30\n  CMP #30"));
+    }
+
+    #[test]
+    fn test_camera_state_access() {
+        let source =
+            "DIM w AS WORD\nSUB Main()\n  Camera.Mode(4)\n  w = Camera.X() + Camera.Y()\nEND SUB\n";
+        let asm = generate(source, false, Mirroring::Vertical).expect("Codegen failed");
+        assert!(asm.contains("  STA $039A"));
+        assert!(asm.contains("  LDA $0394\n  LDX $0395"));
+        assert!(asm.contains("  LDA $0396\n  LDX $0397"));
+        // Without Camera.Update the streaming runtime is left out
+        assert!(!asm.contains("Runtime_Camera_Update:"));
+
+        let err = generate(SOURCE, false, Mirroring::Vertical).unwrap_err();
+        assert!(
+            err.contains("Camera.Update requires a world map"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_camera_call_errors() {
+        let cases = [
+            (
+                "Camera.Follow(1)",
+                "Camera.Follow expects 2 arguments (x, y)",
+            ),
+            ("Camera.Update(1)", "Camera.Update expects no arguments"),
+            ("Camera.Mode(6)", "Camera.Mode expects a literal 4 or 8"),
+            ("Camera.Zoom(2)", "Unknown Camera command 'Zoom'"),
+            ("x = Camera.X(1)", "Camera.X expects no arguments"),
+            ("x = Camera.Z()", "Unknown Camera function 'Z'"),
+        ];
+        for (line, expected) in cases {
+            let source = format!("DIM x AS WORD\nSUB Main()\n  {}\nEND SUB\n", line);
+            let errors = analyze(&source).err().unwrap_or_default();
+            assert!(
+                errors.iter().any(|e| e.contains(expected)),
+                "{}: {:?}",
+                line,
+                errors
+            );
+        }
+        assert!(analyze(SOURCE).unwrap().streams_world());
+    }
+}
@@ -0,0 +1,164 @@
+mod common;
+
+use crate::common::compile_with;
+use swissarmynes::compiler::cartridge::{CartridgeConfig, ConsoleType, Mirroring, TvSystem};
+use swissarmynes::compiler::mapper::Mapper;
+use swissarmynes::server::project::ProjectMetadata;
+use tetanes_core::control_deck::ControlDeck;
+
+#[test]
+fn test_nes2_header_fields() {
+    let config = CartridgeConfig {
+        submapper: 1,
+        mirroring: Mirroring::Horizontal,
+        battery: true,
+        tv_system: TvSystem::Pal,
+        console_type: ConsoleType::VsSystem,
+        ..Default::default()
+    };
+    let header = config.header(Mapper::Mmc3, 0x20000, 0x8000);
+    assert_eq!(&header[..4], b"NES\x1A");
+    assert_eq!(header[4], 8); // 8 x 16 KB
+    assert_eq!(header[5], 4); // 4 x 8 KB
+    assert_eq!(header[6], 0x42); // mapper 4, battery, horizontal
+    assert_eq!(header[7], 0x09); // NES 2.0, Vs. System
+    assert_eq!(header[8], 0x10); // submapper 1
+    assert_eq!(header[10], 0x07); // 8 KB PRG-RAM, saved by the battery bit
+    assert_eq!(header[11], 0x00);
+    assert_eq!(header[12], 1); // PAL
+
+    let config = CartridgeConfig {
+        mirroring: Mirroring::FourScreen,
+        chr_ram: true,
+        chr_size_kb: Some(32),
+        ..Default::default()
+    };
+    let header = config.header(Mapper::Uxrom, 0x20000, 0);
+    assert_eq!(header[5], 0);
+    assert_eq!(header[6], 0x28);
+    assert_eq!(header[10], 0x00);
+    assert_eq!(header[11], 9); // 64 << 9 = 32 KB CHR-RAM
+}
+
+#[test]
+fn test_config_validated_against_mapper() {
+    let battery = CartridgeConfig {
+        battery: true,
+        ..Default::default()
+    };
+    assert!(battery.validate(Mapper::Nrom).is_err());
+    assert!(battery.validate(Mapper::Mmc1).is_ok());
+
+    let big = CartridgeConfig {
+        prg_size_kb: Some(512),
+        ..Default::default()
+    };
+    assert!(big.validate(Mapper::Mmc1).is_err());
+    assert!(big.validate(Mapper::Mmc3).is_ok());
+
+    let chr_ram = CartridgeConfig {
+        chr_ram: true,
+        ..Default::default()
+    };
+    assert!(chr_ram.validate(Mapper::Cnrom).is_err());
+
+    let four = CartridgeConfig {
+        mirroring: Mirroring::FourScreen,
+        ..Default::default()
+    };
+    assert!(four.validate(Mapper::Axrom).is_err());
+
+    let err = compile_with("SUB Main()\nEND SUB\n", None, Some(battery)).unwrap_err();
+    assert!(err.contains("Cartridge Error"), "{}", err);
+}
+
+#[test]
+fn test_config_sizes_and_mapper_applied() {
+    let config = CartridgeConfig {
+        mapper: Some("UxROM".to_string()),
+        prg_size_kb: Some(128),
+        chr_ram: true,
+        ..Default::default()
+    };
+    let rom = compile_with("SUB Main()\nEND SUB\n", None, Some(config)).expect("Compile failed");
+    assert_eq!(rom[4], 8);
+    assert_eq!(rom[5], 0);
+    assert_eq!(rom[6] >> 4, 2);
+    // No CHR-ROM appended; the fixed bank stays last
+    assert_eq!(rom.len(), 16 + 128 * 1024);
+    assert_eq!(rom[16 + 7 * 0x4000], 0x78);
+
+    let config = CartridgeConfig {
+        mapper: Some("MMC1".to_string()),
+        ..Default::default()
+    };
+    let err = compile_with("MAPPER MMC3\nSUB Main()\nEND SUB\n", None, Some(config)).unwrap_err();
+    assert!(err.contains("selects MMC1"), "{}", err);
+
+    // PRG images are a power of two, never below 32 KB
+    for kb in [16, 48] {
+        let config = CartridgeConfig {
+            prg_size_kb: Some(kb),
+            ..Default::default()
+        };
+        assert!(config.validate(Mapper::Mmc1).is_err());
+        let err = compile_with("SUB Main()\nEND SUB\n", None, Some(config)).unwrap_err();
+        assert!(
+            err.contains(&format!("PRG size {} KB is invalid for NROM", kb)),
+            "{}",
+            err
+        );
+    }
+}
+
+#[test]
+fn test_old_project_json_loads_with_defaults() {
+    let json = r#"{ "name": "Old", "created_at": 1, "modified_at": 2 }"#;
+    let meta: ProjectMetadata = serde_json::from_str(json).unwrap();
+    assert_eq!(meta.cartridge, CartridgeConfig::default());
+
+    let json = r#"{ "name": "New", "created_at": 1, "modified_at": 2,
+        "cartridge": { "mapper": "MMC3", "mirroring": "four_screen", "tv_system": "dual" } }"#;
+    let meta: ProjectMetadata = serde_json::from_str(json).unwrap();
+    assert_eq!(meta.cartridge.mirroring, Mirroring::FourScreen);
+    assert_eq!(meta.cartridge.tv_system, TvSystem::Dual);
+    assert!(!meta.cartridge.battery);
+}
+
+#[test]
+fn test_header_loads_in_emulator() {
+    // The IDE emulator rejects NES 2.0 headers with anything in bytes 14-15
+    // or a PRG-NVRAM size
+    for (source, config) in [
+        ("SUB Main()\nEND SUB\n", CartridgeConfig::default()),
+        (
+            "MAPPER MMC1\nSUB Main()\nEND SUB\n",
+            CartridgeConfig {
+                battery: true,
+                ..Default::default()
+            },
+        ),
+        (
+            "MAPPER MMC3\nSUB Main()\nEND SUB\n",
+            CartridgeConfig {
+                tv_system: TvSystem::Pal,
+                ..Default::default()
+            },
+        ),
+        (
+            "MAPPER UXROM\nSUB Main()\nEND SUB\n",
+            CartridgeConfig {
+                chr_ram: true,
+                ..Default::default()
+            },
+        ),
+    ] {
+        let battery = config.battery;
+        let rom = compile_with(source, None, Some(config)).expect("Compile failed");
+        assert_eq!(&rom[14..16], &[0, 0]);
+        match ControlDeck::new().load_rom("game.nes", &mut &rom[..]) {
+            Ok(loaded) => assert_eq!(loaded.battery_backed, battery, "{}", source),
+            Err(e) => panic!("{}: {:?}", source, e),
+        }
+    }
+}
@@ -0,0 +1,143 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use crate::common::{compile_with, listing_for, parse};
+    use swissarmynes::compiler::cartridge::CartridgeConfig;
+    use swissarmynes::compiler::compress::{self, Compression};
+    use swissarmynes::compiler::linker::{Linker, Section, SECTION_CHR};
+    use swissarmynes::server::project::ProjectAssets;
+
+    const LOAD: &str = "SUB Main()\n  CHR.Load(1, 16, 4)\nEND SUB\n";
+
+    fn assets(chr_bank: Vec<u8>) -> ProjectAssets {
+        ProjectAssets {
+            chr_bank,
+            ..Default::default()
+        }
+    }
+
+    fn pattern(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i % 251) as u8).collect()
+    }
+
+    fn generate(source: &str, chr_ram: Option<Compression>) -> Result<Vec<String>, String> {
+        let mut linker = Linker::with_code_start(0x8000);
+        linker.add(Section::new(SECTION_CHR, 0x2000));
+        let layout = linker.link().expect("Link failed");
+        listing_for(&parse(source), |codegen| {
+            codegen.set_layout(layout);
+            codegen.set_chr_ram(chr_ram);
+        })
+    }
+
+    #[test]
+    fn test_rle_roundtrip() {
+        let mut data = vec![0u8; 300];
+        data.extend(pattern(200));
+        data.extend([7, 7, 1, 2, 2]);
+        let encoded = compress::rle_encode(&data);
+        assert!(encoded.len() < data.len());
+        assert_eq!(*encoded.last().unwrap(), 0x00);
+        assert_eq!(compress::rle_decode(&encoded).unwrap(), data);
+        assert!(compress::rle_decode(&[0x83]).is_err());
+    }
+
+    #[test]
+    fn test_raw_chr_stored_in_prg() {
+        let config = CartridgeConfig {
+            chr_ram: true,
+            ..Default::default()
+        };
+        let chr = pattern(0x2000);
+        let rom =
+            compile_with(LOAD, Some(assets(chr.clone())), Some(config)).expect("Compile failed");
+        // No CHR-ROM; 8 KB of CHR-RAM declared in the header
+        assert_eq!(rom[5], 0);
+        assert_eq!(rom[11], 7);
+        assert_eq!(rom.len(), 16 + 0x8000);
+        let prg = &rom[16..];
+        assert!(prg.windows(chr.len()).any(|w| w == chr.as_slice()));
+    }
+
+    #[test]
+    fn test_rle_startup_image() {
+        let config = CartridgeConfig {
+            chr_ram: true,
+            chr_compression: Compression::Rle,
+            ..Default::default()
+        };
+        let mut chr = vec![0u8; 0x2000];
+        chr.extend(pattern(0x400));
+        let rom =
+            compile_with(LOAD, Some(assets(chr.clone())), Some(config)).expect("Compile failed");
+        let prg = &rom[16..];
+        let startup = compress::rle_encode(&chr[..0x2000]);
+        assert!(prg.windows(startup.len()).any(|w| w == startup.as_slice()));
+        assert!(prg.windows(0x400).any(|w| w == &chr[0x2000..]));
+    }
+
+    #[test]
+    fn test_startup_upload_and_chr_load() {
+        let asm = generate(LOAD, Some(Compression::None)).expect("Codegen failed");
+        let text = asm.join("\n");
+        assert!(text.contains("  LDX #$20\n  LDY #$00\nChrUploadLoop:\n  LDA ($02), Y"));
+        assert!(text.contains("  JSR Runtime_CHR_Load"));
+        assert!(!text.contains("Runtime_Rle_ToPpu"));
+        // Uploaded before rendering is turned on
+        let upload = asm.iter().position(|l| l == "ChrUploadLoop:").unwrap();
+        let main = asm.iter().position(|l| l == "  JSR Main").unwrap();
+        assert!(upload < main);
+
+        let asm = generate(LOAD, Some(Compression::Rle)).expect("Codegen failed");
+        assert!(asm.iter().any(|l| l == "  JSR Runtime_Rle_ToPpu"));
+
+        let err = generate(
+            "SUB Main()\n  CHR.Load(0, 0, 1)\nEND SUB\n",
+            Some(Compression::Rle),
+        )
+        .unwrap_err();
+        assert!(err.contains("bank 0"), "{}", err);
+        let err = generate(LOAD, None).unwrap_err();
+        assert!(err.contains("requires a CHR-RAM cartridge"), "{}", err);
+    }
+
+    #[test]
+    fn test_chr_in_switchable_bank() {
+        let source = format!("MAPPER MMC1\n{}", LOAD);
+        let compile = |config: CartridgeConfig, chr: Vec<u8>| {
+            compile_with(&source, Some(assets(chr)), Some(config))
+        };
+        let config = CartridgeConfig {
+            chr_ram: true,
+            chr_prg_bank: Some(1),
+            ..Default::default()
+        };
+        let chr = pattern(0x3000);
+        let rom = compile(config.clone(), chr.clone()).expect("Compile failed");
+        // All of it in bank 1, none in the fixed bank
+        let bank = &rom[16 + 0x4000..16 + 0x8000];
+        let fixed = &rom[rom.len() - 0x4000..];
+        assert!(bank.windows(chr.len()).any(|w| w == chr.as_slice()));
+        assert!(!fixed.windows(0x100).any(|w| w == &chr[..0x100]));
+
+        let err = compile(
+            CartridgeConfig {
+                chr_ram: false,
+                ..config.clone()
+            },
+            chr.clone(),
+        )
+        .unwrap_err();
+        assert!(err.contains("chr_prg_bank needs chr_ram"), "{}", err);
+        let err = compile(
+            CartridgeConfig {
+                chr_prg_bank: Some(15),
+                ..config
+            },
+            chr,
+        )
+        .unwrap_err();
+        assert!(err.contains("out of range for MMC1"), "{}", err);
+    }
+}
@@ -0,0 +1,142 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use crate::common::{analyze, compile, generate_with, layout};
+    use swissarmynes::compiler::collision::{self, HAZARD, LADDER, SOLID, WATER};
+    use swissarmynes::compiler::compress::Compression;
+    use swissarmynes::compiler::linker::{SECTION_COLLISION, SECTION_WORLD};
+    use swissarmynes::server::project::{Metatile, Nametable, ProjectAssets};
+
+    fn source(body: &str) -> String {
+        format!(
+            "DIM f AS BYTE\nDIM x AS WORD\nSUB Main()\n{}\nEND SUB\n",
+            body
+        )
+    }
+
+    fn assets(compression: Option<Compression>) -> ProjectAssets {
+        ProjectAssets {
+            nametables: vec![Nametable {
+                name: "Start".to_string(),
+                data: vec![0x21; 960],
+                attrs: vec![0; 64],
+                compression,
+                ..Default::default()
+            }],
+            metatiles: vec![Metatile {
+                name: "Spikes".to_string(),
+                tiles: [0x21, 0x22, 0x31, 0x32],
+                attr: 0,
+                collision: HAZARD,
+            }],
+            tile_collision: vec![0, SOLID, LADDER],
+            ..Default::default()
+        }
+    }
+
+    fn generate(source: &str, sections: &[&'static str]) -> Result<String, String> {
+        let sections: Vec<(&str, usize)> = sections.iter().map(|name| (*name, 256)).collect();
+        generate_with(source, |codegen| {
+            codegen.set_layout(layout(&sections));
+            codegen.set_screens(vec![Compression::None, Compression::None]);
+        })
+    }
+
+    #[test]
+    fn test_flag_table_merges_metatiles() {
+        let table = collision::flag_table(
+            &[SOLID, 0, LADDER],
+            &[([2, 3, 3, 4], WATER), ([0, 4, 5, 5], HAZARD)],
+        )
+        .unwrap();
+        assert_eq!(table.len(), collision::TABLE_SIZE);
+        assert_eq!(
+            &table[..6],
+            &[
+                SOLID | HAZARD,
+                0,
+                LADDER | WATER,
+                WATER,
+                WATER | HAZARD,
+                HAZARD
+            ]
+        );
+        assert!(table[6..].iter().all(|f| *f == 0));
+
+        let err = collision::flag_table(&[0; 257], &[]).unwrap_err();
+        assert!(err.contains("257 tile collision entries"), "{}", err);
+    }
+
+    #[test]
+    fn test_collision_flags_analysis() {
+        let analyzer = analyze(&source("  f = Collision.Flags(x, 40) AND TileFlag.Solid")).unwrap();
+        assert!(analyzer.reads_collision());
+        let analyzer = analyze(&source("  f = 1")).unwrap();
+        assert!(!analyzer.reads_collision());
+
+        let errors = analyze(&source("  f = Collision.Flags(x)"))
+            .err()
+            .unwrap_or_default();
+        assert!(
+            errors
+                .iter()
+                .any(|e| e.contains("Collision.Flags expects 2 arguments (worldX, worldY)")),
+            "{:?}",
+            errors
+        );
+    }
+
+    #[test]
+    fn test_collision_flags_codegen() {
+        let src = source("  f = Collision.Flags(x, 40)");
+        let asm = generate(&src, &[SECTION_COLLISION]).expect("Codegen failed");
+        assert!(asm.contains("  STA $02\n  STX $03\n  PLA\n  STA $01\n  PLA\n  STA $00"));
+        assert!(asm.contains("  JSR Runtime_Collision_Flags"));
+        assert!(asm.contains("Runtime_Collision_Flags:"));
+        // Without a world map only the startup screen exists
+        assert!(asm.contains("  TXA\n  ORA $01\n  BNE Collision_FlagsOff"));
+        assert!(asm.contains(&format!("Collision_FlagsOff:\n  LDA #{}", SOLID)));
+        // Rows 8 and below carry into the high byte of the tile address
+        assert!(asm.contains("  ADC $0A\n  STA $0A\n  BCC Collision_FlagsTile\n  INC $0B"));
+
+        let asm = generate(&src, &[SECTION_WORLD, SECTION_COLLISION]).expect("Codegen failed");
+        assert!(asm.contains("Collision_FlagsMul:"));
+        assert!(asm.contains("  CMP #2\n  BCS Collision_FlagsOff"));
+
+        // The routine is only emitted when used
+        let asm = generate(&source("  f = 1"), &[SECTION_COLLISION]).expect("Codegen failed");
+        assert!(!asm.contains("Runtime_Collision_Flags:"));
+
+        let err = generate(&src, &[]).unwrap_err();
+        assert!(
+            err.contains("Collision.Flags requires the collision table"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_collision_flags_rom() {
+        let src = source("  f = Collision.Flags(x, 40)");
+        let rom = compile(&src, assets(None)).expect("Compile failed");
+        let prg = &rom[16..];
+        let mut expected = vec![0u8; 0x33];
+        expected[1] = SOLID;
+        expected[2] = LADDER;
+        for tile in [0x21, 0x22, 0x31, 0x32] {
+            expected[tile] = HAZARD;
+        }
+        assert!(prg
+            .windows(expected.len())
+            .any(|w| w == expected.as_slice()));
+
+        // Tiles are read from the screens, so they must stay raw
+        let err = compile(&src, assets(Some(Compression::Rle))).unwrap_err();
+        assert!(
+            err.contains("'Start' is read from ROM at runtime"),
+            "{}",
+            err
+        );
+    }
+}
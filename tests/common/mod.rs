@@ -0,0 +1,108 @@
+//! Helpers shared by the integration tests. Each test binary only uses some
+//! of them.
+#![allow(dead_code)]
+
+use swissarmynes::compiler::analysis::SemanticAnalyzer;
+use swissarmynes::compiler::ast::Program;
+use swissarmynes::compiler::cartridge::CartridgeConfig;
+use swissarmynes::compiler::codegen::CodeGenerator;
+use swissarmynes::compiler::compress::Compression;
+use swissarmynes::compiler::lexer::Lexer;
+use swissarmynes::compiler::linker::{Layout, Linker, Section};
+use swissarmynes::compiler::parser::Parser;
+use swissarmynes::compiler::sprites;
+use swissarmynes::server::api::compile_source_with_cartridge;
+use swissarmynes::server::project::{Nametable, ProjectAssets};
+
+pub fn parse(source: &str) -> Program {
+    let tokens = Lexer::new(source).tokenize().expect("Lex failed");
+    Parser::new(tokens).parse().expect("Parse failed")
+}
+
+pub fn analyze(source: &str) -> Result<SemanticAnalyzer, Vec<String>> {
+    analyze_program(&parse(source))
+}
+
+/// Analyzes a parsed program, e.g. one with declarations added by the test.
+pub fn analyze_program(program: &Program) -> Result<SemanticAnalyzer, Vec<String>> {
+    let mut analyzer = SemanticAnalyzer::new();
+    analyzer.analyze(program)?;
+    Ok(analyzer)
+}
+
+/// Just the analysis errors of `source`.
+pub fn check(source: &str) -> Result<(), Vec<String>> {
+    analyze(source).map(|_| ())
+}
+
+/// The sprites section of `source`, placed at $0000.
+pub fn sprite_table(source: &str) -> Vec<u8> {
+    let analyzer = analyze(source).expect("Analysis failed");
+    sprites::table(&parse(source), &analyzer.symbol_table, 0).expect("Sprite table failed")
+}
+
+/// The listing lines for `program`, with `setup` configuring the code
+/// generator the way `compile_source` would.
+pub fn listing_for(
+    program: &Program,
+    setup: impl FnOnce(&mut CodeGenerator),
+) -> Result<Vec<String>, String> {
+    let analyzer = analyze_program(program).expect("Analysis failed");
+    let mut codegen = CodeGenerator::new(analyzer.symbol_table);
+    setup(&mut codegen);
+    codegen.generate(program)
+}
+
+pub fn listing(source: &str) -> Vec<String> {
+    listing_for(&parse(source), |_| {}).expect("Codegen failed")
+}
+
+/// The listing for `source`, as one string.
+pub fn generate_with(
+    source: &str,
+    setup: impl FnOnce(&mut CodeGenerator),
+) -> Result<String, String> {
+    listing_for(&parse(source), setup).map(|asm| asm.join("\n"))
+}
+
+pub fn generate(source: &str) -> String {
+    generate_with(source, |_| {}).expect("Codegen failed")
+}
+
+/// A linked layout holding only `sections`.
+pub fn layout(sections: &[(&str, usize)]) -> Layout {
+    let mut linker = Linker::new();
+    for (name, size) in sections {
+        linker.add(Section::new(name, *size));
+    }
+    linker.link().expect("Link failed")
+}
+
+pub fn compile(source: &str, assets: ProjectAssets) -> Result<Vec<u8>, String> {
+    compile_with(source, Some(assets), None)
+}
+
+pub fn compile_with(
+    source: &str,
+    assets: Option<ProjectAssets>,
+    cartridge: Option<CartridgeConfig>,
+) -> Result<Vec<u8>, String> {
+    compile_source_with_cartridge(Some(source.to_string()), None, assets, cartridge)
+}
+
+/// CPU address of the first copy of `bytes` in a 32 KB NROM image.
+pub fn rom_addr(rom: &[u8], bytes: &[u8]) -> Option<u16> {
+    let index = rom.windows(bytes.len()).position(|w| w == bytes)?;
+    Some(0x8000 + (index - 16) as u16)
+}
+
+/// A screen of `tile`, `tile + 1` and `tile + 2` repeating, attributes `tile`.
+pub fn nametable(name: &str, tile: u8, compression: Option<Compression>) -> Nametable {
+    Nametable {
+        name: name.to_string(),
+        data: (0..960u32).map(|i| tile + (i % 3) as u8).collect(),
+        attrs: vec![tile; 64],
+        compression,
+        ..Default::default()
+    }
+}
@@ -0,0 +1,268 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use crate::common::{compile, generate_with, layout, listing_for, parse};
+    use swissarmynes::compiler::compress::{self, Compression};
+    use swissarmynes::compiler::linker::SECTION_METATILES;
+    use swissarmynes::server::api::compile_project;
+    use swissarmynes::server::project::{Metatile, Nametable, ProjectAssets, WorldLayout};
+
+    const MAIN: &str = "SUB Main()\nEND SUB\n";
+
+    // Rows of sky, a repeated brick pattern and ground
+    fn screen() -> Vec<u8> {
+        let mut data = vec![0u8; 960];
+        for row in 20..26 {
+            for col in 0..32 {
+                data[row * 32 + col] = 0x10 + (col % 4) as u8 + (row % 2) as u8 * 4;
+            }
+        }
+        for tile in data[26 * 32..].iter_mut() {
+            *tile = 0x30;
+        }
+        data
+    }
+
+    fn assets(compression: Option<Compression>) -> ProjectAssets {
+        ProjectAssets {
+            nametables: vec![Nametable {
+                name: "Title".to_string(),
+                data: screen(),
+                attrs: vec![0x55; 64],
+                compression,
+                ..Default::default()
+            }],
+            ..Default::default()
+        }
+    }
+
+    fn full_nametable() -> Vec<u8> {
+        let mut data = screen();
+        data.extend([0x55; 64]);
+        data
+    }
+
+    fn contains(rom: &[u8], data: &[u8]) -> bool {
+        rom[16..].windows(data.len()).any(|w| w == data)
+    }
+
+    #[test]
+    fn test_lz_roundtrip() {
+        let data = full_nametable();
+        let lz = compress::lz_encode(&data);
+        assert!(lz.len() < compress::rle_encode(&data).len());
+        assert_eq!(*lz.last().unwrap(), 0x00);
+        assert_eq!(compress::lz_decode(&lz).unwrap(), data);
+
+        // Copies only reach back into the stream already written
+        let noise: Vec<u8> = (0..600u32).map(|i| (i * 37 % 251) as u8).collect();
+        assert_eq!(
+            compress::lz_decode(&compress::lz_encode(&noise)).unwrap(),
+            noise
+        );
+        assert!(compress::lz_decode(&[0x80, 0x10, 0x00, 0x00]).is_err());
+        assert!(compress::lz_decode(&[0x02, 0x01]).is_err());
+    }
+
+    #[test]
+    fn test_auto_picks_smallest_and_reports_ratio() {
+        let data = full_nametable();
+        let packed = compress::pack(&data, Compression::Auto);
+        assert_eq!(packed.format, Compression::Lz);
+        assert_eq!(compress::unpack(&packed.data, packed.format).unwrap(), data);
+        let line = packed.report("nametable");
+        assert!(line.starts_with("nametable: LZ, 1024 -> "), "{}", line);
+
+        // Incompressible data stays raw
+        let noise: Vec<u8> = (0..64u8).collect();
+        let packed = compress::pack(&noise, Compression::Auto);
+        assert_eq!(packed.format, Compression::None);
+        assert_eq!(packed.data, noise);
+        assert_eq!(packed.report("world"), "world: raw, 64 -> 64 bytes (100%)");
+    }
+
+    #[test]
+    fn test_nametable_compressed_unless_overridden() {
+        let raw = full_nametable();
+        let rom = compile(MAIN, assets(None)).expect("Compile failed");
+        assert!(contains(&rom, &compress::lz_encode(&raw)));
+        assert!(!contains(&rom, &raw));
+
+        let rom = compile(MAIN, assets(Some(Compression::Rle))).expect("Compile failed");
+        assert!(contains(&rom, &compress::rle_encode(&raw)));
+
+        let rom = compile(MAIN, assets(Some(Compression::None))).expect("Compile failed");
+        assert!(contains(&rom, &raw));
+
+        // Collision.Tile reads the ROM copy, so it keeps the raw layout
+        let tile = "DIM T AS BYTE\nSUB Main()\n  T = Collision.Tile(8, 8)\nEND SUB\n";
+        let rom = compile(tile, assets(None)).expect("Compile failed");
+        assert!(contains(&rom, &raw));
+        let err = compile(tile, assets(Some(Compression::Lz))).unwrap_err();
+        assert!(err.contains("cannot be compressed"), "{}", err);
+
+        let mut with_world = assets(None);
+        with_world.world = Some(WorldLayout {
+            width: 8,
+            height: 2,
+            data: vec![0; 16],
+            compression: None,
+            objects: vec![],
+        });
+        let rom = compile(MAIN, with_world).expect("Compile failed");
+        assert!(contains(&rom, &[8, 2, 1, 0x90, 0x00, 0x00]));
+    }
+
+    #[test]
+    fn test_startup_decompresses_nametable() {
+        let asm = listing_for(&parse(MAIN), |codegen| {
+            codegen.set_nametable_compression(Compression::Lz);
+            codegen.set_compression_report(vec!["nametable: LZ, 1024 -> 212 bytes (20%)".into()]);
+        })
+        .expect("Codegen failed");
+
+        assert!(asm
+            .iter()
+            .any(|l| l == "; nametable: LZ, 1024 -> 212 bytes (20%)"));
+        assert!(!asm.iter().any(|l| l == "LoadNT1:"));
+        let call = asm
+            .iter()
+            .position(|l| l == "  JSR Runtime_Lz_ToPpu")
+            .unwrap();
+        let main = asm.iter().position(|l| l == "  JSR Main").unwrap();
+        assert!(call < main);
+        assert!(asm.iter().any(|l| l == "Runtime_Lz_ToPpu:"));
+        assert!(!asm.iter().any(|l| l == "Runtime_Rle_ToPpu:"));
+    }
+
+    const METATILES: [[u8; 4]; 4] = [[1, 2, 3, 4], [5, 6, 7, 8], [9, 9, 9, 9], [0, 1, 0, 1]];
+
+    // Metatile ids that repeat too little for RLE or LZ to keep up
+    fn grid() -> Vec<i32> {
+        let mut seed = 7u32;
+        (0..240)
+            .map(|_| {
+                seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+                (seed >> 16) as i32 % 4
+            })
+            .collect()
+    }
+
+    fn metatile_assets(grid: Vec<i32>, compression: Option<Compression>) -> ProjectAssets {
+        let mut map: Vec<u8> = grid.iter().map(|id| *id as u8).collect();
+        map.extend([0x55; 64]);
+        let screen = compress::unpack_metatiles(&map, &METATILES).expect("Unpack failed");
+        ProjectAssets {
+            nametables: vec![Nametable {
+                name: "Title".to_string(),
+                data: screen[..960].to_vec(),
+                attrs: screen[960..].to_vec(),
+                metatile_grid: grid,
+                compression,
+                ..Default::default()
+            }],
+            metatiles: METATILES
+                .iter()
+                .enumerate()
+                .map(|(i, tiles)| Metatile {
+                    name: format!("M{}", i),
+                    tiles: *tiles,
+                    attr: 0,
+                    collision: 0,
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_metatile_map_compression() {
+        let assets = metatile_assets(grid(), None);
+        let screen = assets.nametables[0].to_bytes();
+        // Row 0's top tiles come from the top corners of its metatiles
+        assert_eq!(screen[..4], [1, 2, 9, 9]);
+        assert_eq!(screen[32..36], [3, 4, 9, 9]);
+        let packed = compress::pack_metatiles(&screen, &grid(), &METATILES).unwrap();
+        assert_eq!(packed.data.len(), 304);
+        assert!(packed.data.len() < compress::pack(&screen, Compression::Auto).data.len());
+        assert_eq!(
+            compress::unpack_metatiles(&packed.data, &METATILES).unwrap(),
+            screen
+        );
+        // Empty cells or a grid that does not match the tiles cannot be used
+        let mut empty = grid();
+        empty[17] = -1;
+        assert!(compress::pack_metatiles(&screen, &empty, &METATILES).is_none());
+        let mut other = grid();
+        other[17] = (other[17] + 1) % 4;
+        assert!(compress::pack_metatiles(&screen, &other, &METATILES).is_none());
+
+        // Auto picks the metatile map, the report comes back with the ROM and
+        // the corner planes are in their own section
+        let output = compile_project(Some(MAIN.to_string()), None, Some(assets), None)
+            .expect("Compile failed");
+        assert_eq!(
+            output.compression_report,
+            ["nametable: metatile map, 1024 -> 304 bytes (29%)"]
+        );
+        assert!(contains(&output.rom, &packed.data));
+        assert!(!contains(&output.rom, &screen[..960]));
+        assert!(contains(
+            &output.rom,
+            &[1, 5, 9, 0, 2, 6, 9, 1, 3, 7, 9, 0, 4, 8, 9, 1]
+        ));
+
+        // The override still wins, and a screen off the grid is rejected
+        let rom = compile(MAIN, metatile_assets(grid(), Some(Compression::None)))
+            .expect("Compile failed");
+        assert!(contains(&rom, &screen));
+        let mut assets = metatile_assets(grid(), Some(Compression::Metatile));
+        assets.nametables[0].data[0] = 0x42;
+        let err = compile(MAIN, assets).unwrap_err();
+        assert!(
+            err.contains("'Title' is not drawn entirely from metatiles"),
+            "{}",
+            err
+        );
+        let mut assets = metatile_assets(grid(), None);
+        assets.world = Some(WorldLayout {
+            width: 1,
+            height: 1,
+            data: vec![0],
+            compression: Some(Compression::Metatile),
+            objects: vec![],
+        });
+        let err = compile(MAIN, assets).unwrap_err();
+        assert!(
+            err.contains("only screens can be stored as a metatile map"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_metatile_decoder() {
+        let layout = layout(&[(SECTION_METATILES, 16)]);
+        let table = layout.addr(SECTION_METATILES);
+        let asm = generate_with(MAIN, |codegen| {
+            codegen.set_layout(layout);
+            codegen.set_nametable_compression(Compression::Metatile);
+        })
+        .expect("Codegen failed");
+
+        assert!(asm.contains("  JSR Runtime_Metatile_ToPpu"));
+        // Four metatiles: each corner plane is 4 bytes after the previous one
+        assert!(asm.contains(&format!(
+            "Metatile_Top:\n  LDA ($02), Y\n  TAX\n  LDA ${:04X}, X\n  STA $2007\n  LDA ${:04X}, X",
+            table,
+            table + 4
+        )));
+        assert!(asm.contains(&format!(
+            "Metatile_Bottom:\n  LDA ($02), Y\n  TAX\n  LDA ${:04X}, X\n  STA $2007\n  LDA ${:04X}, X",
+            table + 8,
+            table + 12
+        )));
+        assert!(!asm.contains("Runtime_Lz_ToPpu:"));
+    }
+}
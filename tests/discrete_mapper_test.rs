@@ -0,0 +1,104 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use crate::common::{check, compile_with, parse};
+    use swissarmynes::compiler::mapper::{self, Mapper};
+
+    fn prg(rom: &[u8]) -> &[u8] {
+        &rom[16..16 + rom[4] as usize * 0x4000]
+    }
+
+    // Finds `STA abs,Y` (99 lo hi) and returns the operand
+    fn sta_abs_y(rom: &[u8]) -> u16 {
+        let code = prg(rom);
+        let i = code
+            .windows(4)
+            .position(|w| w[0] == 0xA8 && w[1] == 0x99)
+            .expect("No bank write found");
+        code[i + 2] as u16 | (code[i + 3] as u16) << 8
+    }
+
+    #[test]
+    fn test_mapper_names() {
+        let program = parse("MAPPER UNROM\nSUB Main()\nEND SUB\n");
+        assert_eq!(mapper::target(&program), Ok(Mapper::Uxrom));
+        assert_eq!(Mapper::from_name("3"), Some(Mapper::Cnrom));
+        assert_eq!(Mapper::from_name("AxROM"), Some(Mapper::Axrom));
+    }
+
+    #[test]
+    fn test_bank_calls_checked_against_mapper() {
+        let err = check("SUB Main()\n  PRG.Bank(1)\nEND SUB\n").unwrap_err();
+        assert!(err[0].contains("PRG.Bank requires"), "{:?}", err);
+
+        let err = check("MAPPER UxROM\nSUB Main()\n  CHR.Bank(1)\nEND SUB\n").unwrap_err();
+        assert!(err[0].contains("CHR.Bank requires"), "{:?}", err);
+
+        let err = check("MAPPER CNROM\nSUB Main()\n  CHR.Bank(4)\nEND SUB\n").unwrap_err();
+        assert!(
+            err[0].contains("out of range for CNROM (banks 0-3)"),
+            "{:?}",
+            err
+        );
+
+        let err = check("MAPPER AxROM\nSUB Main()\n  PRG.Bank(8)\nEND SUB\n").unwrap_err();
+        assert!(err[0].contains("out of range"), "{:?}", err);
+
+        assert!(check("MAPPER CNROM\nSUB Main()\n  CHR.Bank(3)\nEND SUB\n").is_ok());
+        assert!(check("MAPPER MMC3\nSUB Main()\n  CHR.Bank(31)\n  PRG.Bank(5)\nEND SUB\n").is_ok());
+        assert!(check("MAPPER UxROM\nSUB Main()\n  PRG.Bank(14)\nEND SUB\n").is_ok());
+    }
+
+    #[test]
+    fn test_cnrom_image_and_bus_conflict_table() {
+        let rom = compile_with(
+            "MAPPER CNROM\nSUB Main()\n  CHR.Bank(2)\nEND SUB\n",
+            None,
+            None,
+        )
+        .expect("Compile failed");
+        assert_eq!(rom[4], 2);
+        assert_eq!(rom[6] >> 4, 3);
+        // The CHR write goes through an identity table in ROM
+        let table = sta_abs_y(&rom);
+        let offset = (table - 0x8000) as usize;
+        assert_eq!(&prg(&rom)[offset..offset + 4], &[0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn test_uxrom_banked_image() {
+        let source = "MAPPER UxROM\nBANK 2\nSUB Level()\nEND SUB\nBANK FIXED\n\
+                      SUB Main()\n  Level()\n  PRG.Bank(1)\nEND SUB\n";
+        let rom = compile_with(source, None, None).expect("Compile failed");
+        // Bank 2 plus the fixed bank rounds up to 4 x 16 KB
+        assert_eq!(rom[4], 4);
+        assert_eq!(rom[6] >> 4, 2);
+        let bank = |n: usize| &rom[16 + n * 0x4000..16 + (n + 1) * 0x4000];
+        assert_eq!(&bank(2)[..3], &[0x4C, 0x03, 0x80]);
+        // No reset stubs: UxROM powers up with the last bank at $C000
+        assert_eq!(bank(0)[0x3FF0], 0x00);
+        assert_eq!(bank(3)[0], 0x78);
+
+        let table = sta_abs_y(&rom);
+        let offset = (table - 0xC000) as usize;
+        assert_eq!(
+            &bank(3)[offset..offset + 16],
+            &(0..16).collect::<Vec<u8>>()[..]
+        );
+    }
+
+    #[test]
+    fn test_axrom_repeats_fixed_region_in_every_bank() {
+        let source = "MAPPER AxROM\nBANK 1\nSUB Level()\nEND SUB\nBANK FIXED\n\
+                      SUB Main()\n  Level()\nEND SUB\n";
+        let rom = compile_with(source, None, None).expect("Compile failed");
+        // Two 32 KB banks
+        assert_eq!(rom[4], 4);
+        assert_eq!(rom[6] >> 4, 7);
+        let bank = |n: usize| &rom[16 + n * 0x8000..16 + (n + 1) * 0x8000];
+        assert_eq!(&bank(0)[0x4000..], &bank(1)[0x4000..]);
+        assert_eq!(bank(0)[0x4000], 0x78);
+        assert_eq!(&bank(1)[..3], &[0x4C, 0x03, 0x80]);
+    }
+}
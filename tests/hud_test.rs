@@ -0,0 +1,130 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use crate::common::{analyze, compile, generate_with};
+    use swissarmynes::compiler::hud::sprite_zero_tile;
+    use swissarmynes::server::project::ProjectAssets;
+
+    /// A CHR bank with `rows` written into the low plane of each tile.
+    fn chr(tiles: &[(usize, &[u8])]) -> Vec<u8> {
+        let mut bank = vec![0; 4096];
+        for (tile, rows) in tiles {
+            bank[tile * 16..tile * 16 + rows.len()].copy_from_slice(rows);
+        }
+        bank
+    }
+
+    fn assets(chr_bank: Vec<u8>) -> ProjectAssets {
+        ProjectAssets {
+            chr_bank,
+            ..Default::default()
+        }
+    }
+
+    fn generate(source: &str, tile: Option<u8>) -> Result<String, String> {
+        generate_with(source, |codegen| {
+            if let Some(tile) = tile {
+                codegen.set_hud_tile(tile);
+            }
+        })
+    }
+
+    #[test]
+    fn test_sprite_zero_tile_choice() {
+        // Tile 2 is opaque on top but also below; tile 5 is only a top row
+        let bank = chr(&[(2, &[0xFF, 0xFF]), (5, &[0xFF]), (7, &[0x7F])]);
+        assert_eq!(sprite_zero_tile(&bank, false), Some(5));
+        // A partly opaque top row never qualifies
+        assert_eq!(sprite_zero_tile(&chr(&[(7, &[0x7F])]), false), None);
+        assert_eq!(sprite_zero_tile(&[], false), None);
+
+        // 8x16 needs an even tile, preferring one whose lower half is empty
+        assert_eq!(sprite_zero_tile(&bank, true), Some(2));
+        let bank = chr(&[(4, &[0xFF]), (5, &[0x01]), (6, &[0xFF])]);
+        assert_eq!(sprite_zero_tile(&bank, true), Some(6));
+        assert_eq!(sprite_zero_tile(&chr(&[(5, &[0xFF])]), true), None);
+    }
+
+    #[test]
+    fn test_hud_split_codegen() {
+        let source = "SUB Main()\n  HUD.Split(32, HudPosition.Bottom)\n  Sprite.Clear()\nEND SUB\n";
+        let asm = generate(source, Some(5)).expect("Codegen failed");
+
+        // The split waits in the NMI once the frame's scroll is written
+        let nmi = &asm[asm.find("TrampolineNMI:").unwrap()..];
+        assert!(nmi.contains("  LDA $E1\n  STA $2005\n  JSR Hud_Split"));
+        assert!(asm.contains("  JSR Runtime_Hud_Split"));
+        // Variable lines past 200 are clamped before the timeout adds 28
+        let split = &asm[asm.find("Runtime_Hud_Split:").unwrap()..];
+        assert!(split.contains("  CMP #201\n  BCC Hud_LineOk\n  LDA #200\nHud_LineOk:\n  STA"));
+
+        // Sprite 0 sits behind the background, the line above the split
+        let place = &asm[asm.find("Hud_PlaceSprite:").unwrap()..];
+        assert!(place.contains("  SBC #2\n  STA $0200\n  LDA #$05\n  STA $0201"));
+        assert!(place.contains("  LDA #$20\n  STA $0202\n  LDA #184\n  STA $0203"));
+
+        // Clearing sprites keeps slot 0 and puts sprite 0 back
+        let clear = &asm[asm.find("Runtime_SpriteClear:").unwrap()..];
+        let end = clear
+            .find("  JMP Hud_PlaceSprite")
+            .expect("Sprite 0 not restored");
+        assert!(!clear[..end].contains("  RTS"));
+
+        // The hit reloads the scroll with the $2006/$2005 sequence
+        let hit = &asm[asm.find("Hud_Hit:").unwrap()..];
+        assert!(hit.starts_with(
+            "Hud_Hit:\n  LDA $00\n  STA $2006\n  LDA $01\n  STA $2005\n  LDA $02\n  STA $2005\n  LDA $03\n  STA $2006"
+        ));
+
+        // Without a HUD nothing waits in the NMI
+        let asm = generate("SUB Main()\n  Sprite.Clear()\nEND SUB\n", None).unwrap();
+        assert!(!asm.contains("Hud_"));
+    }
+
+    #[test]
+    fn test_hud_split_needs_opaque_tile() {
+        let source = "SUB Main()\n  HUD.Split(40)\nEND SUB\n";
+        assert!(compile(source, assets(chr(&[(9, &[0xFF])]))).is_ok());
+
+        let err = compile(source, assets(chr(&[(9, &[0xFE])]))).expect_err("No tile accepted");
+        assert!(
+            err.contains("HUD.Split needs a CHR tile whose top row is fully opaque"),
+            "{}",
+            err
+        );
+
+        let tall = format!("SPRITES 8X16\n{}", source);
+        let err = compile(&tall, assets(chr(&[(9, &[0xFF])]))).expect_err("Odd tile accepted");
+        assert!(err.contains("needs an even CHR tile"), "{}", err);
+    }
+
+    #[test]
+    fn test_hud_call_errors() {
+        let cases = [
+            (
+                "HUD.Split(1)",
+                "HUD.Split scanline 1 is out of range (2-200, or 0 for none)",
+            ),
+            (
+                "HUD.Split(201)",
+                "HUD.Split scanline 201 is out of range (2-200, or 0 for none)",
+            ),
+            (
+                "HUD.Split()",
+                "HUD.Split expects 1 or 2 arguments (scanline, position)",
+            ),
+            ("HUD.Show(1)", "Unknown HUD command 'Show' (Split)"),
+        ];
+        for (call, message) in cases {
+            let source = format!("SUB Main()\n  {}\nEND SUB\n", call);
+            let errors = analyze(&source)
+                .err()
+                .unwrap_or_else(|| panic!("{} accepted", call));
+            assert!(errors.iter().any(|e| e.contains(message)), "{:?}", errors);
+        }
+        let analyzer =
+            analyze("SUB Main()\n  HUD.Split(0)\nEND SUB\n").expect("Split off rejected");
+        assert!(analyzer.splits_hud());
+    }
+}
@@ -0,0 +1,215 @@
+mod common;
+
+use crate::common::{analyze_program, generate_with, parse};
+use std::collections::HashMap;
+use swissarmynes::compiler::assembler::Assembler;
+use swissarmynes::compiler::ast::TopLevel;
+use swissarmynes::compiler::ir::{
+    self, BinOp, Block, Function, Instr, Location, Operand, Terminator,
+};
+
+fn lower_main(source: &str) -> Function {
+    let program = parse(source);
+    let mut symbols = analyze_program(&program)
+        .expect("Analysis failed")
+        .symbol_table;
+    // Give the globals addresses the way allocate_memory would
+    let mut addr = 0x05C0;
+    for decl in &program.declarations {
+        if let TopLevel::Dim(name, _, _) = decl {
+            symbols.assign_address(name, addr).unwrap();
+            addr += 1;
+        }
+    }
+    let body = program
+        .declarations
+        .iter()
+        .find_map(|d| match d {
+            TopLevel::Sub(name, _, body) if name == "Main" => Some(body.clone()),
+            _ => None,
+        })
+        .unwrap();
+    ir::lower("Main", &body, &symbols, &HashMap::new()).expect("Lowering failed")
+}
+
+#[test]
+fn test_ir_constant_folding() {
+    let mut func = lower_main("DIM x AS BYTE\nSUB Main()\n  x = (2 + 3) AND 7\nEND SUB\n");
+    func.optimize();
+    assert_eq!(func.blocks.len(), 1);
+    assert_eq!(
+        func.blocks[0].instrs,
+        vec![Instr::Store {
+            addr: 0x05C0,
+            src: Operand::Const(5),
+            volatile: false
+        }]
+    );
+}
+
+#[test]
+fn test_ir_constant_branch_removes_dead_block() {
+    let mut func = lower_main(
+        "DIM x AS BYTE\nSUB Main()\n  IF 1 = 2 THEN\n    x = 1\n  ELSE\n    x = 2\n  END IF\nEND SUB\n",
+    );
+    func.optimize();
+    let text = func.to_string();
+    assert!(!text.contains("store $05C0, #1"), "{}", text);
+    assert!(text.contains("store $05C0, #2"), "{}", text);
+    assert!(!text.contains("branch"), "{}", text);
+}
+
+#[test]
+fn test_ir_dead_store_elimination() {
+    let mut func = lower_main(
+        "DIM x AS BYTE\nDIM y AS BYTE\nSUB Main()\n  x = 1\n  x = 2\n  y = x\n  x = 3\nEND SUB\n",
+    );
+    func.optimize();
+    assert_eq!(
+        func.blocks[0].instrs,
+        vec![
+            Instr::Store {
+                addr: 0x05C0,
+                src: Operand::Const(2),
+                volatile: false
+            },
+            Instr::Store {
+                addr: 0x05C1,
+                src: Operand::Mem(0x05C0),
+                volatile: false
+            },
+            Instr::Store {
+                addr: 0x05C0,
+                src: Operand::Const(3),
+                volatile: false
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_ir_pokes_are_never_eliminated() {
+    let mut func = lower_main("SUB Main()\n  POKE($2007, 1)\n  POKE($2007, 2)\nEND SUB\n");
+    func.optimize();
+    assert_eq!(func.blocks[0].instrs.len(), 2);
+}
+
+#[test]
+fn test_ir_liveness_and_zero_page_allocation() {
+    // bb0: t0 = [$05C0] + #1 ; t1 = [$05C1] - #1 ; t2 = t0 xor t1 ; store
+    let func = Function {
+        name: "Test".to_string(),
+        blocks: vec![
+            Block {
+                instrs: vec![
+                    Instr::Bin {
+                        dst: 0,
+                        op: BinOp::Add,
+                        lhs: Operand::Mem(0x05C0),
+                        rhs: Operand::Const(1),
+                    },
+                    Instr::Bin {
+                        dst: 1,
+                        op: BinOp::Sub,
+                        lhs: Operand::Mem(0x05C1),
+                        rhs: Operand::Const(1),
+                    },
+                ],
+                term: Terminator::Jump(1),
+            },
+            Block {
+                instrs: vec![
+                    Instr::Bin {
+                        dst: 2,
+                        op: BinOp::Xor,
+                        lhs: Operand::Temp(0),
+                        rhs: Operand::Temp(1),
+                    },
+                    Instr::Store {
+                        addr: 0x05C2,
+                        src: Operand::Temp(2),
+                        volatile: false,
+                    },
+                ],
+                term: Terminator::Return(None),
+            },
+        ],
+        temp_count: 3,
+    };
+
+    let (live_in, live_out) = func.liveness();
+    assert!(live_out[0].contains(&0) && live_out[0].contains(&1));
+    assert_eq!(live_in[1], live_out[0]);
+    assert!(live_out[1].is_empty());
+
+    let alloc = func.allocate().expect("Allocation failed");
+    // t0 and t1 are live together, so they need distinct bytes
+    let zp0 = alloc[&0];
+    let zp1 = alloc[&1];
+    assert!(matches!(zp0, Location::ZeroPage(_)));
+    assert!(matches!(zp1, Location::ZeroPage(_)));
+    assert_ne!(zp0, zp1);
+    // t2 is consumed by the very next instruction and stays in A
+    assert_eq!(alloc[&2], Location::A);
+
+    let asm = func.select(&alloc).join("\n");
+    assert!(asm.contains("EOR $01"), "{}", asm);
+    assert!(asm.contains("STA $05C2"), "{}", asm);
+}
+
+#[test]
+fn test_ir_rejects_temporary_live_across_call() {
+    let func = Function {
+        name: "Test".to_string(),
+        blocks: vec![Block {
+            instrs: vec![
+                Instr::Load {
+                    dst: 0,
+                    addr: 0x2002,
+                },
+                Instr::Call("Other".to_string()),
+                Instr::Store {
+                    addr: 0x05C0,
+                    src: Operand::Temp(0),
+                    volatile: false,
+                },
+            ],
+            term: Terminator::Return(None),
+        }],
+        temp_count: 1,
+    };
+    assert!(func.allocate().is_err());
+}
+
+#[test]
+fn test_ir_codegen_selects_fused_compare() {
+    let source = "DIM x AS BYTE\nSUB Bump(n AS BYTE)\n  WHILE x < n\n    x = x + 1\n  WEND\nEND SUB\nSUB Main()\n  Bump(10)\nEND SUB\n";
+    let asm = generate_with(source, |cg| cg.set_use_ir(true)).expect("Codegen failed");
+
+    assert!(!asm.contains("; IR fallback"), "{}", asm);
+    // Parameter read straight from its slot, comparison fused into the branch
+    assert!(
+        asm.contains("  LDA $05C0\n  CMP $05C1\n  BCC Bump_IR_L1"),
+        "{}",
+        asm
+    );
+    assert!(
+        asm.contains("  LDA #$0A\n  STA $05C1\n  JSR Bump"),
+        "{}",
+        asm
+    );
+
+    let rom = Assembler::new()
+        .assemble(&asm, None, vec![])
+        .expect("Assembly failed");
+    assert_eq!(rom.len(), 40976);
+}
+
+#[test]
+fn test_ir_falls_back_for_word_variables() {
+    let source = "DIM w AS WORD\nSUB Main()\n  w = w + 300\nEND SUB\n";
+    let asm = generate_with(source, |cg| cg.set_use_ir(true)).expect("Codegen failed");
+    assert!(!asm.contains("Main_IR_"));
+    assert!(asm.contains("Main:\n; IR fallback: IR: 'w' is not a byte variable\n"));
+    assert!(asm.contains("STX $05C1"));
+}
@@ -0,0 +1,243 @@
+mod common;
+
+use crate::common::compile;
+use swissarmynes::compiler::audio;
+use swissarmynes::compiler::linker::{self, Layout, Linker, Section};
+use swissarmynes::compiler::mapper::{self, Mapper};
+use swissarmynes::server::project::{AudioNote, AudioTrack, DpcmSample, ProjectAssets};
+
+fn assets(tracks: Vec<AudioTrack>, samples: Vec<DpcmSample>) -> ProjectAssets {
+    ProjectAssets {
+        audio_tracks: tracks,
+        samples,
+        ..Default::default()
+    }
+}
+
+// Each track is a little over 500 bytes of note data
+fn tracks(count: usize) -> Vec<AudioTrack> {
+    (0..count).map(|i| long_track(&format!("T{}", i))).collect()
+}
+
+fn long_track(name: &str) -> AudioTrack {
+    AudioTrack {
+        name: name.to_string(),
+        notes: (0..250)
+            .map(|i| AudioNote {
+                pitch: 10,
+                row: 0,
+                col: i as u8,
+                duration: 8,
+            })
+            .collect(),
+        channel: 0,
+        instrument: 0,
+        priority: 0,
+        vol_env: None,
+        pitch_env: None,
+        arpeggio_env: None,
+    }
+}
+
+fn prg(rom: &[u8], addr: u16) -> u8 {
+    rom[16 + (addr - 0x8000) as usize]
+}
+
+#[test]
+fn test_sections_packed_below_pointer_table() {
+    let mut linker = Linker::new();
+    linker.add(Section::new("palette", 32));
+    linker.add(Section::aligned(
+        "dpcm",
+        1000,
+        linker::DPCM_ALIGN,
+        linker::DPCM_MIN_ADDR,
+    ));
+    linker.add(Section::new("music", 3000));
+    let layout = linker.link().expect("Link failed");
+
+    let dpcm = layout.get("dpcm").unwrap();
+    assert_eq!(dpcm.addr % 64, 0);
+    assert!(dpcm.addr >= 0xC000);
+    assert!(dpcm.addr as usize + dpcm.size <= linker::POINTER_TABLE_ADDR as usize);
+
+    // Non-overlapping and clear of the pointer table and vectors
+    let mut spans: Vec<(usize, usize)> = layout
+        .placements()
+        .iter()
+        .map(|p| (p.addr as usize, p.addr as usize + p.size))
+        .collect();
+    spans.sort();
+    for w in spans.windows(2) {
+        assert!(w[0].1 <= w[1].0, "{:?}", spans);
+    }
+    assert!(spans.last().unwrap().1 <= 0xFF00);
+    assert_eq!(layout.code_limit(), layout.get("music").unwrap().addr);
+    assert!(layout
+        .symbols()
+        .contains(&format!("SECTION_DPCM = ${:04X}", dpcm.addr)));
+}
+
+#[test]
+fn test_overflow_reported_per_section() {
+    let mut linker = Linker::new();
+    linker.add(Section::aligned(
+        "dpcm",
+        0x4000,
+        linker::DPCM_ALIGN,
+        linker::DPCM_MIN_ADDR,
+    ));
+    let err = linker.link().unwrap_err();
+    assert!(err.contains("section 'dpcm'"), "{}", err);
+
+    let mut linker = Linker::new();
+    linker.add(Section::new("music", 0x6000));
+    linker.add(Section::new("nametable", 0x2000));
+    let err = linker.link().unwrap_err();
+    assert!(err.contains("section 'nametable'"), "{}", err);
+}
+
+#[test]
+fn test_legacy_layout_matches_old_map() {
+    let layout = Layout::legacy();
+    assert_eq!(layout.addr(linker::SECTION_MUSIC), audio::MUSIC_DATA_ADDR);
+    assert_eq!(layout.addr(linker::SECTION_DPCM), audio::SAMPLE_DATA_ADDR);
+    assert_eq!(layout.addr(linker::SECTION_PALETTE), 0xE000);
+}
+
+#[test]
+fn test_large_music_links_and_relocates() {
+    // Far beyond the old 896-byte music window
+    let assets = assets(tracks(4), vec![]);
+    let music = audio::compile_audio_data_at(&Some(assets.clone()), 0xD100).unwrap();
+    assert!(music.len() > audio::MUSIC_DATA_SIZE);
+
+    let source = "SUB Main()\n  ASM\n    LDA SECTION_MUSIC\n  END ASM\nEND SUB\n";
+    let rom = compile(source, assets).expect("Compile failed");
+    assert_eq!(rom.len(), 40976);
+
+    // Track data is position independent; the pointer table must match where it landed
+    let body = &music[9..];
+    let found = rom[16..16 + 0x8000]
+        .windows(body.len())
+        .position(|w| w == body)
+        .expect("Music blob not found");
+    let base = 0x8000 + found as u16 - 9;
+    assert_eq!(prg(&rom, base), 4);
+    let ptr = prg(&rom, base + 1) as u16 | (prg(&rom, base + 2) as u16) << 8;
+    assert_eq!(ptr, base + 9);
+    assert_ne!(base, audio::MUSIC_DATA_ADDR);
+    // Inline ASM resolved the exported symbol
+    let code = &rom[16..16 + 0x2000];
+    assert!(code
+        .windows(3)
+        .any(|w| w == [0xAD, (base & 0xFF) as u8, (base >> 8) as u8]));
+}
+
+#[test]
+fn test_code_overflow_names_section() {
+    let assets = assets(tracks(56), vec![]);
+    let source = "SUB Main()\nEND SUB\n".to_string();
+    let err = compile(&source, assets).unwrap_err();
+    assert!(err.contains("ROM overflow"), "{}", err);
+    assert!(err.contains("section 'music'"), "{}", err);
+}
+
+#[test]
+fn test_dpcm_sample_addresses_follow_placement() {
+    let sample = DpcmSample {
+        name: "Kick".to_string(),
+        data: vec![0x55; 100],
+    };
+    let assets = assets(vec![], vec![sample]);
+    let (_, table) = audio::compile_samples_at(&Some(assets.clone()), 0xF000).unwrap();
+    assert_eq!(table[0], ((0xF000 - 0xC000) >> 6) as u8);
+
+    let rom = compile("SUB Main()\nEND SUB\n", assets).expect("Compile failed");
+    // Samples go first, as high as possible: $FF00 - 112 rounded down to 64
+    let addr: u16 = 0xFE80;
+    assert_eq!(prg(&rom, addr), 0x55);
+    assert_eq!(prg(&rom, addr + 99), 0x55);
+}
+
+#[test]
+fn test_banked_sections_fill_their_bank() {
+    let mut linker = Linker::for_mapper(Mapper::Mmc1);
+    linker.add(Section::new("palette", 32));
+    linker.add(Section::banked("music", 0x3000, Some(2)));
+    linker.add(Section::banked("screens_2", 0x0C00, Some(2)));
+    linker.add(Section::banked("screens_3", 0x0400, Some(3)));
+    let layout = linker.link().expect("Link failed");
+
+    // Packed down from the MMC1 reset stub, clear of each other
+    let music = layout.get("music").unwrap();
+    let screens = layout.get("screens_2").unwrap();
+    assert_eq!(
+        music.addr as usize + music.size,
+        mapper::RESET_STUB_ADDR as usize
+    );
+    assert_eq!(screens.addr as usize + screens.size, music.addr as usize);
+    assert_eq!(layout.addr("screens_3"), mapper::RESET_STUB_ADDR - 0x0400);
+    assert_eq!(layout.bank("music"), Some(2));
+    assert_eq!(layout.bank("palette"), None);
+    // Banked sections leave the fixed bank to code
+    assert_eq!(layout.code_limit(), layout.addr("palette"));
+
+    let mut linker = Linker::for_mapper(Mapper::Mmc1);
+    linker.add(Section::banked("music", 0x4000, Some(1)));
+    let err = linker.link().unwrap_err();
+    assert!(err.contains("bank 1"), "{}", err);
+    assert!(err.contains("section 'music'"), "{}", err);
+}
+
+#[test]
+fn test_music_in_switchable_bank() {
+    const SOURCE: &str = "MAPPER MMC1\nSUB Main()\nEND SUB\n";
+    // Too much music for the fixed bank next to the runtime
+    let fixed = assets(tracks(28), vec![]);
+    let err = compile(SOURCE, fixed).unwrap_err();
+    assert!(err.contains("section 'music'"), "{}", err);
+
+    let banked = ProjectAssets {
+        audio_bank: Some(3),
+        ..assets(tracks(28), vec![])
+    };
+    let music = audio::compile_audio_data_at(&Some(banked.clone()), 0x8000).unwrap();
+    let rom = compile(SOURCE, banked).expect("Compile failed");
+    // Bank 3 is the 16 KB at 3 * $4000, mapped at $8000
+    let bank = &rom[16 + 3 * 0x4000..16 + 4 * 0x4000];
+    // Track data follows the count and 28 pointers
+    let body = &music[57..];
+    let found = bank
+        .windows(body.len())
+        .position(|w| w == body)
+        .expect("Music not in bank 3")
+        - 57;
+    let base = 0x8000 + found as u16;
+    assert_eq!(bank[found], 28);
+    let ptr = bank[found + 1] as u16 | (bank[found + 2] as u16) << 8;
+    assert_eq!(ptr, base + 57);
+
+    let err = compile(
+        "SUB Main()\nEND SUB\n",
+        ProjectAssets {
+            audio_bank: Some(0),
+            ..Default::default()
+        },
+    )
+    .unwrap_err();
+    assert!(err.contains("requires a bank-switching mapper"), "{}", err);
+    let err = compile(
+        SOURCE,
+        ProjectAssets {
+            audio_bank: Some(15),
+            ..Default::default()
+        },
+    )
+    .unwrap_err();
+    assert!(
+        err.contains("out of range for MMC1 (banks 0-14)"),
+        "{}",
+        err
+    );
+}
@@ -5,6 +5,7 @@ mod tests {
     use swissarmynes::compiler::codegen::CodeGenerator;
     use swissarmynes::compiler::lexer::Lexer;
     use swissarmynes::compiler::parser::Parser;
+    use swissarmynes::compiler::sprites;
 
     #[test]
     fn test_metasprite_compilation() {
@@ -29,21 +30,23 @@ mod tests {
         let mut analyzer = SemanticAnalyzer::new();
         analyzer.analyze(&program).expect("Analysis failed");
 
+        // Count, then x, y, tile, attr per tile
+        let table = sprites::table(&program, &analyzer.symbol_table, 0).expect("Table failed");
+        assert_eq!(
+            table[..9],
+            [0x02, 0x00, 0x00, 0x10, 0x00, 0x08, 0x00, 0x11, 0x00]
+        );
+
         let symbol_table = analyzer.symbol_table;
         let mut codegen = CodeGenerator::new(symbol_table);
         let asm_lines = codegen.generate(&program).expect("Codegen failed");
         let asm_source = asm_lines.join("\n");
 
-        assert!(asm_source.contains("player_idle:"));
+        assert!(asm_source.contains("; player_idle @ $0000"));
         assert!(asm_source.contains("Runtime_SpriteDraw:"));
         assert!(asm_source.contains("Runtime_SpriteClear:"));
-        // Check for Data Bytes (may be split across lines or spaces)
-        // db $02
-        assert!(asm_source.contains("db $02"));
-        // db $00, $00, $10, $00
-        assert!(asm_source.contains("db $00, $00, $10, $00"));
-        // db $08, $00, $11, $00
-        assert!(asm_source.contains("db $08, $00, $11, $00"));
+        // The metasprite's address is passed to the draw
+        assert!(asm_source.contains("  LDA #$00\n  LDX #$00\n  STA $16\n  STX $17"));
 
         let assembler = Assembler::new();
         let rom = assembler
@@ -0,0 +1,164 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use crate::common::{check, compile_with, generate_with, listing, parse};
+    use swissarmynes::compiler::ast::TopLevel;
+    use swissarmynes::compiler::linker::{
+        Linker, Section, SECTION_ENVELOPES, SECTION_MUSIC, SECTION_SFX,
+    };
+    use swissarmynes::compiler::mapper::{self, Mapper};
+
+    const BANKED: &str = r#"
+MAPPER MMC1
+DIM level AS BYTE
+BANK 1
+SUB LoadLevel(n AS BYTE)
+    level = n
+    Helper()
+END SUB
+SUB Helper()
+    POKE($2001, level)
+END SUB
+BANK FIXED
+SUB Main()
+    LoadLevel(2)
+END SUB
+"#;
+
+    #[test]
+    fn test_parse_mapper_and_bank_directives() {
+        let program = parse(BANKED);
+        assert_eq!(
+            program.declarations[0],
+            TopLevel::Mapper("MMC1".to_string())
+        );
+        assert!(program.declarations.contains(&TopLevel::Bank(Some(1))));
+        assert!(program.declarations.contains(&TopLevel::Bank(None)));
+        assert_eq!(mapper::target(&program), Ok(Mapper::Mmc1));
+    }
+
+    #[test]
+    fn test_bank_validation() {
+        let err = check("BANK 0\nSUB Main()\nEND SUB\n").unwrap_err();
+        assert!(
+            err[0].contains("requires a bank-switching mapper"),
+            "{:?}",
+            err
+        );
+
+        let err = check("MAPPER MMC1\nBANK 15\nSUB A()\nEND SUB\n").unwrap_err();
+        assert!(err[0].contains("out of range"), "{:?}", err);
+
+        let err = check("MAPPER MMC1\nBANK 2\nINTERRUPT NMI()\nEND INTERRUPT\n").unwrap_err();
+        assert!(err[0].contains("fixed bank"), "{:?}", err);
+
+        assert!(check("MAPPER UNKNOWN\nSUB Main()\nEND SUB\n").is_err());
+    }
+
+    #[test]
+    fn test_banked_code_and_trampolines() {
+        let asm = listing(BANKED);
+        let text = asm.join("\n");
+        assert_eq!(asm[0], ".ORG $C000");
+
+        // Fixed code reaches the banked SUB through its trampoline
+        let main = asm.iter().position(|l| l == "Main:").unwrap();
+        assert!(asm[main..].iter().any(|l| l == "  JSR Far_LoadLevel"));
+        assert!(text.contains(
+            "Far_LoadLevel:\n  LDA $1D\n  PHA\n  LDA #$01\n  JSR MMC1_SetPRG\n  JSR $8000"
+        ));
+        assert!(text.contains("Far_Helper:"));
+        assert!(text.contains("  JSR $8003"));
+
+        // The bank block opens with its jump table; same-bank calls stay direct
+        let bank = asm.iter().position(|l| l == ".BANK 1").unwrap();
+        assert_eq!(asm[bank + 1], ".ORG $8000");
+        assert_eq!(asm[bank + 2], "  JMP LoadLevel");
+        assert_eq!(asm[bank + 3], "  JMP Helper");
+        assert!(asm[bank..].iter().any(|l| l == "  JSR Helper"));
+        assert!(!asm[..bank].iter().any(|l| l == "LoadLevel:"));
+
+        // Mapper init runs during startup
+        let startup = asm.iter().position(|l| l == "Startup:").unwrap();
+        let init = asm
+            .iter()
+            .position(|l| l == "  JSR MMC1_WriteControl")
+            .unwrap();
+        assert!(init > startup && init < main);
+    }
+
+    #[test]
+    fn test_mmc1_rom_image() {
+        let rom = compile_with(BANKED, None, None).expect("Compile failed");
+        // Highest bank 1 plus the fixed bank rounds up to 4 x 16 KB
+        assert_eq!(rom.len(), 16 + 4 * 0x4000 + 0x2000);
+        assert_eq!(rom[4], 4);
+        assert_eq!(rom[5], 1);
+        assert_eq!(rom[6] >> 4, 1);
+
+        let bank = |n: usize| &rom[16 + n * 0x4000..16 + (n + 1) * 0x4000];
+        // Bank 1 starts with JMP LoadLevel, just past the 6-byte jump table
+        assert_eq!(&bank(1)[..3], &[0x4C, 0x06, 0x80]);
+        // Every switchable bank carries the reset stub and points its vectors at it
+        for n in 0..3 {
+            assert_eq!(bank(n)[0x3FF0], 0x78);
+            assert_eq!(&bank(n)[0x3FFC..0x3FFE], &[0xF0, 0xFF]);
+        }
+        // The fixed bank begins with Startup (SEI)
+        assert_eq!(bank(3)[0], 0x78);
+    }
+
+    #[test]
+    fn test_nrom_header_and_mmc1_minimum() {
+        let rom = compile_with("SUB Main()\nEND SUB\n", None, None).unwrap();
+        assert_eq!(rom.len(), 40976);
+        // 2 x 16 KB PRG, 1 x 8 KB CHR, vertical mirroring, NES 2.0 identifier
+        assert_eq!(&rom[4..8], &[2, 1, 0x01, 0x08]);
+
+        let source = "MAPPER MMC1\nSUB Main()\nEND SUB\n";
+        let rom = compile_with(source, None, None).unwrap();
+        // No banks in use: the minimum 32 KB MMC1 image
+        assert_eq!(rom[4], 2);
+        assert_eq!(rom.len(), 16 + 0x8000 + 0x2000);
+    }
+
+    #[test]
+    fn test_bank_overflow_is_reported() {
+        let mut source = String::from("MAPPER MMC1\nBANK 0\nSUB Big()\n");
+        for _ in 0..3500 {
+            source.push_str("    POKE($2001, 1)\n");
+        }
+        source.push_str("END SUB\nBANK FIXED\nSUB Main()\n    Big()\nEND SUB\n");
+        let err = compile_with(&source, None, None).unwrap_err();
+        assert!(err.contains("Bank 0 overflow"), "{}", err);
+    }
+
+    #[test]
+    fn test_banked_audio_wrappers() {
+        let source = "MAPPER MMC1\nSUB Main()\nEND SUB\n";
+        let mut linker = Linker::for_mapper(Mapper::Mmc1);
+        for name in [SECTION_MUSIC, SECTION_SFX, SECTION_ENVELOPES] {
+            linker.add(Section::banked(name, 16, Some(2)));
+        }
+        let text = generate_with(source, |codegen| {
+            codegen.set_layout(linker.link().expect("Link failed"))
+        })
+        .expect("Codegen failed");
+
+        // The engine runs in bank 2 behind wrappers that map the caller's
+        // bank back, so the NMI and banked code can both call it
+        for name in ["Sound_Play", "SFX_Play", "Sound_Update"] {
+            assert!(text.contains(&format!("\n{}_Banked:\n", name)), "{}", name);
+            assert!(text.contains(&format!(
+                "\n{}:\n  PHA\n  TXA\n  PHA\n  LDA $1D\n  PHA\n  LDA #$02\n  JSR MMC1_SetPRG\n",
+                name
+            )));
+        }
+        assert!(text.contains(
+            "  JSR Sound_Update_Banked\n  PHA\n  TXA\n  PHA\n  TSX\n  LDA $0103, X\n  JSR MMC1_SetPRG\n"
+        ));
+        // Callers still go through the wrapper
+        assert!(text.contains("  JSR Sound_Update\n"));
+    }
+}
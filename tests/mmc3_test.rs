@@ -0,0 +1,98 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use crate::common::{check, compile_with, listing, parse};
+    use swissarmynes::compiler::ast::{Expression, Statement, TopLevel};
+    use swissarmynes::compiler::mapper::{self, Mapper};
+
+    const SPLIT: &str = r#"
+MAPPER MMC3
+DIM scroll AS BYTE
+BANK 3
+SUB Parallax()
+    POKE($2005, scroll)
+    ON SCANLINE 40 DO Ground
+END SUB
+BANK FIXED
+SUB Ground()
+    POKE($2005, 0)
+END SUB
+SUB Main()
+    ON SCANLINE 32 DO Parallax
+END SUB
+"#;
+
+    #[test]
+    fn test_parse_on_scanline() {
+        let program = parse(SPLIT);
+        assert_eq!(mapper::target(&program), Ok(Mapper::Mmc3));
+        let main = program
+            .declarations
+            .iter()
+            .find_map(|d| match d {
+                TopLevel::Sub(name, _, body) if name == "Main" => Some(body),
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(
+            main[0],
+            Statement::OnScanline(Expression::Integer(32), "Parallax".to_string())
+        );
+    }
+
+    #[test]
+    fn test_on_scanline_validation() {
+        let err =
+            check("SUB H()\nEND SUB\nSUB Main()\n  ON SCANLINE 10 DO H\nEND SUB\n").unwrap_err();
+        assert!(err[0].contains("requires MAPPER MMC3"), "{:?}", err);
+
+        let source = "MAPPER MMC3\nSUB Main()\n  ON SCANLINE 10 DO Missing\nEND SUB\n";
+        let err = check(source).unwrap_err();
+        assert!(err[0].contains("Undefined sub 'Missing'"), "{:?}", err);
+
+        let source =
+            "MAPPER MMC3\nSUB H(a AS BYTE)\nEND SUB\nSUB Main()\n  ON SCANLINE 10 DO H\nEND SUB\n";
+        let err = check(source).unwrap_err();
+        assert!(err[0].contains("must not take arguments"), "{:?}", err);
+
+        assert!(check("MAPPER MMC3\nBANK 61\nSUB A()\nEND SUB\n").is_ok());
+        assert!(check("MAPPER MMC3\nBANK 62\nSUB A()\nEND SUB\n").is_err());
+    }
+
+    #[test]
+    fn test_irq_rearmed_each_frame() {
+        let asm = listing(SPLIT);
+        let text = asm.join("\n");
+        assert!(text.contains(concat!(
+            "  JSR PpuQueue_Flush\n  STA $E000\n  LDA $1F\n  BEQ SkipScanlineArm\n",
+            "  STA $C000\n  STA $C001\n  STA $E001"
+        )));
+        assert!(text.contains("  JSR Scanline_Dispatch"));
+        // Handlers are numbered in generation order; banked ones go through
+        // their trampoline
+        assert!(text.contains("  CMP #$01\n  BNE Scanline_Next1\n  JMP Ground"));
+        assert!(text.contains("  CMP #$02\n  BNE Scanline_Next2\n  JMP Far_Parallax"));
+        assert!(text.contains("Far_Parallax:\n  LDA $1D\n  PHA\n  LDA #$03\n  JSR MMC3_SetPRG"));
+        // Interrupts are unmasked once the mapper is set up
+        let cli = asm.iter().position(|l| l == "  CLI").unwrap();
+        let main = asm.iter().position(|l| l == "  JSR Main").unwrap();
+        assert!(cli < main);
+    }
+
+    #[test]
+    fn test_mmc3_rom_image() {
+        let rom = compile_with(SPLIT, None, None).expect("Compile failed");
+        // Banks 0-3 plus the two fixed 8 KB banks round up to 8 x 8 KB
+        assert_eq!(rom.len(), 16 + 8 * 0x2000 + 0x2000);
+        assert_eq!(rom[4], 4);
+        assert_eq!(rom[6] >> 4, 4);
+        assert_eq!(rom[7] & 0xF0, 0);
+
+        let bank = |n: usize| &rom[16 + n * 0x2000..16 + (n + 1) * 0x2000];
+        // Bank 3 opens with JMP Parallax, just past its 3-byte jump table
+        assert_eq!(&bank(3)[..3], &[0x4C, 0x03, 0x80]);
+        // The fixed region (last 16 KB) begins with Startup (SEI)
+        assert_eq!(bank(6)[0], 0x78);
+    }
+}
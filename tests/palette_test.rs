@@ -0,0 +1,135 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use crate::common::{analyze, compile, generate};
+    use swissarmynes::server::project::{Palette, ProjectAssets};
+
+    fn palette(name: &str, set: Option<&str>, colors: [u8; 4]) -> Palette {
+        Palette {
+            name: name.to_string(),
+            colors,
+            set: set.map(str::to_string),
+        }
+    }
+
+    fn assets(palettes: Vec<Palette>) -> ProjectAssets {
+        ProjectAssets {
+            palettes,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_palette_sets_in_rom() {
+        let palettes = vec![
+            palette("BG0", None, [0x0F, 0x01, 0x11, 0x21]),
+            palette("SP3", None, [0x0F, 0x06, 0x16, 0x26]),
+            palette("BG0", Some("Night Sky"), [0x0F, 0x02, 0x12, 0x22]),
+            palette("BG1", Some("Night Sky"), [0x0F, 0x03, 0x13, 0x23]),
+            palette("BG0", Some("Cave"), [0x0F, 0x07, 0x17, 0x27]),
+        ];
+        let source = "SUB Main()\n  Palette.Load(PALETTE_NIGHT_SKY)\n  Palette.Load(PALETTE_CAVE)\n  Palette.Load(PALETTE_DEFAULT)\nEND SUB\n";
+        let rom = compile(source, assets(palettes)).expect("Compile failed");
+
+        // Startup set, then named sets in the order they first appear
+        let mut expected = vec![0x0F; 96];
+        expected[0..4].copy_from_slice(&[0x0F, 0x01, 0x11, 0x21]);
+        expected[28..32].copy_from_slice(&[0x0F, 0x06, 0x16, 0x26]);
+        expected[32..36].copy_from_slice(&[0x0F, 0x02, 0x12, 0x22]);
+        expected[36..40].copy_from_slice(&[0x0F, 0x03, 0x13, 0x23]);
+        expected[64..68].copy_from_slice(&[0x0F, 0x07, 0x17, 0x27]);
+        assert!(rom[16..].windows(96).any(|w| w == expected.as_slice()));
+
+        // Sets past the last one do not exist
+        let source = "SUB Main()\n  Palette.Load(3)\nEND SUB\n";
+        let err = compile(source, assets(vec![palette("BG0", Some("Cave"), [0; 4])]))
+            .expect_err("Set 3 accepted");
+        assert!(
+            err.contains("Palette.Load set 3 is out of range (0-1)"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_palette_buffer_uploaded_by_nmi() {
+        let asm = generate("SUB Main()\n  Palette.Set(17, $16)\nEND SUB\n");
+        // Startup fills the base colors and the shown buffer at full level
+        assert!(asm.contains("  STA $2007\n  STA $70, X\n  STA $50, X\n  INX"));
+        assert!(asm.contains("  BNE LoadPalLoop\n  LDA #4\n  STA $92"));
+
+        // The upload happens first in the vblank, stepping after the flushes
+        let nmi = &asm[asm.find("TrampolineNMI:").unwrap()..];
+        let upload = nmi.find("JSR Palette_Flush").expect("No palette upload");
+        let drain = nmi.find("JSR PpuQueue_Flush").unwrap();
+        let step = nmi.find("JSR Palette_Update").expect("No palette step");
+        assert!(upload < drain && drain < step);
+        assert!(step < nmi.find("JSR Sound_Update").unwrap());
+
+        let flush = &asm[asm.find("Palette_Flush:").unwrap()..];
+        assert!(flush
+            .contains("  LDA #$3F\n  STA $2006\n  LDA #$00\n  STA $2006\n  LDA $50\n  STA $2007"));
+        assert!(flush.contains("  LDA $6F\n  STA $2007\n"));
+        assert!(flush.contains("  LDA #16\n  STA $91\n  RTS"));
+
+        assert!(asm.contains("  AND #$1F\n  TAX\n  PLA\n  JSR Runtime_Palette_Set"));
+    }
+
+    #[test]
+    fn test_palette_fade_and_cycle_calls() {
+        let source = "SUB Main()\n  Palette.FadeOut(32)\n  Palette.FadeIn(8)\n  Palette.Cycle(1, 1, 3, 6)\nEND SUB\n";
+        let asm = generate(source);
+        assert!(asm.contains("  LDA #$20\n  LDX #$00\n  LDX #$FF\n  JSR Runtime_Palette_Fade"));
+        assert!(asm.contains("  LDX #$01\n  JSR Runtime_Palette_Fade"));
+        // Entry = palette * 4 + start, then length and frames per step
+        assert!(asm.contains("  ASL\n  ASL\n  PHA"));
+        assert!(asm.contains("  STA $00\n  TXA\n  JSR Runtime_Palette_Cycle"));
+
+        // Shading darkens a row per missing level and bottoms out at black
+        let shade = &asm[asm.find("Palette_Shade:").unwrap()..];
+        assert!(shade.contains("  SEC\n  SBC #$10\n  BCC Palette_Black"));
+    }
+
+    #[test]
+    fn test_palette_call_errors() {
+        let cases = [
+            (
+                "Palette.Set(32, 0)",
+                "Palette.Set entry 32 is out of range (0-31)",
+            ),
+            (
+                "Palette.Set(0, 64)",
+                "Palette.Set color 64 is out of range (0-63)",
+            ),
+            (
+                "Palette.Set(0)",
+                "Palette.Set expects 2 arguments (entry, color)",
+            ),
+            (
+                "Palette.FadeIn()",
+                "Palette.FadeIn expects 1 argument (frames)",
+            ),
+            (
+                "Palette.Cycle(8, 0, 2, 4)",
+                "Palette.Cycle palette 8 is out of range (0-7)",
+            ),
+            (
+                "Palette.Cycle(0, 2, 3, 4)",
+                "Palette.Cycle range 2..4 runs past the palette's 4 colors",
+            ),
+            (
+                "Palette.Rotate(1)",
+                "Unknown Palette command 'Rotate' (Set, Load, FadeIn, FadeOut, Cycle)",
+            ),
+        ];
+        for (call, message) in cases {
+            let source = format!("SUB Main()\n  {}\nEND SUB\n", call);
+            let errors = analyze(&source)
+                .err()
+                .unwrap_or_else(|| panic!("{} accepted", call));
+            assert!(errors.iter().any(|e| e.contains(message)), "{:?}", errors);
+        }
+        assert!(analyze("SUB Main()\n  Palette.Cycle(7, 1, 3, 0)\nEND SUB\n").is_ok());
+    }
+}
@@ -0,0 +1,154 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use crate::common::{check, compile_with, parse};
+    use swissarmynes::compiler::ast::TopLevel;
+    use swissarmynes::compiler::password::PasswordFormat;
+    use tetanes_core::control_deck::ControlDeck;
+
+    const GAME: &str = r#"
+DIM level AS BYTE
+DIM lives AS BYTE
+DIM score AS WORD
+DIM code AS STRING
+PASSWORD
+    level AS 5
+    lives AS 3
+    score AS 14
+    KEY 77
+END PASSWORD
+SUB Main()
+    code = Password.Encode()
+    IF Password.Decode(code) THEN
+        lives = 3
+    END IF
+END SUB
+"#;
+
+    // Encodes the fields, clears them, then decodes the stored password
+    const ROUND_TRIP: &str = r#"
+DIM level AS BYTE
+DIM lives AS BYTE
+DIM score AS WORD
+DIM code AS STRING
+DIM done AS BYTE
+PASSWORD
+    level AS 5
+    lives AS 3
+    score AS 14
+    KEY 77
+END PASSWORD
+SUB Main()
+    level = 13
+    lives = 5
+    score = 9999
+    code = Password.Encode()
+    level = 0
+    lives = 0
+    score = 0
+    IF Password.Decode(code) THEN
+        done = 1
+    END IF
+END SUB
+"#;
+
+    fn format() -> PasswordFormat {
+        PasswordFormat::from_program(&parse(GAME)).unwrap().unwrap()
+    }
+
+    #[test]
+    fn test_parse_password_block() {
+        let program = parse(GAME);
+        assert_eq!(
+            program.declarations[4],
+            TopLevel::Password(
+                vec![
+                    ("level".to_string(), 5),
+                    ("lives".to_string(), 3),
+                    ("score".to_string(), 14)
+                ],
+                None,
+                Some(77)
+            )
+        );
+        let format = format();
+        // 22 bits -> 3 payload bytes + checksum = 32 bits of 5-bit characters
+        assert_eq!(format.byte_len(), 4);
+        assert_eq!(format.length(), 7);
+    }
+
+    #[test]
+    fn test_host_encode_decode() {
+        let format = format();
+        let password = format.encode(&[13, 5, 9999]).unwrap();
+        assert_eq!(password.len(), 7);
+        assert_eq!(format.decode(&password).unwrap(), vec![13, 5, 9999]);
+        // Every field feeds the checksum, which is obfuscated first
+        let other = format.encode(&[13, 5, 9998]).unwrap();
+        assert_ne!(password.as_bytes()[0], other.as_bytes()[0]);
+
+        assert!(format.encode(&[32, 0, 0]).is_err());
+        assert!(format.decode("AAAA").is_err());
+        assert!(format.decode("IIIIIII").is_err());
+        let zero = format.encode(&[0, 0, 0]).unwrap();
+        let mut tampered = zero.into_bytes();
+        tampered[2] = b'A';
+        let tampered = String::from_utf8(tampered).unwrap();
+        assert_eq!(
+            format.decode(&tampered),
+            Err("Invalid password".to_string())
+        );
+
+        let custom =
+            PasswordFormat::new(vec![("x".to_string(), 8)], Some("0123456789ABCDEF"), None)
+                .unwrap();
+        let password = custom.encode(&[200]).unwrap();
+        assert!(password.bytes().all(|c| c.is_ascii_hexdigit()));
+        assert_eq!(custom.decode(&password).unwrap(), vec![200]);
+        assert!(PasswordFormat::new(vec![("x".to_string(), 8)], Some("ABC"), None).is_err());
+    }
+
+    #[test]
+    fn test_password_validation() {
+        assert!(check(GAME).is_ok());
+
+        let source = "DIM x AS BYTE\nPASSWORD\n  x AS 9\nEND PASSWORD\n";
+        let err = check(source).unwrap_err();
+        assert!(err[0].contains("holds 8 bits but uses 9"), "{:?}", err);
+
+        let source = "PASSWORD\n  missing AS 4\nEND PASSWORD\n";
+        let err = check(source).unwrap_err();
+        assert!(err[0].contains("not a global variable"), "{:?}", err);
+
+        let source = "DIM s AS STRING\nSUB Main()\n  s = Password.Encode()\nEND SUB\n";
+        let err = check(source).unwrap_err();
+        assert!(
+            err[0].contains("requires a PASSWORD declaration"),
+            "{:?}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_password_runtime() {
+        // Globals from $05C0: level, lives, score, code, done
+        let rom = compile_with(ROUND_TRIP, None, None).expect("Compile failed");
+        let mut deck = ControlDeck::new();
+        deck.load_rom("game.nes", &mut &rom[..])
+            .expect("Load failed");
+        for _ in 0..5 {
+            deck.clock_frame().expect("Emulation failed");
+        }
+        let ram = deck.wram();
+        assert_eq!(ram[0x5C6], 1, "the password did not decode");
+        assert_eq!(ram[0x5C0], 13);
+        assert_eq!(ram[0x5C1], 5);
+        assert_eq!(u16::from_le_bytes([ram[0x5C2], ram[0x5C3]]), 9999);
+
+        // The STRING holds a pointer to the host-encoded password
+        let text = u16::from_le_bytes([ram[0x5C4], ram[0x5C5]]) as usize;
+        let expected = format().encode(&[13, 5, 9999]).unwrap();
+        assert_eq!(&ram[text..text + expected.len()], expected.as_bytes());
+    }
+}
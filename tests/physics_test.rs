@@ -0,0 +1,124 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use crate::common::{analyze, compile, generate_with, layout, nametable};
+    use swissarmynes::compiler::collision::{self, ONE_WAY, SLOPE_SHIFT, SOLID};
+    use swissarmynes::compiler::linker::SECTION_COLLISION;
+    use swissarmynes::server::project::ProjectAssets;
+
+    fn source(body: &str) -> String {
+        format!(
+            "DIM player AS Body\nDIM c AS BYTE\nDIM vx AS INT\nSUB Main()\n{}\nEND SUB\n",
+            body
+        )
+    }
+
+    fn generate(source: &str, collision: bool) -> Result<String, String> {
+        generate_with(source, |codegen| {
+            if collision {
+                codegen.set_layout(layout(&[(SECTION_COLLISION, 256)]));
+            }
+        })
+    }
+
+    #[test]
+    fn test_slope_heights() {
+        let heights = |shape| {
+            (0..8)
+                .map(|x| collision::slope_height(shape, x))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(heights(1), [1, 2, 3, 4, 5, 6, 7, 8]);
+        assert_eq!(heights(2), [8, 7, 6, 5, 4, 3, 2, 1]);
+        // 22.5 degree pairs meet where one tile ends and the next begins
+        assert_eq!(heights(3), [1, 1, 2, 2, 3, 3, 4, 4]);
+        assert_eq!(heights(4), [5, 5, 6, 6, 7, 7, 8, 8]);
+        assert_eq!(heights(5), [8, 8, 7, 7, 6, 6, 5, 5]);
+        assert_eq!(heights(6), [4, 4, 3, 3, 2, 2, 1, 1]);
+        assert_eq!(collision::slope_height(0, 3), 0);
+    }
+
+    #[test]
+    fn test_move_and_collide_codegen() {
+        let src = source("  c = Physics.MoveAndCollide(player, vx, 24)\n  c = c AND Contact.Floor");
+        let asm = generate(&src, true).expect("Codegen failed");
+        // dx/dy go to the working copy, the Body by address ($05C0)
+        assert!(asm.contains("  STA $03B9\n  PLA\n  STA $03B8\n  LDA #$C0\n  STA $0E\n  LDA #$05\n  STA $0F\n  JSR Runtime_Physics_Move"));
+        assert!(asm.contains("Runtime_Physics_Move:"));
+        assert!(asm.contains("Runtime_Collision_Flags:"));
+        // Velocities are 1/16 pixel: arithmetic shift by 4 plus the sub-pixel carry
+        assert!(asm.contains("  CMP #$80\n  ROR\n  CMP #$80\n  ROR\n  CMP #$80\n  ROR\n  CMP #$80\n  ROR\n  CPY #16\n  ADC #0"));
+        // One-way tiles only stop a body landing from above
+        assert!(asm.contains(&format!("  AND #{}\n  BEQ Physics_MoveYDone", ONE_WAY)));
+        assert!(asm.contains(&format!("  AND #{}\n  BNE Physics_Floor", SOLID)));
+        // Slope shape from bits 5-7; 22.5 degree slopes halve the column
+        assert!(asm.contains(&format!(
+            "{}  BEQ Physics_LandNone",
+            "  LSR\n".repeat(SLOPE_SHIFT as usize)
+        )));
+        assert!(asm.contains("  CPX #3\n  BCC Physics_Slope45\n  LSR"));
+        // Only the position and sub-pixels are written back
+        assert!(asm.contains("Physics_Done:\n  LDY #5"));
+
+        // As a statement the contacts are dropped
+        let asm = generate(&source("  Physics.MoveAndCollide(player, 0, vx)"), true)
+            .expect("Codegen failed");
+        assert!(asm.contains("  JSR Runtime_Physics_Move"));
+
+        let asm = generate(&source("  c = 1"), true).expect("Codegen failed");
+        assert!(!asm.contains("Runtime_Physics_Move:"));
+
+        let err = generate(&src, false).unwrap_err();
+        assert!(
+            err.contains("Physics.MoveAndCollide requires the collision table"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_physics_call_errors() {
+        let cases = [
+            (
+                "c = Physics.MoveAndCollide(player, 1)",
+                "Physics.MoveAndCollide expects 3 arguments (body, dx, dy)",
+            ),
+            (
+                "c = Physics.MoveAndCollide(c, 1, 2)",
+                "Physics.MoveAndCollide first argument must be Body",
+            ),
+            ("Physics.Jump(player)", "Unknown Physics command 'Jump'"),
+        ];
+        for (line, expected) in cases {
+            let errors = analyze(&source(&format!("  {}", line)))
+                .err()
+                .unwrap_or_default();
+            assert!(
+                errors.iter().any(|e| e.contains(expected)),
+                "{}: {:?}",
+                line,
+                errors
+            );
+        }
+        let analyzer = analyze(&source(
+            "  player.x = 40\n  player.width = 8\n  player.height = 16\n  c = Physics.MoveAndCollide(player, vx, 16)",
+        ))
+        .unwrap();
+        assert!(analyzer.reads_collision());
+    }
+
+    #[test]
+    fn test_physics_rom() {
+        let assets = ProjectAssets {
+            nametables: vec![nametable("Level", 0, None)],
+            tile_collision: vec![0, SOLID, ONE_WAY, 1 << SLOPE_SHIFT],
+            ..Default::default()
+        };
+        let src = source("  c = Physics.MoveAndCollide(player, vx, 16)");
+        let rom = compile(&src, assets).expect("Compile failed");
+        let prg = &rom[16..];
+        let table = [0, SOLID, ONE_WAY, 1 << SLOPE_SHIFT, 0, 0, 0, 0];
+        assert!(prg.windows(table.len()).any(|w| w == table));
+    }
+}
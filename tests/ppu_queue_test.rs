@@ -0,0 +1,132 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use crate::common::{analyze, generate, generate_with};
+
+    fn routine<'a>(asm: &'a str, label: &str) -> &'a str {
+        let start = asm.find(&format!("{}:", label)).expect("Routine missing");
+        let end = asm[start..].find("  RTS").expect("Routine has no RTS");
+        &asm[start..start + end]
+    }
+
+    #[test]
+    fn test_nmi_drains_queue_within_budget() {
+        let asm = generate("SUB Main()\nEND SUB\n");
+        let nmi = &asm[asm.find("TrampolineNMI:").unwrap()..];
+        let flush = nmi
+            .find("JSR PpuQueue_Flush")
+            .expect("NMI does not drain the queue");
+        assert!(flush < nmi.find("JSR Sound_Update").unwrap());
+
+        let drain = routine(&asm, "PpuQueue_Flush");
+        // The palette upload earlier in the vblank comes out of the budget
+        assert!(drain.contains("  LDA #80\n  SEC\n  SBC $91\n  STA $0387\n  LDX $0380"));
+        // Entries that would exceed the remaining budget wait for next frame
+        assert!(drain.contains("  CMP $0388\n  BCC PpuQueue_Done"));
+        // Zero control byte wraps the reader, bit 7 picks +32, bit 6 fills
+        assert!(drain.contains("  LDX #0\n  JMP PpuQueue_Next"));
+        assert!(drain.contains("  BPL PpuQueue_Inc\n  ORA #$04"));
+        assert!(drain.contains("  AND #$40\n  BNE PpuQueue_Fill"));
+        assert!(asm.contains("PpuQueue_Done:\n  STX $0380\n  LDA $F8\n  STA $2000"));
+    }
+
+    #[test]
+    fn test_overflow_waits_or_drains() {
+        let asm = generate("SUB Main()\nEND SUB\n");
+        let reserve = &asm[asm.find("PpuQueue_Reserve:").unwrap()..];
+        let reserve = &reserve[..reserve.find("PpuQueue_Commit:").unwrap()];
+        assert!(reserve.contains("PpuQueue_Full:\n  LDA #$FF\n  STA $0382"));
+        // NMI off or inside the NMI handler: drain now instead of waiting
+        assert!(reserve.contains("  LDA $F8\n  BPL PpuQueue_Drain\n  LDA $0383"));
+        assert!(reserve.contains("PpuQueue_Wait:\n  LDA $0380\n  CMP $0386"));
+        assert!(reserve.contains("  CMP #$80\n  BCC PpuQueue_Fits"));
+        assert!(asm.contains("  INC $0383\n  JSR CallUserNMI\n  DEC $0383"));
+    }
+
+    #[test]
+    fn test_stdlib_calls_use_queue() {
+        let asm = generate(
+            "
+            DIM col(30) AS BYTE
+            DIM map(60) AS BYTE
+            DIM i AS BYTE
+            SUB Main()
+                Scroll.LoadColumn(256, col)
+                Scroll.LoadColumn(264, col)
+                Scroll.LoadRow(8, map(i * 30))
+                Text.Print(2, 3, \"HI\")
+            END SUB
+            ",
+        );
+        // Both columns are queued; the array's address is passed
+        assert_eq!(
+            asm.matches("JSR Runtime_Scroll_LoadColumn\n  PLA\n  PLA")
+                .count(),
+            2
+        );
+        assert!(asm.contains("  LDA #$C0\n  LDX #$05\n  JSR Runtime_Scroll_LoadColumn"));
+        // An element is addressed at runtime
+        assert!(asm.contains("  LDA $02\n  LDX $03\n  JSR Runtime_Scroll_LoadRow"));
+
+        let text = routine(&asm, "Runtime_Text_Print");
+        assert!(!text.contains("$2006") && !text.contains("$2007"));
+        assert!(text.contains("  CPX #32\n  BNE Text_Count"));
+        assert!(text.contains("  JSR PpuQueue_Reserve"));
+        assert!(text.contains("  JSR PpuQueue_Commit"));
+
+        for label in ["Runtime_Scroll_LoadColumn", "Runtime_Scroll_LoadRow"] {
+            let body = routine(&asm, label);
+            assert!(!body.contains("$2007"));
+            assert!(body.contains("  STA $0783, X"));
+        }
+        assert!(routine(&asm, "Runtime_Scroll_LoadColumn").contains("  LDA #$9E"));
+    }
+
+    #[test]
+    fn test_fill_and_overflow() {
+        let asm = generate(
+            "
+            DIM full AS BOOL
+            SUB Main()
+                PPU.Fill($2300, 7, 100)
+                full = PPU.Overflow()
+            END SUB
+            ",
+        );
+        assert!(asm.contains("  STA $00\n  JSR Runtime_PPU_Fill"));
+        let fill = routine(&asm, "Runtime_PPU_Fill");
+        assert!(fill.contains("  LDA #63\nPpuFill_Len:\n  STA $04\n  ORA #$40"));
+        assert!(asm.contains("  LDA $0382\n  LDX #0\n  STX $0382\n  STA $05C0"));
+
+        let errors = analyze("SUB Main()\n  PPU.Fill($2000, 0)\nEND SUB\n")
+            .err()
+            .unwrap();
+        assert_eq!(
+            errors,
+            vec!["PPU.Fill expects 3 arguments (address, value, count)"]
+        );
+        let errors = analyze("DIM b AS BOOL\nSUB Main()\n  b = PPU.Full()\nEND SUB\n")
+            .err()
+            .unwrap();
+        assert_eq!(errors, vec!["Unknown PPU function 'Full'"]);
+        let errors = analyze("DIM b AS BOOL\nSUB Main()\n  b = PPU.Overflow(1)\nEND SUB\n")
+            .err()
+            .unwrap();
+        assert_eq!(errors, vec!["PPU.Overflow expects no arguments"]);
+    }
+
+    #[test]
+    fn test_queue_ram_only_taken_when_used() {
+        // 500 bytes of globals run from $05C0 into the ring at $0780
+        let quiet = "DIM buf(500) AS BYTE\nSUB Main()\nEND SUB\n";
+        assert!(generate(quiet).contains("; buf @ $05C0"));
+
+        let source = "DIM buf(500) AS BYTE\nSUB Main()\n  Text.Print(1, 1, \"HI\")\nEND SUB\n";
+        let err = generate_with(source, |_| {}).unwrap_err();
+        assert_eq!(
+            err,
+            "RAM overflow: variables end at $07B3, but the PPU queue needs $0780-$07FF"
+        );
+    }
+}
@@ -49,7 +49,7 @@ mod tests {
             name: "NT1".to_string(),
             data: vec![0; 960],
             attrs: attrs.clone(),
-            metatile_grid: vec![],
+            ..Default::default()
         });
 
         assert!(save_project(name, None, Some(&assets)).is_ok());
@@ -101,6 +101,7 @@ mod tests {
             name: "Grass".to_string(),
             tiles: [0, 1, 2, 3],
             attr: 1,
+            collision: 0,
         });
 
         assert!(save_project(name, None, Some(&assets)).is_ok());
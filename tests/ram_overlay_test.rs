@@ -0,0 +1,119 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use crate::common::{listing, listing_for, parse};
+    use swissarmynes::compiler::callgraph::CallGraph;
+
+    #[test]
+    fn test_sibling_subs_share_frames() {
+        let source = r#"
+        DIM g AS BYTE
+        SUB A(x AS WORD)
+            g = 1
+        END SUB
+        SUB B(y AS WORD)
+            g = 2
+        END SUB
+        SUB Main()
+            A(1)
+            B(2)
+        END SUB
+        "#;
+        let asm = listing(source);
+        assert!(asm.iter().any(|l| l == "; A.x @ $05C1"));
+        assert!(asm.iter().any(|l| l == "; B.y @ $05C1"));
+        assert!(asm
+            .iter()
+            .any(|l| l == "; B frame: 2 bytes @ $05C1, overlay saves 2 bytes"));
+        assert!(asm
+            .iter()
+            .any(|l| l == "; SUB frames: 2 bytes, 2 bytes saved by overlays"));
+    }
+
+    #[test]
+    fn test_nested_and_interrupt_subs_do_not_share() {
+        let source = r#"
+        SUB Inner(a AS BYTE)
+        END SUB
+        SUB Outer(b AS BYTE)
+            Inner(b)
+        END SUB
+        SUB Tick(c AS BYTE)
+        END SUB
+        INTERRUPT NMI()
+            Tick(1)
+        END INTERRUPT
+        SUB Main()
+            Outer(1)
+        END SUB
+        "#;
+        let asm = listing(source);
+        assert!(asm.iter().any(|l| l == "; Inner.a @ $05C0"));
+        assert!(asm.iter().any(|l| l == "; Outer.b @ $05C1"));
+        assert!(asm.iter().any(|l| l == "; Tick.c @ $05C2"));
+    }
+
+    #[test]
+    fn test_call_in_arguments_keeps_frames_apart() {
+        let program = parse(
+            r#"
+        SUB Value(v AS BYTE)
+            RETURN v
+        END SUB
+        SUB Use(a AS BYTE, b AS BYTE)
+        END SUB
+        SUB Main()
+            Use(1, Value(2))
+        END SUB
+        "#,
+        );
+        let graph = CallGraph::build(&program);
+        assert!(!graph.reaches("Use", "Value"));
+        assert!(graph.may_overlap("Use", "Value"));
+    }
+
+    #[test]
+    fn test_call_graph_roots() {
+        let program = parse(
+            r#"
+        SUB Helper()
+        END SUB
+        SUB Unused()
+        END SUB
+        INTERRUPT IRQ()
+            Helper()
+        END INTERRUPT
+        SUB Main()
+        END SUB
+        "#,
+        );
+        let graph = CallGraph::build(&program);
+        assert_eq!(graph.roots(), &["Main".to_string(), "IRQ".to_string()]);
+        assert_eq!(graph.callees("IRQ"), vec!["Helper".to_string()]);
+        // Unreachable routines never share storage
+        assert!(graph.may_overlap("Unused", "Helper"));
+        assert!(graph.may_overlap("Main", "Helper"));
+    }
+
+    #[test]
+    fn test_overlays_avoid_ram_overflow() {
+        // 500 bytes of globals leave 76 bytes; 30 SUBs with 4-byte frames need 120.
+        let mut source = String::from("DIM buf(500) AS BYTE\n");
+        let mut main = String::from("SUB Main()\n");
+        for i in 0..30 {
+            source.push_str(&format!(
+                "SUB Work{}(a AS WORD, b AS WORD)\n    buf(0) = 1\nEND SUB\n",
+                i
+            ));
+            main.push_str(&format!("    Work{}(1, 2)\n", i));
+        }
+        main.push_str("END SUB\n");
+        source.push_str(&main);
+
+        let asm = listing_for(&parse(&source), |_| {}).expect("Overlays should fit the frames");
+        assert!(asm
+            .iter()
+            .any(|l| l == "; SUB frames: 4 bytes, 116 bytes saved by overlays"));
+    }
+}
@@ -0,0 +1,87 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use crate::common::{check, compile_with, generate, parse};
+    use swissarmynes::compiler::ast::{DataType, TopLevel};
+    use swissarmynes::compiler::lexer::Lexer;
+    use swissarmynes::compiler::parser::Parser;
+
+    const SAVE: &str = r#"
+MAPPER MMC1
+DIM SAVE highscores(10) AS WORD
+DIM SAVE level AS BYTE
+DIM save AS BYTE
+SUB Main()
+    IF Save.Verify() = 0 THEN
+        level = 1
+        Save.Commit()
+    END IF
+END SUB
+"#;
+
+    #[test]
+    fn test_parse_save_storage_class() {
+        let program = parse(SAVE);
+        assert_eq!(
+            program.declarations[1],
+            TopLevel::SaveDim(
+                "highscores".to_string(),
+                DataType::Array(Box::new(DataType::Word), 10)
+            )
+        );
+        // `save` is still usable as an ordinary variable name
+        assert_eq!(
+            program.declarations[3],
+            TopLevel::Dim("save".to_string(), DataType::Byte, None)
+        );
+        let tokens = Lexer::new("DIM SAVE x AS BYTE = 3\n").tokenize().unwrap();
+        assert!(Parser::new(tokens).parse().is_err());
+    }
+
+    #[test]
+    fn test_save_validation() {
+        assert!(check(SAVE).is_ok());
+
+        let err = check("DIM SAVE x AS BYTE\nSUB Main()\nEND SUB\n").unwrap_err();
+        assert!(
+            err[0].contains("requires a mapper with PRG-RAM"),
+            "{:?}",
+            err
+        );
+
+        let err = check("MAPPER MMC3\nSUB Main()\n  Save.Commit()\nEND SUB\n").unwrap_err();
+        assert!(
+            err[0].contains("requires at least one DIM SAVE"),
+            "{:?}",
+            err
+        );
+
+        let err = check("MAPPER MMC3\nDIM SAVE s AS STRING\nSUB Main()\nEND SUB\n").unwrap_err();
+        assert!(err[0].contains("cannot be a STRING"), "{:?}", err);
+    }
+
+    #[test]
+    fn test_save_region_and_checksum() {
+        let text = generate(SAVE);
+        assert!(text.contains("; highscores @ $6000 (SAVE)"));
+        assert!(text.contains("; level @ $6014 (SAVE)"));
+        // The checksum stops at the end of the SAVE variables
+        assert!(text.contains("  CMP #$60\n  BNE SaveSum_Byte\n  CPY #$15"));
+        assert!(text.contains("Runtime_Save_Commit:\n  JSR Runtime_Save_Checksum"));
+        assert!(text.contains("  STA $7FFE\n  LDA $01\n  STA $7FFF"));
+        assert!(text.contains("  JSR Runtime_Save_Verify"));
+    }
+
+    #[test]
+    fn test_save_sets_battery_bit() {
+        let rom = compile_with(SAVE, None, None).expect("Compile failed");
+        assert_eq!(rom[6] & 0x02, 0x02);
+        // 8 KB of PRG-RAM, kept by the battery bit
+        assert_eq!(rom[10], 0x07);
+
+        let plain = "MAPPER MMC1\nSUB Main()\nEND SUB\n";
+        let rom = compile_with(plain, None, None).expect("Compile failed");
+        assert_eq!(rom[6] & 0x02, 0);
+    }
+}
@@ -0,0 +1,183 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use crate::common::{analyze, compile, generate_with, nametable};
+    use swissarmynes::compiler::compress::{self, Compression};
+    use swissarmynes::compiler::linker::{
+        Linker, Section, SECTION_SCREEN_BANKS, SECTION_SCREEN_TABLE,
+    };
+    use swissarmynes::compiler::mapper::Mapper;
+    use swissarmynes::server::project::{Nametable, ProjectAssets};
+
+    fn assets(nametables: Vec<Nametable>) -> ProjectAssets {
+        ProjectAssets {
+            nametables,
+            ..Default::default()
+        }
+    }
+
+    fn generate(source: &str, screens: Vec<Compression>) -> Result<String, String> {
+        generate_with(source, |codegen| codegen.set_screens(screens))
+    }
+
+    #[test]
+    fn test_every_nametable_in_rom() {
+        let nametables = vec![
+            nametable("Title", 0x10, Some(Compression::None)),
+            nametable("Level 1", 0x40, Some(Compression::None)),
+            nametable("Level 2", 0x80, Some(Compression::Rle)),
+        ];
+        let source = "SUB Main()\n  Screen.Load(SCREEN_LEVEL_2)\nEND SUB\n";
+        let rom = compile(source, assets(nametables.clone())).expect("Compile failed");
+        let prg = &rom[16..];
+        let has = |data: &[u8]| prg.windows(data.len()).any(|w| w == data);
+        assert!(has(&nametables[0].to_bytes()));
+        assert!(has(&nametables[1].to_bytes()));
+        assert!(has(&compress::rle_encode(&nametables[2].to_bytes())));
+
+        // Screen table: address and format per id, screen 0 at the nametable
+        let table: Vec<u8> = prg
+            .windows(9)
+            .find(|w| w[2] == 0 && w[5] == 0 && w[8] == 1 && w[0..2] != w[3..5])
+            .expect("screen table")
+            .to_vec();
+        let addr = |i: usize| (table[i * 3] as usize | (table[i * 3 + 1] as usize) << 8) - 0x8000;
+        for (i, nt) in nametables.iter().enumerate().take(2) {
+            assert_eq!(&prg[addr(i)..addr(i) + 1024], nt.to_bytes().as_slice());
+        }
+        let rle = compress::rle_encode(&nametables[2].to_bytes());
+        assert_eq!(&prg[addr(2)..addr(2) + rle.len()], rle.as_slice());
+    }
+
+    #[test]
+    fn test_screen_constants() {
+        assert_eq!(
+            nametable("Level 1", 0, None).constant_name(),
+            "SCREEN_LEVEL_1"
+        );
+        assert_eq!(
+            nametable("boss-room", 0, None).constant_name(),
+            "SCREEN_BOSS_ROOM"
+        );
+
+        let source = "DIM S AS BYTE\nSUB Main()\n  S = SCREEN_BOSS_ROOM\n  \
+                      Screen.LoadTo(SCREEN_TITLE, 1)\nEND SUB\n";
+        let nametables = vec![nametable("Title", 1, None), nametable("boss-room", 2, None)];
+        assert!(compile(source, assets(nametables)).is_ok());
+
+        let too_many = (0..86)
+            .map(|i| nametable(&format!("S{}", i), 0, None))
+            .collect();
+        let err = compile("SUB Main()\nEND SUB\n", assets(too_many)).unwrap_err();
+        assert!(err.contains("limit 85"), "{}", err);
+    }
+
+    #[test]
+    fn test_screen_load_codegen() {
+        let source =
+            "SUB Main()\n  PPU.Mask($1E)\n  Screen.Load(1)\n  Screen.LoadTo(2, 3)\nEND SUB\n";
+        let screens = vec![Compression::None, Compression::Lz, Compression::Rle];
+        let text = generate(source, screens).expect("Codegen failed");
+        assert!(text.contains("  STA $F9\n  STA $2001"));
+        assert!(text.contains("  LDA #$01\n  LDX #$00\n  LDX #0\n  JSR Runtime_Screen_Load"));
+        assert!(
+            text.contains("  PHA\n  LDA #$03\n  LDX #$00\n  TAX\n  PLA\n  JSR Runtime_Screen_Load")
+        );
+        // Bounds check, NMI off during the upload, shadows restored after
+        assert!(text.contains("Runtime_Screen_Load:\n  CMP #3\n  BCS Screen_Load_Done"));
+        assert!(text.contains("  LDA $F8\n  AND #$7B\n  STA $2000"));
+        assert!(text.contains("  LDA $F9\n  STA $2001\nScreen_Load_Done:"));
+        assert!(text.contains("\nRuntime_Lz_ToPpu:\n"));
+        assert!(text.contains("\nRuntime_Rle_ToPpu:\n"));
+
+        let asm = generate("SUB Main()\nEND SUB\n", vec![Compression::Lz]).unwrap();
+        assert!(!asm.contains("\nRuntime_Screen_Load:\n"));
+        assert!(!asm.contains("\nRuntime_Lz_ToPpu:\n"));
+    }
+
+    #[test]
+    fn test_screen_call_errors() {
+        let errors = analyze("SUB Main()\n  Screen.Load()\nEND SUB\n")
+            .err()
+            .unwrap();
+        assert!(
+            errors[0].contains("Screen.Load expects 1 argument"),
+            "{:?}",
+            errors
+        );
+        let errors = analyze("SUB Main()\n  Screen.LoadTo(0, 4)\nEND SUB\n")
+            .err()
+            .unwrap();
+        assert!(errors[0].contains("out of range (0-3)"), "{:?}", errors);
+        let errors = analyze("SUB Main()\n  Screen.Show(0)\nEND SUB\n")
+            .err()
+            .unwrap();
+        assert!(errors[0].contains("Unknown Screen command"), "{:?}", errors);
+
+        let err = generate("SUB Main()\n  Screen.Load(0)\nEND SUB\n", vec![]).unwrap_err();
+        assert!(err.contains("requires the screen table"), "{}", err);
+    }
+
+    #[test]
+    fn test_banked_screen() {
+        let mut level = nametable("Level 1", 0x40, Some(Compression::None));
+        level.bank = Some(2);
+        let nametables = vec![nametable("Title", 0x10, Some(Compression::None)), level];
+        let source = "MAPPER MMC1\nSUB Main()\n  Screen.Load(SCREEN_LEVEL_1)\nEND SUB\n";
+        let rom = compile(source, assets(nametables.clone())).expect("Compile failed");
+        // Bank 2 of the MMC1 image holds the screen; the fixed bank is last
+        let bank = &rom[16 + 2 * 0x4000..16 + 3 * 0x4000];
+        let fixed = &rom[rom.len() - 0x2000 - 0x4000..rom.len() - 0x2000];
+        let at = bank
+            .windows(1024)
+            .position(|w| w == nametables[1].to_bytes().as_slice())
+            .expect("screen in bank 2");
+        let addr = 0x8000 + at as u16;
+        // The screen table points into the window, the bank table at bank 2
+        let entry = [addr as u8, (addr >> 8) as u8, 0];
+        assert!(fixed.windows(3).any(|w| w == entry));
+        assert!(fixed.windows(2).any(|w| w == [0xFF, 0x02]));
+
+        let err = compile(source, {
+            let mut nametables = nametables.clone();
+            nametables[0].bank = Some(1);
+            assets(nametables)
+        })
+        .unwrap_err();
+        assert!(err.contains("'Title' is the startup screen"), "{}", err);
+        let err = compile(
+            "SUB Main()\n  Screen.Load(SCREEN_LEVEL_1)\nEND SUB\n",
+            assets(nametables),
+        )
+        .unwrap_err();
+        assert!(err.contains("requires a bank-switching mapper"), "{}", err);
+    }
+
+    #[test]
+    fn test_banked_screen_loader() {
+        let source = "MAPPER MMC1\nSUB Main()\n  Screen.Load(1)\nEND SUB\n";
+        let mut linker = Linker::for_mapper(Mapper::Mmc1);
+        linker.add(Section::new(SECTION_SCREEN_TABLE, 6));
+        linker.add(Section::new(SECTION_SCREEN_BANKS, 2));
+        let layout = linker.link().expect("Link failed");
+        let banks = layout.addr(SECTION_SCREEN_BANKS);
+        let text = generate_with(source, |codegen| {
+            codegen.set_layout(layout);
+            codegen.set_screens(vec![Compression::None; 2]);
+        })
+        .expect("Codegen failed");
+        // The loader maps the screen's bank; its wrapper maps the caller's back
+        assert!(text.contains(
+            "Runtime_Screen_Load_Banked:\n  CMP #2\n  BCS Screen_Load_Done\n  JSR Screen_Bank\n"
+        ));
+        assert!(
+            text.contains("Runtime_Screen_Load:\n  PHA\n  TXA\n  PHA\n  LDA $1D\n  PHA\n  TSX\n")
+        );
+        assert!(text.contains("  JSR Runtime_Screen_Load\n"));
+        assert!(text.contains(&format!(
+            "Screen_Bank:\n  PHA\n  TAY\n  LDA ${:04X}, Y\n  BMI Screen_BankFixed\n  JSR MMC1_SetPRG\n",
+            banks
+        )));
+    }
+}
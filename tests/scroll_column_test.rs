@@ -37,12 +37,14 @@ mod tests {
 
         // Verify Runtime Helper
         assert!(asm_source.contains("Runtime_Scroll_LoadColumn:"));
-        assert!(asm_source.contains("STA $0381")); // Type
-        assert!(asm_source.contains("STA $0384, Y")); // Data Copy
+        assert!(asm_source.contains("LDA #$9E")); // Vertical, 30 tiles
+        assert!(asm_source.contains("JSR PpuQueue_Reserve"));
+        assert!(asm_source.contains("STA $0783, X")); // Data Copy
+        assert!(asm_source.contains("JSR PpuQueue_Commit"));
 
         // Verify NMI Processing
         assert!(asm_source.contains("TrampolineNMI:"));
-        assert!(asm_source.contains("LDA $0380")); // Check Flag
+        assert!(asm_source.contains("JSR PpuQueue_Flush"));
         assert!(asm_source.contains("STA $2006")); // Set Addr
         assert!(asm_source.contains("ORA #$04")); // Inc 32
         assert!(asm_source.contains("STA $2007")); // Write Data
@@ -34,12 +34,11 @@ mod tests {
         assert!(asm_source.contains("Runtime_Scroll_LoadRow:"));
         assert!(asm_source.contains("Scroll_RowBaseStore:"));
 
-        // Verify Header Writing (Type 1)
-        assert!(asm_source.contains("LDA #1"));
-        assert!(asm_source.contains("STA $0381")); // Type
+        // Verify Entry (32 tiles, horizontal)
+        assert!(asm_source.contains("LDA #32\n  JSR PpuQueue_Reserve"));
+        assert!(asm_source.contains("CPY #32")); // 32 tiles
 
-        // Verify NMI Processing (Row Loop)
-        assert!(asm_source.contains("VBlankBufferRowLoop:"));
-        assert!(asm_source.contains("CPX #32")); // 32 tiles
+        // Verify NMI Processing
+        assert!(asm_source.contains("PpuQueue_Copy:"));
     }
 }
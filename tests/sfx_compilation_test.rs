@@ -17,17 +17,9 @@ mod tests {
         };
 
         let assets = ProjectAssets {
-            chr_bank: vec![],
-            palettes: vec![],
-            nametables: vec![],
-            audio_tracks: vec![],
             envelopes: vec![], // No user envelopes
-            samples: vec![],
             sound_effects: vec![sfx1],
-            metatiles: vec![],
-            world: None,
-            metasprites: vec![],
-            animations: vec![],
+            ..Default::default()
         };
 
         // 1. Check Envelopes
@@ -110,17 +102,8 @@ mod tests {
         };
 
         let assets = ProjectAssets {
-            chr_bank: vec![],
-            palettes: vec![],
-            nametables: vec![],
-            audio_tracks: vec![],
-            envelopes: vec![],
-            samples: vec![],
             sound_effects: vec![sfx1],
-            metatiles: vec![],
-            world: None,
-            metasprites: vec![],
-            animations: vec![],
+            ..Default::default()
         };
 
         let env_blob = compile_envelopes(&Some(assets)).unwrap();
@@ -0,0 +1,67 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use crate::common::{analyze, compile, generate, rom_addr, sprite_table};
+    use swissarmynes::server::project::ProjectAssets;
+
+    const SPRITES: &str = "METASPRITE Hero
+  TILE -4, -16, $01, 0
+  TILE 4, -16, $02, 0
+  TILE 0, -8, $03, 1
+END METASPRITE
+ANIMATION Walk
+  FRAME Hero, 8
+  LOOP
+END ANIMATION
+";
+
+    fn source(body: &str) -> String {
+        format!(
+            "{}DIM anim AS AnimState\nDIM facing AS BYTE\nSUB Main()\n{}\nEND SUB\n",
+            SPRITES, body
+        )
+    }
+
+    #[test]
+    fn test_flip_sums_follow_boxes() {
+        let src = source("  Sprite.Draw(100, 100, Hero)");
+        // Boxes, then the flip sums x: -4 + 4 = 0, y: -16 + -8 = -24
+        let hero = [
+            3, 0xFC, 0xF0, 0x01, 0x00, 0x04, 0xF0, 0x02, 0x00, 0x00, 0xF8, 0x03, 0x01, 0, 0, 0, 0,
+            0, 0, 0, 0, 0x00, 0xE8,
+        ];
+        assert_eq!(sprite_table(&src)[..hero.len()], hero);
+        let rom = compile(&src, ProjectAssets::default()).expect("Compile failed");
+        assert!(rom_addr(&rom, &hero).is_some(), "Metasprite not in ROM");
+    }
+
+    #[test]
+    fn test_flipped_draw_codegen() {
+        let asm = generate(&source(
+            "  Sprite.Draw(100, 100, Hero)\n  Sprite.Draw(100, 100, Hero, Flip.H)\n  Animation.Draw(80, 60, anim, facing OR Flip.V)",
+        ));
+        assert!(asm.contains("  JSR Runtime_SpriteDraw\n"));
+        assert!(asm.contains(
+            "  LDA #$40\n  LDX #$00\n  AND #$C0\n  STA $07\n  JSR Runtime_SpriteDrawFlags"
+        ));
+        assert!(asm.contains("  AND #$C0\n  STA $07\n  JSR Runtime_Anim_DrawFlags"));
+        // Unflipped entry points clear the flags
+        assert!(asm.contains("Runtime_SpriteDraw:\n  LDA #0\n  STA $07\nRuntime_SpriteDrawFlags:"));
+        assert!(asm.contains("Runtime_Anim_Draw:\n  LDA #0\n  STA $07\nRuntime_Anim_DrawFlags:"));
+        assert!(asm.contains("  LDA ($02), Y\n  EOR $07\n  PHA"));
+    }
+
+    #[test]
+    fn test_flipped_draw_arity() {
+        let errors = analyze(&source("  Sprite.Draw(1, 2, Hero, Flip.H, 0)"))
+            .err()
+            .expect("Expected arity error");
+        assert!(errors[0].contains("Sprite.Draw expects 3 or 4 arguments"));
+
+        let errors = analyze(&source("  Animation.Draw(1, 2)"))
+            .err()
+            .expect("Expected arity error");
+        assert!(errors[0].contains("Animation.Draw expects 3 or 4 arguments"));
+    }
+}
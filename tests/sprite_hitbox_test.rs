@@ -0,0 +1,127 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use crate::common::{analyze, compile, generate, rom_addr, sprite_table};
+    use swissarmynes::server::project::{
+        Animation, AnimationFrame, Metasprite, ProjectAssets, SpriteBox, SpriteTile,
+    };
+
+    const SPRITES: &str = "METASPRITE Hero
+  TILE 0, 0, $10, 0
+  HITBOX 1, -2, 6, 14
+END METASPRITE
+METASPRITE Slime
+  TILE 0, 0, $20, 1
+  TILE 8, 0, $21, 1
+  HITBOX 0, 0, 16, 8
+  HURTBOX 2, 2, 12, 6
+END METASPRITE
+METASPRITE Coin
+  TILE 0, 0, $30, 2
+END METASPRITE
+ANIMATION HeroIdle
+  FRAME Hero, 8
+  LOOP
+END ANIMATION
+ANIMATION SlimeIdle
+  FRAME Slime, 8
+  LOOP
+END ANIMATION
+";
+
+    fn source(body: &str) -> String {
+        format!(
+            "{}DIM hero AS AnimState\nDIM slime AS AnimState\nDIM hit AS BOOL\nDIM x AS WORD\nSUB Main()\n{}\nEND SUB\n",
+            SPRITES, body
+        )
+    }
+
+    #[test]
+    fn test_metasprite_box_data() {
+        let table = sprite_table(&source("  hit = 0"));
+        // Hitbox, then hurtbox copied from it
+        assert_eq!(
+            table[..15],
+            [1, 0, 0, 0x10, 0, 1, 0xFE, 6, 0x0E, 1, 0xFE, 6, 0x0E, 0, 0]
+        );
+        assert_eq!(table[24..32], [0, 0, 16, 8, 2, 2, 12, 6]);
+        // No boxes: zero-sized, never overlaps
+        assert_eq!(
+            table[34..49],
+            [1, 0, 0, 0x30, 2, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]
+        );
+        let asm = generate(&source("  hit = 0"));
+        assert!(!asm.contains("Runtime_Collision_Sprites:"));
+    }
+
+    #[test]
+    fn test_collision_sprites_codegen() {
+        let asm = generate(&source(
+            "  Animation.Play(hero, HeroIdle)\n  Animation.Play(slime, SlimeIdle)\n  hit = Collision.Sprites(hero, x, 100, slime, 40, 100)",
+        ));
+        assert!(asm.contains("  JSR Runtime_Collision_Sprites\n  TSX\n  TXA\n  CLC\n  ADC #8"));
+        assert!(asm.contains("Runtime_Collision_Sprites:"));
+        assert!(asm.contains("Collision_SpriteBox:"));
+        assert!(asm.contains("  JSR Runtime_Collision_Rect"));
+    }
+
+    #[test]
+    fn test_collision_sprites_errors() {
+        let errors = analyze(&source("  hit = Collision.Sprites(hero, 1, 2, slime, 3)"))
+            .err()
+            .expect("Expected arity error");
+        assert!(errors[0].contains("Collision.Sprites expects 6 arguments"));
+
+        let errors = analyze(&source("  hit = Collision.Sprites(hero, 1, 2, x, 3, 4)"))
+            .err()
+            .expect("Expected type error");
+        assert!(errors[0].contains("must be AnimState"));
+    }
+
+    #[test]
+    fn test_project_boxes_rom() {
+        let tile = SpriteTile {
+            x: 0,
+            y: 0,
+            tile: 0x42,
+            attr: 3,
+        };
+        let assets = ProjectAssets {
+            metasprites: vec![Metasprite {
+                name: "Bat".to_string(),
+                tiles: vec![tile],
+                hitbox: Some(SpriteBox {
+                    x: -3,
+                    y: 1,
+                    w: 9,
+                    h: 5,
+                }),
+                hurtbox: Some(SpriteBox {
+                    x: 0,
+                    y: 0,
+                    w: 8,
+                    h: 8,
+                }),
+            }],
+            animations: vec![Animation {
+                name: "BatFly".to_string(),
+                frames: vec![AnimationFrame {
+                    metasprite: "Bat".to_string(),
+                    duration: 4,
+                }],
+                does_loop: true,
+            }],
+            ..Default::default()
+        };
+        let src = "DIM a AS AnimState\nDIM b AS AnimState\nDIM hit AS BOOL\nSUB Main()\n  hit = Collision.Sprites(a, 0, 0, b, 4, 4)\nEND SUB\n";
+        // Project boxes are placed in ROM and the helper assembles in a full build
+        let rom = compile(src, assets).expect("Compile failed");
+        assert_eq!(rom.len(), 40976);
+        let bat = rom_addr(&rom, &[1, 0, 0, 0x42, 3, 0xFD, 1, 9, 5, 0, 0, 8, 8, 0, 0])
+            .expect("Metasprite not in ROM");
+        // The animation follows, its frame pointing at the metasprite
+        let frames = [1, 1, bat as u8, (bat >> 8) as u8, 4];
+        assert_eq!(rom_addr(&rom, &frames), Some(bat + 15));
+    }
+}
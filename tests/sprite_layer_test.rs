@@ -0,0 +1,61 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use crate::common::{analyze, generate};
+
+    fn source(body: &str) -> String {
+        format!(
+            "METASPRITE Hero\n  TILE 0, 0, $10, 0\nEND METASPRITE\nDIM n AS BYTE\nSUB Main()\n{}\nEND SUB\n",
+            body
+        )
+    }
+
+    #[test]
+    fn test_reserve_and_layer_codegen() {
+        let asm = generate(&source(
+            "  Sprite.Reserve(n)\n  Sprite.Layer(SpriteLayer.Fixed)\n  Sprite.Draw(8, 8, Hero)\n  Sprite.Layer(SpriteLayer.Normal)",
+        ));
+        // Reserved slot count is clamped to the 64 OAM slots
+        assert!(asm.contains("  CMP #65\n  BCC GEN_L1\n  LDA #64\nGEN_L1:\n  STA $20"));
+        assert!(asm.contains("  LDA #$01\n  LDX #$00\n  STA $21"));
+        assert!(asm.contains("  LDA #$00\n  LDX #$00\n  STA $21"));
+    }
+
+    #[test]
+    fn test_layer_runtime() {
+        let asm = generate(&source("  Sprite.Clear()\n  Sprite.Draw(8, 8, Hero)"));
+        // Sprite layers are initialised at startup
+        assert!(asm.contains("  STA $18\n  JSR Runtime_SpriteClear"));
+        // Band counters over 8 mark their lines crowded for the next frame
+        assert!(asm.contains(
+            "SpriteClear_Bands:\n  LDA $30, X\n  AND #$7F\n  CMP #9\n  LDA #0\n  BCC SpriteClear_Calm\n  LDA #$80"
+        ));
+        // Every other frame the crowded block climbs from last frame's count
+        assert!(asm.contains("  LDA #64\n  SEC\n  SBC $28\n  CMP $20"));
+        // Tiles count in both bands they cover and only crowded ones cycle
+        assert!(asm.contains("  INC $30, X\n  LDA $30, X\n  ORA $0A"));
+        assert!(
+            asm.contains("  LDA $1C\n  BEQ SpriteDraw_Stable\n  BIT $0A\n  BMI SpriteDraw_Crowded")
+        );
+        assert!(asm.contains("SpriteDraw_Skip:\n  PLA\n  PLA"));
+    }
+
+    #[test]
+    fn test_layer_errors() {
+        let errors = analyze(&source("  Sprite.Reserve()"))
+            .err()
+            .expect("Expected arity error");
+        assert!(errors[0].contains("Sprite.Reserve expects 1 argument (slots)"));
+
+        let errors = analyze(&source("  Sprite.Layer(1, 2)"))
+            .err()
+            .expect("Expected arity error");
+        assert!(errors[0].contains("Sprite.Layer expects 1 argument (layer)"));
+
+        let errors = analyze(&source("  Sprite.Priority(1)"))
+            .err()
+            .expect("Expected unknown command");
+        assert!(errors[0].contains("Reserve, Layer"));
+    }
+}
@@ -0,0 +1,94 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use crate::common::{analyze, compile, generate, rom_addr, sprite_table};
+    use swissarmynes::compiler::lexer::Lexer;
+    use swissarmynes::compiler::parser::Parser;
+    use swissarmynes::server::project::{Metasprite, ProjectAssets, SpriteMode, SpriteTile};
+
+    fn source(directive: &str, tile: &str) -> String {
+        format!(
+            "{}METASPRITE Knight\n  TILE 0, 0, {}, 0\n  TILE 8, 0, $10, 1\nEND METASPRITE\nSUB Main()\n  PPU.Ctrl($80)\n  Sprite.Draw(8, 8, Knight)\nEND SUB\n",
+            directive, tile
+        )
+    }
+
+    fn assets(mode: SpriteMode, tile: u8) -> ProjectAssets {
+        ProjectAssets {
+            metasprites: vec![Metasprite {
+                name: "Knight".to_string(),
+                tiles: vec![SpriteTile {
+                    x: 0,
+                    y: 0,
+                    tile,
+                    attr: 0,
+                }],
+                hitbox: None,
+                hurtbox: None,
+            }],
+            sprite_mode: mode,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_tall_sprite_codegen() {
+        let src = source("SPRITES 8X16\n", "258");
+        let asm = generate(&src);
+        // PPUCTRL bit 5 from startup on, and kept by PPU.Ctrl
+        assert!(asm.contains("  JSR Runtime_SpriteClear\n  LDA #$20\n  STA $F8\n  STA $2000"));
+        assert!(asm.contains("  ORA #$20\n  STA $F8\n  STA $2000"));
+        // Tile 258 = pair $02/$03 of the $1000 pattern table
+        let knight = [2, 0, 0, 0x03, 0, 8, 0, 0x10, 1];
+        assert_eq!(sprite_table(&src)[..9], knight);
+        let rom = compile(&src, ProjectAssets::default()).expect("Compile failed");
+        assert!(rom_addr(&rom, &knight).is_some(), "Metasprite not in ROM");
+        // A 16-line sprite spans up to three bands
+        assert!(asm.contains("  ADC #16\n  LSR\n  LSR\n  LSR\n  CMP $0B\n  BEQ SpriteDraw_Place"));
+        assert!(asm.contains("SpriteDraw_Band2:"));
+    }
+
+    #[test]
+    fn test_small_sprites_unchanged() {
+        let src = source("SPRITES 8X8\n", "3");
+        assert_eq!(sprite_table(&src)[..5], [2, 0, 0, 0x03, 0]);
+        let asm = generate(&src);
+        assert!(!asm.contains("ORA #$20"));
+        assert!(!asm.contains("SpriteDraw_Band2:"));
+    }
+
+    #[test]
+    fn test_tall_sprite_errors() {
+        let errors = analyze(&source("SPRITES 8X16\n", "3"))
+            .err()
+            .expect("Expected odd tile error");
+        assert!(
+            errors[0].contains("Metasprite 'Knight' uses tile 3; 8X16 sprites need an even tile")
+        );
+
+        let errors = analyze(&source("SPRITES 8X16\nSPRITES 8X8\n", "2"))
+            .err()
+            .expect("Expected conflict");
+        assert!(errors[0].contains("Conflicting SPRITES directives"));
+
+        let tokens = Lexer::new("SPRITES 8X32\n").tokenize().expect("Lex failed");
+        let err = Parser::new(tokens)
+            .parse()
+            .expect_err("Expected parse error");
+        assert!(err.contains("Expected 8X8 or 8X16 after SPRITES"));
+    }
+
+    #[test]
+    fn test_project_sprite_mode() {
+        let src = "SUB Main()\n  Sprite.Draw(8, 8, Knight)\nEND SUB\n";
+        let rom = compile(src, assets(SpriteMode::Tall, 4)).expect("Compile failed");
+        assert_eq!(rom.len(), 40976);
+
+        let err = compile(src, assets(SpriteMode::Tall, 5)).expect_err("Expected odd tile error");
+        assert!(err.contains("8X16 sprites need an even tile"));
+
+        // The default 8x8 mode accepts any tile
+        compile(src, assets(SpriteMode::Small, 5)).expect("Compile failed");
+    }
+}
@@ -0,0 +1,210 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use crate::common::{analyze_program, compile, layout, listing_for, parse};
+    use std::collections::BTreeMap;
+    use swissarmynes::compiler::actors::room_spawn_table;
+    use swissarmynes::compiler::ast::{PlacedObject, Program, TopLevel};
+    use swissarmynes::compiler::linker::SECTION_WORLD_OBJECTS;
+    use swissarmynes::server::project::{Nametable, ProjectAssets, WorldLayout, WorldObject};
+
+    // 2x2 world; room (0, 1) is empty
+    const ROOMS: [i32; 4] = [0, 0, -1, 0];
+
+    const ACTORS: &str = r#"
+DIM rx AS BYTE
+ACTOR Goomba
+  hp AS BYTE = 3
+  speed AS WORD = 300
+  name AS STRING
+END ACTOR
+ACTOR Door
+  target AS BYTE
+END ACTOR
+"#;
+
+    fn source(body: &str) -> String {
+        format!("{}SUB Main()\n{}\nEND SUB\n", ACTORS, body)
+    }
+
+    fn object(room: (u32, u32), actor: &str, x: u8, y: u8, params: &[(&str, i32)]) -> PlacedObject {
+        PlacedObject {
+            room,
+            actor: actor.to_string(),
+            x,
+            y,
+            params: params.iter().map(|(n, v)| (n.to_string(), *v)).collect(),
+        }
+    }
+
+    fn program(source: &str, objects: Vec<PlacedObject>) -> Program {
+        let mut program = parse(source);
+        program
+            .declarations
+            .push(TopLevel::World(2, 2, ROOMS.to_vec()));
+        for object in objects {
+            program
+                .declarations
+                .push(TopLevel::WorldObject(Box::new(object)));
+        }
+        program
+    }
+
+    #[test]
+    fn test_room_spawn_table() {
+        let program = program(
+            &source("  World.SpawnRoomObjects(rx, 1)"),
+            vec![
+                object((1, 1), "Door", 8, 16, &[("target", 2)]),
+                object((0, 0), "Goomba", 100, 200, &[]),
+                object((1, 1), "Goomba", 40, 50, &[("speed", 0x1234)]),
+            ],
+        );
+        let analyzer = analyze_program(&program).expect("Analysis failed");
+        assert!(analyzer.spawns_room_objects());
+        let table = room_spawn_table(&program, &analyzer.symbol_table);
+        assert_eq!(
+            table,
+            [
+                2, 2, // Width, height
+                10, 0, 15, 0, 16, 0, 17, 0, // Offset per room
+                0, 100, 200, 0, 0xFF, // (0, 0): Goomba
+                0xFF, // (1, 0)
+                0xFF, // (0, 1)
+                1, 8, 16, 1, 9, 2, // (1, 1): Door, target = 2
+                0, 40, 50, 2, 10, 0x34, 11, 0x12, 0xFF, // Goomba, speed (WORD)
+            ]
+        );
+
+        // The spawner walks the section and spawns through the ACTOR types
+        let layout = layout(&[(SECTION_WORLD_OBJECTS, table.len())]);
+        let addr = layout.addr(SECTION_WORLD_OBJECTS);
+        let asm = listing_for(&program, |codegen| codegen.set_layout(layout))
+            .expect("Codegen failed")
+            .join("\n");
+        assert!(asm.contains(
+            "  PHA\n  LDA #$01\n  LDX #$00\n  STA $09\n  PLA\n  STA $08\n  JSR Runtime_World_Spawn"
+        ));
+        let spawn = &asm[asm.find("Runtime_World_Spawn:").unwrap()..];
+        assert!(spawn.starts_with(&format!(
+            "Runtime_World_Spawn:\n  LDA $08\n  CMP ${:04X}",
+            addr
+        )));
+        assert!(asm.contains(
+            "World_SpawnKind:\n  CMP #0\n  BNE World_SpawnKind0\n  JMP Actor_Spawn_Goomba\nWorld_SpawnKind0:\n  CMP #1\n  BNE World_SpawnKind1\n  JMP Actor_Spawn_Door\nWorld_SpawnKind1:\n  LDA #$FF\n  RTS"
+        ));
+
+        // Without a world map there is nothing to spawn from
+        let err = listing_for(&program, |_| {}).unwrap_err();
+        assert!(
+            err.contains("World.SpawnRoomObjects requires a world map"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_world_object_errors() {
+        let cases = [
+            (
+                object((0, 0), "Koopa", 0, 0, &[]),
+                "World object 'Koopa' in room (0, 0) is not an ACTOR in source",
+            ),
+            (
+                object((2, 0), "Door", 0, 0, &[]),
+                "World object 'Door' in room (2, 0) is outside the 2x2 world",
+            ),
+            (
+                object((0, 1), "Door", 0, 0, &[]),
+                "World object 'Door' is in room (0, 1), which is empty (-1)",
+            ),
+            (
+                object((0, 0), "Door", 0, 240, &[]),
+                "World object 'Door' in room (0, 0) is below the room (y 240, 0-239)",
+            ),
+            (
+                object((0, 0), "Door", 0, 0, &[("hp", 1)]),
+                "World object 'Door' sets unknown field 'hp'",
+            ),
+            (
+                object((0, 0), "Door", 0, 0, &[("x", 1)]),
+                "World object 'Door' sets unknown field 'x'",
+            ),
+            (
+                object((0, 0), "Goomba", 0, 0, &[("name", 1)]),
+                "World object 'Goomba' cannot set field 'name'",
+            ),
+            (
+                object((0, 0), "Goomba", 0, 0, &[("hp", 256)]),
+                "World object 'Goomba' field 'hp' value 256 is out of range",
+            ),
+        ];
+        for (placed, message) in cases {
+            let errors = analyze_program(&program(&source(""), vec![placed]))
+                .err()
+                .unwrap_or_else(|| panic!("{} accepted", message));
+            assert!(errors.iter().any(|e| e.contains(message)), "{:?}", errors);
+        }
+
+        let calls = [
+            (
+                source("  World.SpawnRoomObjects(0)"),
+                "World.SpawnRoomObjects expects 2 arguments (roomX, roomY)",
+            ),
+            (
+                "SUB Main()\n  World.SpawnRoomObjects(0, 0)\nEND SUB\n".to_string(),
+                "World.SpawnRoomObjects requires an ACTOR declaration",
+            ),
+        ];
+        for (source, message) in calls {
+            let errors = analyze_program(&program(&source, vec![]))
+                .err()
+                .unwrap_or_else(|| panic!("{} accepted", message));
+            assert!(errors.iter().any(|e| e.contains(message)), "{:?}", errors);
+        }
+    }
+
+    #[test]
+    fn test_world_objects_from_assets() {
+        let json = r#"{"width": 1, "height": 1, "data": [0]}"#;
+        let world: WorldLayout = serde_json::from_str(json).expect("Parse failed");
+        assert!(world.objects.is_empty());
+
+        let assets = |actor: &str| ProjectAssets {
+            nametables: vec![Nametable {
+                name: "Start".to_string(),
+                data: vec![0; 960],
+                attrs: vec![0; 64],
+                ..Default::default()
+            }],
+            world: Some(WorldLayout {
+                width: 2,
+                height: 2,
+                data: ROOMS.to_vec(),
+                compression: None,
+                objects: vec![WorldObject {
+                    room_x: 1,
+                    room_y: 0,
+                    actor: actor.to_string(),
+                    x: 0x77,
+                    y: 0x66,
+                    params: BTreeMap::from([("target".to_string(), 0x55)]),
+                }],
+            }),
+            ..Default::default()
+        };
+        let src = source("  World.SpawnRoomObjects(1, 0)");
+        let rom = compile(&src, assets("Door")).expect("Compile failed");
+        assert!(rom
+            .windows(7)
+            .any(|w| w == [1, 0x77, 0x66, 1, 9, 0x55, 0xFF]));
+
+        let err = compile(&src, assets("Koopa")).unwrap_err();
+        assert!(
+            err.contains("World object 'Koopa' in room (1, 0) is not an ACTOR in source"),
+            "{}",
+            err
+        );
+    }
+}
@@ -0,0 +1,245 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use crate::common::{analyze_program, compile, layout, listing_for, parse};
+    use swissarmynes::compiler::analysis::SemanticAnalyzer;
+    use swissarmynes::compiler::ast::{Program, TopLevel};
+    use swissarmynes::compiler::cartridge::Mirroring;
+    use swissarmynes::compiler::compress::Compression;
+    use swissarmynes::compiler::linker::SECTION_WORLD;
+    use swissarmynes::server::project::{Nametable, ProjectAssets, WorldLayout};
+
+    // 2x2 world; room (0, 1) is empty
+    const ROOMS: [i32; 4] = [0, 1, -1, 1];
+
+    fn source(body: &str) -> String {
+        format!("DIM x AS BYTE\nSUB Main()\n{}\nEND SUB\n", body)
+    }
+
+    fn assets(compression: Option<Compression>) -> ProjectAssets {
+        ProjectAssets {
+            nametables: vec![
+                Nametable {
+                    name: "Start".to_string(),
+                    data: vec![0x21; 960],
+                    attrs: vec![0; 64],
+                    ..Default::default()
+                },
+                Nametable {
+                    name: "Cave".to_string(),
+                    data: vec![0x42; 960],
+                    attrs: vec![0; 64],
+                    compression,
+                    ..Default::default()
+                },
+            ],
+            world: Some(WorldLayout {
+                width: 2,
+                height: 2,
+                data: ROOMS.to_vec(),
+                compression: None,
+                objects: vec![],
+            }),
+            ..Default::default()
+        }
+    }
+
+    fn analyze(source: &str) -> Result<SemanticAnalyzer, Vec<String>> {
+        analyze_program(&program(source))
+    }
+
+    // `source` with the world map declaration the assets add
+    fn program(source: &str) -> Program {
+        let mut program = parse(source);
+        program
+            .declarations
+            .push(TopLevel::World(2, 2, ROOMS.to_vec()));
+        program
+    }
+
+    fn generate(source: &str, world: bool, mirroring: Mirroring) -> Result<String, String> {
+        listing_for(&program(source), |codegen| {
+            if world {
+                codegen.set_layout(layout(&[(SECTION_WORLD, 7)]));
+            }
+            codegen.set_mirroring(mirroring);
+            codegen.set_screens(vec![Compression::None, Compression::None]);
+        })
+        .map(|asm| asm.join("\n"))
+    }
+
+    #[test]
+    fn test_world_flip() {
+        let src =
+            source("  World.Enter(1, 0)\n  World.Transition(Direction.Down)\n  x = World.RoomY()");
+        let asm = generate(&src, true, Mirroring::Vertical).expect("Codegen failed");
+        assert!(asm.contains("  STA $03A1\n  PLA\n  STA $03A0\n  JSR Runtime_World_Enter"));
+        assert!(asm.contains("  JSR Runtime_World_Flip"));
+        assert!(asm.contains("  LDA $03A1\n  LDX #0"));
+        // Rooms load through Screen.Load into the camera's nametable layout
+        assert!(asm.contains("Runtime_Screen_Load:"));
+        assert!(asm.contains("  LDX $07\n  PLA\n  JMP Runtime_Screen_Load"));
+        // Moves off the map or into empty rooms are ignored
+        assert!(asm.contains("  CMP #$FF\n  BEQ World_Blocked"));
+        // Flips need neither the camera nor the scrolling runtime
+        assert!(!asm.contains("Runtime_Camera_Update:"));
+        assert!(!asm.contains("Runtime_World_Scroll:"));
+        assert!(!asm.contains("World_Place:"));
+    }
+
+    #[test]
+    fn test_world_scroll_streams_through_camera() {
+        let src = source("  World.Enter(0, 0)\n  World.Transition(Direction.Right, 4)");
+        let asm = generate(&src, true, Mirroring::Vertical).expect("Codegen failed");
+        assert!(asm.contains("  LDX #4\n  JSR Runtime_World_Scroll"));
+        assert!(asm.contains("Runtime_World_Scroll:"));
+        assert!(asm.contains("  JSR World_Place"));
+        assert!(asm.contains("Camera_MoveX:"));
+        // A spare nametable to the right: the first column goes out up front
+        assert!(asm.contains("  CPX #3\n  BNE World_ScrollLoop"));
+        assert!(!asm.contains("  CPX #1\n  BNE World_ScrollLoop"));
+        // Frames are paced by the NMI's queue flush
+        assert!(asm.contains("  LDA #0\n  STA $0387\n  LDA $F8\n  BMI World_ScrollWait"));
+
+        let asm = generate(&src, true, Mirroring::Horizontal).expect("Codegen failed");
+        assert!(!asm.contains("  CPX #3\n  BNE World_ScrollLoop"));
+        assert!(asm.contains("  CPX #1\n  BNE World_ScrollLoop"));
+    }
+
+    #[test]
+    fn test_world_rom_keeps_room_map_raw() {
+        // Flips decompress screens, but rooms are looked up in ROM
+        let src = source("  World.Enter(0, 0)\n  World.Transition(Direction.Right)");
+        let rom = compile(&src, assets(Some(Compression::Rle))).expect("Compile failed");
+        let prg = &rom[16..];
+        assert!(prg.windows(7).any(|w| w == [2, 2, 0, 0, 1, 0xFF, 1]));
+
+        // Scrolling streams screens, so they must stay raw
+        let src = source("  World.Enter(0, 0)\n  World.Transition(Direction.Right, 8)");
+        compile(&src, assets(None)).expect("Compile failed");
+        let err = compile(&src, assets(Some(Compression::Rle))).unwrap_err();
+        assert!(
+            err.contains("'Cave' is read from ROM at runtime"),
+            "{}",
+            err
+        );
+
+        let err = generate(&source("  World.Enter(0, 0)"), false, Mirroring::Vertical).unwrap_err();
+        assert!(err.contains("World.Enter requires a world map"), "{}", err);
+    }
+
+    #[test]
+    fn test_world_map_fits_bytes() {
+        let src = source("  World.Enter(0, 0)");
+        let error = |world: WorldLayout| {
+            let mut assets = assets(None);
+            assets.world = Some(world);
+            compile(&src, assets).unwrap_err()
+        };
+        let wide = WorldLayout {
+            width: 256,
+            height: 1,
+            data: vec![0; 256],
+            compression: None,
+            objects: vec![],
+        };
+        assert_eq!(
+            error(wide),
+            "Asset Error: world is 256x1 rooms (at most 255 in each direction)"
+        );
+        let mut rooms = ROOMS.to_vec();
+        rooms[3] = 300;
+        let err = error(WorldLayout {
+            width: 2,
+            height: 2,
+            data: rooms,
+            compression: None,
+            objects: vec![],
+        });
+        assert_eq!(
+            err,
+            "Asset Error: world room id 300 is out of range (0-254, or -1 for empty)"
+        );
+    }
+
+    #[test]
+    fn test_world_empty_rooms_unreachable() {
+        // (0, 0) -> Down is the empty room
+        let errors = analyze(&source("  World.Transition(Direction.Down)"))
+            .err()
+            .unwrap_or_default();
+        assert!(
+            errors
+                .iter()
+                .any(|e| e.contains("World room (0, 1) is empty (-1)")),
+            "{:?}",
+            errors
+        );
+        // From (1, 0), Down and Right only reach (1, 1)
+        let src = source(
+            "  World.Enter(1, 0)\n  World.Transition(Direction.Down)\n  World.Transition(Direction.Right)",
+        );
+        assert!(analyze(&src).is_ok());
+        // ...but adding Left reaches (0, 1) from (1, 1)
+        let src = source(
+            "  World.Enter(1, 0)\n  World.Transition(Direction.Down)\n  World.Transition(Direction.Left)",
+        );
+        assert!(analyze(&src).is_err());
+        // A computed direction can go anywhere
+        let src = source("  World.Enter(1, 0)\n  World.Transition(x)");
+        assert!(analyze(&src).is_err());
+        let errors = analyze(&source("  World.Enter(0, 1)"))
+            .err()
+            .unwrap_or_default();
+        assert!(
+            errors
+                .iter()
+                .any(|e| e.contains("World.Enter room (0, 1) is empty (-1)")),
+            "{:?}",
+            errors
+        );
+    }
+
+    #[test]
+    fn test_world_call_errors() {
+        let cases = [
+            (
+                "World.Enter(1)",
+                "World.Enter expects 2 arguments (roomX, roomY)",
+            ),
+            (
+                "World.Transition()",
+                "World.Transition expects 1 or 2 arguments (direction, speed)",
+            ),
+            (
+                "World.Transition(Direction.Up, 3)",
+                "World.Transition speed must be a literal 1, 2, 4 or 8",
+            ),
+            (
+                "World.Transition(7)",
+                "World.Transition direction 7 is out of range (0-3)",
+            ),
+            ("World.Enter(5, 0)", "is outside the 2x2 world"),
+            ("World.Warp(1)", "Unknown World command 'Warp'"),
+            ("x = World.RoomX(1)", "World.RoomX expects no arguments"),
+            ("x = World.Room()", "Unknown World function 'Room'"),
+        ];
+        for (line, expected) in cases {
+            let errors = analyze(&source(&format!("  {}", line)))
+                .err()
+                .unwrap_or_default();
+            assert!(
+                errors.iter().any(|e| e.contains(expected)),
+                "{}: {:?}",
+                line,
+                errors
+            );
+        }
+        let analyzer = analyze(&source("  World.Transition(Direction.Right)")).unwrap();
+        assert!(analyzer.reads_world());
+        assert!(!analyzer.streams_world());
+        let analyzer = analyze(&source("  World.Transition(Direction.Right, 2)")).unwrap();
+        assert!(analyzer.streams_world());
+    }
+}